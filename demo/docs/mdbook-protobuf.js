@@ -0,0 +1,39 @@
+// Links written as `proto!(Symbol@key=value)` carry their context in a `data-proto-context`
+// attribute beside a plain anchor, so they work as normal links without this script. Following
+// one remembers its target, then scrolls to and flashes the linked symbol on arrival so the reader
+// can see what the chapter referred to.
+(function () {
+    var STORAGE_KEY = "mdbook-protobuf-context-target";
+
+    document.addEventListener("click", function (event) {
+        var link = event.target.closest && event.target.closest("a[data-proto-context]");
+        if (!link) {
+            return;
+        }
+        var hash = link.getAttribute("href").split("#")[1];
+        if (hash) {
+            sessionStorage.setItem(STORAGE_KEY, decodeURIComponent(hash));
+        }
+    });
+
+    function highlightLinkedSymbol() {
+        var id = decodeURIComponent(window.location.hash.slice(1));
+        if (!id || sessionStorage.getItem(STORAGE_KEY) !== id) {
+            return;
+        }
+        sessionStorage.removeItem(STORAGE_KEY);
+
+        var target = document.getElementById(id);
+        if (!target) {
+            return;
+        }
+
+        target.scrollIntoView();
+        target.classList.remove("flash");
+        void target.offsetWidth; // restart the animation when the same link is followed again
+        target.classList.add("flash");
+    }
+
+    window.addEventListener("hashchange", highlightLinkedSymbol);
+    highlightLinkedSymbol();
+})();
@@ -0,0 +1,218 @@
+//! Times a full preprocessor run over a synthetic descriptor set the size of a large API, of
+//! 300 files declaring ~4,000 messages that reference each other across packages, and over a
+//! single file of 3,000 messages with a commented source location for each symbol.
+//!
+//! Run with `cargo bench -p mdbook-protobuf`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mdbook::book::Book;
+use mdbook::preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext};
+use mdbook_protobuf::ProtobufPreprocessor;
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::source_code_info::Location;
+use prost_types::{
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    MethodDescriptorProto, ServiceDescriptorProto, SourceCodeInfo,
+};
+use std::path::Path;
+
+const PACKAGES: usize = 30;
+const FILES_PER_PACKAGE: usize = 10;
+const MESSAGES_PER_FILE: usize = 13;
+const MESSAGES_IN_LARGE_FILE: usize = 3_000;
+
+fn message_name(file: usize, message: usize) -> String {
+    format!("Message{}x{}", file, message)
+}
+
+fn package_name(file: usize) -> String {
+    format!("bench.pkg{}.v1", file / FILES_PER_PACKAGE)
+}
+
+/// Each message has scalar fields and a reference to a message of the next file, which is in
+/// another package every `FILES_PER_PACKAGE` files
+fn synthetic_descriptor_set() -> FileDescriptorSet {
+    let files = PACKAGES * FILES_PER_PACKAGE;
+
+    let file = |file: usize| {
+        let next = (file + 1) % files;
+        let message_type = (0..MESSAGES_PER_FILE)
+            .map(|message| DescriptorProto {
+                name: Some(message_name(file, message)),
+                field: vec![
+                    FieldDescriptorProto {
+                        name: Some("id".into()),
+                        number: Some(1),
+                        label: Some(Label::Optional.into()),
+                        r#type: Some(Type::String.into()),
+                        json_name: Some("id".into()),
+                        ..Default::default()
+                    },
+                    FieldDescriptorProto {
+                        name: Some("count".into()),
+                        number: Some(2),
+                        label: Some(Label::Optional.into()),
+                        r#type: Some(Type::Int64.into()),
+                        json_name: Some("count".into()),
+                        ..Default::default()
+                    },
+                    FieldDescriptorProto {
+                        name: Some("related".into()),
+                        number: Some(3),
+                        label: Some(Label::Repeated.into()),
+                        r#type: Some(Type::Message.into()),
+                        type_name: Some(format!(
+                            ".{}.{}",
+                            package_name(next),
+                            message_name(next, message)
+                        )),
+                        json_name: Some("related".into()),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            })
+            .collect();
+
+        FileDescriptorProto {
+            name: Some(format!("bench/file{}.proto", file)),
+            package: Some(package_name(file)),
+            syntax: Some("proto3".into()),
+            message_type,
+            service: vec![ServiceDescriptorProto {
+                name: Some(format!("Service{}", file)),
+                method: vec![MethodDescriptorProto {
+                    name: Some("Get".into()),
+                    input_type: Some(format!(".{}.{}", package_name(file), message_name(file, 0))),
+                    output_type: Some(format!(".{}.{}", package_name(file), message_name(file, 1))),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    };
+
+    FileDescriptorSet {
+        file: (0..files).map(file).collect(),
+    }
+}
+
+/// One file whose messages each have two fields, with a leading comment on every message and
+/// field, so that each symbol looks up its location among ~9,000
+fn large_file_descriptor_set() -> FileDescriptorSet {
+    let field = |name: &str, number: i32| FieldDescriptorProto {
+        name: Some(name.into()),
+        number: Some(number),
+        label: Some(Label::Optional.into()),
+        r#type: Some(Type::String.into()),
+        json_name: Some(name.into()),
+        ..Default::default()
+    };
+    let location = |path: Vec<i32>, line: i32| Location {
+        path,
+        span: vec![line, 0, 20],
+        leading_comments: Some(" Documented.\n".into()),
+        ..Default::default()
+    };
+
+    let mut locations = Vec::new();
+    let message_type = (0..MESSAGES_IN_LARGE_FILE)
+        .map(|message| {
+            let path = vec![4, message as i32];
+            let line = message as i32 * 4;
+            locations.push(location(path.clone(), line));
+            for field in 0..2 {
+                locations.push(location(
+                    [&path[..], &[2, field]].concat(),
+                    line + field + 1,
+                ));
+            }
+
+            DescriptorProto {
+                name: Some(format!("Message{}", message)),
+                field: vec![field("id", 1), field("name", 2)],
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("bench/large.proto".into()),
+            package: Some("bench.large.v1".into()),
+            syntax: Some("proto3".into()),
+            message_type,
+            source_code_info: Some(SourceCodeInfo {
+                location: locations,
+            }),
+            ..Default::default()
+        }],
+    }
+}
+
+fn preprocessor_input(root: &Path, descriptor: &Path) -> (PreprocessorContext, Book) {
+    let input = serde_json::json!([
+        {
+            "root": root,
+            "config": {
+                "book": { "src": "src" },
+                "preprocessor": { "protobuf": { "proto_descriptor": descriptor } }
+            },
+            "renderer": "html",
+            "mdbook_version": "0.4.21"
+        },
+        {
+            "sections": [],
+            "__non_exhaustive": null
+        }
+    ]);
+
+    CmdPreprocessor::parse_input(input.to_string().as_bytes()).unwrap()
+}
+
+fn render_large_descriptor_set(c: &mut Criterion) {
+    let book_root = tempfile::tempdir().unwrap();
+    let descriptor = book_root.path().join("descriptor.pb");
+    std::fs::write(&descriptor, synthetic_descriptor_set().encode_to_vec()).unwrap();
+
+    let (ctx, book) = preprocessor_input(book_root.path(), &descriptor);
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    group.bench_function("large_descriptor_set", |b| {
+        b.iter(|| {
+            ProtobufPreprocessor::new()
+                .run(&ctx, book.clone())
+                .expect("should succeed")
+        })
+    });
+    group.finish();
+}
+
+fn render_large_file_with_source_info(c: &mut Criterion) {
+    let book_root = tempfile::tempdir().unwrap();
+    let descriptor = book_root.path().join("descriptor.pb");
+    std::fs::write(&descriptor, large_file_descriptor_set().encode_to_vec()).unwrap();
+
+    let (ctx, book) = preprocessor_input(book_root.path(), &descriptor);
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    group.bench_function("large_file_with_source_info", |b| {
+        b.iter(|| {
+            ProtobufPreprocessor::new()
+                .run(&ctx, book.clone())
+                .expect("should succeed")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    render_large_descriptor_set,
+    render_large_file_with_source_info
+);
+criterion_main!(benches);
@@ -0,0 +1,715 @@
+use crate::labels::Labels;
+use anyhow::{anyhow, Result};
+use askama::Template;
+use prost::encoding::{decode_key, decode_varint, WireType};
+use prost::Message;
+use prost_types::field_descriptor_proto::Type;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// Custom options are extensions, which `prost_types` drops when decoding, so the options of
+// messages, fields and methods are decoded as their encoded bytes with these partial descriptors
+// and the configured extension numbers read from those, as the types their extensions declare.
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFileDescriptorSet {
+    #[prost(message, repeated, tag = "1")]
+    file: Vec<PartialFile>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFile {
+    #[prost(string, optional, tag = "2")]
+    package: Option<String>,
+    #[prost(message, repeated, tag = "4")]
+    message_type: Vec<PartialMessage>,
+    #[prost(message, repeated, tag = "5")]
+    enum_type: Vec<PartialEnum>,
+    #[prost(message, repeated, tag = "6")]
+    service: Vec<PartialService>,
+    #[prost(message, repeated, tag = "7")]
+    extension: Vec<PartialExtension>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialMessage {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    field: Vec<PartialField>,
+    #[prost(message, repeated, tag = "3")]
+    nested_type: Vec<PartialMessage>,
+    #[prost(message, repeated, tag = "4")]
+    enum_type: Vec<PartialEnum>,
+    #[prost(message, repeated, tag = "6")]
+    extension: Vec<PartialExtension>,
+    #[prost(bytes = "vec", optional, tag = "7")]
+    options: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialExtension {
+    #[prost(string, optional, tag = "2")]
+    extendee: Option<String>,
+    #[prost(int32, optional, tag = "3")]
+    number: Option<i32>,
+    #[prost(enumeration = "Type", optional, tag = "5")]
+    r#type: Option<i32>,
+    #[prost(string, optional, tag = "6")]
+    type_name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialEnum {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    value: Vec<PartialEnumValue>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialEnumValue {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(int32, optional, tag = "2")]
+    number: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialField {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    options: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialService {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    method: Vec<PartialMethod>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialMethod {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    options: Option<Vec<u8>>,
+}
+
+/// Display names of the custom options rendered as attributes, keyed by extension number, from
+/// the `option_attributes` table
+pub(crate) fn parse_attribute_options(
+    options: BTreeMap<String, String>,
+) -> Result<BTreeMap<u32, String>> {
+    options
+        .into_iter()
+        .map(|(number, name)| {
+            number
+                .parse::<u32>()
+                .ok()
+                .filter(|number| (1..=536_870_911).contains(number))
+                .map(|number| (number, name))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "`option_attributes.{}` should be keyed by an extension number, e.g. `50001 = \"owner\"`",
+                        number
+                    )
+                })
+        })
+        .collect()
+}
+
+/// The configured custom options set on each message, field and method, keyed by
+/// `.package.Message`, `.package.Message.field` and `.package.Service.Method`
+#[derive(Default)]
+pub(crate) struct OptionAttributes {
+    attributes: HashMap<String, Vec<(String, String)>>,
+}
+
+/// The declared types of the extensions of each options message, by extension number
+#[derive(Default)]
+struct ExtensionTypes {
+    types: HashMap<String, HashMap<u32, ExtensionType>>,
+}
+
+/// The declared type of an extension, with the value names of its enum if it is one
+struct ExtensionType {
+    typ: Type,
+    enum_values: HashMap<i32, String>,
+}
+
+impl ExtensionType {
+    /// Whether a repeated extension of this type is encoded packed, as length-delimited values
+    fn packable(&self) -> bool {
+        !matches!(
+            self.typ,
+            Type::String | Type::Bytes | Type::Message | Type::Group
+        )
+    }
+}
+
+impl ExtensionTypes {
+    fn new(files: &[PartialFile]) -> Self {
+        fn visit_message<'a>(
+            enums: &mut HashMap<String, &'a PartialEnum>,
+            extensions: &mut Vec<&'a PartialExtension>,
+            scope: &str,
+            message: &'a PartialMessage,
+        ) {
+            let name = format!("{}.{}", scope, message.name());
+            for enum_type in &message.enum_type {
+                enums.insert(format!("{}.{}", name, enum_type.name()), enum_type);
+            }
+            extensions.extend(&message.extension);
+            for nested in &message.nested_type {
+                visit_message(enums, extensions, &name, nested);
+            }
+        }
+
+        let mut enums = HashMap::new();
+        let mut extensions = Vec::new();
+        for file in files {
+            let scope = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            for enum_type in &file.enum_type {
+                enums.insert(format!("{}.{}", scope, enum_type.name()), enum_type);
+            }
+            extensions.extend(&file.extension);
+            for message in &file.message_type {
+                visit_message(&mut enums, &mut extensions, &scope, message);
+            }
+        }
+
+        let mut extension_types = Self::default();
+        for extension in extensions {
+            let Ok(number) = u32::try_from(extension.number()) else {
+                continue;
+            };
+            let enum_values = enums
+                .get(extension.type_name())
+                .map(|enum_type| {
+                    enum_type
+                        .value
+                        .iter()
+                        .map(|value| (value.number(), value.name().to_string()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            extension_types
+                .types
+                .entry(extension.extendee().to_string())
+                .or_default()
+                .insert(
+                    number,
+                    ExtensionType {
+                        typ: extension.r#type(),
+                        enum_values,
+                    },
+                );
+        }
+        extension_types
+    }
+
+    /// the types of the extensions of `extendee`, e.g. `.google.protobuf.FieldOptions`
+    fn of(&self, extendee: &str) -> Option<&HashMap<u32, ExtensionType>> {
+        self.types.get(extendee)
+    }
+}
+
+impl OptionAttributes {
+    /// Reads the options named in `names` from an encoded `FileDescriptorSet`
+    pub(crate) fn decode(descriptor: &[u8], names: &BTreeMap<u32, String>) -> Result<Self> {
+        fn visit_message(
+            attributes: &mut OptionAttributes,
+            names: &BTreeMap<u32, String>,
+            types: &ExtensionTypes,
+            scope: &str,
+            message: &PartialMessage,
+        ) {
+            let name = format!("{}.{}", scope, message.name());
+            attributes.insert(
+                &name,
+                names,
+                types.of(".google.protobuf.MessageOptions"),
+                message.options.as_deref(),
+            );
+            for field in &message.field {
+                attributes.insert(
+                    &format!("{}.{}", name, field.name()),
+                    names,
+                    types.of(".google.protobuf.FieldOptions"),
+                    field.options.as_deref(),
+                );
+            }
+            for nested in &message.nested_type {
+                visit_message(attributes, names, types, &name, nested);
+            }
+        }
+
+        let mut attributes = Self::default();
+        if names.is_empty() {
+            return Ok(attributes);
+        }
+
+        let files = PartialFileDescriptorSet::decode(descriptor)?.file;
+        let types = ExtensionTypes::new(&files);
+
+        for file in &files {
+            let scope = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            for message in &file.message_type {
+                visit_message(&mut attributes, names, &types, &scope, message);
+            }
+            for service in &file.service {
+                for method in &service.method {
+                    attributes.insert(
+                        &format!("{}.{}.{}", scope, service.name(), method.name()),
+                        names,
+                        types.of(".google.protobuf.MethodOptions"),
+                        method.options.as_deref(),
+                    );
+                }
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    fn insert(
+        &mut self,
+        symbol: &str,
+        names: &BTreeMap<u32, String>,
+        types: Option<&HashMap<u32, ExtensionType>>,
+        options: Option<&[u8]>,
+    ) {
+        let rows = read_options(options.unwrap_or_default(), names, types);
+        if !rows.is_empty() {
+            self.attributes.insert(symbol.to_string(), rows);
+        }
+    }
+
+    /// The attributes of the symbol `fqsl`, if any of the configured options are set on it
+    pub(crate) fn get(&self, fqsl: &str) -> Option<Attributes> {
+        self.attributes.get(fqsl).map(|rows| Attributes {
+            rows: rows.clone(),
+            labels: Default::default(),
+        })
+    }
+}
+
+/// The values of the options numbered in `names`, as `(name, value)` in extension number order,
+/// read as the type their extension declares in `types`. Numbers read as numbers, bools as
+/// `true`/`false` and enums by value name, and other length-delimited values as text, leaving out
+/// those that aren't UTF-8 (as most message values). Fixed-width values of extensions not in
+/// `types` are left out too, as the bytes of a float can't be told apart from those of a fixed or
+/// sfixed integer. An option set more than once (a repeated option, packed or not) lists its
+/// values comma separated.
+fn read_options(
+    mut options: &[u8],
+    names: &BTreeMap<u32, String>,
+    types: Option<&HashMap<u32, ExtensionType>>,
+) -> Vec<(String, String)> {
+    let mut values: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+
+    while !options.is_empty() {
+        let Ok((number, wire_type)) = decode_key(&mut options) else {
+            break;
+        };
+        let typ = types.and_then(|types| types.get(&number));
+        let read: Vec<String> = match wire_type {
+            WireType::Varint => match decode_varint(&mut options) {
+                Ok(value) => vec![varint_value(value, typ)],
+                Err(_) => break,
+            },
+            WireType::ThirtyTwoBit => match options.split_first_chunk::<4>() {
+                Some((bytes, rest)) => {
+                    options = rest;
+                    thirty_two_bit_value(*bytes, typ).into_iter().collect()
+                }
+                None => break,
+            },
+            WireType::SixtyFourBit => match options.split_first_chunk::<8>() {
+                Some((bytes, rest)) => {
+                    options = rest;
+                    sixty_four_bit_value(*bytes, typ).into_iter().collect()
+                }
+                None => break,
+            },
+            WireType::LengthDelimited => {
+                let Some(len) = decode_varint(&mut options)
+                    .ok()
+                    .and_then(|len| usize::try_from(len).ok())
+                    .filter(|len| *len <= options.len())
+                else {
+                    break;
+                };
+                let (bytes, rest) = options.split_at(len);
+                options = rest;
+                match typ {
+                    Some(typ) if typ.packable() => packed_values(bytes, typ).unwrap_or_default(),
+                    _ => String::from_utf8(bytes.to_vec()).ok().into_iter().collect(),
+                }
+            }
+            // groups are not used for options, and can't be skipped without decoding them
+            WireType::StartGroup | WireType::EndGroup => break,
+        };
+
+        if names.contains_key(&number) {
+            values.entry(number).or_default().extend(read);
+        }
+    }
+
+    values
+        .into_iter()
+        .filter(|(_, values)| !values.is_empty())
+        .map(|(number, values)| (names[&number].clone(), values.join(", ")))
+        .collect()
+}
+
+fn varint_value(value: u64, typ: Option<&ExtensionType>) -> String {
+    match typ.map(|typ| typ.typ) {
+        Some(Type::Uint64) => value.to_string(),
+        Some(Type::Sint32 | Type::Sint64) => {
+            (((value >> 1) as i64) ^ -((value & 1) as i64)).to_string()
+        }
+        Some(Type::Bool) => (value != 0).to_string(),
+        Some(Type::Enum) => {
+            let number = value as i32;
+            typ.and_then(|typ| typ.enum_values.get(&number))
+                .cloned()
+                .unwrap_or_else(|| number.to_string())
+        }
+        // as int64, so that negative int32 values read as such
+        _ => (value as i64).to_string(),
+    }
+}
+
+fn thirty_two_bit_value(bytes: [u8; 4], typ: Option<&ExtensionType>) -> Option<String> {
+    match typ.map(|typ| typ.typ) {
+        Some(Type::Float) => Some(f32::from_le_bytes(bytes).to_string()),
+        Some(Type::Fixed32) => Some(u32::from_le_bytes(bytes).to_string()),
+        Some(Type::Sfixed32) => Some(i32::from_le_bytes(bytes).to_string()),
+        _ => None,
+    }
+}
+
+fn sixty_four_bit_value(bytes: [u8; 8], typ: Option<&ExtensionType>) -> Option<String> {
+    match typ.map(|typ| typ.typ) {
+        Some(Type::Double) => Some(f64::from_le_bytes(bytes).to_string()),
+        Some(Type::Fixed64) => Some(u64::from_le_bytes(bytes).to_string()),
+        Some(Type::Sfixed64) => Some(i64::from_le_bytes(bytes).to_string()),
+        _ => None,
+    }
+}
+
+/// The values of a packed repeated option, decoded as its declared type
+fn packed_values(mut bytes: &[u8], typ: &ExtensionType) -> Option<Vec<String>> {
+    let mut values = Vec::new();
+
+    while !bytes.is_empty() {
+        let value = match typ.typ {
+            Type::Float | Type::Fixed32 | Type::Sfixed32 => {
+                let (chunk, rest) = bytes.split_first_chunk::<4>()?;
+                bytes = rest;
+                thirty_two_bit_value(*chunk, Some(typ))?
+            }
+            Type::Double | Type::Fixed64 | Type::Sfixed64 => {
+                let (chunk, rest) = bytes.split_first_chunk::<8>()?;
+                bytes = rest;
+                sixty_four_bit_value(*chunk, Some(typ))?
+            }
+            _ => varint_value(decode_varint(&mut bytes).ok()?, Some(typ)),
+        };
+        values.push(value);
+    }
+
+    Some(values)
+}
+
+/// The configured custom options of a symbol, as a table of key-value attributes
+#[derive(Template, Clone, PartialEq, Debug)]
+#[template(path = "attributes.html")]
+pub(crate) struct Attributes {
+    rows: Vec<(String, String)>,
+    pub(crate) labels: Arc<Labels>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::encoding::{encode_key, encode_varint};
+
+    fn string_option(number: u32, value: &str, buf: &mut Vec<u8>) {
+        encode_key(number, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn int_option(number: u32, value: i64, buf: &mut Vec<u8>) {
+        encode_key(number, WireType::Varint, buf);
+        encode_varint(value as u64, buf);
+    }
+
+    fn extension(extendee: &str, number: i32, typ: Type) -> PartialExtension {
+        PartialExtension {
+            extendee: Some(extendee.into()),
+            number: Some(number),
+            r#type: Some(typ.into()),
+            type_name: None,
+        }
+    }
+
+    #[test]
+    fn should_read_the_configured_options_of_messages_fields_and_methods() {
+        let mut message_options = Vec::new();
+        // `deprecated`, which is not configured
+        int_option(3, 1, &mut message_options);
+        string_option(50001, "payments-team", &mut message_options);
+        int_option(50002, -1, &mut message_options);
+
+        let mut field_options = Vec::new();
+        string_option(50001, "ledger", &mut field_options);
+        string_option(50001, "billing", &mut field_options);
+
+        let mut method_options = Vec::new();
+        int_option(50002, 250, &mut method_options);
+
+        let descriptor = PartialFileDescriptorSet {
+            file: vec![PartialFile {
+                package: Some("billing".into()),
+                message_type: vec![PartialMessage {
+                    name: Some("Invoice".into()),
+                    field: vec![
+                        PartialField {
+                            name: Some("total".into()),
+                            options: Some(field_options),
+                        },
+                        PartialField {
+                            name: Some("id".into()),
+                            options: None,
+                        },
+                    ],
+                    nested_type: vec![],
+                    enum_type: vec![],
+                    extension: vec![],
+                    options: Some(message_options),
+                }],
+                service: vec![PartialService {
+                    name: Some("Billing".into()),
+                    method: vec![PartialMethod {
+                        name: Some("GetInvoice".into()),
+                        options: Some(method_options),
+                    }],
+                }],
+                enum_type: vec![],
+                extension: vec![],
+            }],
+        };
+        let names = BTreeMap::from([(50001, "owner".into()), (50002, "slo.ms".into())]);
+
+        let attributes = OptionAttributes::decode(&descriptor.encode_to_vec(), &names).unwrap();
+
+        let rows = |fqsl: &str| attributes.get(fqsl).map(|attributes| attributes.rows);
+        assert_eq!(
+            rows(".billing.Invoice"),
+            Some(vec![
+                ("owner".into(), "payments-team".into()),
+                ("slo.ms".into(), "-1".into()),
+            ])
+        );
+        assert_eq!(
+            rows(".billing.Invoice.total"),
+            Some(vec![("owner".into(), "ledger, billing".into())])
+        );
+        assert_eq!(rows(".billing.Invoice.id"), None);
+        assert_eq!(
+            rows(".billing.Billing.GetInvoice"),
+            Some(vec![("slo.ms".into(), "250".into())])
+        );
+    }
+
+    #[test]
+    fn should_read_fixed_width_options_as_their_declared_type() {
+        let mut options = Vec::new();
+        encode_key(50001, WireType::ThirtyTwoBit, &mut options);
+        options.extend_from_slice(&1.5f32.to_le_bytes());
+        encode_key(50002, WireType::SixtyFourBit, &mut options);
+        options.extend_from_slice(&(-2i64).to_le_bytes());
+        encode_key(50003, WireType::ThirtyTwoBit, &mut options);
+        options.extend_from_slice(&7u32.to_le_bytes());
+        // zigzag encoded -3
+        int_option(50004, 5, &mut options);
+
+        let descriptor = PartialFileDescriptorSet {
+            file: vec![
+                PartialFile {
+                    package: Some("billing".into()),
+                    message_type: vec![PartialMessage {
+                        name: Some("Invoice".into()),
+                        field: vec![],
+                        nested_type: vec![],
+                        enum_type: vec![],
+                        extension: vec![],
+                        options: Some(options),
+                    }],
+                    enum_type: vec![],
+                    service: vec![],
+                    extension: vec![],
+                },
+                PartialFile {
+                    package: Some("options".into()),
+                    message_type: vec![PartialMessage {
+                        name: Some("Scope".into()),
+                        field: vec![],
+                        nested_type: vec![],
+                        enum_type: vec![],
+                        // a field option of the same number is not a message option
+                        extension: vec![
+                            extension(".google.protobuf.MessageOptions", 50002, Type::Sfixed64),
+                            extension(".google.protobuf.FieldOptions", 50003, Type::Fixed32),
+                        ],
+                        options: None,
+                    }],
+                    enum_type: vec![],
+                    service: vec![],
+                    extension: vec![
+                        extension(".google.protobuf.MessageOptions", 50001, Type::Float),
+                        extension(".google.protobuf.MessageOptions", 50004, Type::Sint32),
+                    ],
+                },
+            ],
+        };
+        let names = BTreeMap::from([
+            (50001, "weight".into()),
+            (50002, "offset".into()),
+            (50003, "retries".into()),
+            (50004, "delta".into()),
+        ]);
+
+        let attributes = OptionAttributes::decode(&descriptor.encode_to_vec(), &names).unwrap();
+
+        assert_eq!(
+            attributes.get(".billing.Invoice").unwrap().rows,
+            vec![
+                ("weight".into(), "1.5".into()),
+                ("offset".into(), "-2".into()),
+                ("delta".into(), "-3".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_read_packed_bool_and_enum_options_as_their_declared_type() {
+        let packed = |number: u32, values: &[u8], buf: &mut Vec<u8>| {
+            encode_key(number, WireType::LengthDelimited, buf);
+            encode_varint(values.len() as u64, buf);
+            buf.extend_from_slice(values);
+        };
+
+        let mut options = Vec::new();
+        let mut regions = Vec::new();
+        for region in [1i64, -2, 300] {
+            encode_varint(region as u64, &mut regions);
+        }
+        packed(50001, &regions, &mut options);
+        int_option(50002, 1, &mut options);
+        int_option(50003, 2, &mut options);
+        packed(50004, &[1, 7], &mut options);
+        let weights = [0.5f32.to_le_bytes(), 2f32.to_le_bytes()].concat();
+        packed(50005, &weights, &mut options);
+
+        let tier = |number: i32| PartialExtension {
+            type_name: Some(".options.Tier".into()),
+            ..extension(".google.protobuf.MessageOptions", number, Type::Enum)
+        };
+        let descriptor = PartialFileDescriptorSet {
+            file: vec![
+                PartialFile {
+                    package: Some("billing".into()),
+                    message_type: vec![PartialMessage {
+                        name: Some("Invoice".into()),
+                        field: vec![],
+                        nested_type: vec![],
+                        enum_type: vec![],
+                        extension: vec![],
+                        options: Some(options),
+                    }],
+                    enum_type: vec![],
+                    service: vec![],
+                    extension: vec![],
+                },
+                PartialFile {
+                    package: Some("options".into()),
+                    message_type: vec![],
+                    enum_type: vec![PartialEnum {
+                        name: Some("Tier".into()),
+                        value: vec![
+                            PartialEnumValue {
+                                name: Some("FREE".into()),
+                                number: Some(1),
+                            },
+                            PartialEnumValue {
+                                name: Some("GOLD".into()),
+                                number: Some(2),
+                            },
+                        ],
+                    }],
+                    service: vec![],
+                    extension: vec![
+                        extension(".google.protobuf.MessageOptions", 50001, Type::Int32),
+                        extension(".google.protobuf.MessageOptions", 50002, Type::Bool),
+                        tier(50003),
+                        tier(50004),
+                        extension(".google.protobuf.MessageOptions", 50005, Type::Float),
+                    ],
+                },
+            ],
+        };
+        let names = BTreeMap::from([
+            (50001, "regions".into()),
+            (50002, "billable".into()),
+            (50003, "tier".into()),
+            (50004, "tiers".into()),
+            (50005, "weights".into()),
+        ]);
+
+        let attributes = OptionAttributes::decode(&descriptor.encode_to_vec(), &names).unwrap();
+
+        assert_eq!(
+            attributes.get(".billing.Invoice").unwrap().rows,
+            vec![
+                ("regions".into(), "1, -2, 300".into()),
+                ("billable".into(), "true".into()),
+                ("tier".into(), "GOLD".into()),
+                // values the enum doesn't declare read as their number
+                ("tiers".into(), "FREE, 7".into()),
+                ("weights".into(), "0.5, 2".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn option_attributes_should_be_keyed_by_extension_numbers() {
+        let parsed =
+            parse_attribute_options(BTreeMap::from([("50001".into(), "owner".into())])).unwrap();
+        assert_eq!(parsed, BTreeMap::from([(50001, "owner".into())]));
+
+        let error = parse_attribute_options(BTreeMap::from([("owner".into(), "owner".into())]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("`option_attributes.owner` should be keyed"),
+            "{}",
+            error
+        );
+    }
+}
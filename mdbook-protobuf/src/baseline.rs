@@ -0,0 +1,150 @@
+use prost::Message;
+use prost_types::FileDescriptorSet;
+use std::collections::{BTreeMap, HashMap};
+
+/// How a package differs from the same package in the baseline descriptor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PackageChange {
+    /// the baseline has no files in this package
+    New,
+    /// a service, message or enum was added, removed or modified
+    Changed,
+}
+
+impl PackageChange {
+    fn class(&self) -> &'static str {
+        match self {
+            Self::New => "new-package",
+            Self::Changed => "changed-package",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::New => "new package",
+            Self::Changed => "changed",
+        }
+    }
+
+    /// Badge shown at the top of the package page
+    pub(crate) fn badge(&self) -> String {
+        format!(
+            r#"<span class="badge {}">{}</span>"#,
+            self.class(),
+            self.label()
+        )
+    }
+
+    /// suffix for the chapter name, when `baseline_chapter_suffix = true`
+    pub(crate) fn chapter_suffix(&self) -> &'static str {
+        match self {
+            Self::New => " (new)",
+            Self::Changed => " (updated)",
+        }
+    }
+}
+
+/// Encoded definition of each top level symbol in a package, keyed by kind and name. Source info
+/// is not part of the definitions, so comment and formatting changes are not reported.
+type PackageDefinitions = BTreeMap<String, Vec<u8>>;
+
+fn package_definitions(
+    file_descriptor_set: &FileDescriptorSet,
+) -> HashMap<&str, PackageDefinitions> {
+    let mut packages: HashMap<&str, PackageDefinitions> = HashMap::new();
+
+    for file in &file_descriptor_set.file {
+        let definitions = packages.entry(file.package()).or_default();
+
+        for service in &file.service {
+            definitions.insert(
+                format!("service {}", service.name()),
+                service.encode_to_vec(),
+            );
+        }
+        for message in &file.message_type {
+            definitions.insert(
+                format!("message {}", message.name()),
+                message.encode_to_vec(),
+            );
+        }
+        for enum_type in &file.enum_type {
+            definitions.insert(
+                format!("enum {}", enum_type.name()),
+                enum_type.encode_to_vec(),
+            );
+        }
+        for extension in &file.extension {
+            definitions.insert(
+                format!("extend {} {}", extension.extendee(), extension.name()),
+                extension.encode_to_vec(),
+            );
+        }
+    }
+
+    packages
+}
+
+/// Compares each package against the baseline, returning the packages that are new or changed
+pub(crate) fn package_changes(
+    current: &FileDescriptorSet,
+    baseline: &FileDescriptorSet,
+) -> BTreeMap<String, PackageChange> {
+    let baseline = package_definitions(baseline);
+
+    package_definitions(current)
+        .into_iter()
+        .filter_map(|(package, definitions)| {
+            let change = match baseline.get(package) {
+                None => Some(PackageChange::New),
+                Some(baseline_definitions) if *baseline_definitions != definitions => {
+                    Some(PackageChange::Changed)
+                }
+                Some(_) => None,
+            };
+            change.map(|change| (package.to_string(), change))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_descriptor;
+
+    #[test]
+    fn should_report_new_and_changed_packages() {
+        let current = demo_descriptor();
+        let mut baseline = current.clone();
+
+        // the baseline predates the `helloworld` package
+        baseline.file.retain(|file| file.package() != "helloworld");
+
+        // ... and the renaming of a field in `testing`
+        let testing = baseline
+            .file
+            .iter_mut()
+            .find(|file| file.name() == "type_test.proto")
+            .unwrap();
+        testing.message_type[0].field[0].name = Some("renamed".into());
+
+        assert_eq!(
+            package_changes(&current, &baseline),
+            BTreeMap::from([
+                ("helloworld".to_string(), PackageChange::New),
+                ("testing".to_string(), PackageChange::Changed),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_ignore_comment_changes() {
+        let current = demo_descriptor();
+        let mut baseline = current.clone();
+        for file in &mut baseline.file {
+            file.source_code_info = None;
+        }
+
+        assert!(package_changes(&current, &baseline).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::links::{Backlink, SymbolLink};
+use crate::{read_descriptor_bytes, ProtobufPreprocessorArgs};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use mdbook::book::Chapter;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rendered proto chapters from a previous run, reused by `mdbook serve` when neither the
+/// descriptor, the config, nor the prose references into the descriptor have changed.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RenderCache {
+    descriptor_hash: String,
+    symbols: Vec<SymbolLink>,
+    pub(crate) backlinks_hash: String,
+    pub(crate) chapters: Vec<Chapter>,
+    /// book chapters as linked by that run, keyed by path
+    pub(crate) linked_chapters: HashMap<String, LinkedChapter>,
+}
+
+/// A book chapter with its `proto!()` links rewritten, reused while its content is unchanged.
+/// The symbols its links resolve against are covered by the descriptor hash of the cache.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct LinkedChapter {
+    content_hash: String,
+    content: String,
+    usages: Vec<(SymbolLink, Backlink)>,
+}
+
+/// The cache of the descriptor at `file_descriptor_path`, in `cache_dir`
+pub(crate) fn cache_path(file_descriptor_path: &Path, cache_dir: &Path) -> PathBuf {
+    let mut file_name = file_descriptor_path
+        .file_name()
+        .unwrap_or_default()
+        .to_os_string();
+    file_name.push(".mdbook-protobuf-cache.json");
+    cache_dir.join(file_name)
+}
+
+/// `.mdbook-protobuf-cache` beside the book's build directory, for when `cache_dir` isn't set.
+/// Not in the build directory, as renderers clear that, nor beside the descriptor, which is often
+/// in the book's `src` where `mdbook serve` watches for changes.
+pub(crate) fn default_cache_dir(root: &Path, build_dir: &Path) -> PathBuf {
+    let build_dir = root.join(build_dir);
+    build_dir
+        .parent()
+        .unwrap_or(&build_dir)
+        .join(".mdbook-protobuf-cache")
+}
+
+/// Covers everything the rendered output depends on other than the book content
+pub(crate) fn descriptor_hash(args: &ProtobufPreprocessorArgs) -> Result<String> {
+    let descriptor = read_descriptor_bytes(args.file_descriptor_path()?)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(&args.config_digest);
+    hasher.update(descriptor);
+
+    if let Some(baseline_path) = &args.baseline_descriptor {
+        let baseline = fs::read(baseline_path).with_context(|| {
+            format!("Could not read file at path `{}`", baseline_path.display())
+        })?;
+        hasher.update(baseline);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hash of the backlinks from book content, which are rendered into the proto chapters
+pub(crate) fn backlinks_hash(symbol_usages: &HashMap<SymbolLink, Vec<Backlink>>) -> String {
+    let mut content_links: Vec<(String, String)> = symbol_usages
+        .iter()
+        .flat_map(|(symbol, usages)| {
+            usages
+                .iter()
+                .filter(|usage| matches!(usage, Backlink::Content(_)))
+                .map(|usage| {
+                    (
+                        symbol.fqsl(),
+                        serde_json::to_string(usage).expect("backlink should serialize"),
+                    )
+                })
+        })
+        .collect();
+    content_links.sort();
+
+    let mut hasher = Sha256::new();
+    for (symbol, usage) in content_links {
+        hasher.update(symbol);
+        hasher.update(usage);
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Key of a chapter among the linked chapters, its path or the name of a draft chapter
+pub(crate) fn chapter_key(chapter: &Chapter) -> String {
+    chapter
+        .path
+        .as_ref()
+        .map_or_else(|| chapter.name.clone(), |path| path.display().to_string())
+}
+
+fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+impl LinkedChapter {
+    /// `original` is the chapter content before linking, `chapter` the linked chapter
+    pub(crate) fn new(
+        original: &str,
+        chapter: &Chapter,
+        usages: Vec<(SymbolLink, Backlink)>,
+    ) -> Self {
+        Self {
+            content_hash: content_hash(original),
+            content: chapter.content.clone(),
+            usages,
+        }
+    }
+
+    /// Replaces the content of `chapter` with the linked content and records its backlinks, if
+    /// the chapter is unchanged since it was linked
+    pub(crate) fn reuse(
+        &self,
+        chapter: &mut Chapter,
+        symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
+    ) -> bool {
+        if self.content_hash != content_hash(&chapter.content) {
+            return false;
+        }
+
+        chapter.content.clone_from(&self.content);
+        for (symbol, usage) in &self.usages {
+            symbol_usages
+                .entry(symbol.clone())
+                .or_default()
+                .push(usage.clone());
+        }
+        true
+    }
+}
+
+impl RenderCache {
+    pub(crate) fn new(
+        descriptor_hash: String,
+        symbols: Vec<SymbolLink>,
+        backlinks_hash: String,
+        chapters: Vec<Chapter>,
+        linked_chapters: HashMap<String, LinkedChapter>,
+    ) -> Self {
+        Self {
+            descriptor_hash,
+            symbols,
+            backlinks_hash,
+            chapters,
+            linked_chapters,
+        }
+    }
+
+    /// Returns the cache only if it was built from a descriptor with the given hash
+    pub(crate) fn load(path: &Path, descriptor_hash: &str) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+
+        match serde_json::from_str::<Self>(&content) {
+            Ok(cache) if cache.descriptor_hash == descriptor_hash => Some(cache),
+            Ok(_) => {
+                debug!("proto render cache at {} is stale", path.display());
+                None
+            }
+            Err(e) => {
+                debug!("ignoring unreadable proto render cache: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Failing to write the cache only costs a full render next time, so it is not fatal
+    pub(crate) fn save(&self, path: &Path) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(path, json).map_err(anyhow::Error::from)
+            });
+
+        if let Err(e) = result {
+            warn!(
+                "failed to write proto render cache to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// The symbol universe with no usages, enough to resolve `proto!()` links
+    pub(crate) fn symbol_usages(&self) -> HashMap<SymbolLink, Vec<Backlink>> {
+        self.symbols
+            .iter()
+            .map(|symbol| (symbol.clone(), Vec::new()))
+            .collect()
+    }
+}
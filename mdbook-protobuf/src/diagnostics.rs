@@ -0,0 +1,360 @@
+use crate::descriptor_files;
+use crate::view::TypeIndex;
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Files imported by the descriptor set's files, but not in it, that define types it references
+#[derive(Default, PartialEq, Debug)]
+pub(crate) struct MissingImports {
+    /// imported files that are not in the set
+    files: BTreeSet<String>,
+    /// referenced types defined nowhere in the set
+    types: BTreeSet<String>,
+}
+
+impl MissingImports {
+    fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "the descriptor set references types it doesn't define ({}), which will not be linked. \
+            Are the imported files ({}) missing? Pass `--include_imports` to protoc when writing the \
+            descriptor set",
+            self.types.iter().cloned().collect::<Vec<_>>().join(", "),
+            self.files.iter().cloned().collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+/// Reports descriptor sets written without the protoc flags the reference relies on: a warning
+/// when any file has no source info, and missing imports as a warning, or an error when `strict`
+pub(crate) fn check_descriptor(
+    file_descriptor_set: &FileDescriptorSet,
+    strict: bool,
+) -> Result<()> {
+    if lacks_source_info(file_descriptor_set) {
+        warn!(
+            "the descriptor set has no source info, so the reference will have no comments or \
+            `[src]` links. Pass `--include_source_info` to protoc when writing the descriptor set \
+            (`mdbook-protobuf generate`, prost-build and tonic-build include it)"
+        );
+    } else {
+        let files = files_without_source_info(file_descriptor_set);
+        if !files.is_empty() {
+            warn!(
+                "{} in the descriptor set have no source info, so their symbols will have no \
+                comments or `[src]` links. Pass `--include_source_info` to protoc when writing \
+                the descriptor sets they come from",
+                files.join(", ")
+            );
+        }
+    }
+
+    let missing = missing_imports(file_descriptor_set);
+    if missing.is_empty() {
+        Ok(())
+    } else if strict {
+        Err(anyhow!(missing.message()))
+    } else {
+        warn!("{}", missing.message());
+        Ok(())
+    }
+}
+
+fn lacks_source_info(file_descriptor_set: &FileDescriptorSet) -> bool {
+    !file_descriptor_set.file.is_empty()
+        && file_descriptor_set
+            .file
+            .iter()
+            .all(|file| file.source_code_info.is_none())
+}
+
+/// Files written without `--include_source_info`, as when merged from several descriptor sets
+fn files_without_source_info(file_descriptor_set: &FileDescriptorSet) -> Vec<&str> {
+    file_descriptor_set
+        .file
+        .iter()
+        .filter(|file| file.source_code_info.is_none())
+        .map(|file| file.name())
+        .collect()
+}
+
+fn missing_imports(file_descriptor_set: &FileDescriptorSet) -> MissingImports {
+    fn referenced_types<'a>(message: &'a DescriptorProto, types: &mut Vec<&'a str>) {
+        for field in &message.field {
+            if !field.type_name().is_empty() {
+                types.push(field.type_name());
+            }
+        }
+        for nested in &message.nested_type {
+            referenced_types(nested, types);
+        }
+    }
+
+    let index = TypeIndex::new(&file_descriptor_set.file);
+    let present: HashSet<&str> = file_descriptor_set
+        .file
+        .iter()
+        .map(|file| file.name())
+        .collect();
+
+    let mut missing = MissingImports::default();
+
+    for file in &file_descriptor_set.file {
+        let mut types = Vec::new();
+        for message in &file.message_type {
+            referenced_types(message, &mut types);
+        }
+        for method in file.service.iter().flat_map(|service| &service.method) {
+            types.push(method.input_type());
+            types.push(method.output_type());
+        }
+
+        let unresolved: Vec<&str> = types
+            .into_iter()
+            .filter(|type_name| index.resolve(type_name, "").is_none())
+            .collect();
+
+        if !unresolved.is_empty() {
+            missing
+                .types
+                .extend(unresolved.into_iter().map(str::to_string));
+            missing.files.extend(
+                file.dependency
+                    .iter()
+                    .filter(|dependency| !present.contains(dependency.as_str()))
+                    .cloned(),
+            );
+        }
+    }
+
+    missing
+}
+
+/// What to do when `.proto` sources were modified after the descriptor set was written
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum StaleDescriptor {
+    Warn,
+    Error,
+}
+
+impl TryFrom<&str> for StaleDescriptor {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            other => Err(anyhow!(
+                "`stale_descriptor` should be one of \"warn\" or \"error\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+/// Reports the `*.proto` files under `proto_src_dir` modified after the descriptor set (the
+/// oldest of them, for a directory of descriptor sets) was written, which it may not reflect. A
+/// missing `proto_src_dir` skips the check with a warning.
+pub(crate) fn check_staleness(
+    descriptor_path: &Path,
+    proto_src_dir: &Path,
+    severity: StaleDescriptor,
+) -> Result<()> {
+    if !proto_src_dir.is_dir() {
+        warn!(
+            "`proto_src_dir` `{}` is not a directory, skipping the descriptor staleness check",
+            proto_src_dir.display()
+        );
+        return Ok(());
+    }
+
+    let mut descriptor_modified = None;
+    for file in descriptor_files(descriptor_path)? {
+        let modified = modified(&file)?;
+        descriptor_modified =
+            Some(descriptor_modified.map_or(modified, |m: SystemTime| m.min(modified)));
+    }
+    let Some(descriptor_modified) = descriptor_modified else {
+        return Ok(());
+    };
+
+    let mut sources = Vec::new();
+    proto_sources(proto_src_dir, &mut sources)?;
+    let mut newer: Vec<String> = sources
+        .into_iter()
+        .filter(|(_, modified)| *modified > descriptor_modified)
+        .map(|(path, _)| {
+            path.strip_prefix(proto_src_dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string()
+        })
+        .collect();
+    if newer.is_empty() {
+        return Ok(());
+    }
+    newer.sort();
+
+    let message = format!(
+        "the descriptor set `{}` is older than proto sources modified since it was written ({}), so \
+        the reference may be out of date. Regenerate it (e.g. with `mdbook-protobuf generate`)",
+        descriptor_path.display(),
+        newer.join(", ")
+    );
+    match severity {
+        StaleDescriptor::Error => Err(anyhow!(message)),
+        StaleDescriptor::Warn => {
+            warn!("{}", message);
+            Ok(())
+        }
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| {
+            format!(
+                "Could not read the modification time of `{}`",
+                path.display()
+            )
+        })
+}
+
+/// Every `*.proto` file under `dir` with its modification time
+fn proto_sources(dir: &Path, sources: &mut Vec<(PathBuf, SystemTime)>) -> Result<()> {
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Could not read directory at path `{}`", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            proto_sources(&path, sources)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "proto")
+        {
+            let modified = modified(&path)?;
+            sources.push((path, modified));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_descriptor;
+
+    #[test]
+    fn should_detect_a_descriptor_without_source_info() {
+        let mut descriptor = demo_descriptor();
+        assert!(!lacks_source_info(&descriptor));
+
+        for file in &mut descriptor.file {
+            file.source_code_info = None;
+        }
+        assert!(lacks_source_info(&descriptor));
+    }
+
+    #[test]
+    fn should_list_the_files_without_source_info() {
+        let mut descriptor = demo_descriptor();
+        assert!(files_without_source_info(&descriptor).is_empty());
+
+        for file in &mut descriptor.file {
+            if file.name() == "helloworld.proto" {
+                file.source_code_info = None;
+            }
+        }
+        assert!(!lacks_source_info(&descriptor));
+        assert_eq!(
+            files_without_source_info(&descriptor),
+            vec!["helloworld.proto"]
+        );
+    }
+
+    #[test]
+    fn should_report_imports_missing_from_the_descriptor() {
+        let mut descriptor = demo_descriptor();
+        assert_eq!(missing_imports(&descriptor), MissingImports::default());
+        assert!(check_descriptor(&descriptor, true).is_ok());
+
+        // as if written without `--include_imports`
+        descriptor
+            .file
+            .retain(|file| file.name() != "google/protobuf/empty.proto");
+
+        let missing = missing_imports(&descriptor);
+        assert_eq!(
+            missing.files,
+            BTreeSet::from(["google/protobuf/empty.proto".to_string()])
+        );
+        assert_eq!(
+            missing.types,
+            BTreeSet::from([".google.protobuf.Empty".to_string()])
+        );
+
+        assert!(check_descriptor(&descriptor, false).is_ok());
+        let error = check_descriptor(&descriptor, true).unwrap_err().to_string();
+        assert!(error.contains("google/protobuf/empty.proto"), "{}", error);
+        assert!(error.contains("--include_imports"), "{}", error);
+    }
+
+    #[test]
+    fn should_report_proto_sources_newer_than_the_descriptor() {
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor = dir.path().join("descriptor.pb");
+        let proto_dir = dir.path().join("proto");
+        fs::create_dir_all(proto_dir.join("billing")).unwrap();
+        fs::write(&descriptor, b"").unwrap();
+        fs::write(proto_dir.join("common.proto"), b"").unwrap();
+        fs::write(proto_dir.join("billing/invoice.proto"), b"").unwrap();
+        fs::write(proto_dir.join("README.md"), b"").unwrap();
+
+        let set_modified = |path: &Path, time: SystemTime| {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap()
+        };
+        let written = SystemTime::now() - std::time::Duration::from_secs(60);
+        set_modified(&descriptor, written);
+        set_modified(
+            &proto_dir.join("common.proto"),
+            written - std::time::Duration::from_secs(60),
+        );
+        set_modified(&proto_dir.join("README.md"), SystemTime::now());
+
+        let error = check_staleness(&descriptor, &proto_dir, StaleDescriptor::Error)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(&format!(
+            "proto sources modified since it was written ({})",
+            Path::new("billing/invoice.proto").display()
+        )));
+        assert!(check_staleness(&descriptor, &proto_dir, StaleDescriptor::Warn).is_ok());
+
+        set_modified(
+            &descriptor,
+            SystemTime::now() + std::time::Duration::from_secs(60),
+        );
+        assert!(check_staleness(&descriptor, &proto_dir, StaleDescriptor::Error).is_ok());
+
+        assert!(check_staleness(
+            &descriptor,
+            &dir.path().join("missing"),
+            StaleDescriptor::Error
+        )
+        .is_ok());
+    }
+}
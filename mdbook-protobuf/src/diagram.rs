@@ -0,0 +1,174 @@
+use crate::links::{Backlink, SymbolKind, SymbolLink};
+use crate::view::ProtoNamespaceTemplate;
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// Packages with more nodes than this are left without a diagram, as it would be unreadable
+pub(crate) const DEFAULT_DIAGRAM_NODE_LIMIT: usize = 50;
+
+/// Diagram drawn at the top of each package page
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Diagrams {
+    Off,
+    Mermaid,
+}
+
+impl TryFrom<&str> for Diagrams {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "off" => Ok(Self::Off),
+            "mermaid" => Ok(Self::Mermaid),
+            other => Err(anyhow!(
+                "`diagrams` should be one of \"off\" or \"mermaid\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Node {
+    kind: SymbolKind,
+    label: String,
+    href: String,
+}
+
+/// A mermaid flowchart of the services, messages and enums of each package, keyed by package
+/// name, with an edge from each message to the types of its fields and from each service to the
+/// requests and responses of its methods. Symbols of other packages at either end of an edge are
+/// drawn with their full name and link to their own package's page.
+pub(crate) fn package_diagrams(
+    namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: &HashMap<SymbolLink, Vec<Backlink>>,
+    node_limit: usize,
+) -> HashMap<String, String> {
+    // the nodes of every package, by fqsl, for the ends of edges leaving a package
+    let mut all_nodes: HashMap<String, (String, Node)> = HashMap::new();
+    for (package, namespace) in namespaces {
+        namespace.visit_symbols(|symbol| {
+            let kind = symbol.kind();
+            if matches!(
+                kind,
+                SymbolKind::Service | SymbolKind::Message | SymbolKind::Enum
+            ) {
+                let link = symbol.symbol_link();
+                let node = Node {
+                    kind,
+                    label: link.id(),
+                    href: link.href(),
+                };
+                all_nodes.insert(link.fqsl(), (package.clone(), node));
+            }
+        });
+    }
+
+    // from and to fqsl, labelled with the fields or methods making the reference
+    let mut edges: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+    let mut hrefs: HashMap<String, String> = HashMap::new();
+    for (target, backlinks) in symbol_usages {
+        if target.property().is_some() {
+            continue;
+        }
+        for backlink in backlinks {
+            if let Backlink::Symbol { link: source, .. } = backlink {
+                let owner = source.owner();
+                hrefs.insert(owner.fqsl(), owner.href());
+                hrefs.insert(target.fqsl(), target.href());
+                edges
+                    .entry((owner.fqsl(), target.fqsl()))
+                    .or_default()
+                    .insert(source.property().unwrap_or_default().to_string());
+            }
+        }
+    }
+
+    namespaces
+        .keys()
+        .filter_map(|package| {
+            let is_local = |fqsl: &str| {
+                all_nodes
+                    .get(fqsl)
+                    .is_some_and(|(node_package, _)| node_package == package)
+            };
+
+            let edges: Vec<(&str, &str, String)> = edges
+                .iter()
+                .filter(|((from, to), _)| is_local(from) || is_local(to))
+                .map(|((from, to), labels)| {
+                    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                    (from.as_str(), to.as_str(), labels.join(", "))
+                })
+                .collect();
+
+            let mut nodes: BTreeMap<&str, Node> = all_nodes
+                .iter()
+                .filter(|(fqsl, _)| is_local(fqsl))
+                .map(|(fqsl, (_, node))| (fqsl.as_str(), node.clone()))
+                .collect();
+            for (from, to, _) in &edges {
+                for fqsl in [*from, *to] {
+                    if !nodes.contains_key(fqsl) {
+                        let kind = all_nodes
+                            .get(fqsl)
+                            .map_or(SymbolKind::Message, |(_, node)| node.kind);
+                        let node = Node {
+                            kind,
+                            label: fqsl.trim_start_matches('.').to_string(),
+                            href: hrefs.get(fqsl).cloned().unwrap_or_default(),
+                        };
+                        nodes.insert(fqsl, node);
+                    }
+                }
+            }
+
+            if nodes.len() > node_limit {
+                warn!(
+                    "skipping the diagram of `{}`, its {} nodes are more than `diagram_node_limit` ({})",
+                    package,
+                    nodes.len(),
+                    node_limit
+                );
+                return None;
+            }
+
+            Some((package.clone(), mermaid(&nodes, &edges)))
+        })
+        .collect()
+}
+
+fn mermaid(nodes: &BTreeMap<&str, Node>, edges: &[(&str, &str, String)]) -> String {
+    let ids: HashMap<&str, String> = nodes
+        .keys()
+        .enumerate()
+        .map(|(index, fqsl)| (*fqsl, format!("n{}", index)))
+        .collect();
+
+    let mut diagram = String::from("```mermaid\nflowchart LR\n");
+    for (fqsl, node) in nodes {
+        let label = node.label.replace('"', "#quot;");
+        let shape = match node.kind {
+            SymbolKind::Service => format!("([\"{}\"])", label),
+            SymbolKind::Enum => format!("{{{{\"{}\"}}}}", label),
+            _ => format!("[\"{}\"]", label),
+        };
+        let _ = writeln!(diagram, "    {}{}", ids[fqsl], shape);
+    }
+    for (from, to, label) in edges {
+        let _ = writeln!(diagram, "    {} -->|\"{}\"| {}", ids[from], label, ids[to]);
+    }
+    for (fqsl, node) in nodes {
+        // mermaid links are not rewritten by mdbook, so they point at the html pages
+        let _ = writeln!(
+            diagram,
+            "    click {} href \"{}\"",
+            ids[fqsl],
+            node.href.replacen(".md#", ".html#", 1)
+        );
+    }
+    diagram.push_str("```\n\n");
+    diagram
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+
+/// Presence of a field in an editions file, from its resolved `field_presence` feature
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum FieldPresence {
+    /// unset fields are distinguishable from ones set to the default, as with proto2 `optional`
+    Explicit,
+    /// as in proto3, the default is not serialized and reads as unset
+    Implicit,
+    /// as proto2 `required`
+    LegacyRequired,
+}
+
+impl FieldPresence {
+    /// `FeatureSet.FieldPresence` number
+    fn from_feature(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(Self::Explicit),
+            2 => Some(Self::Implicit),
+            3 => Some(Self::LegacyRequired),
+            _ => None,
+        }
+    }
+}
+
+/// Default of `field_presence` in every edition so far (2023 and 2024)
+const EDITION_DEFAULT_PRESENCE: FieldPresence = FieldPresence::Explicit;
+
+// The `edition` and `features` descriptor fields (and the `debug_redact` field option) postdate
+// the `prost-types` this crate builds against, so they are decoded separately with these partial
+// descriptors. Only the fields needed to resolve field presence and redaction, and to name the
+// edition, are declared; prost skips the rest.
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFileDescriptorSet {
+    #[prost(message, repeated, tag = "1")]
+    file: Vec<PartialFile>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFile {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    package: Option<String>,
+    #[prost(message, repeated, tag = "4")]
+    message_type: Vec<PartialMessage>,
+    #[prost(message, optional, tag = "8")]
+    options: Option<PartialFileOptions>,
+    #[prost(string, optional, tag = "12")]
+    syntax: Option<String>,
+    /// an `Edition`, e.g. `EDITION_2023` (1000)
+    #[prost(int32, optional, tag = "14")]
+    edition: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialMessage {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    field: Vec<PartialField>,
+    #[prost(message, repeated, tag = "3")]
+    nested_type: Vec<PartialMessage>,
+    #[prost(message, optional, tag = "7")]
+    options: Option<PartialMessageOptions>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialField {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, optional, tag = "8")]
+    options: Option<PartialFieldOptions>,
+}
+
+/// `FileOptions`, which holds its `features` at a different tag to the other options
+#[derive(Clone, PartialEq, Message)]
+struct PartialFileOptions {
+    #[prost(message, optional, tag = "50")]
+    features: Option<PartialFeatureSet>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialMessageOptions {
+    #[prost(message, optional, tag = "12")]
+    features: Option<PartialFeatureSet>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFieldOptions {
+    #[prost(bool, optional, tag = "16")]
+    debug_redact: Option<bool>,
+    #[prost(message, optional, tag = "21")]
+    features: Option<PartialFeatureSet>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PartialFeatureSet {
+    #[prost(int32, optional, tag = "1")]
+    field_presence: Option<i32>,
+}
+
+/// Resolved presence of every field declared in an editions file, keyed by
+/// `.package.Message.field`. Fields of proto2 and proto3 files are absent.
+#[derive(Default)]
+pub(crate) struct EditionsFeatures {
+    presence: HashMap<String, FieldPresence>,
+    /// fields with the `debug_redact` option, in files of any syntax, keyed the same way
+    redacted: HashSet<String>,
+    /// the `Edition` of each editions file, keyed by file name
+    editions: HashMap<String, i32>,
+}
+
+impl EditionsFeatures {
+    /// Decodes the features of an encoded `FileDescriptorSet`
+    pub(crate) fn decode(descriptor: &[u8]) -> Result<Self> {
+        // `inherited` is `None` outside editions files, where only redaction is recorded
+        fn visit_message(
+            features: &mut EditionsFeatures,
+            scope: &str,
+            message: &PartialMessage,
+            inherited: Option<FieldPresence>,
+        ) {
+            let name = format!("{}.{}", scope, message.name());
+            let inherited = inherited.map(|inherited| {
+                resolve(
+                    inherited,
+                    message.options.as_ref().and_then(|o| o.features.as_ref()),
+                )
+            });
+
+            for field in &message.field {
+                let field_name = format!("{}.{}", name, field.name());
+                if field.options.as_ref().is_some_and(|o| o.debug_redact()) {
+                    features.redacted.insert(field_name.clone());
+                }
+                if let Some(inherited) = inherited {
+                    features.presence.insert(
+                        field_name,
+                        resolve(
+                            inherited,
+                            field.options.as_ref().and_then(|o| o.features.as_ref()),
+                        ),
+                    );
+                }
+            }
+            for nested in &message.nested_type {
+                visit_message(features, &name, nested, inherited);
+            }
+        }
+
+        let mut features = Self::default();
+
+        for file in PartialFileDescriptorSet::decode(descriptor)?.file {
+            let scope = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            if let Some(edition) = file.edition.filter(|_| file.syntax() == "editions") {
+                features.editions.insert(file.name().to_string(), edition);
+            }
+            let file_presence = (file.syntax() == "editions").then(|| {
+                resolve(
+                    EDITION_DEFAULT_PRESENCE,
+                    file.options.as_ref().and_then(|o| o.features.as_ref()),
+                )
+            });
+            for message in &file.message_type {
+                visit_message(&mut features, &scope, message, file_presence);
+            }
+        }
+
+        Ok(features)
+    }
+
+    /// Presence of the field `name` of the message `message_fqsl`, if it is declared in an
+    /// editions file
+    pub(crate) fn field_presence(&self, message_fqsl: &str, name: &str) -> Option<FieldPresence> {
+        self.presence
+            .get(&format!("{}.{}", message_fqsl, name))
+            .copied()
+    }
+
+    /// The year of the edition of the editions file `file_name`, e.g. `2023`, if it is a
+    /// released edition
+    pub(crate) fn edition(&self, file_name: &str) -> Option<&'static str> {
+        match self.editions.get(file_name)? {
+            1000 => Some("2023"),
+            1001 => Some("2024"),
+            _ => None,
+        }
+    }
+
+    /// Whether the field `name` of the message `message_fqsl` has the `debug_redact` option
+    pub(crate) fn is_redacted(&self, message_fqsl: &str, name: &str) -> bool {
+        self.redacted
+            .contains(&format!("{}.{}", message_fqsl, name))
+    }
+}
+
+/// Features set on a symbol override those inherited from its parent
+fn resolve(inherited: FieldPresence, features: Option<&PartialFeatureSet>) -> FieldPresence {
+    features
+        .and_then(|f| f.field_presence)
+        .and_then(FieldPresence::from_feature)
+        .unwrap_or(inherited)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn features(field_presence: i32) -> Option<PartialFeatureSet> {
+        Some(PartialFeatureSet {
+            field_presence: Some(field_presence),
+        })
+    }
+
+    fn field(name: &str, features: Option<PartialFeatureSet>) -> PartialField {
+        PartialField {
+            name: Some(name.into()),
+            options: Some(PartialFieldOptions {
+                features,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn should_inherit_field_presence_from_the_closest_features() {
+        let descriptor = PartialFileDescriptorSet {
+            file: vec![
+                PartialFile {
+                    name: Some("settings.proto".into()),
+                    package: Some("settings".into()),
+                    syntax: Some("editions".into()),
+                    edition: Some(1000),
+                    options: Some(PartialFileOptions {
+                        features: features(2),
+                    }),
+                    message_type: vec![PartialMessage {
+                        name: Some("Limits".into()),
+                        field: vec![field("soft", None), field("hard", features(1))],
+                        nested_type: vec![PartialMessage {
+                            name: Some("Window".into()),
+                            field: vec![field("seconds", None)],
+                            options: Some(PartialMessageOptions {
+                                features: features(3),
+                            }),
+                            ..Default::default()
+                        }],
+                        options: None,
+                    }],
+                },
+                PartialFile {
+                    name: Some("legacy.proto".into()),
+                    package: Some("legacy".into()),
+                    syntax: Some("proto3".into()),
+                    edition: None,
+                    options: None,
+                    message_type: vec![PartialMessage {
+                        name: Some("Limits".into()),
+                        field: vec![
+                            field("soft", None),
+                            PartialField {
+                                name: Some("token".into()),
+                                options: Some(PartialFieldOptions {
+                                    debug_redact: Some(true),
+                                    ..Default::default()
+                                }),
+                            },
+                        ],
+                        ..Default::default()
+                    }],
+                },
+            ],
+        };
+
+        let editions = EditionsFeatures::decode(&descriptor.encode_to_vec()).unwrap();
+
+        assert_eq!(
+            editions.field_presence(".settings.Limits", "soft"),
+            Some(FieldPresence::Implicit)
+        );
+        assert_eq!(
+            editions.field_presence(".settings.Limits", "hard"),
+            Some(FieldPresence::Explicit)
+        );
+        assert_eq!(
+            editions.field_presence(".settings.Limits.Window", "seconds"),
+            Some(FieldPresence::LegacyRequired)
+        );
+        assert_eq!(editions.field_presence(".legacy.Limits", "soft"), None);
+
+        // redaction is recorded whatever the syntax
+        assert!(editions.is_redacted(".legacy.Limits", "token"));
+        assert!(!editions.is_redacted(".legacy.Limits", "soft"));
+
+        assert_eq!(editions.edition("settings.proto"), Some("2023"));
+        assert_eq!(editions.edition("legacy.proto"), None);
+    }
+}
@@ -0,0 +1,558 @@
+use crate::model::{Enum, Field, Message, ProtoModel};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Address the example commands call, for readers to replace with their server's
+const EXAMPLE_ADDRESS: &str = "localhost:50051";
+
+/// Messages nested deeper than this in an example are left empty
+const EXAMPLE_MAX_DEPTH: usize = 4;
+
+/// `[preprocessor.protobuf.grpcurl_examples]`: the server the `grpcurl` examples call, and how
+/// they connect to it
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct GrpcurlOptions {
+    /// `host:port` of the server
+    pub(crate) default_host: String,
+    /// `-plaintext`, for servers without TLS
+    pub(crate) plaintext: bool,
+    /// `-insecure`, skipping the verification of the server's certificate
+    pub(crate) insecure: bool,
+    /// `-cacert`, the certificate authority to verify the server with
+    pub(crate) cacert: Option<String>,
+    /// `-cert` and `-key`, the client's certificate and key for mutual TLS
+    pub(crate) cert: Option<String>,
+    pub(crate) key: Option<String>,
+}
+
+impl Default for GrpcurlOptions {
+    fn default() -> Self {
+        Self {
+            default_host: EXAMPLE_ADDRESS.into(),
+            plaintext: true,
+            insecure: false,
+            cacert: None,
+            cert: None,
+            key: None,
+        }
+    }
+}
+
+impl GrpcurlOptions {
+    fn flags(&self) -> Vec<String> {
+        let switches = [("-plaintext", self.plaintext), ("-insecure", self.insecure)]
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(flag, _)| flag.to_string());
+        let files = [
+            ("-cacert", &self.cacert),
+            ("-cert", &self.cert),
+            ("-key", &self.key),
+        ]
+        .into_iter()
+        .filter_map(|(flag, path)| Some(format!("{} {}", flag, shell_quote(path.as_ref()?))));
+
+        switches.chain(files).collect()
+    }
+}
+
+/// A `grpcurl` command calling each method with a sample of its request, keyed by the method's
+/// fqsl (e.g. `.helloworld.Greeter::SayHello`). Requests of client streaming methods are read
+/// from stdin, a message per line.
+pub(crate) fn grpcurl_examples(
+    model: &ProtoModel,
+    options: &GrpcurlOptions,
+) -> HashMap<String, String> {
+    let skeletons = Skeletons::new(model);
+
+    model
+        .packages
+        .iter()
+        .flat_map(|package| &package.files)
+        .flat_map(|file| &file.services)
+        .flat_map(|service| {
+            let skeletons = &skeletons;
+            service.methods.iter().map(move |method| {
+                let request = skeletons
+                    .message(&method.request_type, &mut HashSet::new())
+                    .to_value();
+                let path = format!("{}/{}", service.fqsl.trim_start_matches('.'), method.name);
+
+                let mut command = vec!["grpcurl".to_string()];
+                command.extend(options.flags());
+                let command = if method.client_streaming {
+                    command.extend(["-d @".into(), options.default_host.clone(), path]);
+                    // quoted, so the shell leaves any `$` or backtick in the request alone
+                    format!("{} <<'EOM'\n{}\nEOM", command.join(" "), request)
+                } else {
+                    let request = serde_json::to_string_pretty(&request).unwrap_or_default();
+                    command.extend([
+                        format!("-d '{}'", request),
+                        options.default_host.clone(),
+                        path,
+                    ]);
+                    command.join(" ")
+                };
+
+                (method.fqsl.clone(), command)
+            })
+        })
+        .collect()
+}
+
+/// A sample of each message (nested ones included) in the protobuf JSON mapping, keyed by fqsl.
+/// Enums show their first non-zero value, as the zero value reads as unset, and oneofs their
+/// first member under a comment naming the others, so the samples are JSON with comments.
+pub(crate) fn json_examples(model: &ProtoModel) -> HashMap<String, String> {
+    let skeletons = Skeletons::new(model);
+
+    model
+        .messages()
+        .map(|message| {
+            let sample = skeletons.message(&message.fqsl, &mut HashSet::new());
+            (message.fqsl.clone(), sample.to_json())
+        })
+        .collect()
+}
+
+/// Single quoted for a POSIX shell, unless it needs no quoting
+fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-:=+@".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// A placeholder value, which unlike a `Value` can carry comments
+enum Sample {
+    Value(Value),
+    /// an array of one element
+    Array(Box<Sample>),
+    /// members by JSON name, each with a comment to write above it
+    Object(Vec<(String, Sample, Option<String>)>),
+}
+
+impl Sample {
+    fn to_value(&self) -> Value {
+        match self {
+            Self::Value(value) => value.clone(),
+            Self::Array(element) => Value::Array(vec![element.to_value()]),
+            Self::Object(members) => Value::Object(
+                members
+                    .iter()
+                    .map(|(name, sample, _)| (name.clone(), sample.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Pretty printed as by `serde_json`, with the comments on lines of their own
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, depth: usize) {
+        let indent = |depth: usize| "  ".repeat(depth);
+        match self {
+            Self::Value(value) => out.push_str(&value.to_string()),
+            Self::Array(element) => {
+                out.push_str(&format!("[\n{}", indent(depth + 1)));
+                element.write(out, depth + 1);
+                out.push_str(&format!("\n{}]", indent(depth)));
+            }
+            Self::Object(members) if members.is_empty() => out.push_str("{}"),
+            Self::Object(members) => {
+                out.push_str("{\n");
+                for (index, (name, sample, comment)) in members.iter().enumerate() {
+                    if let Some(comment) = comment {
+                        out.push_str(&format!("{}// {}\n", indent(depth + 1), comment));
+                    }
+                    out.push_str(&format!("{}{}: ", indent(depth + 1), json!(name)));
+                    sample.write(out, depth + 1);
+                    if index + 1 < members.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                out.push_str(&format!("{}}}", indent(depth)));
+            }
+        }
+    }
+}
+
+struct Skeletons<'a> {
+    messages: HashMap<&'a str, &'a Message>,
+    enums: HashMap<&'a str, &'a Enum>,
+}
+
+impl<'a> Skeletons<'a> {
+    fn new(model: &'a ProtoModel) -> Self {
+        Self {
+            messages: model
+                .messages()
+                .map(|message| (message.fqsl.as_str(), message))
+                .collect(),
+            enums: model
+                .enums()
+                .map(|enumeration| (enumeration.fqsl.as_str(), enumeration))
+                .collect(),
+        }
+    }
+
+    /// The fields of a message with placeholder values, named as in the protobuf JSON mapping.
+    /// Only the first field of each oneof is included, as setting several is invalid, and
+    /// messages already being filled in (recursive ones) or nested too deep are left empty.
+    fn message(&self, type_name: &str, visiting: &mut HashSet<String>) -> Sample {
+        if let Some(value) = well_known_placeholder(type_name) {
+            return Sample::Value(value);
+        }
+        let Some(message) = self.messages.get(type_name) else {
+            return Sample::Object(Vec::new());
+        };
+        if visiting.len() >= EXAMPLE_MAX_DEPTH || !visiting.insert(type_name.to_string()) {
+            return Sample::Object(Vec::new());
+        }
+
+        let mut oneofs = HashSet::new();
+        let fields = message
+            .fields
+            .iter()
+            .filter(|field| {
+                field
+                    .oneof
+                    .as_ref()
+                    .is_none_or(|oneof| oneofs.insert(oneof.clone()))
+            })
+            .map(|field| {
+                let alternatives: Vec<String> = message
+                    .fields
+                    .iter()
+                    .filter(|other| other.oneof.is_some() && other.oneof == field.oneof)
+                    .filter(|other| other.name != field.name)
+                    .map(|other| other.json_name.clone())
+                    .collect();
+                let comment = field
+                    .oneof
+                    .as_ref()
+                    .filter(|_| !alternatives.is_empty())
+                    .map(|oneof| format!("oneof {}, or one of {}", oneof, alternatives.join(", ")));
+
+                (
+                    field.json_name.clone(),
+                    self.field(field, visiting),
+                    comment,
+                )
+            })
+            .collect();
+
+        visiting.remove(type_name);
+        Sample::Object(fields)
+    }
+
+    fn field(&self, field: &Field, visiting: &mut HashSet<String>) -> Sample {
+        let type_name = field.type_name.as_deref().unwrap_or_default();
+
+        // `map<K, V>` fields are repeated entry messages nested in their parent
+        if let Some(entry) = self
+            .messages
+            .get(type_name)
+            .filter(|entry| field.repeated && entry.map_entry)
+        {
+            let value = entry
+                .fields
+                .iter()
+                .find(|f| f.number == 2)
+                .map_or(Sample::Object(Vec::new()), |value| {
+                    self.field(value, visiting)
+                });
+            return Sample::Object(vec![("key".into(), value, None)]);
+        }
+
+        let value = self.value(type_name, visiting);
+        if field.repeated {
+            Sample::Array(Box::new(value))
+        } else {
+            value
+        }
+    }
+
+    fn value(&self, type_name: &str, visiting: &mut HashSet<String>) -> Sample {
+        Sample::Value(match type_name {
+            "string" | "bytes" => json!(""),
+            "bool" => json!(false),
+            "double" | "float" | "int32" | "uint32" | "sint32" | "fixed32" | "sfixed32" => {
+                json!(0)
+            }
+            // 64 bit integers are strings in the JSON mapping
+            "int64" | "uint64" | "sint64" | "fixed64" | "sfixed64" => json!("0"),
+            _ => match self.enums.get(type_name) {
+                Some(enumeration) => enumeration
+                    .values
+                    .iter()
+                    .find(|value| value.number != 0)
+                    .or(enumeration.values.first())
+                    .map_or(json!(0), |value| json!(value.name)),
+                None => return self.message(type_name, visiting),
+            },
+        })
+    }
+}
+
+/// Well known types with a special JSON mapping
+fn well_known_placeholder(type_name: &str) -> Option<Value> {
+    Some(match type_name {
+        ".google.protobuf.Timestamp" => json!("1970-01-01T00:00:00Z"),
+        ".google.protobuf.Duration" => json!("0s"),
+        ".google.protobuf.FieldMask" | ".google.protobuf.StringValue" => json!(""),
+        ".google.protobuf.BytesValue" => json!(""),
+        ".google.protobuf.Any" => json!({ "@type": "" }),
+        ".google.protobuf.Empty" | ".google.protobuf.Struct" => json!({}),
+        ".google.protobuf.ListValue" => json!([]),
+        ".google.protobuf.Value" => Value::Null,
+        ".google.protobuf.BoolValue" => json!(false),
+        ".google.protobuf.Int64Value" | ".google.protobuf.UInt64Value" => json!("0"),
+        ".google.protobuf.Int32Value"
+        | ".google.protobuf.UInt32Value"
+        | ".google.protobuf.DoubleValue"
+        | ".google.protobuf.FloatValue" => json!(0),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_descriptor;
+    use crate::ProtobufPreprocessor;
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        MessageOptions, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    #[test]
+    fn should_call_each_method_with_a_skeleton_of_its_request() {
+        let descriptor = demo_descriptor();
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+        let examples = grpcurl_examples(&model, &GrpcurlOptions::default());
+
+        assert_eq!(
+            examples[".helloworld.Greeter::SayHello"],
+            "grpcurl -plaintext -d '{\n  \"name\": \"\"\n}' localhost:50051 helloworld.Greeter/SayHello"
+        );
+        // only the first member of the oneof is set
+        assert!(examples[".testing.TypeTestService::GetMessage"].contains(
+            "{\n  \"message\": \"\",\n  \"oneofDoubleType\": 0\n}' localhost:50051 testing.TypeTestService/GetMessage"
+        ));
+        // client streams are read from stdin, a message per line
+        assert_eq!(
+            examples[".testing.TypeTestService::SayHello"],
+            "grpcurl -plaintext -d @ localhost:50051 testing.TypeTestService/SayHello <<'EOM'\n{\"message\":\"\",\"oneofDoubleType\":0}\nEOM"
+        );
+
+        let options = GrpcurlOptions {
+            default_host: "api.example.com:443".into(),
+            plaintext: false,
+            cacert: Some("certs/ca.pem".into()),
+            key: Some("my key.pem".into()),
+            ..Default::default()
+        };
+        assert!(
+            grpcurl_examples(&model, &options)[".helloworld.Greeter::SayHello"]
+                .starts_with("grpcurl -cacert certs/ca.pem -key 'my key.pem' -d '{")
+        );
+        assert!(
+            grpcurl_examples(&model, &options)[".helloworld.Greeter::SayHello"]
+                .ends_with("}' api.example.com:443 helloworld.Greeter/SayHello")
+        );
+
+        let skeletons = Skeletons::new(&model);
+        assert_eq!(
+            skeletons
+                .message(".testing.RepeatedTypes", &mut HashSet::new())
+                .to_value(),
+            json!({
+                "repeatedInts": [0],
+                "repeatedMessages": [{ "message": "", "oneofDoubleType": 0 }],
+                "repeatedEnums": ["VALUE_1"],
+                "messageMap": { "key": { "message": "", "oneofDoubleType": 0 } },
+                "fixed32Map": { "key": 0 },
+            })
+        );
+    }
+
+    #[test]
+    fn should_sample_each_message_as_commented_json() {
+        let descriptor = demo_descriptor();
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+        let examples = json_examples(&model);
+
+        assert_eq!(
+            examples[".helloworld.HelloRequest"],
+            "{\n  \"name\": \"\"\n}"
+        );
+        let message = &examples[".testing.Message"];
+        assert!(message.starts_with("{\n  \"message\": \"\",\n  // oneof "));
+        assert!(message.contains("\n  \"oneofDoubleType\": 0\n}"));
+        // enums are sampled with a value other than the default
+        assert!(examples[".testing.RepeatedTypes"]
+            .contains("\"repeatedEnums\": [\n    \"VALUE_1\"\n  ]"));
+        // nested messages have examples of their own
+        assert!(model
+            .messages()
+            .all(|message| examples.contains_key(&message.fqsl)));
+    }
+
+    #[test]
+    fn should_key_samples_by_json_name_and_only_treat_map_entries_as_maps() {
+        let field = |name: &str, number: i32, typ: Type| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            r#type: Some(typ.into()),
+            ..Default::default()
+        };
+        let repeated = |name: &str, number: i32, type_name: &str| FieldDescriptorProto {
+            label: Some(Label::Repeated.into()),
+            type_name: Some(type_name.into()),
+            ..field(name, number, Type::Message)
+        };
+        let entry = |name: &str, map_entry: bool| DescriptorProto {
+            name: Some(name.into()),
+            field: vec![
+                field("key", 1, Type::String),
+                field("value", 2, Type::Int32),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(map_entry),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let descriptor = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("library.proto".into()),
+                package: Some("library".into()),
+                syntax: Some("proto3".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Shelf".into()),
+                    field: vec![
+                        FieldDescriptorProto {
+                            json_name: Some("shelfName".into()),
+                            ..field("name", 1, Type::String)
+                        },
+                        repeated("counts", 2, ".library.Shelf.CountsEntry"),
+                        repeated("index", 3, ".library.Shelf.IndexEntry"),
+                    ],
+                    // named like an entry, but a message of its own
+                    nested_type: vec![entry("CountsEntry", true), entry("IndexEntry", false)],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+
+        assert_eq!(
+            Skeletons::new(&model)
+                .message(".library.Shelf", &mut HashSet::new())
+                .to_value(),
+            json!({
+                "shelfName": "",
+                "counts": { "key": 0 },
+                "index": [{ "key": "", "value": 0 }],
+            })
+        );
+    }
+
+    #[test]
+    fn should_terminate_on_recursive_and_deeply_nested_messages() {
+        let message = |name: &str, fields: &[(&str, &str, bool)]| DescriptorProto {
+            name: Some(name.into()),
+            field: fields
+                .iter()
+                .enumerate()
+                .map(
+                    |(index, (name, type_name, repeated))| FieldDescriptorProto {
+                        name: Some(name.to_string()),
+                        number: Some(index as i32 + 1),
+                        label: Some(
+                            if *repeated {
+                                Label::Repeated
+                            } else {
+                                Label::Optional
+                            }
+                            .into(),
+                        ),
+                        r#type: Some(Type::Message.into()),
+                        type_name: Some(type_name.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .collect(),
+            ..Default::default()
+        };
+        let descriptor = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("tree.proto".into()),
+                package: Some("tree".into()),
+                syntax: Some("proto3".into()),
+                service: vec![ServiceDescriptorProto {
+                    name: Some("Trees".into()),
+                    method: ["Node", "A"]
+                        .into_iter()
+                        .map(|name| MethodDescriptorProto {
+                            name: Some(format!("Plant{}", name)),
+                            input_type: Some(format!(".tree.{}", name)),
+                            output_type: Some(".tree.Node".into()),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                message_type: vec![
+                    message(
+                        "Node",
+                        &[
+                            ("parent", ".tree.Node", false),
+                            ("children", ".tree.Node", true),
+                        ],
+                    ),
+                    message("A", &[("b", ".tree.B", false)]),
+                    message("B", &[("c", ".tree.C", false)]),
+                    message("C", &[("d", ".tree.D", false)]),
+                    message("D", &[("e", ".tree.E", false)]),
+                    message("E", &[("a", ".tree.A", false)]),
+                ],
+                ..Default::default()
+            }],
+        };
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+        let examples = json_examples(&model);
+
+        assert_eq!(
+            examples[".tree.Node"],
+            "{\n  \"parent\": {},\n  \"children\": [\n    {}\n  ]\n}"
+        );
+        assert_eq!(
+            examples[".tree.A"],
+            "{\n  \"b\": {\n    \"c\": {\n      \"d\": {\n        \"e\": {}\n      }\n    }\n  }\n}"
+        );
+
+        // the grpcurl skeletons are cut short alike
+        let examples = grpcurl_examples(&model, &GrpcurlOptions::default());
+        assert_eq!(
+            examples[".tree.Trees::PlantNode"],
+            "grpcurl -plaintext -d '{\n  \"children\": [\n    {}\n  ],\n  \"parent\": {}\n}' localhost:50051 tree.Trees/PlantNode"
+        );
+        assert_eq!(
+            examples[".tree.Trees::PlantA"],
+            "grpcurl -plaintext -d '{\n  \"b\": {\n    \"c\": {\n      \"d\": {\n        \"e\": {}\n      }\n    }\n  }\n}' localhost:50051 tree.Trees/PlantA"
+        );
+    }
+}
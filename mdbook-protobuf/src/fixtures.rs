@@ -0,0 +1,18 @@
+use crate::read_file_descriptor_set;
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use std::path::Path;
+
+// Fixtures shared by the unit tests, read from the demo book's descriptor set
+
+pub(crate) fn demo_descriptor() -> FileDescriptorSet {
+    read_file_descriptor_set(Path::new("../demo/docs/build/proto_file_descriptor_set.pb")).unwrap()
+}
+
+/// The file `name` of the demo descriptor set, e.g. `helloworld.proto`
+pub(crate) fn demo_file(name: &str) -> FileDescriptorProto {
+    demo_descriptor()
+        .file
+        .into_iter()
+        .find(|file| file.name() == name)
+        .expect("file should be in the descriptor set")
+}
@@ -0,0 +1,517 @@
+use crate::links::{Backlink, SymbolKind, SymbolLink};
+use crate::model::{Enum, Message, ProtoModel};
+use crate::view::ProtoNamespaceTemplate;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The type dependency graph of the whole descriptor set in Graphviz DOT: a node per service,
+/// message and enum (nested ones included), clustered by package, with an edge from each message
+/// to the types of its fields and from each service to the requests and responses of its methods,
+/// labelled with the fields and methods. Nodes are identified by fqsl, and deprecated ones are
+/// dashed and grey.
+pub(crate) fn dot(model: &ProtoModel) -> String {
+    let mut clusters = Vec::new();
+    // from and to fqsl, labelled with the fields or methods making the reference
+    let mut edges: BTreeMap<(&str, &str), BTreeSet<&str>> = BTreeMap::new();
+
+    for package in &model.packages {
+        let prefix = format!(".{}.", package.name);
+        let label = |fqsl: &str| {
+            fqsl.strip_prefix(&prefix)
+                .unwrap_or(fqsl.trim_start_matches('.'))
+                .to_string()
+        };
+        let mut nodes = Vec::new();
+
+        for file in &package.files {
+            for service in &file.services {
+                nodes.push(DotNode {
+                    fqsl: &service.fqsl,
+                    label: label(&service.fqsl),
+                    shape: "component",
+                    deprecated: service.deprecated,
+                });
+                for method in &service.methods {
+                    for type_name in [&method.request_type, &method.response_type] {
+                        edges
+                            .entry((&service.fqsl, type_name))
+                            .or_default()
+                            .insert(&method.name);
+                    }
+                }
+            }
+
+            let mut messages = Vec::new();
+            let mut enums: Vec<&Enum> = file.enums.iter().collect();
+            for message in &file.messages {
+                with_nested(message, &mut messages, &mut enums);
+            }
+
+            for message in messages {
+                nodes.push(DotNode {
+                    fqsl: &message.fqsl,
+                    label: label(&message.fqsl),
+                    shape: "box",
+                    deprecated: message.deprecated,
+                });
+                for field in &message.fields {
+                    // primitive types are named without the leading `.` of a symbol
+                    if let Some(type_name) =
+                        field.type_name.as_deref().filter(|t| t.starts_with('.'))
+                    {
+                        edges
+                            .entry((&message.fqsl, type_name))
+                            .or_default()
+                            .insert(&field.name);
+                    }
+                }
+            }
+            for enumeration in enums {
+                nodes.push(DotNode {
+                    fqsl: &enumeration.fqsl,
+                    label: label(&enumeration.fqsl),
+                    shape: "hexagon",
+                    deprecated: enumeration.deprecated,
+                });
+            }
+        }
+
+        clusters.push((package.name.as_str(), nodes));
+    }
+
+    render_dot(clusters, edges)
+}
+
+/// Writes the graph to `path`, creating its directory
+pub(crate) fn write(model: &ProtoModel, path: &Path) -> Result<()> {
+    write_dot(&dot(model), path)
+}
+
+struct DotNode<'a> {
+    fqsl: &'a str,
+    label: String,
+    shape: &'static str,
+    deprecated: bool,
+}
+
+/// The graph in Graphviz DOT, with a cluster of nodes per package, in which the nodes of files
+/// without a `package` are left outside of any cluster
+fn render_dot<'a>(
+    clusters: impl IntoIterator<Item = (&'a str, Vec<DotNode<'a>>)>,
+    edges: BTreeMap<(&'a str, &'a str), BTreeSet<&'a str>>,
+) -> String {
+    let mut out = String::from("digraph protobuf {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [shape=box, fontname=\"Helvetica\"];\n");
+    out.push_str("    edge [fontname=\"Helvetica\", fontsize=10];\n");
+
+    for (package, nodes) in clusters {
+        let indent = if package.is_empty() {
+            "    "
+        } else {
+            let _ = writeln!(
+                out,
+                "    subgraph {} {{",
+                quote(&format!("cluster_{}", package))
+            );
+            let _ = writeln!(out, "        label={};", quote(package));
+            "        "
+        };
+        for node in nodes {
+            let style = if node.deprecated {
+                ", style=dashed, color=gray50, fontcolor=gray50"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "{}{} [label={}, shape={}{}];",
+                indent,
+                quote(node.fqsl),
+                quote(&node.label),
+                node.shape,
+                style
+            );
+        }
+        if !package.is_empty() {
+            out.push_str("    }\n");
+        }
+    }
+
+    for ((from, to), labels) in edges {
+        let labels: Vec<&str> = labels.into_iter().collect();
+        let _ = writeln!(
+            out,
+            "    {} -> {} [label={}];",
+            quote(from),
+            quote(to),
+            quote(&labels.join(", "))
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Writes a DOT graph to `path`, creating its directory, unless it already holds the same graph
+/// so that `mdbook serve` doesn't see a change to rebuild for
+fn write_dot(dot: &str, path: &Path) -> Result<()> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == dot) {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+    fs::write(path, dot).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// File of the `generate_dot` graph, within the output prefix
+pub(crate) const DEPENDENCIES_FILE: &str = "dependencies.dot";
+
+struct MessageNode {
+    label: String,
+    href: String,
+    deprecated: bool,
+}
+
+/// The graph of which messages (nested ones included) refer to which through their fields, by
+/// the symbol usages, for `generate_dot`
+pub(crate) struct MessageDependencies {
+    /// the messages of each package, by fqsl
+    packages: BTreeMap<String, BTreeMap<String, MessageNode>>,
+    /// from and to fqsl, labelled with the fields making the reference
+    edges: BTreeMap<(String, String), BTreeSet<String>>,
+}
+
+impl MessageDependencies {
+    pub(crate) fn new(
+        namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+        symbol_usages: &HashMap<SymbolLink, Vec<Backlink>>,
+    ) -> Self {
+        let mut packages: BTreeMap<String, BTreeMap<String, MessageNode>> = BTreeMap::new();
+        for (package, namespace) in namespaces {
+            let prefix = format!(".{}.", package);
+            namespace.visit_symbols(|symbol| {
+                if symbol.kind() == SymbolKind::Message {
+                    let fqsl = symbol.fqsl();
+                    let node = MessageNode {
+                        label: fqsl
+                            .strip_prefix(&prefix)
+                            .unwrap_or(fqsl.trim_start_matches('.'))
+                            .to_string(),
+                        href: symbol.symbol_link().href(),
+                        deprecated: symbol.deprecated(),
+                    };
+                    packages
+                        .entry(package.clone())
+                        .or_default()
+                        .insert(fqsl, node);
+                }
+            });
+        }
+
+        let is_message = |fqsl: &str| {
+            packages
+                .values()
+                .any(|messages| messages.contains_key(fqsl))
+        };
+
+        let mut edges: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+        for (target, backlinks) in symbol_usages {
+            if target.property().is_some() || !is_message(&target.fqsl()) {
+                continue;
+            }
+            for backlink in backlinks {
+                // methods are properties of services, so only fields are left
+                if let Backlink::Symbol { link: source, .. } = backlink {
+                    let owner = source.owner().fqsl();
+                    if is_message(&owner) {
+                        edges
+                            .entry((owner, target.fqsl()))
+                            .or_default()
+                            .insert(source.property().unwrap_or_default().to_string());
+                    }
+                }
+            }
+        }
+
+        Self { packages, edges }
+    }
+
+    /// In Graphviz DOT, in the format of the type graph
+    pub(crate) fn dot(&self) -> String {
+        let clusters = self.packages.iter().map(|(package, messages)| {
+            let nodes = messages
+                .iter()
+                .map(|(fqsl, message)| DotNode {
+                    fqsl,
+                    label: message.label.clone(),
+                    shape: "box",
+                    deprecated: message.deprecated,
+                })
+                .collect();
+            (package.as_str(), nodes)
+        });
+        let edges = self
+            .edges
+            .iter()
+            .map(|((from, to), fields)| {
+                (
+                    (from.as_str(), to.as_str()),
+                    fields.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+
+        render_dot(clusters, edges)
+    }
+
+    /// Writes the graph in DOT to `path`, as `write` does the type graph
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        write_dot(&self.dot(), path)
+    }
+
+    /// The same graph as a mermaid flowchart, with a subgraph per package, for the chapter
+    pub(crate) fn mermaid(&self) -> String {
+        let ids: HashMap<&str, String> = self
+            .packages
+            .values()
+            .flat_map(|messages| messages.keys())
+            .enumerate()
+            .map(|(index, fqsl)| (fqsl.as_str(), format!("n{}", index)))
+            .collect();
+
+        let mut diagram = String::from("```mermaid\nflowchart LR\n");
+        for (index, (package, messages)) in self.packages.iter().enumerate() {
+            let indent = if package.is_empty() {
+                "    "
+            } else {
+                let _ = writeln!(diagram, "    subgraph p{}[\"{}\"]", index, package);
+                "        "
+            };
+            for (fqsl, message) in messages {
+                let _ = writeln!(
+                    diagram,
+                    "{}{}[\"{}\"]",
+                    indent,
+                    ids[fqsl.as_str()],
+                    message.label.replace('"', "#quot;")
+                );
+            }
+            if !package.is_empty() {
+                diagram.push_str("    end\n");
+            }
+        }
+        for ((from, to), fields) in &self.edges {
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            let _ = writeln!(
+                diagram,
+                "    {} -->|\"{}\"| {}",
+                ids[from.as_str()],
+                fields.join(", "),
+                ids[to.as_str()]
+            );
+        }
+        for messages in self.packages.values() {
+            for (fqsl, message) in messages {
+                // mermaid links are not rewritten by mdbook, so they point at the html pages
+                let _ = writeln!(
+                    diagram,
+                    "    click {} href \"{}\"",
+                    ids[fqsl.as_str()],
+                    message.href.replacen(".md#", ".html#", 1)
+                );
+            }
+        }
+        diagram.push_str("```\n");
+        diagram
+    }
+}
+
+fn with_nested<'a>(
+    message: &'a Message,
+    messages: &mut Vec<&'a Message>,
+    enums: &mut Vec<&'a Enum>,
+) {
+    messages.push(message);
+    enums.extend(&message.enums);
+    for nested in &message.messages {
+        with_nested(nested, messages, enums);
+    }
+}
+
+/// A DOT quoted id, in which only `"` (and the `\` escaping it) needs escaping
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::attributes::OptionAttributes;
+    use crate::editions::EditionsFeatures;
+    use crate::view::SymbolSort;
+    use crate::{build_namespaces, ProtobufPreprocessor};
+    use prost_types::field_descriptor_proto::Type;
+    use prost_types::{
+        DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+        FileDescriptorProto, FileDescriptorSet, MessageOptions, MethodDescriptorProto,
+        ServiceDescriptorProto,
+    };
+
+    fn fixture() -> FileDescriptorSet {
+        let field =
+            |name: &str, number: i32, typ: Type, type_name: Option<&str>| FieldDescriptorProto {
+                name: Some(name.into()),
+                number: Some(number),
+                r#type: Some(typ.into()),
+                type_name: type_name.map(String::from),
+                ..Default::default()
+            };
+
+        FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    name: Some("shop.proto".into()),
+                    package: Some("shop".into()),
+                    syntax: Some("proto3".into()),
+                    service: vec![ServiceDescriptorProto {
+                        name: Some("Orders".into()),
+                        method: vec![MethodDescriptorProto {
+                            name: Some("Place".into()),
+                            input_type: Some(".shop.PlaceOrder".into()),
+                            output_type: Some(".shop.Order".into()),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    message_type: vec![
+                        DescriptorProto {
+                            name: Some("PlaceOrder".into()),
+                            field: vec![
+                                field("note", 1, Type::String, None),
+                                field("status", 2, Type::Enum, Some(".shop.Order.Status")),
+                                field("ping", 3, Type::Message, Some(".Ping")),
+                            ],
+                            ..Default::default()
+                        },
+                        DescriptorProto {
+                            name: Some("Order".into()),
+                            field: vec![field("status", 1, Type::Enum, Some(".shop.Order.Status"))],
+                            enum_type: vec![EnumDescriptorProto {
+                                name: Some("Status".into()),
+                                value: vec![EnumValueDescriptorProto {
+                                    name: Some("STATUS_UNSPECIFIED".into()),
+                                    number: Some(0),
+                                    ..Default::default()
+                                }],
+                                ..Default::default()
+                            }],
+                            options: Some(MessageOptions {
+                                deprecated: Some(true),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    name: Some("ping.proto".into()),
+                    syntax: Some("proto3".into()),
+                    message_type: vec![DescriptorProto {
+                        name: Some("Ping".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn should_cluster_the_type_graph_by_package() {
+        let model = ProtobufPreprocessor::build_model(&fixture());
+        let graph = dot(&model);
+
+        assert_eq!(
+            graph,
+            r#"digraph protobuf {
+    rankdir=LR;
+    node [shape=box, fontname="Helvetica"];
+    edge [fontname="Helvetica", fontsize=10];
+    ".Ping" [label="Ping", shape=box];
+    subgraph "cluster_shop" {
+        label="shop";
+        ".shop.Orders" [label="Orders", shape=component];
+        ".shop.PlaceOrder" [label="PlaceOrder", shape=box];
+        ".shop.Order" [label="Order", shape=box, style=dashed, color=gray50, fontcolor=gray50];
+        ".shop.Order.Status" [label="Order.Status", shape=hexagon];
+    }
+    ".shop.Order" -> ".shop.Order.Status" [label="status"];
+    ".shop.Orders" -> ".shop.Order" [label="Place"];
+    ".shop.Orders" -> ".shop.PlaceOrder" [label="Place"];
+    ".shop.PlaceOrder" -> ".Ping" [label="ping"];
+    ".shop.PlaceOrder" -> ".shop.Order.Status" [label="status"];
+}
+"#
+        );
+        assert_eq!(graph, dot(&model), "should be stable");
+
+        // every statement is terminated, and every block closed
+        let mut depth = 0;
+        for line in graph.lines() {
+            let line = line.trim();
+            if line.ends_with('{') {
+                depth += 1;
+            } else if line == "}" {
+                depth -= 1;
+            } else {
+                assert!(line.ends_with(';'), "{}", line);
+            }
+            assert!(depth >= 0);
+        }
+        assert_eq!(depth, 0);
+    }
+
+    #[test]
+    fn should_graph_the_field_references_between_messages() {
+        let (namespaces, symbol_usages) = build_namespaces(
+            fixture(),
+            EditionsFeatures::default(),
+            OptionAttributes::default(),
+            SymbolSort::Source,
+        );
+        let dependencies = MessageDependencies::new(&namespaces, &symbol_usages);
+
+        // enums, services and the methods referring to messages are left out
+        assert_eq!(
+            dependencies.dot(),
+            r#"digraph protobuf {
+    rankdir=LR;
+    node [shape=box, fontname="Helvetica"];
+    edge [fontname="Helvetica", fontsize=10];
+    ".Ping" [label="Ping", shape=box];
+    subgraph "cluster_shop" {
+        label="shop";
+        ".shop.Order" [label="Order", shape=box, style=dashed, color=gray50, fontcolor=gray50];
+        ".shop.PlaceOrder" [label="PlaceOrder", shape=box];
+    }
+    ".shop.PlaceOrder" -> ".Ping" [label="ping"];
+}
+"#
+        );
+
+        let mermaid = dependencies.mermaid();
+        assert!(mermaid.starts_with("```mermaid\nflowchart LR\n    n0[\"Ping\"]\n"));
+        assert!(mermaid.contains(
+            "    subgraph p1[\"shop\"]\n        n1[\"Order\"]\n        n2[\"PlaceOrder\"]\n    end\n"
+        ));
+        assert!(mermaid.contains("    n2 -->|\"ping\"| n0\n"));
+        assert!(mermaid.contains("    click n0 href \"/proto/_root.html#Ping\"\n"));
+    }
+}
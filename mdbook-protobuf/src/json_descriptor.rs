@@ -0,0 +1,21 @@
+use anyhow::Result;
+use prost::Message;
+use prost_reflect::{DeserializeOptions, DynamicMessage, ReflectMessage};
+use prost_types::FileDescriptorSet;
+
+// Descriptor sets in the protobuf JSON mapping (e.g. from `buf build -o image.json`) are decoded
+// against the reflected `descriptor.proto` and re-encoded, so the rest of the crate (including the
+// editions decoding of the raw bytes) only ever sees the binary encoding.
+
+/// Transcodes a JSON encoded descriptor set to the binary encoding. Keys `descriptor.proto` does
+/// not declare, such as the `bufExtension` of buf images, are skipped.
+pub(crate) fn json_to_binary(json: &[u8]) -> Result<Vec<u8>> {
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let message = DynamicMessage::deserialize_with_options(
+        FileDescriptorSet::default().descriptor(),
+        &mut deserializer,
+        &DeserializeOptions::new().deny_unknown_fields(false),
+    )?;
+    deserializer.end()?;
+    Ok(message.encode_to_vec())
+}
@@ -0,0 +1,139 @@
+use crate::model::ProtoModel;
+use crate::openapi::{schema_name, Schemas};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A JSON Schema of the messages as they are written in the protobuf JSON mapping: fields in lower
+/// camel case, 64 bit integers as strings, enums as their value names and well known types (e.g.
+/// `Timestamp` as a `date-time` string) in their special forms.
+///
+/// With a `message` (fully qualified, e.g. `.helloworld.HelloRequest`) the document is the schema
+/// of that message, otherwise it bundles every message and enum in `$defs`, keyed by full name.
+/// Either way the messages and enums referenced are in `$defs`.
+pub(crate) fn document(model: &ProtoModel, title: &str, message: Option<&str>) -> Result<Value> {
+    let schemas = Schemas::new(model, "#/$defs/");
+
+    let Some(message) = message else {
+        let all: BTreeSet<String> = model
+            .messages()
+            .map(|message| message.fqsl.clone())
+            .chain(model.enums().map(|enumeration| enumeration.fqsl.clone()))
+            .collect();
+
+        return Ok(json!({
+            "$schema": DRAFT,
+            "title": title,
+            "$defs": schemas.components(all),
+        }));
+    };
+
+    let message = model
+        .messages()
+        .find(|candidate| candidate.fqsl == message)
+        .ok_or_else(|| {
+            anyhow!(
+                "`{}` is not a message in the descriptor set, expected a fully qualified name such as `.package.Message`",
+                message
+            )
+        })?;
+
+    Ok(json!({
+        "$schema": DRAFT,
+        "title": message.name,
+        "$ref": format!("#/$defs/{}", schema_name(&message.fqsl)),
+        "$defs": schemas.components(BTreeSet::from([message.fqsl.clone()])),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_descriptor;
+    use crate::ProtobufPreprocessor;
+    use prost_types::field_descriptor_proto::Type;
+    use prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+
+    fn demo_model() -> ProtoModel {
+        let descriptor = demo_descriptor();
+        ProtobufPreprocessor::build_model(&descriptor)
+    }
+
+    #[test]
+    fn should_convert_a_message_with_the_json_mapping() {
+        let schema = document(&demo_model(), "Demo", Some(".helloworld.HelloRequest")).unwrap();
+
+        assert_eq!(schema["$ref"], "#/$defs/helloworld.HelloRequest");
+        let hello_request = &schema["$defs"]["helloworld.HelloRequest"];
+        assert_eq!(hello_request["type"], "object");
+        assert_eq!(hello_request["properties"]["name"]["type"], "string");
+        // only what the message references is bundled
+        assert_eq!(schema["$defs"].as_object().unwrap().len(), 1);
+
+        let repeated_types = document(&demo_model(), "Demo", Some(".testing.RepeatedTypes"))
+            .unwrap()["$defs"]["testing.RepeatedTypes"]
+            .clone();
+        assert_eq!(
+            repeated_types["properties"]["repeatedEnums"],
+            json!({ "type": "array", "items": { "$ref": "#/$defs/testing.Enum" } })
+        );
+        assert_eq!(
+            repeated_types["properties"]["fixed32Map"]["additionalProperties"]["type"],
+            "integer"
+        );
+    }
+
+    #[test]
+    fn should_name_properties_by_their_json_name() {
+        let field = |name: &str, number: i32, json_name: &str| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            r#type: Some(Type::String.into()),
+            json_name: Some(json_name.into()),
+            ..Default::default()
+        };
+        let model = ProtobufPreprocessor::build_model(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("library.proto".into()),
+                package: Some("library".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Book".into()),
+                    field: vec![
+                        field("page_title", 1, "pageTitle"),
+                        field("isbn", 2, "isbn13"),
+                    ],
+                    ..Default::default()
+                }],
+                syntax: Some("proto3".into()),
+                ..Default::default()
+            }],
+        });
+
+        let schema = document(&model, "Library", Some(".library.Book")).unwrap();
+        let properties = schema["$defs"]["library.Book"]["properties"]
+            .as_object()
+            .unwrap();
+        assert_eq!(
+            properties.keys().collect::<Vec<_>>(),
+            ["isbn13", "pageTitle"]
+        );
+    }
+
+    #[test]
+    fn should_bundle_every_message_and_enum_without_a_message() {
+        let schema = document(&demo_model(), "Demo", None).unwrap();
+
+        assert_eq!(schema["title"], "Demo");
+        assert_eq!(
+            schema["$defs"]["testing.Enum"]["enum"],
+            json!(["VALUE_0", "VALUE_1", "VALUE_3", "VALUE_7", "VALUE_9"])
+        );
+        assert!(schema["$defs"]["google.protobuf.Empty"].is_object());
+
+        assert!(document(&demo_model(), "Demo", Some("HelloRequest")).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+/// Text of the headings and captions of the generated reference, configured under
+/// `[preprocessor.protobuf.labels]` to localize it. Protobuf keywords (`optional`, `stream`,
+/// `returns`) and comments are left as they are.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Labels {
+    pub(crate) services: String,
+    pub(crate) messages: String,
+    pub(crate) enums: String,
+    /// backlink count of a symbol used once, e.g. "1 usage"
+    pub(crate) usage: String,
+    pub(crate) usages: String,
+    pub(crate) copy_as_proto: String,
+    /// summary of a message's sample JSON payload
+    pub(crate) json_example: String,
+    pub(crate) deprecated: String,
+    /// banner of a file with `option deprecated = true;`
+    pub(crate) file_deprecated: String,
+    pub(crate) replacement: String,
+    pub(crate) default: String,
+    /// badge of a field with explicit presence, i.e. a hazzer
+    pub(crate) has_presence: String,
+    pub(crate) no_presence: String,
+    /// badge of a repeated scalar field with the packed encoding
+    pub(crate) packed: String,
+    /// badge of a field with the `debug_redact` option
+    pub(crate) redacted: String,
+    pub(crate) see_also: String,
+    pub(crate) operational_notes: String,
+    pub(crate) timeout: String,
+    pub(crate) retries: String,
+    /// the `@retries never`, `@retries unavailable` and `@retries idempotent` hints
+    pub(crate) retry_never: String,
+    pub(crate) retry_unavailable: String,
+    pub(crate) retry_idempotent: String,
+    /// heading of the table of a symbol's custom options, with `option_attributes`
+    pub(crate) attributes: String,
+    pub(crate) bidirectional_streaming: String,
+    /// badges of a method's `idempotency_level`
+    pub(crate) idempotent: String,
+    pub(crate) no_side_effects: String,
+    pub(crate) allow_alias_note: String,
+    /// markdown, explaining `google.protobuf.Any` fields
+    pub(crate) any_note: String,
+    /// summary of a method's `grpcurl` example
+    pub(crate) try_it: String,
+    /// below the `grpcurl` example of a client or server streaming method
+    pub(crate) streaming_example_note: String,
+    /// summary of a file's language specific options (`go_package`, `swift_prefix`...)
+    pub(crate) file_options: String,
+    /// summary of a file's imports
+    pub(crate) imports: String,
+    /// badge of an `import public`, which re-exports the imported file
+    pub(crate) public_import: String,
+    /// badge of an `import weak`
+    pub(crate) weak_import: String,
+    /// title of the chapter of source pages, with `emit_source_pages`
+    pub(crate) source_pages: String,
+    /// title of the chapter of the message dependency graph, with `generate_dot`
+    pub(crate) dependencies: String,
+    /// title of the A-Z chapter of every symbol, with `symbol_index`
+    pub(crate) symbol_index: String,
+    /// title of the landing chapter, with `index_page`
+    pub(crate) index_page: String,
+    /// column headings of the landing chapter
+    pub(crate) package: String,
+    pub(crate) files: String,
+    pub(crate) symbols: String,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self {
+            services: "Services".into(),
+            messages: "Messages".into(),
+            enums: "Enums".into(),
+            usage: "usage".into(),
+            usages: "usages".into(),
+            copy_as_proto: "Copy as proto".into(),
+            json_example: "Example JSON".into(),
+            deprecated: "Deprecated".into(),
+            file_deprecated: "Everything declared in this file is deprecated.".into(),
+            replacement: "Replacement".into(),
+            default: "Default".into(),
+            has_presence: "has presence".into(),
+            no_presence: "no presence".into(),
+            packed: "packed".into(),
+            redacted: "redacted".into(),
+            see_also: "See also".into(),
+            operational_notes: "Operational notes".into(),
+            timeout: "Timeout".into(),
+            retries: "Retries".into(),
+            retry_never: "Do not retry".into(),
+            retry_unavailable: "Retry only when the request was not processed (e.g. UNAVAILABLE)"
+                .into(),
+            retry_idempotent: "Safe to retry, the method is idempotent".into(),
+            attributes: "Attributes".into(),
+            bidirectional_streaming: "bidirectional streaming".into(),
+            idempotent: "idempotent".into(),
+            no_side_effects: "no side effects".into(),
+            allow_alias_note:
+                "This enum allows aliases; more than one value name may share the same number."
+                    .into(),
+            any_note: "Can hold any protobuf message. The actual type is identified by the `type_url` field \
+                (see [`Any`](https://protobuf.dev/reference/protobuf/google.protobuf/#any))."
+                .into(),
+            try_it: "Try it".into(),
+            streaming_example_note: "grpcurl reads a request stream as JSON messages one after \
+                another, and prints each message of a response stream as it arrives."
+                .into(),
+            file_options: "Language options".into(),
+            imports: "Imports".into(),
+            public_import: "public".into(),
+            weak_import: "weak".into(),
+            source_pages: "Proto sources".into(),
+            dependencies: "Message dependencies".into(),
+            symbol_index: "Symbol index".into(),
+            index_page: "Protobuf reference".into(),
+            package: "Package".into(),
+            files: "Files".into(),
+            symbols: "Symbols".into(),
+        }
+    }
+}
+
+impl Labels {
+    /// Config keys of the labels, in declaration order
+    pub(crate) const KEYS: [&'static str; 41] = [
+        "services",
+        "messages",
+        "enums",
+        "usage",
+        "usages",
+        "copy_as_proto",
+        "json_example",
+        "deprecated",
+        "file_deprecated",
+        "replacement",
+        "default",
+        "has_presence",
+        "no_presence",
+        "packed",
+        "redacted",
+        "see_also",
+        "operational_notes",
+        "timeout",
+        "retries",
+        "retry_never",
+        "retry_unavailable",
+        "retry_idempotent",
+        "attributes",
+        "bidirectional_streaming",
+        "idempotent",
+        "no_side_effects",
+        "allow_alias_note",
+        "any_note",
+        "try_it",
+        "streaming_example_note",
+        "file_options",
+        "imports",
+        "public_import",
+        "weak_import",
+        "source_pages",
+        "dependencies",
+        "symbol_index",
+        "index_page",
+        "package",
+        "files",
+        "symbols",
+    ];
+
+    fn label_mut(&mut self, key: &str) -> Option<&mut String> {
+        Some(match key {
+            "services" => &mut self.services,
+            "messages" => &mut self.messages,
+            "enums" => &mut self.enums,
+            "usage" => &mut self.usage,
+            "usages" => &mut self.usages,
+            "copy_as_proto" => &mut self.copy_as_proto,
+            "json_example" => &mut self.json_example,
+            "deprecated" => &mut self.deprecated,
+            "file_deprecated" => &mut self.file_deprecated,
+            "replacement" => &mut self.replacement,
+            "default" => &mut self.default,
+            "has_presence" => &mut self.has_presence,
+            "no_presence" => &mut self.no_presence,
+            "packed" => &mut self.packed,
+            "redacted" => &mut self.redacted,
+            "see_also" => &mut self.see_also,
+            "operational_notes" => &mut self.operational_notes,
+            "timeout" => &mut self.timeout,
+            "retries" => &mut self.retries,
+            "retry_never" => &mut self.retry_never,
+            "retry_unavailable" => &mut self.retry_unavailable,
+            "retry_idempotent" => &mut self.retry_idempotent,
+            "attributes" => &mut self.attributes,
+            "bidirectional_streaming" => &mut self.bidirectional_streaming,
+            "idempotent" => &mut self.idempotent,
+            "no_side_effects" => &mut self.no_side_effects,
+            "allow_alias_note" => &mut self.allow_alias_note,
+            "any_note" => &mut self.any_note,
+            "try_it" => &mut self.try_it,
+            "streaming_example_note" => &mut self.streaming_example_note,
+            "file_options" => &mut self.file_options,
+            "imports" => &mut self.imports,
+            "public_import" => &mut self.public_import,
+            "weak_import" => &mut self.weak_import,
+            "source_pages" => &mut self.source_pages,
+            "dependencies" => &mut self.dependencies,
+            "symbol_index" => &mut self.symbol_index,
+            "index_page" => &mut self.index_page,
+            "package" => &mut self.package,
+            "files" => &mut self.files,
+            "symbols" => &mut self.symbols,
+            _ => return None,
+        })
+    }
+
+    /// The default labels with the configured ones in their place
+    pub(crate) fn with_overrides(overrides: BTreeMap<String, String>) -> Result<Self> {
+        let mut labels = Self::default();
+
+        for (key, label) in overrides {
+            *labels.label_mut(&key).ok_or_else(|| {
+                anyhow!(
+                    "`labels.{}` is not a label, expected one of {}",
+                    key,
+                    Self::KEYS.join(", ")
+                )
+            })? = label;
+        }
+
+        Ok(labels)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_key_should_name_a_label() {
+        let mut labels = Labels::default();
+        for key in Labels::KEYS {
+            assert!(labels.label_mut(key).is_some(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn overrides_should_replace_only_the_configured_labels() {
+        let labels =
+            Labels::with_overrides(BTreeMap::from([("messages".into(), "Messages".into())]))
+                .unwrap();
+        assert_eq!(labels, Labels::default());
+
+        let error = Labels::with_overrides(BTreeMap::from([("fields".into(), "Champs".into())]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("`labels.fields` is not a label"),
+            "{}",
+            error
+        );
+    }
+}
@@ -1,22 +1,29 @@
 use std::any::Any;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::Into;
+use std::fs;
 use std::fs::canonicalize;
 use std::fs::File;
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::anyhow;
-use anyhow::{Error, Result};
+use anyhow::{Context, Error, Result};
 use askama::filters::format;
 use askama::Template;
 use bytes::Bytes;
+use cache::{LinkedChapter, RenderCache};
 use clap::arg;
-use links::{Backlinks, ProtoSymbol};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use links::{Backlink, Backlinks, ProtoSymbol};
 use log::{debug, info, warn};
 use mdbook::book::{Book, Chapter, SectionNumber};
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 use mdbook::BookItem;
+use mdbook::Config;
 use prost::Message;
 use prost_types::field_descriptor_proto::Type;
 use prost_types::source_code_info::Location;
@@ -24,241 +31,1863 @@ use prost_types::{
     DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
     FileDescriptorSet, ServiceDescriptorProto,
 };
+use rayon::prelude::*;
 
+mod attributes;
+mod baseline;
+mod cache;
+mod diagnostics;
+mod diagram;
+mod editions;
+mod examples;
+#[cfg(test)]
+mod fixtures;
+mod graph;
+mod json_descriptor;
+mod jsonschema;
+mod labels;
 mod links;
+mod lints;
+pub mod manifest;
+pub mod model;
+mod openapi;
+mod package_sort;
 mod primitive;
+mod proto_src;
+mod proto_text;
+mod reserved;
+mod schema;
+mod search;
+mod source_page;
 mod view;
 
-use links::SymbolLink;
-use view::{ProtoFileDescriptorTemplate, ProtoNamespaceTemplate};
+/// Page of the `index_page` chapter within the output prefix
+const INDEX_PAGE: &str = "index";
+/// Page of the `generate_dot` chapter within the output prefix, prefixed with `_` as package
+/// names cannot be
+const DEPENDENCIES_PAGE: &str = "_dependencies";
+/// Page of the `symbol_index` chapter within the output prefix
+const SYMBOL_INDEX_PAGE: &str = "_symbols";
+
+use attributes::OptionAttributes;
+use diagnostics::StaleDescriptor;
+use diagram::{Diagrams, DEFAULT_DIAGRAM_NODE_LIMIT};
+use editions::EditionsFeatures;
+use examples::GrpcurlOptions;
+use graph::MessageDependencies;
+use labels::Labels;
+pub use links::BrokenLink;
+use links::{SymbolKind, SymbolLink};
+use links::{
+    DEFAULT_LINK_FUZZY_THRESHOLD, DEFAULT_MAX_CHAPTER_SIZE, DEFAULT_OUTPUT_PREFIX,
+    DEFAULT_PACKAGE_NAME, DEFAULT_SOURCE_LINK_LABEL, ROOT_PACKAGE_PAGE,
+};
+use lints::SchemaLints;
+use manifest::{SymbolIndexEntry, SymbolManifest};
+use mdbook::config::Search;
+use model::ProtoModel;
+use package_sort::PackageSort;
+pub use proto_src::protoc_command;
+use proto_src::{DescriptorSource, ProtoSrc};
+pub use schema::config_schema;
+use search::ProtoSearchIndex;
+use source_page::SOURCE_PAGES_DIR;
+use view::{
+    CommentFormat, PagePer, ProtoFileDescriptorTemplate, ProtoNamespaceTemplate, RelatedMethods,
+    SymbolSort, TypeIndex, DEFAULT_HEADING_BASE_LEVEL,
+};
 
 pub fn read_file_descriptor_set(path: &Path) -> Result<FileDescriptorSet> {
-    info!("Attempting to read {}", path.display());
+    let bytes = Bytes::from(read_descriptor_file(path)?);
 
-    let mut file = File::open(path).map_err(|e| {
-        anyhow!(
-            "Could not read file at path `{}`, does it exist here?",
+    let decoded = FileDescriptorSet::decode(bytes).with_context(|| {
+        format!(
+            "`{}` is not a valid file descriptor set, was it written by `protoc --descriptor_set_out`?",
             path.display()
         )
     })?;
 
+    info!("Successfully decoded file descriptor set");
+    Ok(decoded)
+}
+
+/// Reads the descriptor set file at `path` as the binary encoding, transcoding it if it is JSON:
+/// a `.json` file, or one starting with `{` (which can't start the binary encoding)
+fn read_descriptor_file(path: &Path) -> Result<Vec<u8>> {
+    info!("Attempting to read {}", path.display());
+
+    let mut file = File::open(path).map_err(|e| {
+        let message = match e.kind() {
+            ErrorKind::NotFound => format!(
+                "Could not find a file at path `{}`, does it exist here?",
+                path.display()
+            ),
+            _ => format!("Could not open file at path `{}`", path.display()),
+        };
+        Error::new(e).context(message)
+    })?;
+
     info!("File descriptor set file found at {}", path.display());
     let mut buffer = Vec::new();
 
-    file.read_to_end(&mut buffer)?;
+    file.read_to_end(&mut buffer)
+        .with_context(|| format!("Could not read file at path `{}`", path.display()))?;
+
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+        || buffer.first() == Some(&b'{');
+    if !is_json {
+        return Ok(buffer);
+    }
 
-    let bytes = Bytes::from(buffer);
+    json_descriptor::json_to_binary(&buffer).with_context(|| {
+        format!(
+            "`{}` is not a valid JSON file descriptor set, was it written by `buf build -o image.json`?",
+            path.display()
+        )
+    })
+}
 
-    let decoded = FileDescriptorSet::decode(bytes)
-        .map_err(|e| anyhow!("failed to parse file descriptor set as protobuf"))?;
+/// Extensions of the descriptor sets read when `proto_descriptor` is a directory
+const DESCRIPTOR_EXTENSIONS: [&str; 3] = ["pb", "binpb", "json"];
 
-    info!("Successfully decoded file descriptor set");
-    Ok(decoded)
+/// The descriptor sets at `path`: the file itself, or for a directory the `*.pb`, `*.binpb` and
+/// `*.json` files within it in name order
+fn descriptor_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)
+        .with_context(|| format!("Could not read directory at path `{}`", path.display()))?
+    {
+        let file = entry?.path();
+        let is_descriptor = file
+            .extension()
+            .is_some_and(|extension| DESCRIPTOR_EXTENSIONS.iter().any(|e| extension == *e));
+        if is_descriptor && file.is_file() {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    if files.is_empty() {
+        return Err(anyhow!(
+            "The directory at path `{}` contains no descriptor sets, expected `*.pb` or `*.binpb` files written by `protoc --descriptor_set_out` (or `*.json` from `buf build`)",
+            path.display()
+        ));
+    }
+
+    Ok(files)
+}
+
+/// Reads the descriptor set at `path`, merging every descriptor set within it when it is a
+/// directory. Files in more than one set are kept once by [`dedupe_files`].
+fn read_descriptor(path: &Path) -> Result<FileDescriptorSet> {
+    let mut merged = FileDescriptorSet::default();
+    for file in descriptor_files(path)? {
+        merged.file.extend(read_file_descriptor_set(&file)?.file);
+    }
+    Ok(merged)
+}
+
+/// The encoded descriptor sets at `path`, concatenated, which protobuf decodes as their merge
+pub(crate) fn read_descriptor_bytes(path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for file in descriptor_files(path)? {
+        bytes.extend(read_descriptor_file(&file)?);
+    }
+    Ok(bytes)
+}
+
+/// Drops files compiled into the descriptor set more than once (e.g. a vendored `common.proto` in
+/// the combined output of several `protoc` invocations), keeping the first, so their symbols are
+/// documented and referenced once. Copies that differ from the first are warned about.
+fn dedupe_files(file_descriptor_set: &mut FileDescriptorSet) {
+    let mut seen: HashMap<String, Vec<u8>> = HashMap::new();
+
+    file_descriptor_set
+        .file
+        .retain(|file| match seen.entry(file.name().to_string()) {
+            Entry::Vacant(entry) => {
+                entry.insert(file.encode_to_vec());
+                true
+            }
+            Entry::Occupied(entry) => {
+                if *entry.get() != file.encode_to_vec() {
+                    warn!(
+                        "`{}` is in the descriptor set more than once with different contents, only the first is documented",
+                        file.name()
+                    );
+                }
+                false
+            }
+        });
 }
 
 const PREPROCESSOR_NAME: &'static str = "protobuf";
 
+/// Renderers the generated chapters suit. They are html, so `markdown` output keeps the raw html
+/// (degraded, but the symbols are all there); anything else, e.g. `linkcheck` or `epub`, is left
+/// without the reference rather than given output it can't handle.
+const SUPPORTED_RENDERERS: &[&str] = &["html", "markdown"];
+
 pub struct ProtobufPreprocessor;
 
 impl ProtobufPreprocessor {
     pub fn new() -> ProtobufPreprocessor {
         ProtobufPreprocessor
     }
+
+    /// The packages and symbols of the descriptor set as plain, serializable data, i.e. what the
+    /// reference is rendered from, without the html
+    pub fn build_model(file_descriptor_set: &FileDescriptorSet) -> ProtoModel {
+        let mut file_descriptor_set = file_descriptor_set.clone();
+        dedupe_files(&mut file_descriptor_set);
+
+        // without the original bytes, editions features set on symbols are lost in decoding; the
+        // edition defaults still apply
+        let editions =
+            EditionsFeatures::decode(&file_descriptor_set.encode_to_vec()).unwrap_or_default();
+        let (namespaces, _) = build_namespaces(
+            file_descriptor_set,
+            editions,
+            OptionAttributes::default(),
+            SymbolSort::Source,
+        );
+
+        ProtoModel::from_namespaces(&namespaces)
+    }
+}
+
+/// Where the reference goes among the book's top level chapters, when it isn't nested under one
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Placement {
+    Start,
+    End,
+}
+
+impl TryFrom<&str> for Placement {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "start" => Ok(Self::Start),
+            "end" => Ok(Self::End),
+            other => Err(anyhow!(
+                "`placement` should be one of \"start\" or \"end\", got \"{}\"",
+                other
+            )),
+        }
+    }
 }
 
 pub struct ProtobufPreprocessorArgs {
     nest_under: Option<String>,
-    file_descriptor_path: PathBuf,
+    placement: Placement,
+    descriptor_source: DescriptorSource,
     proto_url_root: Option<String>,
+    /// open `[src]` links in a new tab
+    external_links_new_tab: bool,
+    /// text of the `[src]` links
+    source_link_label: String,
+    /// render the `[src]` links as an icon, titled with `source_link_label`
+    source_link_icon: bool,
+    emit_symbol_manifest: bool,
+    /// where to write the Graphviz DOT type graph, resolved against the book root
+    emit_graph: Option<PathBuf>,
+    incremental: bool,
+    /// directory of the render cache, resolved against the book root, or beside the build
+    /// directory when not configured. Configuring it turns on `incremental`
+    cache_dir: PathBuf,
+    related_methods: RelatedMethods,
+    symbol_sort: SymbolSort,
+    comment_format: CommentFormat,
+    heading_base_level: usize,
+    schema_lints: SchemaLints,
+    page_per: PagePer,
+    package_sort: PackageSort,
+    link_fuzzy_threshold: i64,
+    /// chapters larger than this many bytes are only parsed if they contain a `proto!(` link
+    max_chapter_size: usize,
+    nested_namespaces: bool,
+    /// fail, rather than warn, when the descriptor set is missing imported files
+    strict: bool,
+    /// leave deprecated symbols out of the reference
+    exclude_deprecated: bool,
+    /// render only the symbols linked from the book content, and the types they depend on
+    only_referenced: bool,
+    /// render a `grpcurl` example on each method, calling the configured server
+    grpcurl_examples: Option<GrpcurlOptions>,
+    /// render a sample JSON payload of each message
+    show_json_examples: bool,
+    /// render the gRPC path of each method, `/package.Service/Method`
+    show_method_path: bool,
+    /// leave backlinks from symbols on the same chapter out of the usages of each symbol
+    hide_same_page_backlinks: bool,
+    /// render the version a package ends in (e.g. `v2` of `api.v2`) as a badge on its page
+    show_package_version: bool,
+    /// add a chapter with the reconstructed source of each file, for `[src]` links without
+    /// `proto_url_root`
+    emit_source_pages: bool,
+    /// diagram drawn at the top of each package page
+    diagrams: Diagrams,
+    /// packages with more symbols in their diagram than this are left without one
+    diagram_node_limit: usize,
+    /// `.proto` sources the descriptor set is checked to be newer than
+    proto_src_dir: Option<PathBuf>,
+    stale_descriptor: StaleDescriptor,
+    /// add a chapter listing the sub packages at the path of each parent package without files
+    generate_package_index_pages: bool,
+    /// write the message dependency graph in DOT beside the chapters, with a chapter drawing it
+    generate_dot: bool,
+    /// add an A-Z chapter of every service, method, message and enum
+    symbol_index: bool,
+    /// add a landing chapter listing every package, as the parent of the generated chapters
+    index_page: bool,
+    /// directory of the generated chapters within the book source, without surrounding slashes
+    output_prefix: String,
+    baseline_descriptor: Option<PathBuf>,
+    baseline_chapter_suffix: bool,
+    /// chapter name of the symbols declared without a `package`
+    default_package_name: String,
+    /// package prefix left out of chapter names, without a trailing `.`
+    strip_package_prefix: Option<String>,
+    /// headings and captions of the reference, from `[preprocessor.protobuf.labels]`
+    labels: Arc<Labels>,
+    /// display names of the custom options rendered as attributes, by extension number
+    option_attributes: BTreeMap<u32, String>,
+    /// mdbook's search settings, when the search index should be emitted
+    search_index: Option<Search>,
+    book_src_dir: PathBuf,
+    config_digest: String,
 }
 
 impl ProtobufPreprocessorArgs {
     pub fn new(ctx: &PreprocessorContext) -> Result<Self> {
-        let config = ctx
-            .config
+        Self::from_config(&ctx.root, &ctx.config)
+    }
+
+    pub fn from_config(root: &Path, book_config: &Config) -> Result<Self> {
+        let config = book_config
             .get_preprocessor(PREPROCESSOR_NAME)
             .ok_or(anyhow!("Expected config"))?;
 
-        let file_descriptor_path = config
-            .get("proto_descriptor")
-            .ok_or(anyhow!("expected `proto_descriptor` key in config"))?;
+        let proto_src = book_config
+            .get_deserialized_opt::<ProtoSrc, _>(format!(
+                "preprocessor.{}.proto_src",
+                PREPROCESSOR_NAME
+            ))
+            .context(
+                "`proto_src` should be a table of `include_paths` and `files`, or `buf_module`",
+            )?;
+
+        let descriptor_source = match (proto_src, config.get("proto_descriptor")) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!(
+                    "set either `proto_descriptor` or `proto_src`, not both"
+                ))
+            }
+            (Some(proto_src), None) => DescriptorSource::src(proto_src, root)?,
+            (None, None) => {
+                return Err(anyhow!(
+                    "expected `proto_descriptor` (or `proto_src`) key in config"
+                ))
+            }
+            (None, Some(file_descriptor_path)) => {
+                let mut path = root.to_path_buf();
+                path.push(
+                    file_descriptor_path
+                        .as_str()
+                        .ok_or(anyhow!("`proto_descriptor` should be a string"))?,
+                );
+
+                DescriptorSource::File(canonicalize(path.clone()).map_err(|e| {
+                    anyhow!(
+                        "Failed to find `proto_descriptor` at path {}",
+                        path.display()
+                    )
+                })?)
+            }
+        };
+
+        let related_methods =
+            Self::option(book_config, "related_methods")?.unwrap_or(RelatedMethods::Manual);
+        let symbol_sort = Self::option(book_config, "symbol_sort")?.unwrap_or(SymbolSort::Source);
+        let comment_format =
+            Self::option(book_config, "comment_format")?.unwrap_or(CommentFormat::Markdown);
+        let schema_lints = Self::option(book_config, "schema_lints")?.unwrap_or(SchemaLints::Warn);
+        let page_per = Self::option(book_config, "page_per")?.unwrap_or(PagePer::Package);
+        let placement = Self::option(book_config, "placement")?.unwrap_or(Placement::End);
+        let package_sort =
+            Self::option(book_config, "package_sort")?.unwrap_or(PackageSort::Natural);
+
+        let heading_base_level = match config.get("heading_base_level") {
+            Some(v) => v
+                .as_integer()
+                .filter(|level| (1..=6).contains(level))
+                .ok_or(anyhow!(
+                    "`heading_base_level` should be an integer from 1 to 6"
+                ))? as usize,
+            None => DEFAULT_HEADING_BASE_LEVEL,
+        };
+
+        let link_fuzzy_threshold = match config.get("link_fuzzy_threshold") {
+            Some(v) => v
+                .as_integer()
+                .filter(|threshold| *threshold >= 0)
+                .ok_or(anyhow!(
+                    "`link_fuzzy_threshold` should be a non-negative integer"
+                ))?,
+            None => DEFAULT_LINK_FUZZY_THRESHOLD,
+        };
+
+        let max_chapter_size = match config.get("max_chapter_size") {
+            Some(v) => v
+                .as_integer()
+                .and_then(|size| usize::try_from(size).ok())
+                .ok_or(anyhow!(
+                    "`max_chapter_size` should be a non-negative integer (bytes)"
+                ))?,
+            None => DEFAULT_MAX_CHAPTER_SIZE,
+        };
+
+        let diagrams = Self::option(book_config, "diagrams")?.unwrap_or(Diagrams::Off);
+        let diagram_node_limit = match config.get("diagram_node_limit") {
+            Some(v) => v
+                .as_integer()
+                .and_then(|limit| usize::try_from(limit).ok())
+                .ok_or(anyhow!(
+                    "`diagram_node_limit` should be a non-negative integer"
+                ))?,
+            None => DEFAULT_DIAGRAM_NODE_LIMIT,
+        };
 
-        let mut path = ctx.root.clone();
-        path.push(
-            file_descriptor_path
+        let output_prefix = match config.get("output_prefix") {
+            Some(v) => v
                 .as_str()
-                .ok_or(anyhow!("`proto_descriptor` should be a string"))?,
-        );
+                .map(|prefix| prefix.trim_matches('/'))
+                .filter(|prefix| !prefix.is_empty())
+                .ok_or(anyhow!(
+                    "`output_prefix` should be a non-empty path, e.g. \"reference/grpc\""
+                ))?
+                .to_string(),
+            None => DEFAULT_OUTPUT_PREFIX.to_string(),
+        };
 
-        let file_descriptor_path = canonicalize(path.clone()).map_err(|e| {
-            anyhow!(
-                "Failed to find `proto_descriptor` at path {}",
-                path.display()
-            )
-        })?;
+        let default_package_name = match config.get("default_package_name") {
+            Some(v) => v
+                .as_str()
+                .filter(|name| !name.trim().is_empty())
+                .ok_or(anyhow!(
+                    "`default_package_name` should be a non-empty string"
+                ))?
+                .to_string(),
+            None => DEFAULT_PACKAGE_NAME.to_string(),
+        };
+
+        let source_link_label = match config.get("source_link_label") {
+            Some(v) => v
+                .as_str()
+                .filter(|label| !label.trim().is_empty())
+                .ok_or(anyhow!("`source_link_label` should be a non-empty string"))?
+                .to_string(),
+            None => DEFAULT_SOURCE_LINK_LABEL.to_string(),
+        };
+
+        // not canonicalized, as a missing directory only skips the staleness check
+        let proto_src_dir = config
+            .get("proto_src_dir")
+            .map(|v| {
+                v.as_str()
+                    .map(|dir| root.join(dir))
+                    .ok_or(anyhow!("`proto_src_dir` should be a string"))
+            })
+            .transpose()?;
+        let stale_descriptor =
+            Self::option(book_config, "stale_descriptor")?.unwrap_or(StaleDescriptor::Warn);
+
+        let emit_graph = config
+            .get("emit_graph")
+            .map(|v| {
+                v.as_str().map(|path| root.join(path)).ok_or(anyhow!(
+                    "`emit_graph` should be a path, e.g. \"build/types.dot\""
+                ))
+            })
+            .transpose()?;
+
+        let cache_dir = config
+            .get("cache_dir")
+            .map(|v| {
+                v.as_str().map(|dir| root.join(dir)).ok_or(anyhow!(
+                    "`cache_dir` should be a path, e.g. \"target/mdbook-protobuf\""
+                ))
+            })
+            .transpose()?;
+
+        let baseline_descriptor = config
+            .get("baseline_descriptor")
+            .map(|v| {
+                let path = root.join(
+                    v.as_str()
+                        .ok_or(anyhow!("`baseline_descriptor` should be a string"))?,
+                );
+                canonicalize(&path).map_err(|_| {
+                    anyhow!(
+                        "Failed to find `baseline_descriptor` at path {}",
+                        path.display()
+                    )
+                })
+            })
+            .transpose()?;
+
+        let labels = book_config
+            .get_deserialized_opt::<BTreeMap<String, String>, _>(format!(
+                "preprocessor.{}.labels",
+                PREPROCESSOR_NAME
+            ))
+            .map_err(|_| anyhow!("`labels` should be a table of strings"))?
+            .map(Labels::with_overrides)
+            .transpose()?
+            .unwrap_or_default();
+
+        let option_attributes = book_config
+            .get_deserialized_opt::<BTreeMap<String, String>, _>(format!(
+                "preprocessor.{}.option_attributes",
+                PREPROCESSOR_NAME
+            ))
+            .map_err(|_| anyhow!("`option_attributes` should be a table of strings"))?
+            .map(attributes::parse_attribute_options)
+            .transpose()?
+            .unwrap_or_default();
+
+        // `show_examples = true` calls the default server, unless the table configures another
+        let grpcurl_examples = book_config
+            .get_deserialized_opt::<GrpcurlOptions, _>(format!(
+                "preprocessor.{}.grpcurl_examples",
+                PREPROCESSOR_NAME
+            ))
+            .map_err(|_| {
+                anyhow!("`grpcurl_examples` should be a table of `default_host`, `plaintext`, `insecure`, `cacert`, `cert` and `key`")
+            })?
+            .or_else(|| {
+                config
+                    .get("show_examples")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false)
+                    .then(GrpcurlOptions::default)
+            });
+
+        let emit_search_index = config
+            .get("emit_search_index")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let search_index = emit_search_index.then(|| {
+            book_config
+                .html_config()
+                .and_then(|html| html.search)
+                .unwrap_or_default()
+        });
 
         Ok(Self {
-            file_descriptor_path,
+            descriptor_source,
             nest_under: config
                 .get("nest_under")
                 .and_then(|v| v.as_str().map(|s| s.to_string())),
+            placement,
             proto_url_root: config
                 .get("proto_url_root")
                 .and_then(|v| v.as_str().map(|s| s.to_string())),
+            external_links_new_tab: config
+                .get("external_links_new_tab")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            source_link_label,
+            source_link_icon: config
+                .get("source_link_icon")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            emit_symbol_manifest: config
+                .get("emit_symbol_manifest")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            emit_graph,
+            incremental: cache_dir.is_some()
+                || config
+                    .get("incremental")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            cache_dir: cache_dir
+                .unwrap_or_else(|| cache::default_cache_dir(root, &book_config.build.build_dir)),
+            related_methods,
+            symbol_sort,
+            comment_format,
+            heading_base_level,
+            schema_lints,
+            page_per,
+            package_sort,
+            link_fuzzy_threshold,
+            max_chapter_size,
+            nested_namespaces: config
+                .get("nested_namespaces")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            strict: config
+                .get("strict")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            exclude_deprecated: config
+                .get("exclude_deprecated")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            only_referenced: config
+                .get("only_referenced")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            proto_src_dir,
+            stale_descriptor,
+            grpcurl_examples,
+            show_json_examples: config
+                .get("show_json_examples")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            show_method_path: config
+                .get("show_method_path")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            hide_same_page_backlinks: config
+                .get("hide_same_page_backlinks")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            show_package_version: config
+                .get("show_package_version")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            emit_source_pages: config
+                .get("emit_source_pages")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            diagrams,
+            diagram_node_limit,
+            generate_package_index_pages: config
+                .get("generate_package_index_pages")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            generate_dot: config
+                .get("generate_dot")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            symbol_index: config
+                .get("symbol_index")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            index_page: config
+                .get("index_page")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            labels: Arc::new(labels),
+            option_attributes,
+            search_index,
+            output_prefix,
+            baseline_descriptor,
+            baseline_chapter_suffix: config
+                .get("baseline_chapter_suffix")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            default_package_name,
+            strip_package_prefix: config
+                .get("strip_package_prefix")
+                .and_then(|v| v.as_str())
+                .map(|prefix| prefix.trim_end_matches('.').to_string())
+                .filter(|prefix| !prefix.is_empty()),
+            book_src_dir: root.join(&book_config.book.src),
+            config_digest: serde_json::to_string(config)?,
         })
     }
-}
 
-impl Preprocessor for ProtobufPreprocessor {
-    fn name(&self) -> &str {
-        PREPROCESSOR_NAME
+    /// Chapter name of a package: the `default_package_name` for files without one, otherwise the
+    /// package less any `strip_package_prefix` (unless nothing would be left)
+    fn chapter_title<'a>(&'a self, package: &'a str) -> &'a str {
+        if package.is_empty() {
+            return &self.default_package_name;
+        }
+
+        self.strip_package_prefix
+            .as_deref()
+            .and_then(|prefix| package.strip_prefix(prefix))
+            .and_then(|rest| rest.strip_prefix('.'))
+            .filter(|rest| !rest.is_empty())
+            .unwrap_or(package)
     }
 
-    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
-        let args = ProtobufPreprocessorArgs::new(ctx)?;
+    /// The descriptor set file, built from `proto_src` the first time it is asked for
+    fn file_descriptor_path(&self) -> Result<&Path> {
+        self.descriptor_source.path()
+    }
+
+    /// Reads a string option with a fixed set of allowed values
+    fn option<T>(book_config: &Config, key: &str) -> Result<Option<T>>
+    where
+        T: for<'a> TryFrom<&'a str, Error = Error>,
+    {
+        book_config
+            .get(&format!("preprocessor.{}.{}", PREPROCESSOR_NAME, key))
+            .map(|v| {
+                v.as_str()
+                    .ok_or(anyhow!("`{}` should be a string", key))
+                    .and_then(T::try_from)
+            })
+            .transpose()
+    }
+}
 
-        let file_descriptor_set = read_file_descriptor_set(args.file_descriptor_path.as_path())?;
+/// Templates keyed by package, with the usages of every symbol they define or reference
+type Namespaces = (
+    BTreeMap<String, ProtoNamespaceTemplate>,
+    HashMap<SymbolLink, Vec<Backlink>>,
+);
 
-        info!("found {} proto files", file_descriptor_set.file.len());
+/// Decodes every file in the set into per-package templates, registering each symbol (and the
+/// symbols its fields and methods reference) in the returned usages map.
+fn build_namespaces(
+    file_descriptor_set: FileDescriptorSet,
+    editions: EditionsFeatures,
+    attributes: OptionAttributes,
+    symbol_sort: SymbolSort,
+) -> Namespaces {
+    info!("found {} proto files", file_descriptor_set.file.len());
 
-        let mut namespaces: BTreeMap<String, ProtoNamespaceTemplate> = BTreeMap::new();
+    let mut namespaces: BTreeMap<String, ProtoNamespaceTemplate> = BTreeMap::new();
 
-        let mut symbol_usages: HashMap<SymbolLink, Vec<links::Backlink>> = HashMap::new();
+    let mut symbol_usages: HashMap<SymbolLink, Vec<Backlink>> = HashMap::new();
 
-        let packages: HashSet<String> = file_descriptor_set
-            .file
-            .iter()
-            .map(|f| f.package().to_string())
-            .collect();
+    let types = TypeIndex::new(&file_descriptor_set.file)
+        .with_editions(editions)
+        .with_attributes(attributes);
 
-        for file_descriptor in file_descriptor_set.file {
-            let value = namespaces
-                .entry(file_descriptor.package().to_string())
-                .or_default();
+    for file_descriptor in file_descriptor_set.file {
+        let value = namespaces
+            .entry(file_descriptor.package().to_string())
+            .or_default();
 
-            value.add_file(ProtoFileDescriptorTemplate::from_descriptor(
-                file_descriptor,
-                &packages,
-                &mut symbol_usages,
-            ));
+        value.add_file(ProtoFileDescriptorTemplate::from_descriptor(
+            file_descriptor,
+            &types,
+            symbol_sort,
+            &mut symbol_usages,
+        ));
+    }
+
+    (namespaces, symbol_usages)
+}
+
+/// Reads and lints the configured descriptor, then builds the per-package templates from it
+fn load_namespaces(args: &ProtobufPreprocessorArgs) -> Result<Namespaces> {
+    let mut file_descriptor_set = read_descriptor(args.file_descriptor_path()?)?;
+    dedupe_files(&mut file_descriptor_set);
+    diagnostics::check_descriptor(&file_descriptor_set, args.strict)?;
+    lints::lint_field_numbers(&file_descriptor_set, args.schema_lints)?;
+
+    let package_changes = match &args.baseline_descriptor {
+        Some(baseline_path) => {
+            let baseline = read_file_descriptor_set(baseline_path)?;
+            baseline::package_changes(&file_descriptor_set, &baseline)
+        }
+        None => BTreeMap::new(),
+    };
+
+    // `prost_types` predates editions, so their features are decoded from the descriptor separately
+    // as are custom options, which are extensions
+    let descriptor_bytes = read_descriptor_bytes(args.file_descriptor_path()?)?;
+    let editions = EditionsFeatures::decode(&descriptor_bytes)?;
+    let attributes = OptionAttributes::decode(&descriptor_bytes, &args.option_attributes)?;
+
+    let (mut namespaces, symbol_usages) =
+        build_namespaces(file_descriptor_set, editions, attributes, args.symbol_sort);
+
+    let symbol_usages = if args.exclude_deprecated {
+        links::remove_deprecated(&mut namespaces, symbol_usages)
+    } else {
+        symbol_usages
+    };
+
+    for (package, change) in package_changes {
+        if let Some(namespace) = namespaces.get_mut(&package) {
+            namespace.set_change(change);
         }
+    }
 
-        for book_item in &mut book.sections {
-            if let BookItem::Chapter(chapter) = book_item {
-                links::link_proto_symbols(chapter, &mut symbol_usages)?;
+    if args.show_package_version {
+        for (package, namespace) in namespaces.iter_mut() {
+            if let Some(version) = package_sort::package_version(package) {
+                namespace.set_version(version);
             }
         }
+    }
 
-        links::assign_backlinks(&mut namespaces, symbol_usages);
+    let symbol_usages = match args.page_per {
+        PagePer::Package => symbol_usages,
+        PagePer::File => links::assign_file_pages(&mut namespaces, symbol_usages),
+    };
 
-        if let Some(source_url) = args.proto_url_root {
-            info!("assigning source url to proto symbols: {}", &source_url);
-            links::assign_source_url(&mut namespaces, source_url);
-        } else {
-            warn!("proto_url_root was not set, so `[src]` links will not go to the correct destination");
+    let symbol_usages = if args.output_prefix != DEFAULT_OUTPUT_PREFIX {
+        links::assign_output_prefix(&mut namespaces, symbol_usages, &args.output_prefix)
+    } else {
+        symbol_usages
+    };
+
+    Ok((namespaces, symbol_usages))
+}
+
+/// Renders one chapter per package, not yet numbered or placed within the book
+fn render_chapters(
+    mut namespaces: BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+    args: &ProtobufPreprocessorArgs,
+) -> Result<Vec<Chapter>> {
+    // the graph is of every symbol, not only those rendered
+    if let Some(graph_path) = &args.emit_graph {
+        info!("writing type graph to {}", graph_path.display());
+        if let Err(e) = graph::write(&ProtoModel::from_namespaces(&namespaces), graph_path) {
+            warn!("skipping the type graph: {:#}", e);
         }
+    }
 
-        // @todo support searching sub chapters
-        let target_chapter = if let Some(nest_under) = args.nest_under {
-            let found_section = book.sections.iter_mut().find_map(|s| match s {
-                BookItem::Chapter(c) => {
-                    if c.name == nest_under {
-                        Some(c)
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            });
+    let symbol_usages = if args.only_referenced {
+        links::retain_referenced(&mut namespaces, symbol_usages)
+    } else {
+        symbol_usages
+    };
 
-            if let None = found_section {
-                warn!("`nest_under` config was defined, but no chapter matching name `{}` was found. Note nested chapters are not yet supported.", nest_under);
-            }
+    // once the files are final, so that only the imports of rendered files are linked
+    links::assign_import_links(&mut namespaces, args.page_per, &args.output_prefix);
 
-            found_section
+    // the reference pages of the symbols, to link the type references of the source pages to
+    let symbol_hrefs: HashMap<String, String> = if args.emit_source_pages {
+        symbol_usages
+            .keys()
+            .map(|link| (link.fqsl(), link.href()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let diagrams = match args.diagrams {
+        Diagrams::Mermaid => {
+            diagram::package_diagrams(&namespaces, &symbol_usages, args.diagram_node_limit)
+        }
+        Diagrams::Off => HashMap::new(),
+    };
+
+    let dependencies = if args.generate_dot {
+        let dependencies = MessageDependencies::new(&namespaces, &symbol_usages);
+        let dot_path = args
+            .book_src_dir
+            .join(&args.output_prefix)
+            .join(graph::DEPENDENCIES_FILE);
+        info!("writing message dependency graph to {}", dot_path.display());
+        if let Err(e) = dependencies.write(&dot_path) {
+            warn!("skipping the message dependency graph: {:#}", e);
+        }
+        Some(dependencies)
+    } else {
+        None
+    };
+
+    links::resolve_comment_links(&mut namespaces, &symbol_usages);
+
+    for namespace in namespaces.values_mut() {
+        namespace.assign_related_methods(args.related_methods);
+        namespace.set_heading_base_level(args.heading_base_level);
+        namespace.set_comment_format(args.comment_format);
+        namespace.set_external_links_new_tab(args.external_links_new_tab);
+        if !args.show_method_path {
+            namespace.hide_method_paths();
+        }
+    }
+
+    if args.grpcurl_examples.is_some() || args.show_json_examples {
+        let model = ProtoModel::from_namespaces(&namespaces);
+        let examples = match &args.grpcurl_examples {
+            Some(options) => examples::grpcurl_examples(&model, options),
+            None => HashMap::new(),
+        };
+        let json_examples = if args.show_json_examples {
+            examples::json_examples(&model)
         } else {
-            None
+            HashMap::new()
         };
+        for namespace in namespaces.values_mut() {
+            namespace.set_examples(&examples);
+            namespace.set_json_examples(&json_examples);
+        }
+    }
 
-        let chapters: Result<Vec<Chapter>> = namespaces
-            .iter()
-            .map(|(namespace_key, namespace)| {
-                let content = namespace.render()?;
-                let path = PathBuf::from(format!("proto/{}", &namespace_key.replace(".", "/")));
-                Ok(Chapter::new(
-                    namespace_key.as_ref(),
-                    content,
+    links::assign_backlinks(
+        &mut namespaces,
+        symbol_usages,
+        args.hide_same_page_backlinks,
+    );
+
+    for namespace in namespaces.values_mut() {
+        namespace.set_labels(&args.labels);
+    }
+
+    links::assign_source_link_label(
+        &mut namespaces,
+        &args.source_link_label,
+        args.source_link_icon,
+    );
+
+    if let Some(source_url) = &args.proto_url_root {
+        info!("assigning source url to proto symbols: {}", source_url);
+        links::assign_source_url(
+            &mut namespaces,
+            source_url.clone(),
+            args.external_links_new_tab,
+        );
+    } else if args.emit_source_pages {
+        links::assign_source_pages(&mut namespaces, &args.output_prefix);
+    } else {
+        warn!(
+            "proto_url_root was not set, so `[src]` links will not go to the correct destination"
+        );
+    }
+
+    if args.emit_symbol_manifest {
+        let manifest_path = args
+            .book_src_dir
+            .join(&args.output_prefix)
+            .join(manifest::SYMBOL_MANIFEST_FILE);
+        info!("writing symbol manifest to {}", manifest_path.display());
+        SymbolManifest::from_namespaces(&namespaces).write(&manifest_path)?;
+    }
+
+    if let Some(search_config) = &args.search_index {
+        let search_index_path = args
+            .book_src_dir
+            .join(&args.output_prefix)
+            .join(search::SEARCH_INDEX_FILE);
+        info!("writing search index to {}", search_index_path.display());
+        ProtoSearchIndex::from_namespaces(&namespaces, search_config).write(&search_index_path)?;
+    }
+
+    // pointing the chapters at the descriptor (relative to the book source where possible, as
+    // mdbook expects) associates them with the file they are generated from
+    let file_descriptor_path = args.file_descriptor_path()?;
+    let source_path = file_descriptor_path
+        .strip_prefix(&args.book_src_dir)
+        .unwrap_or(file_descriptor_path)
+        .to_path_buf();
+
+    let symbol_counts = package_symbol_counts(&namespaces);
+    let file_counts: BTreeMap<String, usize> = namespaces
+        .iter()
+        .map(|(package, namespace)| (package.clone(), namespace.file_count()))
+        .collect();
+    let symbol_index = args
+        .symbol_index
+        .then(|| symbol_index_chapter(&namespaces, args, &source_path));
+
+    // packages render independently, so are rendered in parallel
+    let mut chapters = namespaces
+        .into_par_iter()
+        .map(|(namespace_key, namespace)| {
+            let title = args.chapter_title(&namespace_key).to_string();
+            // files without a `package` declaration share the empty key
+            let page = if namespace_key.is_empty() {
+                ROOT_PACKAGE_PAGE.into()
+            } else {
+                namespace_key.replace(".", "/")
+            };
+            let path = PathBuf::from(format!("{}/{}", args.output_prefix, page));
+            let change = namespace.change();
+            let name = match change {
+                Some(change) if args.baseline_chapter_suffix => {
+                    format!("{}{}", title, change.chapter_suffix())
+                }
+                _ => title.clone(),
+            };
+
+            let diagram = diagrams
+                .get(&namespace_key)
+                .map(String::as_str)
+                .unwrap_or_default();
+
+            let chapter = match args.page_per {
+                PagePer::Package => Chapter::new(
+                    &name,
+                    format!("{}{}", diagram, namespace.render()?),
                     path,
                     Vec::new(),
-                ))
-            })
-            .collect();
+                ),
+                PagePer::File => {
+                    let badge = namespace
+                        .badges()
+                        .map(|badges| format!("{}\n\n", badges))
+                        .unwrap_or_default();
+                    let files = namespace.into_files();
 
-        if let Some(target) = target_chapter {
-            for (idx, mut chapter) in chapters?.into_iter().enumerate() {
-                let mut section_number = target.clone().number.unwrap().0;
-                section_number.push((idx + 1) as u32);
-                chapter.number = Some(SectionNumber(section_number));
-                chapter.parent_names.extend(target.parent_names.clone());
-                chapter.parent_names.push(target.name.clone());
+                    let index: String = files
+                        .iter()
+                        .map(|(page, filename, _)| {
+                            format!("- [{}](/{}/{}.md)\n", filename, args.output_prefix, page)
+                        })
+                        .collect();
+
+                    let mut chapter = Chapter::new(
+                        &name,
+                        format!("# {}\n\n{}{}{}", title, badge, diagram, index),
+                        path,
+                        Vec::new(),
+                    );
 
-                let section = BookItem::Chapter(chapter);
+                    for (page, filename, file) in files {
+                        let mut file_chapter = Chapter::new(
+                            &filename,
+                            file.render()?,
+                            format!("{}/{}", args.output_prefix, page),
+                            vec![name.clone()],
+                        );
+                        file_chapter.source_path = Some(source_path.clone());
+                        chapter.sub_items.push(BookItem::Chapter(file_chapter));
+                    }
+
+                    chapter
+                }
+            };
+
+            Ok((
+                namespace_key,
+                Chapter {
+                    source_path: Some(source_path.clone()),
+                    ..chapter
+                },
+            ))
+        })
+        .collect::<Result<BTreeMap<String, Chapter>>>()?;
 
-                target.sub_items.push(section);
+    let mut chapters = if args.nested_namespaces {
+        nest_namespace_chapters(chapters, &symbol_counts, args, &source_path)
+    } else {
+        if args.generate_package_index_pages {
+            let packages = package_hierarchy(chapters.keys());
+            for package in &packages {
+                if !chapters.contains_key(package) {
+                    let index = package_index_chapter(
+                        package,
+                        &packages,
+                        &symbol_counts,
+                        args,
+                        &source_path,
+                    );
+                    chapters.insert(package.clone(), index);
+                }
             }
+        }
+
+        let mut chapters: Vec<(String, Chapter)> = chapters.into_iter().collect();
+        chapters.sort_by(|(a, _), (b, _)| args.package_sort.compare(a, b));
+        chapters.into_iter().map(|(_, chapter)| chapter).collect()
+    };
+
+    if args.emit_source_pages {
+        chapters.push(source_pages_chapter(args, &symbol_hrefs, &source_path)?);
+    }
+
+    if let Some(dependencies) = dependencies {
+        let title = &args.labels.dependencies;
+        let mut chapter = Chapter::new(
+            title,
+            format!(
+                "# {}\n\n{}\n[{}]({})\n",
+                title,
+                dependencies.mermaid(),
+                graph::DEPENDENCIES_FILE,
+                graph::DEPENDENCIES_FILE
+            ),
+            format!("{}/{}", args.output_prefix, DEPENDENCIES_PAGE),
+            Vec::new(),
+        );
+        chapter.source_path = Some(source_path.clone());
+        chapters.push(chapter);
+    }
+
+    if let Some(symbol_index) = symbol_index {
+        chapters.push(symbol_index);
+    }
+
+    if args.index_page {
+        if file_counts.contains_key(INDEX_PAGE) {
+            warn!(
+                "skipping `index_page`, as the chapter of package `{}` is at its path",
+                INDEX_PAGE
+            );
         } else {
-            book.sections
-                .extend(chapters?.into_iter().map(BookItem::Chapter));
+            let mut index = index_chapter(&file_counts, &symbol_counts, args, &source_path);
+            index
+                .sub_items
+                .extend(chapters.into_iter().map(BookItem::Chapter));
+            chapters = vec![index];
         }
+    }
 
-        Ok(book)
+    Ok(chapters)
+}
+
+/// Landing chapter of the reference, listing every package with how many files and services,
+/// messages and enums it declares
+fn index_chapter(
+    file_counts: &BTreeMap<String, usize>,
+    symbol_counts: &BTreeMap<String, usize>,
+    args: &ProtobufPreprocessorArgs,
+    source_path: &Path,
+) -> Chapter {
+    let mut packages: Vec<&String> = file_counts.keys().collect();
+    packages.sort_by(|a, b| args.package_sort.compare(a, b));
+
+    let rows: String = packages
+        .into_iter()
+        .map(|package| {
+            let page = if package.is_empty() {
+                ROOT_PACKAGE_PAGE.into()
+            } else {
+                package.replace('.', "/")
+            };
+            format!(
+                "| [{}](/{}/{}.md) | {} | {} |\n",
+                args.chapter_title(package),
+                args.output_prefix,
+                page,
+                file_counts[package],
+                symbol_counts.get(package).copied().unwrap_or_default()
+            )
+        })
+        .collect();
+
+    let labels = &args.labels;
+    let title = &labels.index_page;
+    let mut chapter = Chapter::new(
+        title,
+        format!(
+            "# {}\n\n| {} | {} | {} |\n| --- | --: | --: |\n{}",
+            title, labels.package, labels.files, labels.symbols, rows
+        ),
+        format!("{}/{}", args.output_prefix, INDEX_PAGE),
+        Vec::new(),
+    );
+    chapter.source_path = Some(source_path.to_path_buf());
+    chapter
+}
+
+/// Chapter of every service, method, message and enum, sorted by name and grouped by first letter
+fn symbol_index_chapter(
+    namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+    args: &ProtobufPreprocessorArgs,
+    source_path: &Path,
+) -> Chapter {
+    // (name, package, kind, link), the name being the last segment, e.g. `SayHello` of
+    // `Greeter::SayHello`
+    let mut entries: Vec<(String, String, SymbolKind, SymbolLink)> = Vec::new();
+    for (package, namespace) in namespaces {
+        namespace.visit_symbols(|symbol| {
+            if symbol.kind() == SymbolKind::Field {
+                return;
+            }
+            let link = symbol.symbol_link();
+            let id = link.id();
+            let name = id.rsplit(['.', ':']).next().unwrap_or(&id).to_string();
+            entries.push((name, package.clone(), symbol.kind(), link.clone()));
+        });
     }
+    entries.sort_by(|(a, a_package, ..), (b, b_package, ..)| {
+        a.to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b))
+            .then_with(|| args.package_sort.compare(a_package, b_package))
+    });
 
-    fn supports_renderer(&self, renderer: &str) -> bool {
-        renderer != "not-supported"
+    let title = &args.labels.symbol_index;
+    let mut content = format!("# {}\n", title);
+    let mut letter = None;
+    for (name, package, kind, link) in entries {
+        // identifiers start with a letter or `_`, the latter grouped under `#`
+        let first = name
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .map_or('#', |c| c.to_ascii_uppercase());
+        if letter != Some(first) {
+            content.push_str(&format!("\n## {}\n\n", first));
+            letter = Some(first);
+        }
+        content.push_str(&format!(
+            "- [{}]({}) {} in `{}`\n",
+            link.id(),
+            link.href(),
+            kind,
+            args.chapter_title(&package)
+        ));
     }
+
+    let mut chapter = Chapter::new(
+        title,
+        content,
+        format!("{}/{}", args.output_prefix, SYMBOL_INDEX_PAGE),
+        Vec::new(),
+    );
+    chapter.source_path = Some(source_path.to_path_buf());
+    chapter
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Chapter listing the proto files, with a chapter of the reconstructed source of each beneath it
+fn source_pages_chapter(
+    args: &ProtobufPreprocessorArgs,
+    symbol_hrefs: &HashMap<String, String>,
+    source_path: &Path,
+) -> Result<Chapter> {
+    let mut files = read_descriptor(args.file_descriptor_path()?)?.file;
+    // files in the set more than once were warned about when the namespaces were loaded
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.name().to_string()));
+    files.sort_by(|a, b| a.name().cmp(b.name()));
 
-    #[test]
-    fn it_should_read_proto_descriptor() {
-        let path = Path::new("../demo/docs/build/proto_file_descriptor_set.pb");
-        let descriptor = read_file_descriptor_set(path);
+    let title = &args.labels.source_pages;
+    let dir = format!("{}/{}", args.output_prefix, SOURCE_PAGES_DIR);
+    let index: String = files
+        .iter()
+        .map(|file| {
+            format!(
+                "- [{}](/{}/{}.md)\n",
+                file.name(),
+                dir,
+                source_page::page(file.name())
+            )
+        })
+        .collect();
 
-        assert!(descriptor.is_ok());
-        dbg!(&descriptor);
+    let mut chapter = Chapter::new(title, format!("# {}\n\n{}", title, index), &dir, Vec::new());
+    chapter.source_path = Some(source_path.to_path_buf());
+
+    for file in &files {
+        let mut file_chapter = Chapter::new(
+            file.name(),
+            format!(
+                "# {}\n\n{}",
+                file.name(),
+                source_page::source_page(file, symbol_hrefs)
+            ),
+            format!("{}/{}", dir, source_page::page(file.name())),
+            vec![title.clone()],
+        );
+        file_chapter.source_path = Some(source_path.to_path_buf());
+        chapter.sub_items.push(BookItem::Chapter(file_chapter));
     }
 
-    #[test]
-    fn preprocessor_run() {
-        let input_json = r##"[
-                {
-                    "root": "./",
-                    "config": {
-                        "book": {
-                            "authors": ["AUTHOR"],
-                            "language": "en",
-                            "multilingual": false,
-                            "src": "src",
-                            "title": "TITLE"
-                        },
-                        "preprocessor": {
-                            "protobuf": {
-                                "proto_descriptor": "../demo/docs/build/proto_file_descriptor_set.pb",
-                                "proto_url_root": "http://example.com/proto/"
-                            }
-                        }
-                    },
-                    "renderer": "html",
-                    "mdbook_version": "0.4.21"
-                },
-                {
-                    "sections": [
-                        {
-                            "Chapter": {
+    Ok(chapter)
+}
+
+/// Number of services, messages and enums (nested ones included) declared in each package
+fn package_symbol_counts(
+    namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+) -> BTreeMap<String, usize> {
+    namespaces
+        .iter()
+        .map(|(package, namespace)| {
+            let mut count = 0;
+            namespace.visit_symbols(|symbol| {
+                if matches!(
+                    symbol.kind(),
+                    SymbolKind::Service | SymbolKind::Message | SymbolKind::Enum
+                ) {
+                    count += 1;
+                }
+            });
+            (package.clone(), count)
+        })
+        .collect()
+}
+
+/// Parent package of a dotted package name, e.g. `foo.bar` for `foo.bar.baz`
+fn parent_package(package: &str) -> Option<&str> {
+    package.rsplit_once('.').map(|(parent, _)| parent)
+}
+
+/// The given packages and every package above them, e.g. `foo` and `foo.bar` for `foo.bar.baz`
+fn package_hierarchy<'a>(packages: impl Iterator<Item = &'a String>) -> BTreeSet<String> {
+    let mut hierarchy = BTreeSet::new();
+    for package in packages {
+        let mut package = package.as_str();
+        hierarchy.insert(package.to_string());
+        while let Some(parent) = parent_package(package) {
+            hierarchy.insert(parent.to_string());
+            package = parent;
+        }
+    }
+    hierarchy
+}
+
+/// Direct sub packages of `package`, in chapter order
+fn child_packages<'a>(
+    package: &str,
+    packages: &'a BTreeSet<String>,
+    args: &ProtobufPreprocessorArgs,
+) -> Vec<&'a String> {
+    let mut children: Vec<&String> = packages
+        .iter()
+        .filter(|child| parent_package(child) == Some(package))
+        .collect();
+    children.sort_by(|a, b| args.package_sort.compare(a, b));
+    children
+}
+
+/// Chapter for a package that declares no files, at the path its sub packages' URLs sit beneath,
+/// listing each sub package with how many symbols it (and its own sub packages) declares
+fn package_index_chapter(
+    package: &str,
+    packages: &BTreeSet<String>,
+    symbol_counts: &BTreeMap<String, usize>,
+    args: &ProtobufPreprocessorArgs,
+    source_path: &Path,
+) -> Chapter {
+    let index: String = child_packages(package, packages, args)
+        .into_iter()
+        .map(|child| {
+            let prefix = format!("{}.", child);
+            let count: usize = symbol_counts
+                .iter()
+                .filter(|(counted, _)| *counted == child || counted.starts_with(&prefix))
+                .map(|(_, count)| count)
+                .sum();
+
+            format!(
+                "- [{}](/{}/{}.md) ({} symbol{})\n",
+                args.chapter_title(child),
+                args.output_prefix,
+                child.replace('.', "/"),
+                count,
+                if count == 1 { "" } else { "s" }
+            )
+        })
+        .collect();
+
+    let title = args.chapter_title(package);
+    let mut chapter = Chapter::new(
+        title,
+        format!("# {}\n\n{}", title, index),
+        format!("{}/{}", args.output_prefix, package.replace('.', "/")),
+        Vec::new(),
+    );
+    chapter.source_path = Some(source_path.to_path_buf());
+    chapter
+}
+
+/// Nests each package chapter beneath the chapter of its parent package (`foo.bar.baz` under
+/// `foo.bar` under `foo`). Parent packages that declare no files get an index chapter listing
+/// their children.
+fn nest_namespace_chapters(
+    mut chapters: BTreeMap<String, Chapter>,
+    symbol_counts: &BTreeMap<String, usize>,
+    args: &ProtobufPreprocessorArgs,
+    source_path: &Path,
+) -> Vec<Chapter> {
+    let packages = package_hierarchy(chapters.keys());
+
+    let mut roots: Vec<&String> = packages
+        .iter()
+        .filter(|package| parent_package(package).is_none())
+        .collect();
+    roots.sort_by(|a, b| args.package_sort.compare(a, b));
+
+    roots
+        .into_iter()
+        .map(|package| {
+            assemble_namespace_chapter(
+                package,
+                &packages,
+                &mut chapters,
+                symbol_counts,
+                args,
+                source_path,
+            )
+        })
+        .collect()
+}
+
+/// Takes the package's chapter (or an index listing its children) with its sub packages nested
+fn assemble_namespace_chapter(
+    package: &str,
+    packages: &BTreeSet<String>,
+    chapters: &mut BTreeMap<String, Chapter>,
+    symbol_counts: &BTreeMap<String, usize>,
+    args: &ProtobufPreprocessorArgs,
+    source_path: &Path,
+) -> Chapter {
+    let mut chapter = chapters.remove(package).unwrap_or_else(|| {
+        package_index_chapter(package, packages, symbol_counts, args, source_path)
+    });
+
+    for child in child_packages(package, packages, args) {
+        let child =
+            assemble_namespace_chapter(child, packages, chapters, symbol_counts, args, source_path);
+        chapter.sub_items.push(BookItem::Chapter(child));
+    }
+
+    chapter
+}
+
+/// Records each generated chapter's ancestry and, when placed beneath a numbered chapter, numbers
+/// it, recursing into sub chapters
+fn place_chapters(
+    items: &mut [BookItem],
+    parent_number: Option<&SectionNumber>,
+    parent_names: &[String],
+) {
+    for (idx, item) in items.iter_mut().enumerate() {
+        if let BookItem::Chapter(chapter) = item {
+            chapter.number = parent_number.map(|number| {
+                let mut number = number.clone();
+                number.push((idx + 1) as u32);
+                number
+            });
+            chapter.parent_names = parent_names.to_vec();
+
+            let mut names = parent_names.to_vec();
+            names.push(chapter.name.clone());
+            place_chapters(&mut chapter.sub_items, chapter.number.as_ref(), &names);
+        }
+    }
+}
+
+/// Calls `f` with each chapter of `items`, before recursing into its sub chapters
+fn for_each_chapter_mut(
+    items: &mut [BookItem],
+    f: &mut impl FnMut(&mut Chapter) -> Result<()>,
+) -> Result<()> {
+    for item in items {
+        if let BookItem::Chapter(chapter) = item {
+            f(chapter)?;
+            for_each_chapter_mut(&mut chapter.sub_items, f)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn link_chapters(
+    book: &mut Book,
+    symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
+    args: &ProtobufPreprocessorArgs,
+) -> Result<()> {
+    for_each_chapter_mut(&mut book.sections, &mut |chapter| {
+        links::link_proto_symbols(
+            chapter,
+            symbol_usages,
+            args.link_fuzzy_threshold,
+            args.max_chapter_size,
+        )
+    })
+}
+
+/// Like `link_chapters`, but reuses the linked content and backlinks of chapters unchanged since
+/// they were linked into `previous`. Returns the linked chapters, and whether any was relinked.
+fn link_chapters_incremental(
+    book: &mut Book,
+    symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
+    args: &ProtobufPreprocessorArgs,
+    previous: &HashMap<String, LinkedChapter>,
+) -> Result<(HashMap<String, LinkedChapter>, bool)> {
+    let mut linked_chapters = HashMap::new();
+    let mut relinked = false;
+
+    for_each_chapter_mut(&mut book.sections, &mut |chapter| {
+        let key = cache::chapter_key(chapter);
+
+        if let Some(linked) = previous.get(&key) {
+            if linked.reuse(chapter, symbol_usages) {
+                debug!("chapter `{}` is unchanged, reusing its links", key);
+                linked_chapters.insert(key, linked.clone());
+                return Ok(());
+            }
+        }
+
+        // linked against the symbols alone, to collect the usages from this chapter
+        let mut chapter_usages = symbol_usages
+            .keys()
+            .map(|symbol| (symbol.clone(), Vec::new()))
+            .collect();
+        let original = chapter.content.clone();
+        links::link_proto_symbols(
+            chapter,
+            &mut chapter_usages,
+            args.link_fuzzy_threshold,
+            args.max_chapter_size,
+        )?;
+
+        let usages: Vec<(SymbolLink, Backlink)> = chapter_usages
+            .into_iter()
+            .flat_map(|(symbol, usages)| {
+                usages.into_iter().map(move |usage| (symbol.clone(), usage))
+            })
+            .collect();
+        for (symbol, usage) in &usages {
+            symbol_usages
+                .entry(symbol.clone())
+                .or_default()
+                .push(usage.clone());
+        }
+
+        linked_chapters.insert(key, LinkedChapter::new(&original, chapter, usages));
+        relinked = true;
+        Ok(())
+    })?;
+
+    Ok((linked_chapters, relinked))
+}
+
+/// Resolves every `proto!(...)` link in the book against the descriptor without rendering
+/// anything, returning the links that fail to resolve.
+pub fn validate_links(root: &Path, config: &Config, book: &Book) -> Result<Vec<BrokenLink>> {
+    let args = ProtobufPreprocessorArgs::from_config(root, config)?;
+    let (_, symbol_usages) = load_namespaces(&args)?;
+    let links: Vec<SymbolLink> = symbol_usages.into_keys().collect();
+
+    Ok(book
+        .iter()
+        .filter_map(|book_item| match book_item {
+            BookItem::Chapter(chapter) => Some(links::find_broken_links(
+                chapter,
+                &links,
+                args.link_fuzzy_threshold,
+            )),
+            _ => None,
+        })
+        .flatten()
+        .collect())
+}
+
+/// Every symbol a `proto!(...)` link can resolve to, with the page it is rendered on
+pub fn symbol_index(root: &Path, config: &Config) -> Result<Vec<SymbolIndexEntry>> {
+    let args = ProtobufPreprocessorArgs::from_config(root, config)?;
+    let (namespaces, _) = load_namespaces(&args)?;
+
+    Ok(SymbolIndexEntry::from_namespaces(&namespaces))
+}
+
+/// OpenAPI 3 document of the methods of the book's descriptor set bound to REST paths with
+/// `google.api.http` options (as for grpc-gateway), titled after the book
+pub fn openapi_spec(root: &Path, config: &Config, version: &str) -> Result<serde_json::Value> {
+    let args = ProtobufPreprocessorArgs::from_config(root, config)?;
+    let (namespaces, _) = load_namespaces(&args)?;
+    let bindings = openapi::http_bindings(&read_descriptor_bytes(args.file_descriptor_path()?)?)?;
+
+    Ok(openapi::document(
+        &ProtoModel::from_namespaces(&namespaces),
+        &bindings,
+        config.book.title.as_deref().unwrap_or("API"),
+        version,
+    ))
+}
+
+/// JSON Schema of the messages of the book's descriptor set in the protobuf JSON mapping: of one
+/// message when given (fully qualified), otherwise of all of them, titled after the book
+pub fn json_schema(
+    root: &Path,
+    config: &Config,
+    message: Option<&str>,
+) -> Result<serde_json::Value> {
+    let args = ProtobufPreprocessorArgs::from_config(root, config)?;
+    let (namespaces, _) = load_namespaces(&args)?;
+
+    jsonschema::document(
+        &ProtoModel::from_namespaces(&namespaces),
+        config.book.title.as_deref().unwrap_or("API"),
+        message,
+    )
+}
+
+/// Symbols whose fully qualified name fuzzy matches `query`, best match first
+pub fn find_symbols(root: &Path, config: &Config, query: &str) -> Result<Vec<SymbolIndexEntry>> {
+    let matcher = SkimMatcherV2::default();
+
+    let mut scored: Vec<_> = symbol_index(root, config)?
+        .into_iter()
+        .filter_map(|entry| {
+            matcher
+                .fuzzy_match(&entry.fqsl, query)
+                .map(|score| (score, entry))
+        })
+        .collect();
+
+    // stable, so equal scores keep the index order
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    Ok(scored.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// Like a full render, but skips decoding and rendering the descriptor when a cache entry matches
+/// both the descriptor and the backlinks collected from the book content.
+fn render_incremental(args: &ProtobufPreprocessorArgs, book: &mut Book) -> Result<Vec<Chapter>> {
+    let descriptor_hash = cache::descriptor_hash(args)?;
+    let cache_path = cache::cache_path(args.file_descriptor_path()?, &args.cache_dir);
+
+    let (namespaces, symbols, symbol_usages, backlinks_hash, linked_chapters) =
+        match RenderCache::load(&cache_path, &descriptor_hash) {
+            Some(mut cached) => {
+                let mut prose_usages = cached.symbol_usages();
+                let (linked_chapters, relinked) = link_chapters_incremental(
+                    book,
+                    &mut prose_usages,
+                    args,
+                    &cached.linked_chapters,
+                )?;
+                let backlinks_hash = cache::backlinks_hash(&prose_usages);
+
+                if cached.backlinks_hash == backlinks_hash {
+                    info!(
+                    "proto descriptor and references unchanged, reusing rendered chapters from {}",
+                    cache_path.display()
+                );
+                    if relinked {
+                        cached.linked_chapters = linked_chapters;
+                        cached.save(&cache_path);
+                    }
+                    return Ok(cached.chapters);
+                }
+
+                let (namespaces, mut symbol_usages) = load_namespaces(args)?;
+                let symbols = symbol_usages.keys().cloned().collect();
+
+                for (symbol, usages) in prose_usages {
+                    symbol_usages.entry(symbol).or_default().extend(usages);
+                }
+
+                (
+                    namespaces,
+                    symbols,
+                    symbol_usages,
+                    backlinks_hash,
+                    linked_chapters,
+                )
+            }
+            None => {
+                let (namespaces, mut symbol_usages) = load_namespaces(args)?;
+                let symbols = symbol_usages.keys().cloned().collect();
+                let (linked_chapters, _) =
+                    link_chapters_incremental(book, &mut symbol_usages, args, &HashMap::new())?;
+                let backlinks_hash = cache::backlinks_hash(&symbol_usages);
+
+                (
+                    namespaces,
+                    symbols,
+                    symbol_usages,
+                    backlinks_hash,
+                    linked_chapters,
+                )
+            }
+        };
+
+    let chapters = render_chapters(namespaces, symbol_usages, args)?;
+    RenderCache::new(
+        descriptor_hash,
+        symbols,
+        backlinks_hash,
+        chapters.clone(),
+        linked_chapters,
+    )
+    .save(&cache_path);
+
+    Ok(chapters)
+}
+
+impl Preprocessor for ProtobufPreprocessor {
+    fn name(&self) -> &str {
+        PREPROCESSOR_NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
+        let args = ProtobufPreprocessorArgs::new(ctx)?;
+
+        if let Some(proto_src_dir) = &args.proto_src_dir {
+            diagnostics::check_staleness(
+                args.file_descriptor_path()?,
+                proto_src_dir,
+                args.stale_descriptor,
+            )?;
+        }
+
+        let chapters = if args.incremental {
+            render_incremental(&args, &mut book)?
+        } else {
+            let (namespaces, mut symbol_usages) = load_namespaces(&args)?;
+            link_chapters(&mut book, &mut symbol_usages, &args)?;
+            render_chapters(namespaces, symbol_usages, &args)?
+        };
+
+        // @todo support searching sub chapters
+        let target_chapter = if let Some(nest_under) = &args.nest_under {
+            let found_section = book.sections.iter_mut().find_map(|s| match s {
+                BookItem::Chapter(c) => {
+                    if &c.name == nest_under {
+                        Some(c)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            });
+
+            if let None = found_section {
+                warn!("`nest_under` config was defined, but no chapter matching name `{}` was found. Note nested chapters are not yet supported.", nest_under);
+            }
+
+            found_section
+        } else {
+            None
+        };
+
+        let mut chapters: Vec<BookItem> = chapters.into_iter().map(BookItem::Chapter).collect();
+
+        if let Some(target) = target_chapter {
+            let mut parent_names = target.parent_names.clone();
+            parent_names.push(target.name.clone());
+            place_chapters(&mut chapters, target.number.as_ref(), &parent_names);
+
+            target.sub_items.extend(chapters);
+        } else {
+            place_chapters(&mut chapters, None, &[]);
+
+            match args.placement {
+                Placement::Start => {
+                    book.sections.splice(0..0, chapters);
+                }
+                Placement::End => book.sections.extend(chapters),
+            }
+        }
+
+        Ok(book)
+    }
+
+    fn supports_renderer(&self, renderer: &str) -> bool {
+        SUPPORTED_RENDERERS.contains(&renderer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn it_should_read_proto_descriptor() {
+        let path = Path::new("../demo/docs/build/proto_file_descriptor_set.pb");
+        let descriptor = read_file_descriptor_set(path);
+
+        assert!(descriptor.is_ok());
+        dbg!(&descriptor);
+    }
+
+    #[test]
+    fn it_should_explain_a_missing_descriptor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.pb");
+
+        let error = format!("{:#}", read_file_descriptor_set(&path).unwrap_err());
+
+        assert!(error.contains("Could not find a file at path"), "{}", error);
+        assert!(error.contains("missing.pb"), "{}", error);
+        // the io error is kept as the cause
+        assert!(error.contains("No such file or directory"), "{}", error);
+    }
+
+    #[test]
+    fn it_should_explain_a_file_that_is_not_a_descriptor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.toml");
+        fs::write(&path, "[book]\ntitle = \"not a descriptor\"\n").unwrap();
+
+        let error = read_file_descriptor_set(&path).unwrap_err();
+        let message = format!("{:#}", error);
+
+        assert!(
+            message.contains("is not a valid file descriptor set"),
+            "{}",
+            message
+        );
+        assert!(error.root_cause().is::<prost::DecodeError>(), "{}", message);
+    }
+
+    #[test]
+    fn it_should_read_json_descriptor_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.json");
+        fs::write(
+            &path,
+            r#"{"file": [{"name": "billing.proto", "package": "billing", "messageType": [{"name": "Invoice"}], "bufExtension": {"isImport": false}}]}"#,
+        )
+        .unwrap();
+
+        let descriptor = read_file_descriptor_set(&path).unwrap();
+        assert_eq!(descriptor.file[0].message_type[0].name(), "Invoice");
+
+        // sniffed without the extension too
+        let unnamed = dir.path().join("image");
+        fs::copy(&path, &unnamed).unwrap();
+        assert_eq!(read_file_descriptor_set(&unnamed).unwrap(), descriptor);
+
+        fs::write(&path, r#"{"file": [{"name": 1}]}"#).unwrap();
+        let message = format!("{:#}", read_file_descriptor_set(&path).unwrap_err());
+        assert!(
+            message.contains("is not a valid JSON file descriptor set"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("expected a string at line 1 column 20"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn it_should_only_support_known_renderers() {
+        let preprocessor = ProtobufPreprocessor::new();
+
+        assert!(preprocessor.supports_renderer("html"));
+        assert!(preprocessor.supports_renderer("markdown"));
+        assert!(!preprocessor.supports_renderer("linkcheck"));
+        assert!(!preprocessor.supports_renderer("epub"));
+    }
+
+    #[test]
+    fn preprocessor_run() {
+        let input_json = r##"[
+                {
+                    "root": "./",
+                    "config": {
+                        "book": {
+                            "authors": ["AUTHOR"],
+                            "language": "en",
+                            "multilingual": false,
+                            "src": "src",
+                            "title": "TITLE"
+                        },
+                        "preprocessor": {
+                            "protobuf": {
+                                "proto_descriptor": "../demo/docs/build/proto_file_descriptor_set.pb",
+                                "proto_url_root": "http://example.com/proto/"
+                            }
+                        }
+                    },
+                    "renderer": "html",
+                    "mdbook_version": "0.4.21"
+                },
+                {
+                    "sections": [
+                        {
+                            "Chapter": {
                                 "name": "Chapter 1",
                                 "content": "# Chapter 1\n [Message](proto!(Message)) [](proto!(MessageEmpty))",
                                 "number": [1],
@@ -279,4 +1908,2045 @@ mod test {
         let result = ProtobufPreprocessor::new().run(&ctx, book);
         assert!(result.is_ok());
     }
+
+    const DEMO_DESCRIPTOR: &str = "../demo/docs/build/proto_file_descriptor_set.pb";
+
+    /// Builds the preprocessor input for a book rooted at `root`, with the demo descriptor used
+    /// unless the given protobuf config overrides `proto_descriptor`.
+    fn preprocessor_input(
+        root: &Path,
+        protobuf_config: serde_json::Value,
+        sections: serde_json::Value,
+    ) -> (PreprocessorContext, Book) {
+        let mut config = serde_json::json!({
+            "proto_descriptor": canonicalize(DEMO_DESCRIPTOR).unwrap(),
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(protobuf_config.as_object().unwrap().clone());
+
+        let input = serde_json::json!([
+            {
+                "root": root,
+                "config": {
+                    "book": { "src": "src" },
+                    "preprocessor": { "protobuf": config }
+                },
+                "renderer": "html",
+                "mdbook_version": "0.4.21"
+            },
+            {
+                "sections": sections,
+                "__non_exhaustive": null
+            }
+        ]);
+
+        mdbook::preprocess::CmdPreprocessor::parse_input(input.to_string().as_bytes()).unwrap()
+    }
+
+    fn chapter_section(name: &str, content: &str) -> serde_json::Value {
+        serde_json::json!({
+            "Chapter": {
+                "name": name,
+                "content": content,
+                "number": [1],
+                "sub_items": [],
+                "path": format!("{}.md", name),
+                "source_path": format!("{}.md", name),
+                "parent_names": []
+            }
+        })
+    }
+
+    fn proto_chapters(book: &Book) -> Vec<&Chapter> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter)
+                    if chapter
+                        .path
+                        .as_ref()
+                        .is_some_and(|p| p.starts_with("proto")) =>
+                {
+                    Some(chapter)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn preprocessor_emits_symbol_manifest() {
+        let book_root = tempfile::tempdir().unwrap();
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "emit_symbol_manifest": true }),
+            serde_json::json!([]),
+        );
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let manifest_file = book_root.path().join("src/proto/symbols.json");
+        let manifest: SymbolManifest =
+            serde_json::from_reader(File::open(manifest_file).expect("manifest should exist"))
+                .expect("manifest should deserialize");
+
+        assert_eq!(manifest.version, manifest::SYMBOL_MANIFEST_VERSION);
+
+        let hello_request = manifest
+            .symbols
+            .iter()
+            .find(|s| s.fqsl == ".helloworld.HelloRequest")
+            .expect("HelloRequest should be listed");
+
+        assert_eq!(
+            hello_request,
+            &manifest::SymbolManifestEntry {
+                fqsl: ".helloworld.HelloRequest".into(),
+                kind: links::SymbolKind::Message,
+                href: "/proto/helloworld.html#HelloRequest".into(),
+                anchor: "HelloRequest".into(),
+                deprecated: false,
+                summary: Some("The request message containing the user's name.".into()),
+            }
+        );
+
+        let say_hello = manifest
+            .symbols
+            .iter()
+            .find(|s| s.fqsl == ".testing.TypeTestService::SayHello")
+            .expect("SayHello should be listed");
+
+        assert_eq!(say_hello.kind, links::SymbolKind::Method);
+        assert!(say_hello.deprecated);
+
+        assert!(manifest
+            .symbols
+            .iter()
+            .any(|s| s.fqsl == ".helloworld.HelloRequest::name"
+                && s.kind == links::SymbolKind::Field));
+    }
+
+    #[test]
+    fn it_should_link_and_validate_sub_chapters() {
+        let book_root = tempfile::tempdir().unwrap();
+        let guide = |content: &str| {
+            let mut guide = chapter_section("guide", "# Guide");
+            guide["Chapter"]["sub_items"] = serde_json::json!([chapter_section("nested", content)]);
+            serde_json::json!([guide])
+        };
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({}),
+            guide("[missing](proto!(DoesNotExistAnywhere))"),
+        );
+        let broken = validate_links(&ctx.root, &ctx.config, &book).expect("should succeed");
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].chapter, "nested");
+        assert_eq!(broken[0].query, "DoesNotExistAnywhere");
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({}),
+            guide("[HelloRequest](proto!(HelloRequest))"),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        let nested = book
+            .iter()
+            .find_map(|item| match item {
+                BookItem::Chapter(chapter) if chapter.name == "nested" => Some(chapter),
+                _ => None,
+            })
+            .unwrap();
+        assert!(nested
+            .content
+            .contains(r#"href="/proto/helloworld.md#HelloRequest""#));
+    }
+
+    #[test]
+    fn incremental_run_reuses_cached_chapters_until_inputs_change() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptor = book_root.path().join("descriptor.pb");
+        fs::copy(DEMO_DESCRIPTOR, &descriptor).unwrap();
+
+        let config =
+            serde_json::json!({ "proto_descriptor": "descriptor.pb", "incremental": true });
+        let prose = chapter_section("intro", "[HelloRequest](proto!(HelloRequest))");
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            config.clone(),
+            serde_json::json!([prose.clone()]),
+        );
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        // replace the cached chapter content so that reuse is observable
+        let cache_file = book_root
+            .path()
+            .join(".mdbook-protobuf-cache/descriptor.pb.mdbook-protobuf-cache.json");
+        let mut cached: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(&cache_file).expect("cache should be written"),
+        )
+        .unwrap();
+        for chapter in cached["chapters"].as_array_mut().unwrap() {
+            chapter["content"] = "from cache".into();
+        }
+        fs::write(&cache_file, cached.to_string()).unwrap();
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            config.clone(),
+            serde_json::json!([prose.clone()]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        let chapters = proto_chapters(&book);
+        assert!(!chapters.is_empty());
+        assert!(chapters.iter().all(|c| c.content == "from cache"));
+
+        // book content still gets linked on the skip path
+        let BookItem::Chapter(intro) = &book.sections[0] else {
+            panic!("expected chapter")
+        };
+        assert!(intro
+            .content
+            .contains(r#"href="/proto/helloworld.md#HelloRequest""#));
+
+        // a new reference into the descriptor changes the rendered backlinks
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            config.clone(),
+            serde_json::json!([chapter_section("intro", "[HelloReply](proto!(HelloReply))")]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        assert!(proto_chapters(&book)
+            .iter()
+            .all(|c| c.content != "from cache"));
+    }
+
+    #[test]
+    fn incremental_run_only_relinks_changed_chapters() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptor = book_root.path().join("descriptor.pb");
+        fs::copy(DEMO_DESCRIPTOR, &descriptor).unwrap();
+
+        let config =
+            serde_json::json!({ "proto_descriptor": "descriptor.pb", "incremental": true });
+        let unchanged = chapter_section("unchanged", "[HelloRequest](proto!(HelloRequest))");
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            config.clone(),
+            serde_json::json!([
+                unchanged.clone(),
+                chapter_section("edited", "[HelloReply](proto!(HelloReply))")
+            ]),
+        );
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        // replace the cached linked content so that reuse is observable
+        let cache_file = book_root
+            .path()
+            .join(".mdbook-protobuf-cache/descriptor.pb.mdbook-protobuf-cache.json");
+        let mut cached: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(&cache_file).expect("cache should be written"),
+        )
+        .unwrap();
+        for linked in cached["linked_chapters"]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            linked["content"] = "from cache".into();
+        }
+        fs::write(&cache_file, cached.to_string()).unwrap();
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            config.clone(),
+            serde_json::json!([
+                unchanged,
+                chapter_section("edited", "See [HelloReply](proto!(HelloReply))")
+            ]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let chapter = |name: &str| {
+            book.iter()
+                .find_map(|item| match item {
+                    BookItem::Chapter(chapter) if chapter.name == name => {
+                        Some(chapter.content.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        assert_eq!(chapter("unchanged"), "from cache");
+        assert!(chapter("edited").contains(r#"href="/proto/helloworld.md#HelloReply""#));
+
+        // the backlinks of the reused chapter are still rendered
+        let helloworld = proto_chapters(&book)
+            .into_iter()
+            .find(|chapter| chapter.name == "helloworld")
+            .unwrap();
+        assert!(helloworld.content.contains("unchanged.md"));
+    }
+
+    #[test]
+    fn incremental_cache_is_invalidated_by_config_changes() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptor = book_root.path().join("descriptor.pb");
+        fs::copy(DEMO_DESCRIPTOR, &descriptor).unwrap();
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "proto_descriptor": "descriptor.pb", "incremental": true }),
+            serde_json::json!([]),
+        );
+        let args = ProtobufPreprocessorArgs::new(&ctx).unwrap();
+        let original_hash = cache::descriptor_hash(&args).unwrap();
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        // beside the build directory, not the descriptor
+        let cache_file = cache::cache_path(args.file_descriptor_path().unwrap(), &args.cache_dir);
+        assert_eq!(
+            cache_file,
+            book_root
+                .path()
+                .join(".mdbook-protobuf-cache/descriptor.pb.mdbook-protobuf-cache.json")
+        );
+        assert!(RenderCache::load(&cache_file, &original_hash).is_some());
+        assert!(!book_root
+            .path()
+            .join("descriptor.pb.mdbook-protobuf-cache.json")
+            .exists());
+
+        let (ctx, _) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({
+                "proto_descriptor": "descriptor.pb",
+                "incremental": true,
+                "proto_url_root": "https://example.com/"
+            }),
+            serde_json::json!([]),
+        );
+        let changed_hash =
+            cache::descriptor_hash(&ProtobufPreprocessorArgs::new(&ctx).unwrap()).unwrap();
+        assert_ne!(original_hash, changed_hash);
+        assert!(RenderCache::load(&cache_file, &changed_hash).is_none());
+    }
+
+    #[test]
+    fn cache_dir_should_hold_the_cache_and_turn_on_incremental() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptor = book_root.path().join("descriptor.pb");
+        fs::copy(DEMO_DESCRIPTOR, &descriptor).unwrap();
+
+        let run = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let args = ProtobufPreprocessorArgs::new(&ctx).unwrap();
+            ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            args
+        };
+
+        let args = run(serde_json::json!({
+            "proto_descriptor": "descriptor.pb",
+            "cache_dir": "target/cache"
+        }));
+        assert!(args.incremental);
+
+        let cache_file = book_root
+            .path()
+            .join("target/cache/descriptor.pb.mdbook-protobuf-cache.json");
+        assert_eq!(
+            cache::cache_path(args.file_descriptor_path().unwrap(), &args.cache_dir),
+            cache_file
+        );
+        let original_hash = cache::descriptor_hash(&args).unwrap();
+        assert!(RenderCache::load(&cache_file, &original_hash).is_some());
+        assert!(!book_root.path().join(".mdbook-protobuf-cache").exists());
+
+        // a config change misses the cache, and the rebuild replaces it
+        let args = run(serde_json::json!({
+            "proto_descriptor": "descriptor.pb",
+            "cache_dir": "target/cache",
+            "heading_base_level": 3
+        }));
+        let changed_hash = cache::descriptor_hash(&args).unwrap();
+        assert_ne!(original_hash, changed_hash);
+        assert!(RenderCache::load(&cache_file, &original_hash).is_none());
+        assert!(RenderCache::load(&cache_file, &changed_hash).is_some());
+    }
+
+    /// (level, id) of every markdown heading, as mdbook's search indexer would see them
+    fn chapter_headings(chapter: &Chapter) -> Vec<(usize, String)> {
+        pulldown_cmark::Parser::new_ext(
+            &chapter.content,
+            pulldown_cmark::Options::ENABLE_HEADING_ATTRIBUTES,
+        )
+        .filter_map(|event| match event {
+            pulldown_cmark::Event::Start(pulldown_cmark::Tag::Heading { level, id, .. }) => {
+                Some((level as usize, id?.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn namespace_chapters_emit_a_heading_per_symbol() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({}),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let headings: Vec<(usize, String)> = proto_chapters(&book)
+            .into_iter()
+            .flat_map(chapter_headings)
+            .collect();
+
+        for expected in [
+            (2, "Greeter"),
+            (3, "Greeter::SayHello"),
+            (3, "Greeter::StreamHello"),
+            (2, "HelloRequest"),
+            (2, "HelloReply"),
+            (2, "Message"),
+            (3, "Message.NestedType"),
+            (3, "Message.NestedEnum"),
+            (2, "Enum"),
+        ] {
+            assert!(
+                headings.contains(&(expected.0, expected.1.to_string())),
+                "missing heading {:?} in {:?}",
+                expected,
+                headings
+            );
+        }
+
+        let (namespaces, _) = build_namespaces(
+            read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap(),
+            EditionsFeatures::default(),
+            OptionAttributes::default(),
+            SymbolSort::Source,
+        );
+        for symbol in SymbolManifest::from_namespaces(&namespaces).symbols {
+            if symbol.kind != links::SymbolKind::Field {
+                assert!(
+                    headings.iter().any(|(_, id)| id == &symbol.anchor),
+                    "{} has no heading",
+                    symbol.fqsl
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn heading_base_level_shifts_headings_and_toc_links_match_them() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "heading_base_level": 3 }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        for chapter in proto_chapters(&book) {
+            let headings = chapter_headings(chapter);
+
+            assert!(headings.iter().all(|(level, _)| *level >= 3));
+
+            let toc = chapter
+                .content
+                .split(r#"<nav class="toc">"#)
+                .nth(1)
+                .and_then(|rest| rest.split("</nav>").next())
+                .expect("chapter should have a toc");
+
+            let toc_ids: Vec<&str> = toc
+                .split(r##"href="#"##)
+                .skip(1)
+                .filter_map(|rest| rest.split('"').next())
+                .collect();
+
+            assert!(!toc_ids.is_empty());
+            for id in toc_ids {
+                assert!(
+                    headings.iter().any(|(_, heading_id)| heading_id == id),
+                    "toc link #{} has no heading in {:?}",
+                    id,
+                    headings
+                );
+            }
+        }
+
+        let headings: Vec<(usize, String)> = proto_chapters(&book)
+            .into_iter()
+            .flat_map(chapter_headings)
+            .collect();
+        assert!(headings.contains(&(3, "Greeter".to_string())));
+        assert!(headings.contains(&(4, "Greeter::SayHello".to_string())));
+        assert!(headings.contains(&(4, "Message.NestedType".to_string())));
+    }
+
+    #[test]
+    fn heading_base_level_should_be_a_valid_heading_level() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, _) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "heading_base_level": 7 }),
+            serde_json::json!([]),
+        );
+
+        assert!(ProtobufPreprocessorArgs::new(&ctx).is_err());
+    }
+
+    #[test]
+    fn proto_chapters_point_their_source_path_at_the_descriptor() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({}),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let descriptor = canonicalize(DEMO_DESCRIPTOR).unwrap();
+        for chapter in proto_chapters(&book) {
+            assert_eq!(chapter.source_path.as_ref(), Some(&descriptor));
+        }
+    }
+
+    /// Renders the demo descriptor's chapters directly, without a book or the preprocessor protocol
+    fn render_demo_chapters() -> Vec<Chapter> {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, _) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "proto_url_root": "https://example.com/proto/" }),
+            serde_json::json!([]),
+        );
+        let args = ProtobufPreprocessorArgs::new(&ctx).unwrap();
+
+        let (namespaces, symbol_usages) = load_namespaces(&args).unwrap();
+        render_chapters(namespaces, symbol_usages, &args).unwrap()
+    }
+
+    /// Compares each rendered demo chapter against its golden file in `snapshots/`, so template
+    /// changes show up as reviewable diffs. Run with `UPDATE_SNAPSHOTS=1` to rewrite the golden
+    /// files after an intended change.
+    #[test]
+    fn demo_chapters_match_snapshots() {
+        let snapshot_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+        let chapters = render_demo_chapters();
+        assert!(!chapters.is_empty());
+
+        let mut mismatched = Vec::new();
+        for chapter in &chapters {
+            let path = chapter.path.as_ref().unwrap();
+            let snapshot_path = snapshot_dir.join(path).with_extension("html");
+
+            if update {
+                fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+                fs::write(&snapshot_path, &chapter.content).unwrap();
+                continue;
+            }
+
+            let expected = fs::read_to_string(&snapshot_path).unwrap_or_else(|_| {
+                panic!(
+                    "no snapshot at {}, run with UPDATE_SNAPSHOTS=1 to create it",
+                    snapshot_path.display()
+                )
+            });
+            if expected != chapter.content {
+                mismatched.push(snapshot_path.display().to_string());
+            }
+        }
+
+        assert!(
+            mismatched.is_empty(),
+            "rendered chapters differ from {:?}, run with UPDATE_SNAPSHOTS=1 and review the diff",
+            mismatched
+        );
+    }
+
+    /// Every root-relative `/...md#anchor` href in the book, and whether a chapter at that path
+    /// has an element with that id
+    fn book_hrefs_resolve(book: &Book) -> Vec<(String, bool)> {
+        let chapters: Vec<&Chapter> = book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter),
+                _ => None,
+            })
+            .collect();
+
+        let has_anchor = |page: &str, anchor: &str| {
+            chapters.iter().any(|chapter| {
+                chapter
+                    .path
+                    .as_ref()
+                    .is_some_and(|path| path.with_extension("") == Path::new(page))
+                    && (chapter_headings(chapter).iter().any(|(_, id)| id == anchor)
+                        || chapter.content.contains(&format!(r#"id="{}""#, anchor)))
+            })
+        };
+
+        chapters
+            .iter()
+            .flat_map(|chapter| chapter.content.split(r#"href="/"#).skip(1))
+            .filter_map(|rest| rest.split('"').next())
+            .filter(|href| href.contains(".md#"))
+            .map(|href| {
+                let (page, anchor) = href.split_once(".md#").unwrap();
+                (href.to_string(), has_anchor(page, anchor))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exclude_deprecated_leaves_deprecated_symbols_out_of_the_reference() {
+        let book_root = tempfile::tempdir().unwrap();
+        let testing_chapter = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            assert!(book_hrefs_resolve(&book)
+                .iter()
+                .all(|(_, resolves)| *resolves));
+
+            proto_chapters(&book)
+                .into_iter()
+                .find(|chapter| chapter.name == "testing")
+                .expect("should have a testing chapter")
+                .clone()
+        };
+        let deprecated = [
+            "{#TypeTest.DeprecatedMessage",
+            r##"href="#TypeTest.DeprecatedMessage""##,
+            r#"id="TypeTest::deprecated_bool""#,
+            "{#TypeTestService::SayHello",
+        ];
+
+        let all = testing_chapter(serde_json::json!({}));
+        for symbol in deprecated {
+            assert!(all.content.contains(symbol), "{}", symbol);
+        }
+
+        let current = testing_chapter(serde_json::json!({ "exclude_deprecated": true }));
+        for symbol in deprecated {
+            assert!(!current.content.contains(symbol), "{}", symbol);
+        }
+        assert!(current.content.contains("{#TypeTest "));
+        assert!(current.content.contains(r#"id="TypeTest::optional_bool""#));
+    }
+
+    #[test]
+    fn only_referenced_should_omit_symbols_the_content_does_not_depend_on() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "only_referenced": true }),
+            serde_json::json!([chapter_section(
+                "Usage",
+                "See [the message](proto!(.testing.Message))"
+            )]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        assert!(book_hrefs_resolve(&book)
+            .iter()
+            .all(|(_, resolves)| *resolves));
+
+        let chapters = proto_chapters(&book);
+        let names: Vec<&str> = chapters
+            .iter()
+            .map(|chapter| chapter.name.as_str())
+            .collect();
+        assert_eq!(names, ["google.protobuf", "testing"]);
+
+        let testing = &chapters[1].content;
+        // the linked message, its nested types, and the enum one of its fields refers to
+        assert!(testing.contains("{#Message "));
+        assert!(testing.contains("{#Message.NestedType "));
+        assert!(testing.contains("{#Enum "));
+        for omitted in ["{#RepeatedTypes ", "{#TypeTest ", "{#TypeTestService "] {
+            assert!(!testing.contains(omitted), "{}", omitted);
+        }
+        assert!(chapters[0].content.contains("{#Empty "));
+    }
+
+    #[test]
+    fn emit_source_pages_should_link_src_to_the_reconstructed_source() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "emit_source_pages": true }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        let hrefs = book_hrefs_resolve(&book);
+        assert!(hrefs.iter().all(|(_, resolves)| *resolves), "{:?}", hrefs);
+
+        let chapters = proto_chapters(&book);
+        let helloworld = chapters
+            .iter()
+            .find(|chapter| chapter.name == "helloworld")
+            .unwrap();
+        assert!(helloworld
+            .content
+            .contains(r#"<a class="source" href="/proto/_source/helloworld.md#HelloRequest">"#));
+
+        let source = chapters
+            .iter()
+            .find(|chapter| chapter.path == Some(PathBuf::from("proto/_source/helloworld")))
+            .expect("should have a source page per file");
+        assert_eq!(source.name, "helloworld.proto");
+        assert_eq!(source.parent_names, ["Proto sources"]);
+        assert!(source
+            .content
+            .contains(r#"<span id="HelloRequest">message HelloRequest {</span>"#));
+        assert!(source.content.contains(
+            r#"returns (<a href="/proto/helloworld.md#HelloReply">.helloworld.HelloReply</a>);"#
+        ));
+    }
+
+    #[test]
+    fn diagrams_should_draw_the_references_of_each_package() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "diagrams": "mermaid", "diagram_node_limit": 10 }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        let chapters = proto_chapters(&book);
+        let chapter = |name: &str| {
+            chapters
+                .iter()
+                .find(|chapter| chapter.name == name)
+                .unwrap()
+        };
+
+        let helloworld = &chapter("helloworld").content;
+        assert!(
+            helloworld.starts_with("```mermaid\nflowchart LR\n"),
+            "{}",
+            helloworld
+        );
+        assert!(helloworld
+            .contains("    n0([\"Greeter\"])\n    n1[\"HelloReply\"]\n    n2[\"HelloRequest\"]\n"));
+        assert!(helloworld.contains("    n0 -->|\"SayHello, StreamHello\"| n2\n"));
+        assert!(helloworld.contains("    click n2 href \"/proto/helloworld.html#HelloRequest\"\n"));
+
+        // the reference from another package links to its page
+        let nested = &chapter("testing.nested").content;
+        assert!(nested.contains("    n0[\"testing.TypeTest\"]\n    n1{{\"Foo\"}}\n"));
+        assert!(nested.contains("    n0 -->|\"foreign_type_deeper\"| n1\n"));
+        assert!(nested.contains("    click n0 href \"/proto/testing.html#TypeTest\"\n"));
+
+        // over the node limit
+        assert!(!chapter("testing").content.contains("```mermaid"));
+    }
+
+    #[test]
+    fn labels_should_localize_the_generated_reference() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "related_methods": "heuristic", "labels": {
+                "services": "Services RPC",
+                "enums": "Énumérations",
+                "usage": "utilisation",
+                "copy_as_proto": "Copier en proto",
+                "see_also": "Voir aussi",
+            } }),
+            serde_json::json!([chapter_section(
+                "Usage",
+                "See [the greeter](proto!(Greeter))"
+            )]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        let content: String = proto_chapters(&book)
+            .iter()
+            .map(|chapter| chapter.content.as_str())
+            .collect();
+
+        for label in [
+            "<label>Services RPC</label>",
+            "<label>Énumérations</label>",
+            "1&nbsp;utilisation",
+            "<summary>Copier en proto</summary>",
+            "Voir aussi:&nbsp;",
+            // labels left unconfigured stay in English
+            "<label>Messages</label>",
+        ] {
+            assert!(content.contains(label), "{}", label);
+        }
+        for label in ["<label>Services</label>", "Copy as proto", "1&nbsp;usage"] {
+            assert!(!content.contains(label), "{}", label);
+        }
+
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "labels": { "fields": "Champs" } }),
+            serde_json::json!([]),
+        );
+        assert!(ProtobufPreprocessor::new().run(&ctx, book).is_err());
+    }
+
+    #[test]
+    fn proto_src_should_not_be_combined_with_proto_descriptor() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, _) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "proto_src": { "files": ["helloworld.proto"] } }),
+            serde_json::json!([]),
+        );
+
+        let error = ProtobufPreprocessorArgs::new(&ctx).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "set either `proto_descriptor` or `proto_src`, not both"
+        );
+    }
+
+    #[test]
+    fn stale_descriptor_should_fail_the_build_when_configured() {
+        let book_root = tempfile::tempdir().unwrap();
+        fs::create_dir(book_root.path().join("proto")).unwrap();
+        // written after the demo descriptor set
+        fs::write(book_root.path().join("proto/edited.proto"), "").unwrap();
+
+        let run = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            ProtobufPreprocessor::new().run(&ctx, book)
+        };
+
+        let error =
+            run(serde_json::json!({ "proto_src_dir": "proto", "stale_descriptor": "error" }))
+                .unwrap_err();
+        assert!(error.to_string().contains("(edited.proto)"), "{}", error);
+        assert!(run(serde_json::json!({ "proto_src_dir": "proto" })).is_ok());
+        assert!(run(serde_json::json!({ "stale_descriptor": "error" })).is_ok());
+    }
+
+    #[test]
+    fn show_examples_renders_grpcurl_commands_and_json_payloads() {
+        let book_root = tempfile::tempdir().unwrap();
+        let helloworld = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .into_iter()
+                .find(|chapter| chapter.name == "helloworld")
+                .expect("should have a helloworld chapter")
+                .content
+                .clone()
+        };
+
+        assert!(!helloworld(serde_json::json!({})).contains("grpcurl"));
+
+        let content = helloworld(serde_json::json!({ "show_examples": true }));
+        assert!(content.contains("<summary>Try it</summary>"));
+        assert!(content.contains(
+            "grpcurl -plaintext -d &#x27;{\n  &quot;name&quot;: &quot;&quot;\n}&#x27; localhost:50051 helloworld.Greeter/SayHello"
+        ));
+        assert!(content.contains("helloworld.Greeter/StreamHello"));
+        assert!(!content.contains("json-example"));
+
+        let content = helloworld(serde_json::json!({
+            "grpcurl_examples": { "default_host": "api.example.com:443", "plaintext": false }
+        }));
+        assert!(content.contains(r#"<code class="language-bash">grpcurl -d &#x27;{"#));
+        assert!(content.contains("}&#x27; api.example.com:443 helloworld.Greeter/SayHello"));
+        // only client streams are read from stdin
+        assert!(!content.contains("newline-delimited JSON"));
+
+        let (ctx, _) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "grpcurl_examples": { "host": "api.example.com:443" } }),
+            serde_json::json!([]),
+        );
+        assert!(ProtobufPreprocessorArgs::new(&ctx).is_err());
+
+        let content = helloworld(serde_json::json!({ "show_json_examples": true }));
+        assert!(!content.contains("grpcurl"));
+        assert!(content.contains(
+            "<summary>Example JSON</summary>\n        <pre><code class=\"language-json\">{\n  &quot;name&quot;: &quot;&quot;\n}</code></pre>"
+        ));
+    }
+
+    #[test]
+    fn page_per_file_renders_a_chapter_per_file_with_working_links() {
+        let link_section = serde_json::json!([chapter_section(
+            "Usage",
+            "See [the nested enum](proto!(testing.nested.Foo)) and [a field](proto!(TypeTest::bytes_type)) and [a greeting](proto!(Greeter::SayHello))"
+        )]);
+
+        for page_per in ["package", "file"] {
+            let book_root = tempfile::tempdir().unwrap();
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({ "page_per": page_per }),
+                link_section.clone(),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+
+            let hrefs = book_hrefs_resolve(&book);
+            assert!(hrefs.len() > 10, "{} mode found too few links", page_per);
+            assert!(hrefs
+                .iter()
+                .any(|(href, _)| href.ends_with(".md#file-google/protobuf/empty.proto")));
+            for (href, resolves) in hrefs {
+                assert!(resolves, "{} mode has broken link {}", page_per, href);
+            }
+
+            let testing = proto_chapters(&book)
+                .into_iter()
+                .find(|chapter| chapter.name == "testing")
+                .expect("should have testing package chapter");
+
+            if page_per == "file" {
+                let mut file_pages: Vec<_> = testing
+                    .sub_items
+                    .iter()
+                    .filter_map(|item| match item {
+                        BookItem::Chapter(chapter) => chapter.path.clone(),
+                        _ => None,
+                    })
+                    .collect();
+                file_pages.sort();
+                assert_eq!(
+                    file_pages,
+                    vec![
+                        PathBuf::from("proto/testing/type_test"),
+                        PathBuf::from("proto/testing/type_test_same_namespace"),
+                    ]
+                );
+                assert!(testing
+                    .content
+                    .contains("- [type_test.proto](/proto/testing/type_test.md)"));
+            } else {
+                assert!(testing.sub_items.is_empty());
+            }
+        }
+    }
+
+    fn sub_chapters(chapter: &Chapter) -> Vec<&Chapter> {
+        chapter
+            .sub_items
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn start_placement_puts_the_reference_before_existing_chapters() {
+        let book_root = tempfile::tempdir().unwrap();
+        let top_level_names = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                config,
+                serde_json::json!([chapter_section("Introduction", "# Introduction")]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            book.sections
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter.name.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let reference = vec!["google.protobuf", "helloworld", "testing", "testing.nested"];
+
+        let start = top_level_names(serde_json::json!({ "placement": "start" }));
+        assert_eq!(start[..4], reference);
+        assert_eq!(start[4], "Introduction");
+
+        let end = top_level_names(serde_json::json!({}));
+        assert_eq!(end[0], "Introduction");
+        assert_eq!(end[1..], reference);
+    }
+
+    #[test]
+    fn nested_namespaces_mirror_the_package_hierarchy_under_nest_under() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "nested_namespaces": true, "nest_under": "Protocol" }),
+            serde_json::json!([chapter_section("Protocol", "# Protocol")]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let BookItem::Chapter(protocol) = &book.sections[0] else {
+            panic!("should be a chapter")
+        };
+        let top_level = sub_chapters(protocol);
+        assert_eq!(
+            top_level
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["google", "helloworld", "testing"]
+        );
+
+        // `google` declares no files, so is a stub listing its children
+        let google = top_level[0];
+        assert_eq!(google.number, Some(SectionNumber(vec![1, 1])));
+        assert!(google
+            .content
+            .contains("- [google.protobuf](/proto/google/protobuf.md)"));
+
+        let protobuf = sub_chapters(google)[0];
+        assert_eq!(protobuf.name, "google.protobuf");
+        assert_eq!(protobuf.number, Some(SectionNumber(vec![1, 1, 1])));
+        assert_eq!(protobuf.parent_names, vec!["Protocol", "google"]);
+
+        let testing = top_level[2];
+        assert!(chapter_headings(testing)
+            .iter()
+            .any(|(_, id)| id == "Message"));
+        let nested = sub_chapters(testing)[0];
+        assert_eq!(nested.name, "testing.nested");
+        assert_eq!(nested.path, Some(PathBuf::from("proto/testing/nested")));
+        assert_eq!(nested.number, Some(SectionNumber(vec![1, 3, 1])));
+        assert_eq!(nested.parent_names, vec!["Protocol", "testing"]);
+
+        for (href, resolves) in book_hrefs_resolve(&book) {
+            assert!(resolves, "broken link {}", href);
+        }
+    }
+
+    #[test]
+    fn package_index_pages_fill_the_paths_of_parent_packages() {
+        let book_root = tempfile::tempdir().unwrap();
+        let chapters = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            for (href, resolves) in book_hrefs_resolve(&book) {
+                assert!(resolves, "broken link {}", href);
+            }
+            proto_chapters(&book)
+                .into_iter()
+                .map(|chapter| (chapter.path.clone().unwrap(), chapter.content.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        assert!(!chapters(serde_json::json!({})).contains_key(Path::new("proto/google")));
+
+        let chapters = chapters(serde_json::json!({ "generate_package_index_pages": true }));
+        let google = &chapters[Path::new("proto/google")];
+        assert!(google.starts_with("# google\n\n"));
+        assert!(
+            google.contains("- [google.protobuf](/proto/google/protobuf.md) (1 symbol)"),
+            "{}",
+            google
+        );
+        // packages with files of their own keep their chapter
+        assert!(chapters[Path::new("proto/testing")].contains("TypeTest"));
+    }
+
+    #[test]
+    fn index_page_lists_each_package_above_their_chapters() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "index_page": true }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        for (href, resolves) in book_hrefs_resolve(&book) {
+            assert!(resolves, "broken link {}", href);
+        }
+
+        let BookItem::Chapter(index) = &book.sections[0] else {
+            panic!("expected chapter")
+        };
+        assert_eq!(index.name, "Protobuf reference");
+        assert_eq!(index.path, Some(PathBuf::from("proto/index")));
+        assert!(index
+            .content
+            .starts_with("# Protobuf reference\n\n| Package | Files | Symbols |\n"));
+        assert!(
+            index
+                .content
+                .contains("| [helloworld](/proto/helloworld.md) | 1 | 3 |\n"),
+            "{}",
+            index.content
+        );
+
+        // every package is listed, and nested beneath the index
+        assert!(!index.sub_items.is_empty());
+        for item in &index.sub_items {
+            let BookItem::Chapter(package) = item else {
+                panic!("expected chapter")
+            };
+            let path = package.path.as_ref().unwrap().display().to_string();
+            assert!(
+                index.content.contains(&format!("(/{}.md)", path)),
+                "{} should be listed",
+                path
+            );
+            assert_eq!(package.parent_names, vec!["Protobuf reference"]);
+        }
+    }
+
+    #[test]
+    fn generate_dot_writes_the_dependency_graph_with_a_chapter() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "generate_dot": true }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let dot = fs::read_to_string(book_root.path().join("src/proto/dependencies.dot"))
+            .expect("should write the graph");
+        assert!(dot.contains(r#"subgraph "cluster_testing" {"#));
+        assert!(
+            dot.contains(r#"".testing.TypeTest" -> ".testing.OtherMessage" [label="other_type"];"#)
+        );
+
+        let chapter = proto_chapters(&book)
+            .into_iter()
+            .find(|chapter| chapter.path == Some(PathBuf::from("proto/_dependencies")))
+            .expect("should have a dependencies chapter");
+        assert!(chapter
+            .content
+            .starts_with("# Message dependencies\n\n```mermaid\n"));
+        assert!(chapter
+            .content
+            .ends_with("[dependencies.dot](dependencies.dot)\n"));
+
+        // an unchanged graph is left alone, so `mdbook serve` doesn't rebuild for it
+        let dot_path = book_root.path().join("src/proto/dependencies.dot");
+        let written = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&dot_path)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "generate_dot": true }),
+            serde_json::json!([]),
+        );
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        assert_eq!(
+            fs::metadata(&dot_path).unwrap().modified().unwrap(),
+            written
+        );
+
+        // nor does failing to write it fail the build
+        fs::remove_file(&dot_path).unwrap();
+        fs::create_dir(&dot_path).unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "generate_dot": true }),
+            serde_json::json!([]),
+        );
+        assert!(ProtobufPreprocessor::new().run(&ctx, book).is_ok());
+    }
+
+    #[test]
+    fn symbol_index_lists_every_symbol_under_its_first_letter() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "symbol_index": true }),
+            serde_json::json!([]),
+        );
+        let book = ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+        for (href, resolves) in book_hrefs_resolve(&book) {
+            assert!(resolves, "broken link {}", href);
+        }
+
+        let index = proto_chapters(&book)
+            .into_iter()
+            .find(|chapter| chapter.path == Some(PathBuf::from("proto/_symbols")))
+            .expect("should have a symbol index chapter");
+        assert!(index.content.starts_with("# Symbol index\n\n## "));
+
+        let section = |letter: &str| {
+            index
+                .content
+                .split("\n## ")
+                .find(|section| section.starts_with(&format!("{}\n", letter)))
+                .unwrap_or_else(|| panic!("should have a section for {}", letter))
+        };
+        assert!(section("S").contains(
+            "- [Greeter::SayHello](/proto/helloworld.md#Greeter::SayHello) method in `helloworld`\n"
+        ));
+        assert!(section("G")
+            .contains("- [Greeter](/proto/helloworld.md#Greeter) service in `helloworld`\n"));
+        // fields are left out
+        assert!(!index.content.contains("::bytes_type"));
+
+        let letters: Vec<&str> = index
+            .content
+            .lines()
+            .filter_map(|line| line.strip_prefix("## "))
+            .collect();
+        let mut sorted = letters.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(letters, sorted);
+    }
+
+    #[test]
+    fn preprocessor_emits_search_index_in_mdbook_format() {
+        let book_root = tempfile::tempdir().unwrap();
+        let (ctx, book) = preprocessor_input(
+            book_root.path(),
+            serde_json::json!({ "emit_search_index": true }),
+            serde_json::json!([]),
+        );
+        ProtobufPreprocessor::new()
+            .run(&ctx, book)
+            .expect("should succeed");
+
+        let index: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(book_root.path().join("src/proto/proto-search.json")).unwrap(),
+        )
+        .unwrap();
+
+        let doc_urls = index["doc_urls"].as_array().unwrap();
+        let doc_ref = doc_urls
+            .iter()
+            .position(|url| url == "proto/helloworld.html#Greeter::SayHello")
+            .expect("method should be indexed");
+
+        let doc = &index["index"]["documentStore"]["docs"][doc_ref.to_string()];
+        assert_eq!(doc["title"], "Greeter::SayHello");
+        assert_eq!(doc["breadcrumbs"], "helloworld » Greeter::SayHello");
+        assert_eq!(
+            doc["body"],
+            ".helloworld.Greeter::SayHello Sends a greeting"
+        );
+
+        assert!(index["index"]["index"]["body"]["root"]["g"].is_object());
+        assert_eq!(index["search_options"]["fields"]["title"]["boost"], 2);
+        assert_eq!(index["results_options"]["limit_results"], 30);
+    }
+
+    #[test]
+    fn hide_same_page_backlinks_keeps_only_backlinks_from_other_chapters() {
+        let message_backlinks = |config: serde_json::Value| {
+            let book_root = tempfile::tempdir().unwrap();
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                config,
+                serde_json::json!([chapter_section(
+                    "Usage",
+                    "See [the message](proto!(.testing.Message))"
+                )]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            let testing = proto_chapters(&book)
+                .into_iter()
+                .find(|chapter| chapter.name == "testing")
+                .unwrap()
+                .content
+                .clone();
+            let message = &testing[testing.find("{#Message ").unwrap()..];
+            let backlinks = &message[..message.find("</details>").unwrap()];
+            backlinks.matches("<li>").count()
+        };
+
+        // the content link, and the fields and methods of `testing` referring to it
+        assert!(message_backlinks(serde_json::json!({})) > 1);
+        assert_eq!(
+            message_backlinks(serde_json::json!({ "hide_same_page_backlinks": true })),
+            1
+        );
+    }
+
+    #[test]
+    fn show_method_path_false_hides_the_grpc_path_of_methods() {
+        let book_root = tempfile::tempdir().unwrap();
+        let helloworld = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .into_iter()
+                .find(|chapter| chapter.name == "helloworld")
+                .expect("should have a helloworld chapter")
+                .content
+                .clone()
+        };
+
+        let content = helloworld(serde_json::json!({}));
+        assert!(
+            content.contains(r#"<code class="method-path">/helloworld.Greeter/SayHello</code>"#)
+        );
+
+        let content = helloworld(serde_json::json!({ "show_method_path": false }));
+        assert!(!content.contains("method-path"));
+        // the signature is still rendered
+        assert!(content.contains(
+            "rpc StreamHello (helloworld.HelloRequest) returns (stream helloworld.HelloReply);"
+        ));
+    }
+
+    #[test]
+    fn methods_render_a_badge_of_their_idempotency_level() {
+        let chapters = render_demo_chapters();
+        let testing = chapters
+            .iter()
+            .find(|chapter| chapter.name == "testing")
+            .expect("should have a testing chapter");
+        let method = |name: &str| {
+            testing
+                .content
+                .split(r#"<fieldset class="method">"#)
+                .find(|method| method.contains(&format!("rpc {} (", name)))
+                .unwrap_or_else(|| panic!("{} should be rendered", name))
+        };
+
+        assert!(method("GetMessage")
+            .contains(r#"<span class="badge idempotency">no side effects</span>"#));
+        assert!(
+            method("PutMessage").contains(r#"<span class="badge idempotency">idempotent</span>"#)
+        );
+        assert!(!method("SayHello").contains("idempotency"));
+    }
+
+    #[test]
+    fn show_package_version_renders_a_badge_on_versioned_packages() {
+        let book_root = tempfile::tempdir().unwrap();
+        let file = |name: &str, package: &str| FileDescriptorProto {
+            name: Some(name.into()),
+            package: Some(package.into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Order".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let descriptor = FileDescriptorSet {
+            file: vec![
+                file("orders/v2/orders.proto", "orders.v2"),
+                file("orders/orders.proto", "orders"),
+            ],
+        };
+        fs::write(
+            book_root.path().join("versioned.pb"),
+            descriptor.encode_to_vec(),
+        )
+        .unwrap();
+
+        let chapter = |config: serde_json::Value, name: &str| {
+            let mut protobuf_config = serde_json::json!({ "proto_descriptor": "versioned.pb" });
+            protobuf_config
+                .as_object_mut()
+                .unwrap()
+                .extend(config.as_object().unwrap().clone());
+            let (ctx, book) =
+                preprocessor_input(book_root.path(), protobuf_config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            book.iter()
+                .find_map(|item| match item {
+                    BookItem::Chapter(chapter) if chapter.name == name => {
+                        Some(chapter.content.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("should have a `{}` chapter", name))
+        };
+
+        let badge = r#"<div class="badges"><span class="badge version">v2</span></div>"#;
+        assert!(!chapter(serde_json::json!({}), "orders.v2").contains("badge version"));
+        for page_per in ["package", "file"] {
+            let config = serde_json::json!({ "show_package_version": true, "page_per": page_per });
+            assert!(chapter(config.clone(), "orders.v2").contains(badge));
+            assert!(!chapter(config, "orders").contains("badge version"));
+        }
+    }
+
+    #[test]
+    fn baseline_descriptor_marks_new_and_changed_packages() {
+        let book_root = tempfile::tempdir().unwrap();
+
+        let mut baseline = read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap();
+        baseline.file.retain(|file| file.package() != "helloworld");
+        let type_test = baseline
+            .file
+            .iter_mut()
+            .find(|file| file.name() == "type_test.proto")
+            .unwrap();
+        type_test.message_type[0].field[0].name = Some("renamed".into());
+        fs::write(
+            book_root.path().join("baseline.pb"),
+            baseline.encode_to_vec(),
+        )
+        .unwrap();
+
+        for page_per in ["package", "file"] {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({
+                    "baseline_descriptor": "baseline.pb",
+                    "baseline_chapter_suffix": true,
+                    "page_per": page_per,
+                }),
+                serde_json::json!([]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+
+            let chapters: Vec<&Chapter> = book
+                .sections
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter),
+                    _ => None,
+                })
+                .collect();
+            let chapter = |name: &str| {
+                chapters
+                    .iter()
+                    .find(|chapter| chapter.name == name)
+                    .unwrap_or_else(|| panic!("{} mode has no chapter `{}`", page_per, name))
+            };
+
+            assert!(chapter("helloworld (new)")
+                .content
+                .contains(r#"<span class="badge new-package">new package</span>"#));
+            assert!(chapter("testing (updated)")
+                .content
+                .contains(r#"<span class="badge changed-package">changed</span>"#));
+
+            let unchanged = chapter("google.protobuf");
+            assert!(!unchanged.content.contains("-package"));
+        }
+    }
+
+    #[test]
+    fn output_prefix_moves_chapters_and_every_link_together() {
+        let link_section = serde_json::json!([chapter_section(
+            "Usage",
+            "Say [hello](proto!(Greeter::SayHello))"
+        )]);
+
+        for page_per in ["package", "file"] {
+            let book_root = tempfile::tempdir().unwrap();
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({
+                    "output_prefix": "reference/grpc",
+                    "page_per": page_per,
+                    "emit_symbol_manifest": true,
+                }),
+                link_section.clone(),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+
+            let chapters = proto_chapters(&book);
+            assert!(chapters.is_empty(), "nothing should be generated in proto/");
+
+            let generated: Vec<&Chapter> = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) if chapter.name != "Usage" => Some(chapter),
+                    _ => None,
+                })
+                .collect();
+            assert!(!generated.is_empty());
+            for chapter in &generated {
+                assert!(chapter
+                    .path
+                    .as_ref()
+                    .is_some_and(|path| path.starts_with("reference/grpc")));
+            }
+
+            let hrefs = book_hrefs_resolve(&book);
+            // the content link, the symbols' links to each other and their backlinks
+            assert!(hrefs.iter().any(|(href, _)| href.starts_with("Usage.md#")));
+            assert!(hrefs
+                .iter()
+                .any(|(href, _)| href.ends_with("#Greeter::SayHello")));
+            for (href, resolves) in hrefs {
+                assert!(
+                    !href.starts_with("proto/"),
+                    "{} mode has a link into proto/: {}",
+                    page_per,
+                    href
+                );
+                assert!(resolves, "{} mode has broken link {}", page_per, href);
+            }
+
+            let manifest: SymbolManifest = serde_json::from_str(
+                &fs::read_to_string(book_root.path().join("src/reference/grpc/symbols.json"))
+                    .unwrap(),
+            )
+            .unwrap();
+            assert!(manifest
+                .symbols
+                .iter()
+                .all(|symbol| symbol.href.starts_with("/reference/grpc/")));
+        }
+    }
+
+    fn message_field(name: &str, type_name: &str) -> prost_types::FieldDescriptorProto {
+        prost_types::FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            r#type: Some(Type::Message.into()),
+            type_name: Some(type_name.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn files_without_a_package_render_under_the_default_package_name() {
+        let book_root = tempfile::tempdir().unwrap();
+
+        let mut descriptor = read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap();
+        // a package-less file referencing a packaged message, and referenced from a packaged file
+        descriptor.file.push(prost_types::FileDescriptorProto {
+            name: Some("rootless.proto".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![prost_types::DescriptorProto {
+                name: Some("Ping".into()),
+                field: vec![message_field("request", ".helloworld.HelloRequest")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        descriptor.file.push(prost_types::FileDescriptorProto {
+            name: Some("consumer.proto".into()),
+            package: Some("consumer".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![prost_types::DescriptorProto {
+                name: Some("Pong".into()),
+                field: vec![message_field("ping", ".Ping")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        fs::write(
+            book_root.path().join("rootless.pb"),
+            descriptor.encode_to_vec(),
+        )
+        .unwrap();
+
+        for (config, title, ping_href) in [
+            (serde_json::json!({}), "(no package)", "proto/_root.md#Ping"),
+            (
+                serde_json::json!({ "default_package_name": "Global" }),
+                "Global",
+                "proto/_root.md#Ping",
+            ),
+            (
+                serde_json::json!({ "page_per": "file" }),
+                "(no package)",
+                "proto/rootless.md#Ping",
+            ),
+        ] {
+            let mut config = config;
+            config["proto_descriptor"] = "rootless.pb".into();
+
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                config,
+                serde_json::json!([chapter_section("Usage", "A [ping](proto!(Ping))")]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+
+            let chapters = proto_chapters(&book);
+            let root = chapters
+                .iter()
+                .find(|chapter| chapter.name == title)
+                .unwrap_or_else(|| panic!("no chapter named `{}`", title));
+            assert_eq!(root.path, Some(PathBuf::from("proto/_root")));
+            assert!(chapters.iter().all(|chapter| !chapter.name.is_empty()));
+
+            let consumer = chapters
+                .iter()
+                .find(|chapter| chapter.content.contains("{#Pong .symbol}"))
+                .expect("Pong should be rendered");
+            assert!(consumer
+                .content
+                .contains(&format!(r#"<a href="/{}">Ping</a>"#, ping_href)));
+
+            let hrefs = book_hrefs_resolve(&book);
+            assert!(hrefs.iter().any(|(href, _)| href == ping_href));
+            for (href, resolves) in hrefs {
+                assert!(resolves, "broken link {}", href);
+            }
+        }
+    }
+
+    #[test]
+    fn strip_package_prefix_shortens_chapter_names_only() {
+        for nested_namespaces in [false, true] {
+            let book_root = tempfile::tempdir().unwrap();
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({
+                    "strip_package_prefix": "testing.",
+                    "nested_namespaces": nested_namespaces,
+                }),
+                serde_json::json!([chapter_section(
+                    "Usage",
+                    "[foo](proto!(testing.nested.Foo))"
+                )]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+
+            let names: BTreeMap<String, PathBuf> = proto_chapters(&book)
+                .into_iter()
+                .map(|chapter| (chapter.name.clone(), chapter.path.clone().unwrap()))
+                .collect();
+
+            assert_eq!(names["nested"], PathBuf::from("proto/testing/nested"));
+            // stripping the whole name would leave nothing, and other packages are untouched
+            assert_eq!(names["testing"], PathBuf::from("proto/testing"));
+            assert_eq!(names["helloworld"], PathBuf::from("proto/helloworld"));
+            assert!(!names.contains_key("testing.nested"));
+
+            for (href, resolves) in book_hrefs_resolve(&book) {
+                assert!(resolves, "broken link {}", href);
+            }
+        }
+    }
+
+    #[test]
+    fn package_chapters_sort_versions_naturally() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptor = FileDescriptorSet {
+            file: ["api.v10", "api.v2", "api.v1beta1", "api.v1"]
+                .into_iter()
+                .map(|package| prost_types::FileDescriptorProto {
+                    name: Some(format!("{}.proto", package.replace('.', "/"))),
+                    package: Some(package.into()),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        fs::write(book_root.path().join("api.pb"), descriptor.encode_to_vec()).unwrap();
+
+        let chapter_names = |config: serde_json::Value| {
+            let mut config = config;
+            config["proto_descriptor"] = "api.pb".into();
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            book.iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(chapter) => Some(chapter.name.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            chapter_names(serde_json::json!({})),
+            vec!["api.v1", "api.v1beta1", "api.v2", "api.v10"]
+        );
+        assert_eq!(
+            chapter_names(serde_json::json!({ "package_sort": "lexicographic" })),
+            vec!["api.v1", "api.v10", "api.v1beta1", "api.v2"]
+        );
+        assert_eq!(
+            chapter_names(serde_json::json!({ "nested_namespaces": true })),
+            vec!["api", "api.v1", "api.v1beta1", "api.v2", "api.v10"]
+        );
+    }
+
+    #[test]
+    fn source_links_open_in_a_new_tab_unless_disabled() {
+        let book_root = tempfile::tempdir().unwrap();
+        let source_links = |new_tab: bool| {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({
+                    "proto_url_root": "https://example.com/proto/",
+                    "external_links_new_tab": new_tab
+                }),
+                serde_json::json!([]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .iter()
+                .flat_map(|chapter| {
+                    chapter
+                        .content
+                        .lines()
+                        .filter(|line| line.contains(r#"class="source""#))
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let links = source_links(true);
+        assert!(!links.is_empty());
+        for link in links {
+            assert!(
+                link.contains(r#"href="https://example.com/proto/"#)
+                    && link.contains(r#"" target="_blank" rel="noopener">[src]"#),
+                "{}",
+                link
+            );
+        }
+
+        for link in source_links(false) {
+            assert!(!link.contains("target="), "{}", link);
+        }
+
+        // as does the protobuf.dev link of the note on `Any` fields
+        let descriptor = FileDescriptorSet {
+            file: vec![prost_types::FileDescriptorProto {
+                name: Some("events.proto".into()),
+                package: Some("events".into()),
+                message_type: vec![prost_types::DescriptorProto {
+                    name: Some("Event".into()),
+                    field: vec![prost_types::FieldDescriptorProto {
+                        name: Some("payload".into()),
+                        number: Some(1),
+                        r#type: Some(Type::Message.into()),
+                        type_name: Some(".google.protobuf.Any".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        fs::write(
+            book_root.path().join("events.pb"),
+            descriptor.encode_to_vec(),
+        )
+        .unwrap();
+        let any_note = |new_tab: bool| {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({
+                    "proto_descriptor": "events.pb",
+                    "external_links_new_tab": new_tab
+                }),
+                serde_json::json!([]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .iter()
+                .flat_map(|chapter| chapter.content.lines())
+                .find(|line| line.contains(r#"class="note any-type""#))
+                .expect("should note the Any field")
+                .to_string()
+        };
+        assert!(any_note(true).contains(
+            r#"<a target="_blank" rel="noopener" href="https://protobuf.dev/reference/protobuf/google.protobuf/#any">"#
+        ));
+        assert!(!any_note(false).contains("target="));
+    }
+
+    #[test]
+    fn source_links_should_render_the_configured_label() {
+        let book_root = tempfile::tempdir().unwrap();
+        let greeter_source = |config: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(book_root.path(), config, serde_json::json!([]));
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            let chapters = proto_chapters(&book);
+            let helloworld = chapters
+                .iter()
+                .find(|chapter| chapter.name == "helloworld")
+                .unwrap();
+            helloworld
+                .content
+                .lines()
+                .find(|line| line.contains(r#"class="source""#))
+                .unwrap()
+                .to_string()
+        };
+
+        let link = greeter_source(serde_json::json!({ "source_link_label": "view source" }));
+        assert!(
+            link.contains(r#"<a class="source" href="helloworld.proto#L7-L13">view source</a>"#),
+            "{}",
+            link
+        );
+
+        let link = greeter_source(serde_json::json!({ "source_link_icon": true }));
+        assert!(
+            link.contains(
+                r#"href="helloworld.proto#L7-L13" title="[src]" aria-label="[src]"><i class="fa fa-code" aria-hidden="true"></i></a>"#
+            ),
+            "{}",
+            link
+        );
+    }
+
+    #[test]
+    fn the_demo_model_should_serialize_with_resolved_types() {
+        let descriptor = read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap();
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+
+        let json = serde_json::to_value(&model).unwrap();
+        let helloworld = json["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|package| package["name"] == "helloworld")
+            .expect("should have the helloworld package");
+        let request = helloworld["files"][0]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|message| message["name"] == "HelloRequest")
+            .expect("should have HelloRequest");
+
+        assert_eq!(request["fqsl"], ".helloworld.HelloRequest");
+        assert_eq!(
+            request["comments"]["leading"],
+            "The request message containing the user's name."
+        );
+        assert_eq!(request["fields"][0]["name"], "name");
+        assert_eq!(request["fields"][0]["number"], 1);
+        assert_eq!(request["fields"][0]["type_name"], "string");
+        assert_eq!(
+            request["fields"][0]["fqsl"],
+            ".helloworld.HelloRequest::name"
+        );
+
+        let say_hello = &helloworld["files"][0]["services"][0]["methods"][0];
+        assert_eq!(say_hello["request_type"], ".helloworld.HelloRequest");
+
+        // nested messages are reachable too
+        assert!(model.messages().any(|m| m.fqsl == ".helloworld.HelloReply"));
+    }
+
+    #[test]
+    fn the_model_should_list_the_reserved_numbers_and_names_of_enums() {
+        use prost_types::enum_descriptor_proto::EnumReservedRange;
+
+        let descriptor = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("orders.proto".into()),
+                package: Some("orders".into()),
+                enum_type: vec![EnumDescriptorProto {
+                    name: Some("Status".into()),
+                    reserved_range: vec![
+                        EnumReservedRange {
+                            start: Some(2),
+                            end: Some(2),
+                        },
+                        EnumReservedRange {
+                            start: Some(9),
+                            end: Some(11),
+                        },
+                    ],
+                    reserved_name: vec!["CANCELLED".into()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+        let status = model.enums().next().expect("should have Status");
+        assert_eq!(status.reserved_ranges, vec![(2, 2), (9, 11)]);
+        assert_eq!(status.reserved_names, vec!["CANCELLED"]);
+    }
+
+    #[test]
+    fn the_model_should_list_the_reserved_field_numbers_and_names_of_messages() {
+        use prost_types::descriptor_proto::ReservedRange;
+
+        let descriptor = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("orders.proto".into()),
+                package: Some("orders".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Order".into()),
+                    // end-exclusive, unlike enum ranges
+                    reserved_range: vec![
+                        ReservedRange {
+                            start: Some(2),
+                            end: Some(3),
+                        },
+                        ReservedRange {
+                            start: Some(9),
+                            end: Some(12),
+                        },
+                    ],
+                    reserved_name: vec!["legacy_total".into()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let model = ProtobufPreprocessor::build_model(&descriptor);
+        let order = model.messages().next().expect("should have Order");
+        assert_eq!(order.reserved_ranges, vec![(2, 2), (9, 11)]);
+        assert_eq!(order.reserved_names, vec!["legacy_total"]);
+    }
+
+    #[test]
+    fn files_in_the_descriptor_set_twice_are_documented_once() {
+        let book_root = tempfile::tempdir().unwrap();
+
+        let mut descriptor = read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap();
+        let original = descriptor.clone();
+        // as if a second protoc invocation's `--include_imports` output were appended, with a copy
+        // that has drifted from the first
+        descriptor.file.extend(original.file.clone());
+        descriptor.file.last_mut().unwrap().message_type.clear();
+        fs::write(
+            book_root.path().join("doubled.pb"),
+            descriptor.encode_to_vec(),
+        )
+        .unwrap();
+
+        let mut deduped = descriptor.clone();
+        dedupe_files(&mut deduped);
+        assert_eq!(deduped, original);
+
+        let chapter_contents = |proto_descriptor: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({ "proto_descriptor": proto_descriptor }),
+                serde_json::json!([]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .iter()
+                .map(|chapter| chapter.content.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            chapter_contents("doubled.pb".into()),
+            chapter_contents(canonicalize(DEMO_DESCRIPTOR).unwrap().to_str().into())
+        );
+    }
+
+    #[test]
+    fn descriptor_directories_merge_every_descriptor_set() {
+        let book_root = tempfile::tempdir().unwrap();
+        let descriptors = book_root.path().join("descriptors");
+        fs::create_dir(&descriptors).unwrap();
+
+        // one set per module, both vendoring the first file
+        let demo = read_file_descriptor_set(Path::new(DEMO_DESCRIPTOR)).unwrap();
+        let (first, second) = demo.file.split_at(demo.file.len() / 2);
+        let second = [&first[..1], second].concat();
+        for (name, files) in [("b.binpb", second), ("a.pb", first.to_vec())] {
+            let set = FileDescriptorSet { file: files };
+            fs::write(descriptors.join(name), set.encode_to_vec()).unwrap();
+        }
+        fs::write(descriptors.join("README.md"), "not a descriptor").unwrap();
+
+        let chapter_contents = |proto_descriptor: serde_json::Value| {
+            let (ctx, book) = preprocessor_input(
+                book_root.path(),
+                serde_json::json!({ "proto_descriptor": proto_descriptor }),
+                serde_json::json!([]),
+            );
+            let book = ProtobufPreprocessor::new()
+                .run(&ctx, book)
+                .expect("should succeed");
+            proto_chapters(&book)
+                .iter()
+                .map(|chapter| (chapter.name.clone(), chapter.content.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            chapter_contents("descriptors".into()),
+            chapter_contents(canonicalize(DEMO_DESCRIPTOR).unwrap().to_str().into())
+        );
+
+        let empty = book_root.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        let error = read_descriptor(&empty).unwrap_err().to_string();
+        assert!(error.contains("contains no descriptor sets"), "{}", error);
+    }
 }
@@ -1,13 +1,42 @@
-use crate::view::ProtoNamespaceTemplate;
+use crate::labels::Labels;
+use crate::model::{self, ProtoModel};
+use crate::source_page::SOURCE_PAGES_DIR;
+use crate::view::{CommentFormat, PagePer, ProtoNamespaceTemplate};
 use anyhow::{anyhow, Result};
 use askama::Template;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use log::warn;
 use mdbook::book::{Chapter, Link};
 use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
 use pulldown_cmark_to_cmark::{cmark, cmark_with_options};
+use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+/// Directory (relative to the book source) the generated chapters are placed in by default
+pub(crate) const DEFAULT_OUTPUT_PREFIX: &str = "proto";
+
+/// Page of the symbols declared without a `package`, within the output directory
+pub(crate) const ROOT_PACKAGE_PAGE: &str = "_root";
+
+/// Chapter name of the symbols declared without a `package`, unless `default_package_name` is set
+pub(crate) const DEFAULT_PACKAGE_NAME: &str = "(no package)";
+
+/// Text of the links to the source of each symbol, unless `source_link_label` is set
+pub(crate) const DEFAULT_SOURCE_LINK_LABEL: &str = "[src]";
+
+/// Chapters larger than this (in bytes) are skipped without being parsed, unless they contain a
+/// `proto!(` link
+pub(crate) const DEFAULT_MAX_CHAPTER_SIZE: usize = 4 * 1024 * 1024;
+
+/// Longest link text or chapter name (in characters) carried into a backlink label
+const MAX_LABEL_CHARS: usize = 200;
+
+/// Minimum fuzzy match score for a symbol to be suggested as a near match of a broken link
+pub(crate) const DEFAULT_LINK_FUZZY_THRESHOLD: i64 = 1;
 
 pub(crate) trait ProtoSymbol {
     fn symbol_link(&self) -> &SymbolLink;
@@ -15,29 +44,74 @@ pub(crate) trait ProtoSymbol {
         self.symbol_link().fqsl()
     }
 
+    fn kind(&self) -> SymbolKind;
+    fn deprecated(&self) -> bool;
+    /// first paragraph of the leading comment, if any
+    fn summary(&self) -> Option<String>;
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>);
+
     fn set_backlinks(&mut self, backlinks: Backlinks);
-    fn set_source_url(&mut self, source_url: String);
+    fn set_source_url(&mut self, source_url: String, new_tab: bool);
+    /// Points the `[src]` link at the file's source page, below `pages_root`
+    fn set_source_page(&mut self, pages_root: &str);
+    fn set_source_link_label(&mut self, label: &str, icon: bool);
+    fn set_labels(&mut self, labels: &Arc<Labels>);
+    fn set_comment_format(&mut self, format: CommentFormat);
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Service,
+    Method,
+    Message,
+    Field,
+    Enum,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Service => "service",
+            Self::Method => "method",
+            Self::Message => "message",
+            Self::Field => "field",
+            Self::Enum => "enum",
+        })
+    }
 }
 
 #[derive(Template, Default)]
 #[template(path = "backlinks.html")]
 pub(crate) struct Backlinks {
     links: Vec<Backlink>,
+    labels: Arc<Labels>,
 }
 
 impl Backlinks {
     pub(crate) fn new(links: Vec<Backlink>) -> Self {
-        Self { links }
+        Self {
+            links,
+            labels: Default::default(),
+        }
+    }
+
+    pub(crate) fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum Backlink {
     Content(ContentLink),
-    Symbol(SymbolLink),
+    Symbol {
+        link: SymbolLink,
+        /// how the symbol is used, e.g. `request` or `response` by a method
+        context: Option<String>,
+    },
 }
 
-#[derive(Template, Clone)]
+#[derive(Template, Clone, Serialize, Deserialize)]
 #[template(path = "content_link.html")]
 pub(crate) struct ContentLink {
     path: String,
@@ -51,7 +125,7 @@ impl ContentLink {
     }
 }
 
-#[derive(Template, Clone, Eq, Hash, PartialEq, Debug)]
+#[derive(Template, Clone, Eq, Hash, PartialEq, Debug, Serialize, Deserialize)]
 #[template(path = "symbol_link.html")]
 pub(crate) struct SymbolLink {
     symbol: String,
@@ -59,6 +133,15 @@ pub(crate) struct SymbolLink {
     property: Option<String>,
     label_override: Option<String>,
     own_id: Option<String>,
+    /// `key=value` data from a `proto!(Symbol@key=value)` query, carried to the target page
+    #[serde(default)]
+    context: Option<String>,
+    /// chapter the symbol is rendered on when that is not its package's page
+    #[serde(default)]
+    page: Option<String>,
+    /// directory of the generated chapters, when not the default `proto`
+    #[serde(default)]
+    output_prefix: Option<String>,
 }
 
 impl SymbolLink {
@@ -81,6 +164,9 @@ impl SymbolLink {
             property,
             label_override: None,
             own_id: None,
+            context: None,
+            page: None,
+            output_prefix: None,
         }
     }
 
@@ -92,9 +178,17 @@ impl SymbolLink {
     }
 
     fn find_best_match<'a>(fqsl: &str, packages: &'a HashSet<String>) -> Option<&'a String> {
+        let fqsl = &fqsl[1..];
+        // a package only matches whole segments, so the empty package (of files without a
+        // `package` declaration) matches everything, but loses to any other match
         packages
             .iter()
-            .filter(|pkg| fqsl[1..].starts_with(pkg.as_str()))
+            .filter(|pkg| {
+                pkg.is_empty()
+                    || fqsl
+                        .strip_prefix(pkg.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
             .max_by_key(|pkg| pkg.len())
     }
 
@@ -102,6 +196,34 @@ impl SymbolLink {
         self.property = Some(property)
     }
 
+    pub(crate) fn property(&self) -> Option<&str> {
+        self.property.as_deref()
+    }
+
+    /// The link to the symbol declaring the field or method this links to, or itself for a link
+    /// without a property
+    pub(crate) fn owner(&self) -> SymbolLink {
+        Self {
+            property: None,
+            label_override: None,
+            own_id: None,
+            context: None,
+            ..self.clone()
+        }
+    }
+
+    /// Links to the symbol and each symbol it is nested in, outermost first (e.g. `Outer`,
+    /// `Outer.Middle`, `Outer.Middle.Inner`), all rendered on the same page as it
+    pub(crate) fn ancestors(&self) -> Vec<SymbolLink> {
+        let segments: Vec<&str> = self.symbol.split('.').collect();
+        (1..=segments.len())
+            .map(|depth| Self {
+                symbol: segments[..depth].join("."),
+                ..self.owner()
+            })
+            .collect()
+    }
+
     pub(crate) fn id(&self) -> String {
         if let Some(property) = &self.property {
             format!("{}::{}", self.symbol, property)
@@ -110,8 +232,12 @@ impl SymbolLink {
         }
     }
 
-    fn fqsl(&self) -> String {
-        format!(".{}.{}", self.path.replace('/', "."), self.id())
+    pub(crate) fn fqsl(&self) -> String {
+        if self.path.is_empty() {
+            format!(".{}", self.id())
+        } else {
+            format!(".{}.{}", self.path.replace('/', "."), self.id())
+        }
     }
 
     fn label(&self) -> String {
@@ -126,12 +252,71 @@ impl SymbolLink {
         }
     }
 
-    fn set_label(&mut self, label: String) {
-        self.label_override = Some(label)
+    pub(crate) fn set_label(&mut self, label: String) {
+        self.label_override = Some(truncate_label(label))
     }
 
-    fn href(&self) -> String {
-        format!("/proto/{}.md#{}", self.path, self.id())
+    /// Link to the symbol's anchor. Any context is carried beside it, in the link's
+    /// `data-proto-context` attribute, so that the link works as it is without the shipped script
+    pub(crate) fn href(&self) -> String {
+        let prefix = self
+            .output_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_OUTPUT_PREFIX);
+        let page = match self.page.as_ref().unwrap_or(&self.path) {
+            page if page.is_empty() => ROOT_PACKAGE_PAGE,
+            page => page,
+        };
+        format!("/{}/{}.md#{}", prefix, page, self.id())
+    }
+
+    /// The link as markdown, `[label](href)`, escaped so that any label and href parse back to
+    /// the same link. Lets tests assert on link structure without parsing html.
+    #[cfg(test)]
+    pub(crate) fn to_markdown_link(&self) -> String {
+        let label = backslash_escape(&self.label(), |c| c.is_ascii_punctuation());
+
+        let href = self.href();
+        let href = if href.contains(|c: char| c.is_whitespace() || "()<>".contains(c)) {
+            format!("<{}>", backslash_escape(&href, |c| "\\<>&".contains(c)))
+        } else {
+            backslash_escape(&href, |c| "\\&".contains(c))
+        };
+
+        format!("[{}]({})", label, href)
+    }
+
+    /// Whether both links point into the same chapter
+    pub(crate) fn same_page(&self, other: &SymbolLink) -> bool {
+        let page = |link: &SymbolLink| {
+            link.href()
+                .split_once('#')
+                .map(|(page, _)| page.to_string())
+        };
+        page(self) == page(other)
+    }
+
+    /// Package path and top level symbol name, which together identify the file defining it
+    pub(crate) fn page_key(&self) -> (String, String) {
+        let top_level = self.symbol.split('.').next().unwrap_or(&self.symbol);
+        (self.path.clone(), top_level.to_string())
+    }
+
+    pub(crate) fn set_page(&mut self, page: String) {
+        self.page = Some(page)
+    }
+
+    pub(crate) fn set_output_prefix(&mut self, output_prefix: String) {
+        self.output_prefix = Some(output_prefix)
+    }
+
+    fn set_context(&mut self, context: String) {
+        self.context = Some(context)
+    }
+
+    /// types from `google.protobuf`, which are shared too widely to relate symbols by
+    pub(crate) fn is_well_known_type(&self) -> bool {
+        self.path == "google/protobuf" || self.symbol.starts_with("google.protobuf.")
     }
 
     pub(crate) fn set_own_id(&mut self, id: String) {
@@ -146,35 +331,459 @@ impl SymbolLink {
 
         fqsl_parts.ends_with(&*query_parts)
     }
+
+    /// How closely the fully qualified symbol resembles `query`, for suggesting near matches when
+    /// none `matches`. Zero when it does not resemble it at all.
+    pub(crate) fn fuzzy_score(&self, query: &str) -> i64 {
+        SkimMatcherV2::default()
+            .fuzzy_match(&self.fqsl(), query)
+            .unwrap_or(0)
+    }
 }
 
+/// Sets the backlinks of every symbol, leaving out those from symbols on the same chapter when
+/// `hide_same_page` is set
 pub fn assign_backlinks(
     document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
     symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+    hide_same_page: bool,
 ) {
-    for (_, namespace) in document {
+    // a single walk per package, with the packages walked in parallel
+    document.par_iter_mut().for_each(|(_, namespace)| {
         namespace.mutate_symbols(|symbol| {
-            if let Some(usages) = symbol_usages.get(&symbol.symbol_link()) {
-                symbol.set_backlinks(Backlinks::new(usages.clone()))
+            if let Some(usages) = symbol_usages.get(symbol.symbol_link()) {
+                let usages = usages
+                    .iter()
+                    .filter(|usage| match usage {
+                        Backlink::Symbol { link, .. } if hide_same_page => {
+                            !link.same_page(symbol.symbol_link())
+                        }
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect();
+                symbol.set_backlinks(Backlinks::new(usages))
             }
         })
-    }
+    });
 }
 
 pub fn assign_source_url(
     document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
     source_url: String,
+    new_tab: bool,
 ) {
     for (_, namespace) in document {
-        namespace.mutate_symbols(|symbol| symbol.set_source_url(source_url.clone()))
+        namespace.mutate_symbols(|symbol| symbol.set_source_url(source_url.clone(), new_tab))
+    }
+}
+
+/// Points the `[src]` links at the generated source pages, rather than an external url
+pub(crate) fn assign_source_pages(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    output_prefix: &str,
+) {
+    let pages_root = format!("/{}/{}", output_prefix, SOURCE_PAGES_DIR);
+    for namespace in document.values_mut() {
+        namespace.mutate_symbols(|symbol| symbol.set_source_page(&pages_root))
+    }
+}
+
+pub(crate) fn assign_source_link_label(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    label: &str,
+    icon: bool,
+) {
+    for namespace in document.values_mut() {
+        namespace.mutate_symbols(|symbol| symbol.set_source_link_label(label, icon))
     }
 }
 
+/// Applies `rewrite` to every link in the document and in the usages (rekeying them), for
+/// changes to where symbols are rendered. Must run before any other links are derived from these.
+fn rewrite_links(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+    mut rewrite: impl FnMut(&mut SymbolLink),
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    for namespace in document.values_mut() {
+        namespace.mutate_links(&mut rewrite);
+    }
+
+    symbol_usages
+        .into_iter()
+        .map(|(mut symbol, mut backlinks)| {
+            rewrite(&mut symbol);
+            for backlink in &mut backlinks {
+                if let Backlink::Symbol { link, .. } = backlink {
+                    rewrite(link);
+                }
+            }
+            (symbol, backlinks)
+        })
+        .collect()
+}
+
+/// Points every link at the page of the file its symbol is defined in, for when each file is
+/// rendered as its own chapter
+pub(crate) fn assign_file_pages(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    let pages: HashMap<(String, String), String> = document
+        .values()
+        .flat_map(|namespace| namespace.file_pages())
+        .collect();
+
+    rewrite_links(document, symbol_usages, |link| {
+        if let Some(page) = pages.get(&link.page_key()) {
+            link.set_page(page.clone());
+        }
+    })
+}
+
+/// Links the imports of each file to the imported file, when it is rendered too
+pub(crate) fn assign_import_links(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    page_per: PagePer,
+    output_prefix: &str,
+) {
+    let hrefs: HashMap<String, String> = document
+        .iter()
+        .flat_map(|(package, namespace)| {
+            let page = match package.as_str() {
+                "" => ROOT_PACKAGE_PAGE.to_string(),
+                package => package.replace('.', "/"),
+            };
+            namespace.file_hrefs(&page, page_per, output_prefix)
+        })
+        .collect();
+
+    for namespace in document.values_mut() {
+        namespace.set_import_hrefs(&hrefs);
+    }
+}
+
+/// Points every link into the directory the chapters are generated in
+pub(crate) fn assign_output_prefix(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+    output_prefix: &str,
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    rewrite_links(document, symbol_usages, |link| {
+        link.set_output_prefix(output_prefix.to_string())
+    })
+}
+
+/// Drops the deprecated symbols (and so everything within them) from the document, along with
+/// their usages and the backlinks from them, so that they are neither rendered nor linked to
+pub(crate) fn remove_deprecated(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    let removed: Vec<String> = document
+        .values_mut()
+        .flat_map(|namespace| namespace.remove_deprecated())
+        .map(|link| link.fqsl())
+        .collect();
+
+    without_removed(symbol_usages, &removed)
+}
+
+/// Whether `fqsl` is `symbol` itself or declared within it (a nested type, field or method)
+fn is_within(fqsl: &str, symbol: &str) -> bool {
+    fqsl.strip_prefix(symbol)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with("::"))
+}
+
+/// The usages of the symbols that were not removed, without the backlinks from removed ones
+fn without_removed(
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+    removed: &[String],
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    let is_removed = |link: &SymbolLink| {
+        let fqsl = link.fqsl();
+        removed.iter().any(|removed| is_within(&fqsl, removed))
+    };
+
+    symbol_usages
+        .into_iter()
+        .filter(|(symbol, _)| !is_removed(symbol))
+        .map(|(symbol, mut backlinks)| {
+            backlinks.retain(
+                |backlink| !matches!(backlink, Backlink::Symbol { link, .. } if is_removed(link)),
+            );
+            (symbol, backlinks)
+        })
+        .collect()
+}
+
+/// Drops the top level symbols that neither contain a symbol linked from the book content, nor
+/// are (transitively) the type of a field, request or response of one that does, along with their
+/// usages and the backlinks from them. Must be called once the content has been linked.
+pub(crate) fn retain_referenced(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: HashMap<SymbolLink, Vec<Backlink>>,
+) -> HashMap<SymbolLink, Vec<Backlink>> {
+    let model = ProtoModel::from_namespaces(document);
+    let dependencies = top_level_dependencies(&model);
+    let top_level = |fqsl: &str| {
+        dependencies
+            .keys()
+            .find(|symbol| is_within(fqsl, symbol))
+            .cloned()
+    };
+
+    let mut pending: Vec<String> = symbol_usages
+        .iter()
+        .filter(|(_, usages)| {
+            usages
+                .iter()
+                .any(|usage| matches!(usage, Backlink::Content(_)))
+        })
+        .filter_map(|(symbol, _)| top_level(&symbol.fqsl()))
+        .collect();
+
+    let mut keep = HashSet::new();
+    while let Some(symbol) = pending.pop() {
+        if keep.insert(symbol.clone()) {
+            pending.extend(
+                dependencies[&symbol]
+                    .iter()
+                    .filter_map(|dependency| top_level(dependency)),
+            );
+        }
+    }
+
+    let removed: Vec<String> = document
+        .values_mut()
+        .flat_map(|namespace| namespace.retain_symbols(&keep))
+        .map(|link| link.fqsl())
+        .collect();
+    document.retain(|_, namespace| !namespace.is_empty());
+
+    without_removed(symbol_usages, &removed)
+}
+
+/// The types referred to by each top level service, message and enum, including those of its
+/// nested messages
+fn top_level_dependencies(model: &ProtoModel) -> HashMap<String, Vec<String>> {
+    fn message_dependencies(message: &model::Message, dependencies: &mut Vec<String>) {
+        dependencies.extend(
+            message
+                .fields
+                .iter()
+                .filter_map(|field| field.type_name.clone()),
+        );
+        for nested in &message.messages {
+            message_dependencies(nested, dependencies);
+        }
+    }
+
+    let mut top_level = HashMap::new();
+
+    for file in model.packages.iter().flat_map(|package| &package.files) {
+        for service in &file.services {
+            let types = service
+                .methods
+                .iter()
+                .flat_map(|method| [method.request_type.clone(), method.response_type.clone()]);
+            top_level.insert(service.fqsl.clone(), types.collect());
+        }
+        for message in &file.messages {
+            let mut dependencies = Vec::new();
+            message_dependencies(message, &mut dependencies);
+            top_level.insert(message.fqsl.clone(), dependencies);
+        }
+        for enumeration in &file.enums {
+            top_level.insert(enumeration.fqsl.clone(), Vec::new());
+        }
+    }
+
+    top_level
+}
+
+/// Resolves a query to a symbol only when it is unambiguous
+pub(crate) fn resolve_unique<'a>(links: &'a [SymbolLink], query: &str) -> Option<&'a SymbolLink> {
+    let mut matches = links.iter().filter(|link| link.matches(query));
+    match (matches.next(), matches.next()) {
+        (Some(link), None) => Some(link),
+        _ => None,
+    }
+}
+
+pub fn resolve_comment_links(
+    document: &mut BTreeMap<String, ProtoNamespaceTemplate>,
+    symbol_usages: &HashMap<SymbolLink, Vec<Backlink>>,
+) {
+    let links: Vec<SymbolLink> = symbol_usages.keys().cloned().collect();
+    let resolve = |query: &str| resolve_unique(&links, query).cloned();
+
+    for namespace in document.values_mut() {
+        namespace.mutate_symbols(|symbol| symbol.resolve_comment_links(&resolve))
+    }
+}
+
+/// The outcome of resolving a single `proto!(...)` query against the known symbols
+pub(crate) enum LinkResolution {
+    Resolved(SymbolLink),
+    /// no symbol matched, with the closest fuzzy matches (if any were similar) or otherwise a
+    /// sample of valid queries
+    NotFound {
+        near_matches: Vec<String>,
+        samples: Vec<String>,
+    },
+    /// more than one symbol matched, with the fully qualified query for each
+    Ambiguous(Vec<String>),
+}
+
+impl LinkResolution {
+    /// Near matches scoring below `fuzzy_threshold` are not suggested
+    pub(crate) fn resolve(links: &[SymbolLink], query: &str, fuzzy_threshold: i64) -> Self {
+        let matches: Vec<_> = links.iter().filter(|&s| s.matches(query)).collect();
+
+        match matches.len() {
+            0 => {
+                let mut scored_links: Vec<_> = links
+                    .iter()
+                    .map(|link| (link.fqsl(), link.fuzzy_score(query)))
+                    .collect();
+
+                scored_links.sort_by_key(|(_, distance)| *distance);
+
+                let near_matches = scored_links
+                    .iter()
+                    .rev()
+                    .filter(|(_, distance)| *distance > 0 && *distance >= fuzzy_threshold)
+                    .take(3)
+                    .map(|(fqsl, _)| format!("proto!({})", fqsl))
+                    .collect();
+
+                let samples = scored_links
+                    .iter()
+                    .take(3)
+                    .map(|(fqsl, _)| format!("proto!({})", fqsl))
+                    .collect();
+
+                Self::NotFound {
+                    near_matches,
+                    samples,
+                }
+            }
+            1 => Self::Resolved(matches[0].clone()),
+            _ => Self::Ambiguous(
+                matches
+                    .iter()
+                    .map(|s| format!("proto!({})", s.fqsl()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Explanation of why the query did not resolve, or `None` when it did
+    pub(crate) fn error_message(&self, query: &str) -> Option<String> {
+        match self {
+            Self::Resolved(_) => None,
+            Self::NotFound {
+                near_matches,
+                samples,
+            } => Some(if near_matches.is_empty() {
+                format!("No protobuf symbol matched your query `{}`, or was similar. Sample of valid formats:\n{}", query, samples.join("\n"))
+            } else {
+                format!("No protobuf symbol matched your query `{}`, consider one of the following near matches:\n{}", query, near_matches.join("\n"))
+            }),
+            Self::Ambiguous(replacements) => Some(format!(
+                "More than one protobuf symbol matched your query. Replace your link with one of the following:\n{}",
+                replacements.join("\n")
+            )),
+        }
+    }
+}
+
+/// A `proto!(...)` link in the book content that does not resolve to exactly one symbol
+#[derive(Debug)]
+pub struct BrokenLink {
+    pub chapter: String,
+    pub query: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "in chapter `{}`, link `proto!({})`: {}",
+            self.chapter, self.query, self.reason
+        )
+    }
+}
+
+/// Splits `Symbol@key=value&other=value` into the symbol query and its context, if any
+fn split_context(query: &str) -> (&str, Option<&str>) {
+    match query.split_once('@') {
+        Some((query, context)) => (query, Some(context)),
+        None => (query, None),
+    }
+}
+
+fn proto_link_regex() -> &'static Regex {
+    static PROTO_LINK: OnceLock<Regex> = OnceLock::new();
+    PROTO_LINK.get_or_init(|| Regex::new(r"proto!\((.*)\)").expect("should be valid regex"))
+}
+
+fn markdown_options() -> Options {
+    let mut opts = Options::empty();
+    opts.insert(Options::ENABLE_TABLES);
+    opts.insert(Options::ENABLE_FOOTNOTES);
+    opts.insert(Options::ENABLE_STRIKETHROUGH);
+    opts.insert(Options::ENABLE_TASKLISTS);
+    opts
+}
+
+/// Resolves every `proto!(...)` link in the chapter without modifying it, returning those that fail
+pub(crate) fn find_broken_links(
+    chapter: &Chapter,
+    links: &[SymbolLink],
+    fuzzy_threshold: i64,
+) -> Vec<BrokenLink> {
+    let re = proto_link_regex();
+
+    Parser::new_ext(&chapter.content, markdown_options())
+        .filter_map(|e| match e {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                re.captures(&dest_url).map(|caps| caps[1].to_string())
+            }
+            _ => None,
+        })
+        .filter_map(|query| {
+            let (symbol_query, _) = split_context(&query);
+            LinkResolution::resolve(links, symbol_query, fuzzy_threshold)
+                .error_message(symbol_query)
+                .map(|reason| BrokenLink {
+                    chapter: chapter.name.clone(),
+                    query,
+                    reason,
+                })
+        })
+        .collect()
+}
+
 pub fn link_proto_symbols(
     chapter: &mut Chapter,
     symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
+    fuzzy_threshold: i64,
+    max_chapter_size: usize,
 ) -> Result<()> {
-    let matcher = SkimMatcherV2::default();
+    // parsing and re-rendering a huge chapter is slow and memory hungry, so don't unless it could
+    // have links to rewrite
+    if chapter.content.len() > max_chapter_size && !chapter.content.contains("proto!(") {
+        warn!(
+            "skipping chapter `{}` ({} bytes, over `max_chapter_size`) as it has no proto!() links",
+            truncate_label(chapter.name.clone()),
+            chapter.content.len()
+        );
+        return Ok(());
+    }
 
     let mut chapter_link_id = 1;
 
@@ -182,128 +791,134 @@ pub fn link_proto_symbols(
 
     // @todo assign symbol usages. maybe discriminate type with enum so they can be rendered differently.
 
-    let re = Regex::new(r"proto!\((.*)\)").expect("should be valid regex");
+    let re = proto_link_regex();
 
     let mut buf = String::with_capacity(chapter.content.len());
 
     let mut current_link: Option<SymbolLink> = None;
 
-    let mut opts = Options::empty();
-    opts.insert(Options::ENABLE_TABLES);
-    opts.insert(Options::ENABLE_FOOTNOTES);
-    opts.insert(Options::ENABLE_STRIKETHROUGH);
-    opts.insert(Options::ENABLE_TASKLISTS);
-
-    let events: Result<Vec<Event>> = Parser::new_ext(&chapter.content, opts).filter_map(|e| {
-        match e {
-            Event::Start(Tag::Link {
-                             link_type,
-                             dest_url,
-                             title,
-                             id
-                         }) if re.is_match(&dest_url) => {
-                let Some(caps) = re.captures(&dest_url) else {
-                    panic!("match with no capture!");
-                };
-
-                let link_query = &caps[1];
-
-                let matches: Vec<_> = links.iter().filter(|&s| {
-                    s.matches(link_query)
-                }).collect();
-
-                let mut symbol_link = match matches.len() {
-                    0 => {
-                        let mut scored_links: Vec<_> = links.iter().map(|link| {
-                            let fqsl = link.fqsl();
-
-                            let distance = matcher.fuzzy_match(&fqsl, &link_query).unwrap_or(0);
-
-                            (fqsl, distance)
-                        }).collect();
-
-                        scored_links.sort_by_key(|(_, distance)| *distance);
-
-                        let suggestions: Vec<_> = scored_links.iter().rev().filter(|(_, distance)| *distance > 0).take(3).map(|(fqsl, _)| {
-                            format!("proto!({})", &fqsl)
-                        }).collect();
-
-                        let err_str = if suggestions.is_empty() {
-                            let random_sample: Vec<_> = scored_links.iter().map(|(fqsl, _)| format!("proto!({})", &fqsl)).take(3).collect();
-                            format!("No protobuf symbol matched your query `{}`, or was similar. Sample of valid formats:\n{}", &link_query, random_sample.join("\n"))
-                        } else {
-                            format!("No protobuf symbol matched your query `{}`, consider one of the following near matches:\n{}", &link_query, suggestions.join("\n"))
+    // the first error ends the event stream, so the rewrite never holds every event in memory
+    let mut error = None;
+
+    let events = Parser::new_ext(&chapter.content, markdown_options())
+        .filter_map(|e| {
+            match e {
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) if re.is_match(&dest_url) => {
+                    let Some(caps) = re.captures(&dest_url) else {
+                        panic!("match with no capture!");
+                    };
+
+                    let (link_query, context) = split_context(&caps[1]);
+
+                    let mut symbol_link =
+                        match LinkResolution::resolve(&links, link_query, fuzzy_threshold) {
+                            LinkResolution::Resolved(link) => link,
+                            unresolved => {
+                                let err_str =
+                                    unresolved.error_message(link_query).expect("is unresolved");
+                                return Some(Err(anyhow!(err_str)));
+                            }
                         };
 
-                        return Some(Err(anyhow!(err_str)));
-                    }
-                    1 => matches[0],
-                    _ => {
-                        let replacements: Vec<_> = matches.iter().map(|&s| {
-                            format!("proto!({})", s.fqsl())
-                        }).collect();
+                    // don't backlink to draft chapters
+                    if let Some(path) = &chapter.path {
+                        let current_usages_of_symbol =
+                            symbol_usages.entry(symbol_link.clone()).or_default();
 
-                        let err_str = format!("More than one protobuf symbol matched your query. Replace your link with one of the following:\n{}", replacements.join("\n"));
+                        let usage_id = chapter_link_id;
 
-                        return Some(Err(anyhow!(err_str)));
-                    }
-                }.clone();
+                        let id = format!("{}{}", &usage_id, symbol_link.fqsl());
 
-                // don't backlink to draft chapters
-                if let Some(path) = &chapter.path {
-                    let current_usages_of_symbol = symbol_usages.entry(symbol_link.clone()).or_default();
+                        symbol_link.set_own_id(id.clone());
 
-                    let usage_id = chapter_link_id;
+                        let label =
+                            format!("{}[{}]", truncate_label(chapter.name.clone()), &usage_id);
 
-                    let id = format!("{}{}", &usage_id, symbol_link.fqsl());
+                        let content_link = ContentLink {
+                            id,
+                            path: path.to_string_lossy().into_owned(),
+                            label,
+                        };
 
-                    symbol_link.set_own_id(id.clone());
+                        current_usages_of_symbol.push(Backlink::Content(content_link))
+                    }
 
-                    let label = format!("{}[{}]", chapter.name, &usage_id);
+                    if let Some(context) = context {
+                        symbol_link.set_context(context.to_string());
+                    }
 
-                    let content_link = ContentLink { id, path: path.to_str().unwrap().to_string(), label };
+                    current_link = Some(symbol_link);
 
-                    current_usages_of_symbol.push(Backlink::Content(content_link))
+                    None
                 }
-
-                current_link = Some(symbol_link);
-
-                None
-            }
-            Event::Text(inner_text) if current_link.is_some() => {
-                current_link.as_mut().expect("is some").set_label(inner_text.to_string());
-                None
+                Event::Text(inner_text) if current_link.is_some() => {
+                    current_link
+                        .as_mut()
+                        .expect("is some")
+                        .set_label(inner_text.to_string());
+                    None
+                }
+                Event::End(TagEnd::Link) if current_link.is_some() => {
+                    let result = match current_link.as_ref().expect("is some").render() {
+                        Ok(link_html) => {
+                            let link = CowStr::Boxed(link_html.into());
+                            Ok(Event::InlineHtml(link))
+                        }
+                        Err(e) => Err(anyhow!(e)),
+                    };
+
+                    current_link = None;
+                    chapter_link_id += 1;
+
+                    Some(result)
+                }
+                _ => Some(Ok(e)),
             }
-            Event::End(TagEnd::Link) if current_link.is_some() => {
-                let result = match current_link.as_ref().expect("is some").render() {
-                    Ok(link_html) => {
-                        let link = CowStr::Boxed(link_html.into());
-                        Ok(Event::InlineHtml(link))
-                    }
-                    Err(e) => {
-                        Err(anyhow!(e))
-                    }
-                };
+        })
+        .map_while(|event| event.map_err(|e| error = Some(e)).ok());
 
-                current_link = None;
-                chapter_link_id += 1;
+    cmark(events, &mut buf).map_err(|err| anyhow::Error::from(err))?;
 
-                Some(result)
-            }
-            _ => Some(Ok(e))
-        }
-    }).collect();
+    if let Some(e) = error {
+        return Err(e);
+    }
 
-    chapter.content = cmark(events?.iter(), &mut buf)
-        .map(|_| buf)
-        .map_err(|err| anyhow::Error::from(err))?;
+    chapter.content = buf;
 
     Ok(())
 }
 
+/// Backslash escapes the characters markdown would otherwise interpret
+#[cfg(test)]
+fn backslash_escape(text: &str, special: impl Fn(char) -> bool) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if special(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// Caps a label taken from book content, so pathological link text or chapter names don't bloat
+/// every page they are backlinked from
+fn truncate_label(label: String) -> String {
+    match label.char_indices().nth(MAX_LABEL_CHARS) {
+        Some((end, _)) => format!("{}…", &label[..end]),
+        None => label,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::links::{link_proto_symbols, SymbolLink};
+    use crate::links::{
+        link_proto_symbols, SymbolLink, DEFAULT_LINK_FUZZY_THRESHOLD, DEFAULT_MAX_CHAPTER_SIZE,
+    };
     use mdbook::book::Chapter;
     use std::collections::{HashMap, HashSet};
 
@@ -319,7 +934,10 @@ mod test {
                 path: "package".to_string(),
                 property: None,
                 label_override: None,
-                own_id: None
+                own_id: None,
+                context: None,
+                page: None,
+                output_prefix: None
             }
         )
     }
@@ -336,7 +954,10 @@ mod test {
                 path: "package/deeper".to_string(),
                 property: None,
                 label_override: None,
-                own_id: None
+                own_id: None,
+                context: None,
+                page: None,
+                output_prefix: None
             }
         )
     }
@@ -353,7 +974,10 @@ mod test {
                 path: "package".to_string(),
                 property: Some("FooCall".into()),
                 label_override: None,
-                own_id: None
+                own_id: None,
+                context: None,
+                page: None,
+                output_prefix: None
             }
         )
     }
@@ -363,18 +987,37 @@ mod test {
         let fqsl = ".Foo";
         let packages = HashSet::from(["package".into()]);
 
+        let link = SymbolLink::from_fqsl(fqsl.to_string(), &packages);
+        assert_eq!(link.fqsl(), fqsl);
+        assert_eq!(link.href(), "/proto/_root.md#Foo");
         assert_eq!(
-            SymbolLink::from_fqsl(fqsl.to_string(), &packages),
+            link,
             SymbolLink {
                 symbol: "Foo".to_string(),
                 path: "".to_string(),
                 property: None,
                 label_override: None,
-                own_id: None
+                own_id: None,
+                context: None,
+                page: None,
+                output_prefix: None
             }
         )
     }
 
+    #[test]
+    fn should_only_match_whole_package_segments() {
+        let packages = HashSet::from(["".into(), "foo".into()]);
+
+        let root = SymbolLink::from_fqsl(".foobar.Baz".into(), &packages);
+        assert_eq!(root.fqsl(), ".foobar.Baz");
+        assert_eq!(root.href(), "/proto/_root.md#foobar.Baz");
+
+        let packaged = SymbolLink::from_fqsl(".foo.Baz::qux".into(), &packages);
+        assert_eq!(packaged.fqsl(), ".foo.Baz::qux");
+        assert_eq!(packaged.href(), "/proto/foo.md#Baz::qux");
+    }
+
     #[test]
     fn should_preserve_normal_links() {
         let mut chapter = Chapter {
@@ -399,7 +1042,13 @@ Lorem ipsum [footnote link][1] [external link](https://example.com)
 
         let original_content = chapter.content.clone();
 
-        link_proto_symbols(&mut chapter, &mut Default::default()).expect("should succeed");
+        link_proto_symbols(
+            &mut chapter,
+            &mut Default::default(),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        )
+        .expect("should succeed");
 
         assert_eq!(chapter.content.trim(), original_content.trim())
     }
@@ -427,7 +1076,13 @@ Lorem ipsum [proto link](proto!(HelloWorld))
             parent_names: vec![],
         };
 
-        link_proto_symbols(&mut chapter, &mut HashMap::from(links)).expect("should succeed");
+        link_proto_symbols(
+            &mut chapter,
+            &mut HashMap::from(links),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        )
+        .expect("should succeed");
 
         assert_eq!(
             chapter.content.trim(),
@@ -441,6 +1096,55 @@ Lorem ipsum <a href="/proto/hello.md#HelloWorld">proto link</a>
         )
     }
 
+    #[test]
+    fn should_replace_proto_links_within_headings() {
+        let links = [(
+            SymbolLink::from_fqsl(".hello.HelloWorld".into(), &HashSet::from(["hello".into()])),
+            Default::default(),
+        )];
+
+        let mut chapter = Chapter {
+            name: "Guide".to_string(),
+            content: r#"
+# Greeting with [HelloWorld](proto!(HelloWorld))
+
+Setext [heading](proto!(HelloWorld))
+------------------------------------
+
+Lorem ipsum
+"#
+            .to_string(),
+            number: None,
+            sub_items: vec![],
+            path: Some("guide.md".into()),
+            source_path: None,
+            parent_names: vec![],
+        };
+        let mut symbol_usages = HashMap::from(links);
+
+        link_proto_symbols(
+            &mut chapter,
+            &mut symbol_usages,
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        )
+        .expect("should succeed");
+
+        assert_eq!(
+            chapter.content.trim(),
+            r#"
+# Greeting with <a href="/proto/hello.md#HelloWorld" id="1.hello.HelloWorld">HelloWorld</a>
+
+## Setext <a href="/proto/hello.md#HelloWorld" id="2.hello.HelloWorld">heading</a>
+
+Lorem ipsum
+"#
+            .trim()
+        );
+        // both are backlinked from the symbol
+        assert_eq!(symbol_usages.values().next().unwrap().len(), 2);
+    }
+
     #[test]
     fn should_error_and_offer_solutions_in_the_result_when_too_many_symbols_match() {
         let packages = HashSet::from(["hello".into(), "other.namespace".into()]);
@@ -476,7 +1180,12 @@ Lorem ipsum [proto link](proto!(HelloWorld))
             parent_names: vec![],
         };
 
-        let res = link_proto_symbols(&mut chapter, &mut HashMap::from(links));
+        let res = link_proto_symbols(
+            &mut chapter,
+            &mut HashMap::from(links),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        );
 
         // contains check used as the order is (intentionally) not stable
         assert!(vec![
@@ -520,7 +1229,12 @@ Lorem ipsum [proto link](proto!(HelloWord))
             parent_names: vec![],
         };
 
-        let res = link_proto_symbols(&mut chapter, &mut HashMap::from(links));
+        let res = link_proto_symbols(
+            &mut chapter,
+            &mut HashMap::from(links),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        );
 
         assert_eq!(
             res.unwrap_err().to_string(),
@@ -559,7 +1273,13 @@ Lorem ipsum [proto link](proto!(HelloWorld))
             parent_names: vec![],
         };
 
-        link_proto_symbols(&mut chapter, &mut HashMap::from(links)).expect("should succeed");
+        link_proto_symbols(
+            &mut chapter,
+            &mut HashMap::from(links),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        )
+        .expect("should succeed");
 
         assert_eq!(
             chapter.content.trim(),
@@ -572,4 +1292,215 @@ Lorem ipsum <a href="/proto/hello.md#HelloWorld">proto link</a>
             .trim()
         )
     }
+
+    #[test]
+    fn should_carry_link_context_beside_the_href() {
+        let packages = HashSet::from(["billing".into()]);
+        let mut total = SymbolLink::from_fqsl(".billing.Invoice".into(), &packages);
+        total.set_property("total".into());
+        let links = [(total.clone(), Default::default())];
+
+        let mut chapter = Chapter {
+            name: "".to_string(),
+            content: r#"
+# test chapter
+
+Lorem ipsum [the total](proto!(Invoice::total@context=pricing))
+
+"#
+            .to_string(),
+            number: None,
+            sub_items: vec![],
+            path: None,
+            source_path: None,
+            parent_names: vec![],
+        };
+
+        let mut symbol_usages = HashMap::from(links);
+        link_proto_symbols(
+            &mut chapter,
+            &mut symbol_usages,
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            DEFAULT_MAX_CHAPTER_SIZE,
+        )
+        .expect("should succeed");
+
+        // the fragment is the plain anchor, so the link works without the script
+        assert!(chapter.content.contains(
+            r#"<a href="/proto/billing.md#Invoice::total" data-proto-context="context=pricing">the total</a>"#
+        ));
+        let mut expected = total.clone();
+        expected.set_context("context=pricing".into());
+        assert_eq!(
+            expected.to_markdown_link(),
+            r"[Invoice\:\:total](/proto/billing.md#Invoice::total)"
+        );
+        // the context does not change which symbol the link resolves to
+        assert!(symbol_usages.contains_key(&total));
+        assert_eq!(symbol_usages.len(), 1);
+    }
+
+    #[test]
+    fn fuzzy_score_should_rank_closer_symbols_higher() {
+        let packages = HashSet::from(["hello".into()]);
+        let hello_world = SymbolLink::from_fqsl(".hello.HelloWorld".into(), &packages);
+        let help_wanted = SymbolLink::from_fqsl(".hello.HelpWanted".into(), &packages);
+
+        assert!(hello_world.fuzzy_score("HeloWrld") > help_wanted.fuzzy_score("HeloWrld"));
+        assert!(help_wanted.fuzzy_score("HlpWnt") > hello_world.fuzzy_score("HlpWnt"));
+        assert!(hello_world.fuzzy_score("HelloWorld") > hello_world.fuzzy_score("HeloWrld"));
+        assert_eq!(hello_world.fuzzy_score("Goodbye"), 0);
+    }
+
+    #[test]
+    fn fuzzy_threshold_should_drop_weak_suggestions() {
+        use crate::links::LinkResolution;
+
+        let packages = HashSet::from(["hello".into()]);
+        let links = [
+            SymbolLink::from_fqsl(".hello.HelloWorld".into(), &packages),
+            SymbolLink::from_fqsl(".hello.HelpWanted".into(), &packages),
+        ];
+        let near_matches =
+            |query: &str, threshold: i64| match LinkResolution::resolve(&links, query, threshold) {
+                LinkResolution::NotFound { near_matches, .. } => near_matches,
+                _ => panic!("`{}` should not resolve", query),
+            };
+
+        // `hw` only loosely matches either symbol
+        assert_eq!(near_matches("hw", DEFAULT_LINK_FUZZY_THRESHOLD).len(), 2);
+        assert!(near_matches("hw", 100).is_empty());
+
+        // a close typo still clears the higher threshold
+        assert_eq!(
+            near_matches("HeloWrld", 100),
+            vec!["proto!(.hello.HelloWorld)"]
+        );
+
+        let message = LinkResolution::resolve(&links, "hw", 100)
+            .error_message("hw")
+            .unwrap();
+        assert!(message.starts_with("No protobuf symbol matched your query `hw`, or was similar."));
+    }
+
+    fn large_chapter(content: String) -> Chapter {
+        Chapter {
+            name: "Generated".to_string(),
+            content,
+            number: None,
+            sub_items: vec![],
+            path: Some("generated.md".into()),
+            source_path: None,
+            parent_names: vec![],
+        }
+    }
+
+    #[test]
+    fn should_skip_large_chapters_without_proto_links() {
+        // markdown the round trip would normalise, were the chapter parsed
+        let content = "* item\\\n+ `code` \\u{d800} &#xD800;\n".repeat(100_000);
+        let mut chapter = large_chapter(content.clone());
+
+        link_proto_symbols(
+            &mut chapter,
+            &mut HashMap::new(),
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            1024,
+        )
+        .expect("should succeed");
+
+        assert_eq!(chapter.content, content);
+    }
+
+    #[test]
+    fn should_still_link_large_chapters_with_proto_links() {
+        let hello =
+            SymbolLink::from_fqsl(".hello.HelloWorld".into(), &HashSet::from(["hello".into()]));
+        let mut symbol_usages = HashMap::from([(hello.clone(), Vec::new())]);
+
+        let filler = "Lorem ipsum dolor sit amet.\n\n".repeat(50_000);
+        let mut chapter = large_chapter(format!(
+            "{}[{}](proto!(HelloWorld))\n",
+            filler,
+            "very long link text ".repeat(100)
+        ));
+
+        link_proto_symbols(
+            &mut chapter,
+            &mut symbol_usages,
+            DEFAULT_LINK_FUZZY_THRESHOLD,
+            1024,
+        )
+        .expect("should succeed");
+
+        assert!(chapter
+            .content
+            .starts_with("Lorem ipsum dolor sit amet.\n\nLorem"));
+        assert!(chapter
+            .content
+            .contains(r#"<a href="/proto/hello.md#HelloWorld""#));
+        assert_eq!(symbol_usages[&hello].len(), 1);
+
+        // the link text is capped
+        let label_start = chapter.content.find(">very long").unwrap();
+        let label_end = label_start + chapter.content[label_start..].find("</a>").unwrap();
+        assert!(chapter.content[label_start..label_end].chars().count() <= 202);
+    }
+
+    #[test]
+    fn markdown_link_should_round_trip_through_pulldown_cmark() {
+        use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', ' ', '\t', '[', ']', '(', ')', '<', '>', '\\', '`', '*', '_', '&', '!',
+            '#', '~', '|', '"', '\'', ':', '?', '=', '-', '.', 'é', '✓',
+        ];
+
+        // xorshift, so failures are reproducible without a dependency
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random_text = |max_len: u64| -> String {
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            };
+            let len = next() % max_len;
+            (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect()
+        };
+
+        let packages = HashSet::from(["hello".into()]);
+        for _ in 0..2_000 {
+            let mut link = SymbolLink::from_fqsl(".hello.HelloWorld".into(), &packages);
+            let label = random_text(24);
+            let context = random_text(12);
+            link.set_label(label.clone());
+            link.set_context(context.clone());
+
+            let markdown = link.to_markdown_link();
+            let events: Vec<Event> = Parser::new(&markdown).collect();
+
+            let [Event::Start(Tag::Paragraph), Event::Start(Tag::Link { dest_url, .. }), inner @ .., Event::End(TagEnd::Link), Event::End(TagEnd::Paragraph)] =
+                events.as_slice()
+            else {
+                panic!(
+                    "`{}` did not parse as a single link: {:?}",
+                    markdown, events
+                );
+            };
+
+            let text: String = inner
+                .iter()
+                .map(|event| match event {
+                    Event::Text(text) => text.to_string(),
+                    other => panic!("`{}` has unexpected {:?} in its label", markdown, other),
+                })
+                .collect();
+
+            assert_eq!(dest_url.as_ref(), link.href(), "{}", markdown);
+            assert_eq!(text, label, "{}", markdown);
+        }
+    }
 }
@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use log::warn;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+
+/// First and last field numbers reserved for the protobuf implementation
+const IMPLEMENTATION_RESERVED: (i32, i32) = (19_000, 19_999);
+/// Largest field number representable on the wire (2^29 - 1)
+const MAX_FIELD_NUMBER: i32 = 536_870_911;
+
+/// What to do when the descriptor set contains field numbers protoc would normally reject
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum SchemaLints {
+    Off,
+    Warn,
+    /// fail the build, reporting every finding
+    Error,
+}
+
+impl TryFrom<&str> for SchemaLints {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            other => Err(anyhow!(
+                "`schema_lints` should be one of \"off\", \"warn\" or \"error\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+/// Checks the field numbers and extension ranges of every message, reporting findings at the
+/// given severity
+pub(crate) fn lint_field_numbers(
+    file_descriptor_set: &FileDescriptorSet,
+    severity: SchemaLints,
+) -> Result<()> {
+    if severity == SchemaLints::Off {
+        return Ok(());
+    }
+
+    let findings = field_number_findings(file_descriptor_set);
+
+    match severity {
+        SchemaLints::Error if !findings.is_empty() => {
+            Err(anyhow!("schema lints failed:\n{}", findings.join("\n")))
+        }
+        _ => {
+            findings.iter().for_each(|finding| warn!("{}", finding));
+            Ok(())
+        }
+    }
+}
+
+fn field_number_findings(file_descriptor_set: &FileDescriptorSet) -> Vec<String> {
+    let mut findings = Vec::new();
+
+    for file in &file_descriptor_set.file {
+        for message in &file.message_type {
+            lint_message(&format!(".{}", file.package()), message, &mut findings);
+        }
+    }
+
+    findings
+}
+
+fn lint_message(parent: &str, message: &DescriptorProto, findings: &mut Vec<String>) {
+    let message_name = format!("{}.{}", parent.trim_end_matches('.'), message.name());
+
+    for field in &message.field {
+        let number = field.number();
+        let field_name = format!("{}::{}", message_name, field.name());
+
+        if (IMPLEMENTATION_RESERVED.0..=IMPLEMENTATION_RESERVED.1).contains(&number) {
+            findings.push(format!(
+                "{} uses field number {}, which is reserved for the protobuf implementation ({}-{})",
+                field_name, number, IMPLEMENTATION_RESERVED.0, IMPLEMENTATION_RESERVED.1
+            ));
+        }
+
+        if !(1..=MAX_FIELD_NUMBER).contains(&number) {
+            findings.push(format!(
+                "{} uses field number {}, outside the valid range 1-{}",
+                field_name, number, MAX_FIELD_NUMBER
+            ));
+        }
+
+        // reserved and extension ranges are end-exclusive
+        if message
+            .reserved_range
+            .iter()
+            .any(|range| (range.start()..range.end()).contains(&number))
+        {
+            findings.push(format!(
+                "{} uses field number {}, which {} reserves",
+                field_name, number, message_name
+            ));
+        }
+
+        if let Some(range) = message
+            .extension_range
+            .iter()
+            .find(|range| (range.start()..range.end()).contains(&number))
+        {
+            findings.push(format!(
+                "{} uses field number {}, which overlaps the extension range {}-{}",
+                field_name,
+                number,
+                range.start(),
+                range.end() - 1
+            ));
+        }
+    }
+
+    for range in &message.extension_range {
+        let (start, end) = (range.start(), range.end() - 1);
+
+        if start <= IMPLEMENTATION_RESERVED.1 && end >= IMPLEMENTATION_RESERVED.0 {
+            findings.push(format!(
+                "{} declares extension range {}-{}, which overlaps the range reserved for the protobuf implementation ({}-{})",
+                message_name, start, end, IMPLEMENTATION_RESERVED.0, IMPLEMENTATION_RESERVED.1
+            ));
+        }
+
+        if start < 1 || end > MAX_FIELD_NUMBER {
+            findings.push(format!(
+                "{} declares extension range {}-{}, outside the valid range 1-{}",
+                message_name, start, end, MAX_FIELD_NUMBER
+            ));
+        }
+    }
+
+    for nested in &message.nested_type {
+        lint_message(&message_name, nested, findings);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost_types::descriptor_proto::{ExtensionRange, ReservedRange};
+    use prost_types::{FieldDescriptorProto, FileDescriptorProto};
+
+    fn field(name: &str, number: i32) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            ..Default::default()
+        }
+    }
+
+    fn invalid_fixture() -> FileDescriptorSet {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("broken.proto".into()),
+                package: Some("broken".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Corrupt".into()),
+                    field: vec![
+                        field("fine", 1),
+                        field("implementation", 19_500),
+                        field("too_big", 536_870_912),
+                        field("reserved", 10),
+                        field("extended", 100),
+                    ],
+                    reserved_range: vec![ReservedRange {
+                        start: Some(10),
+                        end: Some(11),
+                    }],
+                    extension_range: vec![ExtensionRange {
+                        start: Some(100),
+                        end: Some(200),
+                        ..Default::default()
+                    }],
+                    nested_type: vec![DescriptorProto {
+                        name: Some("Nested".into()),
+                        field: vec![field("zero", 0)],
+                        extension_range: vec![ExtensionRange {
+                            start: Some(18_000),
+                            end: Some(20_000),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn should_report_each_field_number_rule() {
+        let findings = field_number_findings(&invalid_fixture());
+
+        for expected in [
+            ".broken.Corrupt::implementation uses field number 19500, which is reserved for the protobuf implementation",
+            ".broken.Corrupt::too_big uses field number 536870912, outside the valid range",
+            ".broken.Corrupt::reserved uses field number 10, which .broken.Corrupt reserves",
+            ".broken.Corrupt::extended uses field number 100, which overlaps the extension range 100-199",
+            ".broken.Corrupt.Nested::zero uses field number 0, outside the valid range",
+            ".broken.Corrupt.Nested declares extension range 18000-19999, which overlaps the range reserved",
+        ] {
+            assert!(
+                findings.iter().any(|finding| finding.starts_with(expected)),
+                "missing `{}` in {:#?}",
+                expected,
+                findings
+            );
+        }
+
+        assert_eq!(findings.len(), 6);
+        assert!(!findings.iter().any(|finding| finding.contains("::fine")));
+    }
+
+    #[test]
+    fn severity_should_control_whether_findings_fail() {
+        let fixture = invalid_fixture();
+
+        assert!(lint_field_numbers(&fixture, SchemaLints::Off).is_ok());
+        assert!(lint_field_numbers(&fixture, SchemaLints::Warn).is_ok());
+        assert!(lint_field_numbers(&fixture, SchemaLints::Error).is_err());
+    }
+}
@@ -1,19 +1,26 @@
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::{fs, io};
 
-use clap::{Arg, ArgMatches, Command};
+use anyhow::anyhow;
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use log::{debug, error, info};
 use mdbook::errors::Error;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
-use mdbook_protobuf::ProtobufPreprocessor;
+use mdbook::{Config, MDBook};
+use mdbook_protobuf::{
+    config_schema, find_symbols, json_schema, openapi_spec, protoc_command, symbol_index,
+    validate_links, ProtobufPreprocessor,
+};
 use semver::{Version, VersionReq};
 use toml_edit::{value, Array, DocumentMut, Item, Table, Value};
 
 const CSS: &[u8] = include_bytes!("assets/mdbook-protobuf.css");
-const FILES: &[(&str, &[u8])] = &[("mdbook-protobuf.css", CSS)];
+const JS: &[u8] = include_bytes!("assets/mdbook-protobuf.js");
+const FILES: &[(&str, &[u8])] = &[("mdbook-protobuf.css", CSS), ("mdbook-protobuf.js", JS)];
 
 pub fn make_app() -> Command {
     Command::new("nop-preprocessor").about("A mdbook preprocessor which does precisely nothing").subcommand(
@@ -21,7 +28,55 @@ pub fn make_app() -> Command {
     ).subcommand(
         Command::new("install").arg(
             Arg::new("dir").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).arg(
+            Arg::new("theme-dir").long("theme-dir").help("Directory to write the CSS to, relative to the book root.\nDefaults to `theme/` when the book has one, otherwise the book root")
         ).about("Install the required asset files and include it in the config"),
+    ).subcommand(
+        Command::new("validate").arg(
+            Arg::new("book").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).about("Check that every `proto!(...)` link in the book resolves, without building it"),
+    ).subcommand(
+        Command::new("dump-index").arg(
+            Arg::new("book").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).about("Print every symbol `proto!(...)` links can resolve to as JSON"),
+    ).subcommand(
+        Command::new("list-symbols").arg(
+            Arg::new("query").default_value("").help("Fuzzy search for symbols, e.g. `hellreq`")
+        ).arg(
+            Arg::new("dir").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).arg(
+            Arg::new("limit").long("limit").value_parser(clap::value_parser!(usize)).default_value("20").help("Maximum number of symbols to print")
+        ).arg(
+            Arg::new("output-format").long("output-format").value_parser(["text", "json"]).default_value("text").help("`text` for a line per symbol, or `json` for an array of `fqsl`, `kind` and `deprecated` objects")
+        ).about("Print the symbols matching a query, best match first, to find the argument for a `proto!(...)` link"),
+    ).subcommand(
+        Command::new("schema").about("Print a JSON Schema for `book.toml`, describing the `[preprocessor.protobuf]` options"),
+    ).subcommand(
+        Command::new("generate").arg(
+            Arg::new("book").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).arg(
+            Arg::new("proto-path").long("proto-path").short('I').action(ArgAction::Append).help("Directory in which to search for imports, passed through to protoc")
+        ).arg(
+            Arg::new("protoc").long("protoc").default_value("protoc").help("The protoc binary to invoke")
+        ).arg(
+            Arg::new("files").required(true).num_args(1..).help("The .proto files to include in the descriptor set")
+        ).about("Generate the configured `proto_descriptor` by invoking protoc"),
+    ).subcommand(
+        Command::new("openapi").arg(
+            Arg::new("out").required(true).help("Path to write the OpenAPI document to")
+        ).arg(
+            Arg::new("book").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).arg(
+            Arg::new("api-version").long("api-version").default_value("1.0.0").help("Version of the API, for the document's `info.version`")
+        ).about("Write an OpenAPI 3 document of the methods with `google.api.http` bindings, as served by grpc-gateway"),
+    ).subcommand(
+        Command::new("jsonschema").arg(
+            Arg::new("out").required(true).help("Path to write the JSON Schema to")
+        ).arg(
+            Arg::new("book").long("book").default_value(".").help("Root directory for the book,\nshould contain the configuration file (`book.toml`)")
+        ).arg(
+            Arg::new("message").long("message").help("Fully qualified message to write the schema of, e.g. `.helloworld.HelloRequest`. Without it every message and enum is bundled in `$defs`")
+        ).about("Write a JSON Schema of the messages as they are written in the protobuf JSON mapping"),
     )
 }
 
@@ -36,6 +91,20 @@ fn main() {
         handle_supports(&preprocessor, sub_args);
     } else if let Some(sub_args) = matches.subcommand_matches("install") {
         handle_install(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("validate") {
+        handle_validate(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("dump-index") {
+        handle_dump_index(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("list-symbols") {
+        handle_list_symbols(sub_args);
+    } else if matches.subcommand_matches("schema").is_some() {
+        handle_schema();
+    } else if let Some(sub_args) = matches.subcommand_matches("generate") {
+        handle_generate(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("openapi") {
+        handle_openapi(sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("jsonschema") {
+        handle_jsonschema(sub_args);
     } else if let Err(e) = handle_preprocessing(&preprocessor) {
         error!("{:?}", e);
         process::exit(1);
@@ -137,7 +206,9 @@ fn handle_install(sub_args: &ArgMatches) -> ! {
         item["proto_url_root"] = value(proto_url_root);
     }
 
-    let added_files = add_additional_files(&mut doc);
+    let theme_dir = theme_dir(&proj_dir, sub_args.get_one::<String>("theme-dir"));
+    let assets = asset_paths(theme_dir.as_deref());
+    let added_files = add_additional_files(&mut doc, &assets);
 
     if !has_pre || added_files {
         info!("Saving changed configuration to {}", config.display());
@@ -148,7 +219,7 @@ fn handle_install(sub_args: &ArgMatches) -> ! {
     }
 
     let mut printed = false;
-    for (name, content) in FILES {
+    for (name, content) in &assets {
         let filepath = proj_dir.join(name);
         if filepath.exists() {
             debug!(
@@ -165,6 +236,9 @@ fn handle_install(sub_args: &ArgMatches) -> ! {
                 );
             }
             debug!("Writing content for '{}' into {}", name, filepath.display());
+            if let Some(dir) = filepath.parent() {
+                fs::create_dir_all(dir).expect("can't create directory");
+            }
             let mut file = File::create(filepath).expect("can't open file for writing");
             file.write_all(content)
                 .expect("can't write content to file");
@@ -176,6 +250,279 @@ fn handle_install(sub_args: &ArgMatches) -> ! {
     process::exit(0);
 }
 
+fn handle_validate(sub_args: &ArgMatches) -> ! {
+    let proj_dir = sub_args
+        .get_one::<String>("book")
+        .expect("Required argument");
+
+    process::exit(validate_book(Path::new(proj_dir)));
+}
+
+/// Reports every broken `proto!(...)` link in the book, returning the exit code
+fn validate_book(proj_dir: &Path) -> i32 {
+    let book = match MDBook::load(proj_dir) {
+        Ok(book) => book,
+        Err(e) => {
+            error!("Failed to load book at {}: {:?}", proj_dir.display(), e);
+            return 1;
+        }
+    };
+
+    match validate_links(&book.root, &book.config, &book.book) {
+        Ok(broken_links) if broken_links.is_empty() => {
+            info!("All proto links resolved");
+            0
+        }
+        Ok(broken_links) => {
+            for broken_link in &broken_links {
+                error!("{}", broken_link);
+            }
+            error!("{} proto link(s) failed to resolve", broken_links.len());
+            1
+        }
+        Err(e) => {
+            error!("{:?}", e);
+            1
+        }
+    }
+}
+
+fn handle_dump_index(sub_args: &ArgMatches) -> ! {
+    let proj_dir = sub_args
+        .get_one::<String>("book")
+        .expect("Required argument");
+
+    if let Err(e) = dump_index(Path::new(proj_dir), &mut io::stdout()) {
+        error!("{:?}", e);
+        process::exit(1);
+    }
+
+    process::exit(0);
+}
+
+/// Writes the symbol index of the book's configured descriptor as a JSON array
+fn dump_index(proj_dir: &Path, out: &mut impl Write) -> Result<(), Error> {
+    let config = Config::from_disk(proj_dir.join("book.toml"))?;
+    let index = symbol_index(proj_dir, &config)?;
+
+    serde_json::to_writer_pretty(&mut *out, &index)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+fn handle_list_symbols(sub_args: &ArgMatches) -> ! {
+    let proj_dir = sub_args
+        .get_one::<String>("dir")
+        .expect("Required argument");
+    let query = sub_args
+        .get_one::<String>("query")
+        .expect("Required argument");
+    let limit = *sub_args
+        .get_one::<usize>("limit")
+        .expect("Required argument");
+    let json = sub_args
+        .get_one::<String>("output-format")
+        .is_some_and(|format| format == "json");
+
+    if let Err(e) = list_symbols(Path::new(proj_dir), query, limit, json, &mut io::stdout()) {
+        error!("{:?}", e);
+        process::exit(1);
+    }
+
+    process::exit(0);
+}
+
+/// Writes up to `limit` symbols matching the query, one per line as `proto!(<fqsl>)`, its kind and
+/// page, or as a JSON array of their fqsl, kind and deprecation (to diff between builds in CI)
+fn list_symbols(
+    proj_dir: &Path,
+    query: &str,
+    limit: usize,
+    json: bool,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    let config = Config::from_disk(proj_dir.join("book.toml"))?;
+    let symbols = find_symbols(proj_dir, &config, query)?;
+
+    if json {
+        let symbols: Vec<_> = symbols
+            .iter()
+            .take(limit)
+            .map(|entry| {
+                serde_json::json!({
+                    "fqsl": entry.fqsl,
+                    "kind": entry.kind,
+                    "deprecated": entry.deprecated,
+                })
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut *out, &symbols)?;
+        writeln!(out)?;
+
+        return Ok(());
+    }
+
+    for entry in symbols.iter().take(limit) {
+        writeln!(
+            out,
+            "proto!({})\t{}\t{}",
+            entry.fqsl, entry.kind, entry.href
+        )?;
+    }
+
+    Ok(())
+}
+
+fn handle_schema() -> ! {
+    if let Err(e) = print_schema(&mut io::stdout()) {
+        error!("{:?}", e);
+        process::exit(1);
+    }
+
+    process::exit(0);
+}
+
+/// Writes the JSON Schema of the `book.toml` configuration
+fn print_schema(out: &mut impl Write) -> Result<(), Error> {
+    serde_json::to_writer_pretty(&mut *out, &config_schema())?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+fn handle_openapi(sub_args: &ArgMatches) -> ! {
+    let book_dir = sub_args
+        .get_one::<String>("book")
+        .expect("Required argument");
+    let out = sub_args
+        .get_one::<String>("out")
+        .expect("Required argument");
+    let version = sub_args
+        .get_one::<String>("api-version")
+        .expect("Required argument");
+
+    if let Err(e) = write_openapi(Path::new(book_dir), version, Path::new(out)) {
+        error!("{:?}", e);
+        process::exit(1);
+    }
+
+    info!("Wrote OpenAPI document to {}", out);
+    process::exit(0);
+}
+
+/// Writes the OpenAPI document of the book's configured descriptor to `out`
+fn write_openapi(proj_dir: &Path, version: &str, out: &Path) -> Result<(), Error> {
+    let config = Config::from_disk(proj_dir.join("book.toml"))?;
+    let document = openapi_spec(proj_dir, &config, version)?;
+
+    let mut file = File::create(out)?;
+    serde_json::to_writer_pretty(&mut file, &document)?;
+    writeln!(file)?;
+
+    Ok(())
+}
+
+fn handle_jsonschema(sub_args: &ArgMatches) -> ! {
+    let book_dir = sub_args
+        .get_one::<String>("book")
+        .expect("Required argument");
+    let out = sub_args
+        .get_one::<String>("out")
+        .expect("Required argument");
+    let message = sub_args.get_one::<String>("message");
+
+    if let Err(e) = write_json_schema(
+        Path::new(book_dir),
+        message.map(String::as_str),
+        Path::new(out),
+    ) {
+        error!("{:?}", e);
+        process::exit(1);
+    }
+
+    info!("Wrote JSON Schema to {}", out);
+    process::exit(0);
+}
+
+/// Writes the JSON Schema of the book's configured descriptor (or one message of it) to `out`
+fn write_json_schema(proj_dir: &Path, message: Option<&str>, out: &Path) -> Result<(), Error> {
+    let config = Config::from_disk(proj_dir.join("book.toml"))?;
+    let document = json_schema(proj_dir, &config, message)?;
+
+    let mut file = File::create(out)?;
+    serde_json::to_writer_pretty(&mut file, &document)?;
+    writeln!(file)?;
+
+    Ok(())
+}
+
+fn handle_generate(sub_args: &ArgMatches) -> ! {
+    let book_dir = sub_args
+        .get_one::<String>("book")
+        .expect("Required argument");
+    let protoc = sub_args
+        .get_one::<String>("protoc")
+        .expect("Required argument");
+    let proto_paths: Vec<&String> = sub_args
+        .get_many::<String>("proto-path")
+        .unwrap_or_default()
+        .collect();
+    let files: Vec<&String> = sub_args
+        .get_many::<String>("files")
+        .expect("Required argument")
+        .collect();
+
+    match generate_descriptor(Path::new(book_dir), protoc, &proto_paths, &files) {
+        Ok(descriptor_path) => {
+            info!("Wrote descriptor set to {}", descriptor_path.display());
+            process::exit(0);
+        }
+        Err(e) => {
+            error!("{:?}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs protoc over the given files, writing the descriptor set to the book's configured
+/// `proto_descriptor` path
+fn generate_descriptor<S: AsRef<OsStr>>(
+    book_dir: &Path,
+    protoc: &str,
+    proto_paths: &[S],
+    files: &[S],
+) -> Result<PathBuf, Error> {
+    let config = Config::from_disk(book_dir.join("book.toml"))?;
+    let descriptor_path = config
+        .get_preprocessor("protobuf")
+        .and_then(|c| c.get("proto_descriptor"))
+        .and_then(|v| v.as_str())
+        .ok_or(anyhow!(
+            "expected `proto_descriptor` string in the `preprocessor.protobuf` config"
+        ))?;
+    let descriptor_path = book_dir.join(descriptor_path);
+
+    if let Some(parent) = descriptor_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let output = protoc_command(protoc, proto_paths, files, &descriptor_path)
+        .output()
+        .map_err(|e| anyhow!("failed to run `{}`: {}", protoc, e))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{}` exited with {}:\n{}",
+            protoc,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(descriptor_path)
+}
+
 fn has_file(elem: &Option<&mut Array>, file: &str) -> bool {
     match elem {
         Some(elem) => elem.iter().any(|elem| match elem.as_str() {
@@ -186,18 +533,44 @@ fn has_file(elem: &Option<&mut Array>, file: &str) -> bool {
     }
 }
 
-fn add_additional_files(doc: &mut DocumentMut) -> bool {
+/// The directory, relative to the book root, to write the CSS to: `--theme-dir`, or mdbook's
+/// conventional `theme/` when the book has one. `None` is the book root.
+fn theme_dir(proj_dir: &Path, theme_dir: Option<&String>) -> Option<String> {
+    match theme_dir {
+        Some(dir) => Some(dir.trim_end_matches('/').to_string()).filter(|dir| !dir.is_empty()),
+        None => proj_dir.join("theme").is_dir().then(|| "theme".to_string()),
+    }
+}
+
+/// The path of each asset, relative to the book root, with the CSS in `theme_dir`
+fn asset_paths(theme_dir: Option<&str>) -> Vec<(String, &'static [u8])> {
+    FILES
+        .iter()
+        .map(|(name, content)| match theme_dir {
+            Some(dir) if name.ends_with(".css") => (format!("{}/{}", dir, name), *content),
+            _ => (name.to_string(), *content),
+        })
+        .collect()
+}
+
+fn add_additional_files(doc: &mut DocumentMut, assets: &[(String, &[u8])]) -> bool {
     let mut changed = false;
     let mut printed = false;
 
-    for (file, _) in FILES {
-        let ext = Path::new(file)
+    for (file, _) in assets {
+        let path = Path::new(file);
+        let ext = path
             .extension()
             .and_then(|ext| ext.to_str())
             .expect("file should have extension");
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("file should have a name");
 
+        // by name, so that files installed in another directory are not added twice
         let additional_section = additional(doc, ext);
-        if has_file(&additional_section, file) {
+        if has_file(&additional_section, name) {
             debug!("'{}' already in 'additional-{}'. Skipping", file, ext)
         } else {
             printed = true;
@@ -245,3 +618,284 @@ fn insert_additional(doc: &mut DocumentMut, additional_type: &str, file: &str) {
         .unwrap()
         .push(file);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DEMO_DESCRIPTOR: &str = "../demo/docs/build/proto_file_descriptor_set.pb";
+
+    fn book_with_content(content: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor = fs::canonicalize(DEMO_DESCRIPTOR).unwrap();
+
+        fs::write(
+            dir.path().join("book.toml"),
+            format!(
+                "[preprocessor.protobuf]\nproto_descriptor = {:?}\n",
+                descriptor.display().to_string()
+            ),
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/SUMMARY.md"),
+            "# Summary\n\n- [Intro](intro.md)\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/intro.md"), content).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn validate_should_exit_non_zero_for_broken_links() {
+        let book = book_with_content("# Intro\n\nSee [missing](proto!(DoesNotExistAnywhere))\n");
+
+        assert_ne!(validate_book(book.path()), 0);
+    }
+
+    #[test]
+    fn validate_should_exit_zero_when_all_links_resolve() {
+        let book = book_with_content("# Intro\n\nSee [greeter](proto!(Greeter))\n");
+
+        assert_eq!(validate_book(book.path()), 0);
+    }
+
+    #[test]
+    fn dump_index_should_list_resolvable_symbols() {
+        let book = book_with_content("# Intro\n");
+        let mut out = Vec::new();
+
+        dump_index(book.path(), &mut out).expect("should succeed");
+
+        let index: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let entries = index.as_array().expect("should be an array");
+        let hello_request = entries
+            .iter()
+            .find(|entry| entry["fqsl"] == ".helloworld.HelloRequest")
+            .expect("HelloRequest should be indexed");
+
+        assert_eq!(hello_request["kind"], "message");
+        assert_eq!(hello_request["package"], "helloworld");
+        assert_eq!(hello_request["href"], "/proto/helloworld.html#HelloRequest");
+        assert!(entries
+            .iter()
+            .any(|entry| entry["fqsl"] == ".helloworld.HelloRequest::name"));
+    }
+
+    #[test]
+    fn list_symbols_should_print_the_best_match_first() {
+        let book = book_with_content("# Intro\n");
+        let mut out = Vec::new();
+
+        list_symbols(book.path(), "hellreq", 1, false, &mut out).expect("should succeed");
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 1, "output should be capped at the limit");
+        assert_eq!(
+            lines[0],
+            "proto!(.helloworld.HelloRequest)\tmessage\t/proto/helloworld.html#HelloRequest"
+        );
+    }
+
+    #[test]
+    fn list_symbols_should_print_json_when_requested() {
+        let book = book_with_content("# Intro\n");
+        let mut out = Vec::new();
+
+        list_symbols(book.path(), "", usize::MAX, true, &mut out).expect("should succeed");
+
+        let symbols: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+        assert!(symbols.contains(&serde_json::json!({
+            "fqsl": ".helloworld.HelloRequest",
+            "kind": "message",
+            "deprecated": false,
+        })));
+        assert!(symbols.contains(&serde_json::json!({
+            "fqsl": ".testing.TypeTestService::SayHello",
+            "kind": "method",
+            "deprecated": true,
+        })));
+    }
+
+    #[test]
+    fn schema_should_describe_the_preprocessor_options() {
+        let mut out = Vec::new();
+
+        print_schema(&mut out).expect("should succeed");
+
+        let schema: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let options = &schema["properties"]["preprocessor"]["properties"]["protobuf"];
+        assert_eq!(
+            options["oneOf"],
+            serde_json::json!([{ "required": ["proto_descriptor"] }, { "required": ["proto_src"] }])
+        );
+        assert_eq!(
+            options["properties"]["symbol_sort"]["enum"],
+            serde_json::json!(["source", "alpha"])
+        );
+    }
+
+    #[test]
+    fn openapi_should_write_a_document_titled_after_the_book() {
+        let book = book_with_content("# Intro\n");
+        let out = book.path().join("openapi.json");
+
+        write_openapi(book.path(), "2.1.0", &out).expect("should succeed");
+
+        let document: serde_json::Value = serde_json::from_slice(&fs::read(&out).unwrap()).unwrap();
+        assert_eq!(document["openapi"], "3.0.3");
+        assert_eq!(document["info"]["version"], "2.1.0");
+        // the demo has no `google.api.http` bindings
+        assert_eq!(document["paths"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn jsonschema_should_write_the_schema_of_a_message() {
+        let book = book_with_content("# Intro\n");
+        let out = book.path().join("hello_request.schema.json");
+
+        write_json_schema(book.path(), Some(".helloworld.HelloRequest"), &out)
+            .expect("should succeed");
+
+        let schema: serde_json::Value = serde_json::from_slice(&fs::read(&out).unwrap()).unwrap();
+        assert_eq!(
+            schema["$defs"]["helloworld.HelloRequest"]["properties"]["name"]["type"],
+            "string"
+        );
+    }
+
+    fn book_with_descriptor_path(descriptor: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("book.toml"),
+            format!(
+                "[preprocessor.protobuf]\nproto_descriptor = {:?}\n",
+                descriptor
+            ),
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn protoc_command_should_include_source_info_and_proto_paths() {
+        let command = protoc_command(
+            "protoc",
+            &["proto", "vendor"],
+            &["proto/a.proto", "proto/b.proto"],
+            Path::new("build/out.pb"),
+        );
+
+        assert_eq!(command.get_program(), "protoc");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![
+                "--include_imports",
+                "--include_source_info",
+                "--descriptor_set_out=build/out.pb",
+                "--proto_path=proto",
+                "--proto_path=vendor",
+                "proto/a.proto",
+                "proto/b.proto",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    fn fake_protoc(dir: &Path, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("fake-protoc");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generate_should_invoke_protoc_with_the_configured_descriptor_path() {
+        let book = book_with_descriptor_path("build/descriptor.pb");
+        // records its arguments and writes the file named by --descriptor_set_out
+        let protoc = fake_protoc(
+            book.path(),
+            r#"echo "$@" > "$(dirname "$0")/args"
+for arg in "$@"; do
+  case "$arg" in --descriptor_set_out=*) : > "${arg#--descriptor_set_out=}";; esac
+done"#,
+        );
+
+        let descriptor_path =
+            generate_descriptor(book.path(), &protoc, &["proto"], &["proto/hello.proto"])
+                .expect("should succeed");
+
+        assert_eq!(descriptor_path, book.path().join("build/descriptor.pb"));
+        assert!(descriptor_path.exists());
+
+        let args = fs::read_to_string(book.path().join("args")).unwrap();
+        assert_eq!(
+            args.trim(),
+            format!(
+                "--include_imports --include_source_info --descriptor_set_out={} --proto_path=proto proto/hello.proto",
+                descriptor_path.display()
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generate_should_surface_protoc_stderr_on_failure() {
+        let book = book_with_descriptor_path("descriptor.pb");
+        let protoc = fake_protoc(
+            book.path(),
+            r#"echo "hello.proto:3:1: Expected top-level statement" >&2; exit 1"#,
+        );
+
+        let err = generate_descriptor(book.path(), &protoc, &[], &["hello.proto"])
+            .expect_err("should fail");
+
+        assert!(err
+            .to_string()
+            .contains("hello.proto:3:1: Expected top-level statement"));
+    }
+
+    #[test]
+    fn install_should_put_the_css_in_the_theme_directory() {
+        let book = tempfile::tempdir().unwrap();
+        assert_eq!(theme_dir(book.path(), None), None);
+        assert_eq!(
+            theme_dir(book.path(), Some(&"styles/".to_string())),
+            Some("styles".to_string())
+        );
+
+        fs::create_dir(book.path().join("theme")).unwrap();
+        let theme = theme_dir(book.path(), None);
+        assert_eq!(theme.as_deref(), Some("theme"));
+
+        let assets = asset_paths(theme.as_deref());
+        let paths: Vec<&str> = assets.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["theme/mdbook-protobuf.css", "mdbook-protobuf.js"]
+        );
+
+        let mut doc = "[output.html]\n".parse::<DocumentMut>().unwrap();
+        assert!(add_additional_files(&mut doc, &assets));
+        assert_eq!(
+            doc["output"]["html"]["additional-css"].to_string(),
+            r#"["theme/mdbook-protobuf.css"]"#
+        );
+        assert_eq!(
+            doc["output"]["html"]["additional-js"].to_string(),
+            r#"["mdbook-protobuf.js"]"#
+        );
+
+        // a book that already includes the CSS from elsewhere is left as it is
+        let mut doc = "[output.html]\nadditional-css = [\"mdbook-protobuf.css\"]\nadditional-js = [\"mdbook-protobuf.js\"]\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        assert!(!add_additional_files(&mut doc, &assets));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::links::SymbolKind;
+use crate::view::ProtoNamespaceTemplate;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const SYMBOL_MANIFEST_FILE: &str = "symbols.json";
+
+/// Bump whenever a field is removed or changes meaning, new optional fields do not require a bump.
+pub const SYMBOL_MANIFEST_VERSION: u32 = 1;
+
+/// Machine-readable listing of every symbol in the generated reference, written to
+/// `<book src>/proto/symbols.json` when `emit_symbol_manifest = true`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SymbolManifest {
+    pub version: u32,
+    pub symbols: Vec<SymbolManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SymbolManifestEntry {
+    /// fully qualified symbol, as accepted by `proto!()`, e.g. `.helloworld.Greeter::SayHello`
+    pub fqsl: String,
+    pub kind: SymbolKind,
+    /// root-relative url of the rendered page, including the anchor
+    pub href: String,
+    /// element id of the symbol within the page
+    pub anchor: String,
+    pub deprecated: bool,
+    /// first paragraph of the leading comment
+    pub summary: Option<String>,
+}
+
+/// One symbol that `proto!()` can link to, as printed by `mdbook-protobuf dump-index`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SymbolIndexEntry {
+    /// fully qualified symbol, as accepted by `proto!()`, e.g. `.helloworld.Greeter::SayHello`
+    pub fqsl: String,
+    pub kind: SymbolKind,
+    /// root-relative url of the rendered page, including the anchor
+    pub href: String,
+    /// package declaring the symbol, e.g. `helloworld`
+    pub package: String,
+    pub deprecated: bool,
+}
+
+impl SymbolIndexEntry {
+    pub(crate) fn from_namespaces(
+        namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+    ) -> Vec<Self> {
+        let mut entries = Vec::new();
+
+        for (package, namespace) in namespaces {
+            namespace.visit_symbols(|symbol| {
+                entries.push(SymbolIndexEntry {
+                    fqsl: symbol.fqsl(),
+                    kind: symbol.kind(),
+                    href: symbol.symbol_link().href().replacen(".md#", ".html#", 1),
+                    package: package.clone(),
+                    deprecated: symbol.deprecated(),
+                })
+            });
+        }
+
+        entries
+    }
+}
+
+impl SymbolManifest {
+    pub(crate) fn from_namespaces(namespaces: &BTreeMap<String, ProtoNamespaceTemplate>) -> Self {
+        let mut symbols = Vec::new();
+
+        for namespace in namespaces.values() {
+            namespace.visit_symbols(|symbol| {
+                let link = symbol.symbol_link();
+                symbols.push(SymbolManifestEntry {
+                    fqsl: symbol.fqsl(),
+                    kind: symbol.kind(),
+                    href: link.href().replacen(".md#", ".html#", 1),
+                    anchor: link.id(),
+                    deprecated: symbol.deprecated(),
+                    summary: symbol.summary(),
+                })
+            });
+        }
+
+        Self {
+            version: SYMBOL_MANIFEST_VERSION,
+            symbols,
+        }
+    }
+
+    /// Writes the manifest, leaving the file untouched when the content is unchanged so that
+    /// `mdbook serve` does not see a change in the source directory and rebuild in a loop.
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+
+        if fs::read_to_string(path).is_ok_and(|existing| existing == json) {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+
+        fs::write(path, json)
+            .with_context(|| format!("Failed to write symbol manifest to {}", path.display()))
+    }
+}
@@ -0,0 +1,169 @@
+use crate::view::ProtoNamespaceTemplate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The documented packages and their symbols as plain data, for tools (custom renderers, linters)
+/// that want what the reference is generated from without its html. Type references are resolved
+/// and editions defaults applied, as in the rendered reference.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProtoModel {
+    /// ordered by name
+    pub packages: Vec<Package>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Package {
+    /// empty for files without a `package` declaration
+    pub name: String,
+    pub files: Vec<File>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct File {
+    /// path of the proto file, e.g. `helloworld/helloworld.proto`
+    pub name: String,
+    pub services: Vec<Service>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<Enum>,
+}
+
+/// Comments attached to a symbol, as markdown
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Comments {
+    /// the leading comment, less any directive lines (e.g. `@timeout 5s`) and deprecation paragraph
+    pub leading: Option<String>,
+    pub trailing: Option<String>,
+    pub leading_detached: Vec<String>,
+    /// the text of a `Deprecated: ...` paragraph
+    pub deprecation: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    /// fully qualified symbol, as accepted by `proto!()`, e.g. `.helloworld.Greeter`
+    pub fqsl: String,
+    pub comments: Comments,
+    pub deprecated: bool,
+    pub methods: Vec<Method>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    /// e.g. `.helloworld.Greeter::SayHello`
+    pub fqsl: String,
+    pub comments: Comments,
+    pub deprecated: bool,
+    /// fully qualified request message, e.g. `.helloworld.HelloRequest`
+    pub request_type: String,
+    pub response_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Message {
+    pub name: String,
+    /// e.g. `.helloworld.HelloRequest`, or `.pkg.Outer.Inner` for nested messages
+    pub fqsl: String,
+    pub comments: Comments,
+    pub deprecated: bool,
+    /// the synthetic `XEntry` message protoc nests for a `map<K, V> x` field, with the
+    /// `map_entry` option set
+    pub map_entry: bool,
+    /// ordered by field number, including the fields of oneofs
+    pub fields: Vec<Field>,
+    pub oneofs: Vec<Oneof>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<Enum>,
+    /// reserved field numbers, as end-inclusive `(start, end)` ranges
+    pub reserved_ranges: Vec<(i32, i32)>,
+    pub reserved_names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// name in the protobuf JSON mapping, e.g. `pageSize`, or as set by the `json_name` option
+    pub json_name: String,
+    /// e.g. `.helloworld.HelloRequest::name`
+    pub fqsl: String,
+    pub number: i32,
+    /// scalar type (e.g. `string`), or the fully qualified message or enum. `None` when the type
+    /// could not be resolved
+    pub type_name: Option<String>,
+    pub repeated: bool,
+    /// has explicit presence (proto3 `optional`, or the editions `field_presence` feature)
+    pub optional: bool,
+    pub deprecated: bool,
+    /// proto2 `[default = ...]`, or the effective default of an editions field
+    pub default_value: Option<String>,
+    /// name of the oneof the field is a member of
+    pub oneof: Option<String>,
+    pub comments: Comments,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Oneof {
+    pub name: String,
+    pub comments: Comments,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub fqsl: String,
+    pub comments: Comments,
+    pub deprecated: bool,
+    pub allow_alias: bool,
+    pub values: Vec<EnumValue>,
+    /// reserved numbers, as end-inclusive `(start, end)` ranges
+    pub reserved_ranges: Vec<(i32, i32)>,
+    pub reserved_names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EnumValue {
+    pub name: String,
+    pub number: i32,
+    pub deprecated: bool,
+    pub comments: Comments,
+}
+
+impl ProtoModel {
+    pub(crate) fn from_namespaces(namespaces: &BTreeMap<String, ProtoNamespaceTemplate>) -> Self {
+        Self {
+            packages: namespaces
+                .iter()
+                .map(|(package, namespace)| Package {
+                    name: package.clone(),
+                    files: namespace.to_model(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Every message, including nested ones, in the order they are declared
+    pub fn messages(&self) -> impl Iterator<Item = &Message> {
+        fn with_nested(message: &Message) -> Box<dyn Iterator<Item = &Message> + '_> {
+            Box::new(std::iter::once(message).chain(message.messages.iter().flat_map(with_nested)))
+        }
+
+        self.packages
+            .iter()
+            .flat_map(|package| &package.files)
+            .flat_map(|file| &file.messages)
+            .flat_map(with_nested)
+    }
+
+    /// Every enum, including those nested in messages
+    pub fn enums(&self) -> impl Iterator<Item = &Enum> {
+        let files = self.packages.iter().flat_map(|package| &package.files);
+
+        files
+            .clone()
+            .flat_map(|file| &file.enums)
+            .chain(self.messages().flat_map(|message| &message.enums))
+    }
+}
@@ -0,0 +1,745 @@
+use crate::model::{Enum, Field, Message, Method, ProtoModel};
+use anyhow::Result;
+use prost::Message as _;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// `google.api.http` is an extension of `MethodOptions`, which `prost_types` drops in decoding, so
+// the bindings are decoded from the descriptor separately with these partial descriptors (as the
+// editions features are).
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PartialFileDescriptorSet {
+    #[prost(message, repeated, tag = "1")]
+    file: Vec<PartialFile>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PartialFile {
+    #[prost(string, optional, tag = "2")]
+    package: Option<String>,
+    #[prost(message, repeated, tag = "6")]
+    service: Vec<PartialService>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PartialService {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    method: Vec<PartialMethod>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PartialMethod {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    options: Option<PartialMethodOptions>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PartialMethodOptions {
+    /// `google.api.http`
+    #[prost(message, optional, tag = "72295728")]
+    http: Option<HttpRule>,
+}
+
+/// `google.api.HttpRule`
+#[derive(Clone, PartialEq, prost::Message)]
+struct HttpRule {
+    #[prost(oneof = "Pattern", tags = "2, 3, 4, 5, 6, 8")]
+    pattern: Option<Pattern>,
+    #[prost(string, tag = "7")]
+    body: String,
+    #[prost(string, tag = "12")]
+    response_body: String,
+    #[prost(message, repeated, tag = "11")]
+    additional_bindings: Vec<HttpRule>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum Pattern {
+    #[prost(string, tag = "2")]
+    Get(String),
+    #[prost(string, tag = "3")]
+    Put(String),
+    #[prost(string, tag = "4")]
+    Post(String),
+    #[prost(string, tag = "5")]
+    Delete(String),
+    #[prost(string, tag = "6")]
+    Patch(String),
+    #[prost(message, tag = "8")]
+    Custom(CustomHttpPattern),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct CustomHttpPattern {
+    #[prost(string, tag = "1")]
+    kind: String,
+    #[prost(string, tag = "2")]
+    path: String,
+}
+
+/// A REST binding of a method, from its `google.api.http` rule or one of the rule's
+/// `additional_bindings`
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct HttpBinding {
+    /// lowercase, as OpenAPI path items key their operations
+    verb: String,
+    /// path template, e.g. `/v1/{name=shelves/*}/books`
+    template: String,
+    /// request field mapped to the body, `*` for every field not bound by the path
+    body: Option<String>,
+    /// response field mapped to the body, rather than the whole response
+    response_body: Option<String>,
+}
+
+impl HttpBinding {
+    fn from_rule(rule: &HttpRule) -> Option<Self> {
+        let (verb, template) = match rule.pattern.as_ref()? {
+            Pattern::Get(path) => ("get", path),
+            Pattern::Put(path) => ("put", path),
+            Pattern::Post(path) => ("post", path),
+            Pattern::Delete(path) => ("delete", path),
+            Pattern::Patch(path) => ("patch", path),
+            Pattern::Custom(custom) => (custom.kind.as_str(), &custom.path),
+        };
+        let non_empty = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+
+        Some(Self {
+            verb: verb.to_lowercase(),
+            template: template.clone(),
+            body: non_empty(&rule.body),
+            response_body: non_empty(&rule.response_body),
+        })
+    }
+
+    /// The OpenAPI path, with the segment patterns of variables dropped
+    /// (`/v1/{name=shelves/*}` is `/v1/{name}`), and the variables in it
+    fn path(&self) -> (String, Vec<String>) {
+        let mut path = String::new();
+        let mut variables = Vec::new();
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            let variable = rest[start + 1..end]
+                .split_once('=')
+                .map_or(&rest[start + 1..end], |(variable, _)| variable);
+            path.push_str(&rest[..start]);
+            path.push_str(&format!("{{{}}}", variable));
+            variables.push(variable.to_string());
+            rest = &rest[end + 1..];
+        }
+        path.push_str(rest);
+
+        (path, variables)
+    }
+}
+
+/// The REST bindings of every method annotated with `google.api.http` in an encoded
+/// `FileDescriptorSet`, keyed by the method's fqsl, e.g. `.library.Library::GetBook`
+pub(crate) fn http_bindings(descriptor: &[u8]) -> Result<HashMap<String, Vec<HttpBinding>>> {
+    let mut bindings = HashMap::new();
+
+    for file in PartialFileDescriptorSet::decode(descriptor)?.file {
+        let scope = if file.package().is_empty() {
+            String::new()
+        } else {
+            format!(".{}", file.package())
+        };
+        for service in &file.service {
+            for method in &service.method {
+                let Some(rule) = method.options.as_ref().and_then(|o| o.http.as_ref()) else {
+                    continue;
+                };
+                let method_bindings: Vec<_> = std::iter::once(rule)
+                    .chain(&rule.additional_bindings)
+                    .filter_map(HttpBinding::from_rule)
+                    .collect();
+                bindings.insert(
+                    format!("{}.{}::{}", scope, service.name(), method.name()),
+                    method_bindings,
+                );
+            }
+        }
+    }
+
+    Ok(bindings)
+}
+
+/// An OpenAPI 3 document with an operation for each binding. Message fields are named as in the
+/// protobuf JSON mapping (lower camel case), as grpc-gateway marshals them. Request fields not
+/// bound to the path or body become query parameters when they are scalars or enums; message
+/// fields are left out of the query, rather than flattened.
+pub(crate) fn document(
+    model: &ProtoModel,
+    bindings: &HashMap<String, Vec<HttpBinding>>,
+    title: &str,
+    version: &str,
+) -> Value {
+    let schemas = Schemas::new(model, "#/components/schemas/");
+    let mut referenced = BTreeSet::new();
+    let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+
+    for package in &model.packages {
+        for file in &package.files {
+            for service in &file.services {
+                for method in &service.methods {
+                    let Some(method_bindings) = bindings.get(&method.fqsl) else {
+                        continue;
+                    };
+                    for (idx, binding) in method_bindings.iter().enumerate() {
+                        let operation_id = match idx {
+                            0 => format!("{}_{}", service.name, method.name),
+                            _ => format!("{}_{}{}", service.name, method.name, idx + 1),
+                        };
+                        let (path, variables) = binding.path();
+                        let mut operation =
+                            schemas.operation(method, binding, &variables, &mut referenced);
+                        operation.insert("operationId".into(), json!(operation_id));
+                        operation.insert("tags".into(), json!([service.name]));
+                        if method.deprecated || service.deprecated {
+                            operation.insert("deprecated".into(), json!(true));
+                        }
+                        paths
+                            .entry(path)
+                            .or_default()
+                            .insert(binding.verb.clone(), Value::Object(operation));
+                    }
+                }
+            }
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+        "components": { "schemas": schemas.components(referenced) },
+    })
+}
+
+/// Messages and enums of the model by fqsl, to derive JSON schemas from
+pub(crate) struct Schemas<'a> {
+    messages: HashMap<&'a str, &'a Message>,
+    enums: HashMap<&'a str, &'a Enum>,
+    /// where the schemas of messages and enums are referenced from, e.g. `#/$defs/`
+    reference_prefix: &'static str,
+}
+
+impl<'a> Schemas<'a> {
+    pub(crate) fn new(model: &'a ProtoModel, reference_prefix: &'static str) -> Self {
+        Self {
+            messages: model
+                .messages()
+                .map(|message| (message.fqsl.as_str(), message))
+                .collect(),
+            enums: model
+                .enums()
+                .map(|enumeration| (enumeration.fqsl.as_str(), enumeration))
+                .collect(),
+            reference_prefix,
+        }
+    }
+
+    fn operation(
+        &self,
+        method: &Method,
+        binding: &HttpBinding,
+        variables: &[String],
+        referenced: &mut BTreeSet<String>,
+    ) -> Map<String, Value> {
+        let mut operation = Map::new();
+        if let Some(summary) = method.comments.leading.as_ref() {
+            operation.insert("description".into(), json!(summary.trim()));
+        }
+
+        let request = self.messages.get(method.request_type.as_str());
+        let request_field =
+            |name: &str| request.and_then(|r| r.fields.iter().find(|f| f.name == name));
+
+        let mut parameters: Vec<Value> = variables
+            .iter()
+            .map(|variable| {
+                let schema = self
+                    .nested_field(method.request_type.as_str(), variable)
+                    .map_or(json!({ "type": "string" }), |field| {
+                        self.field_schema(field, referenced)
+                    });
+                json!({ "name": variable, "in": "path", "required": true, "schema": schema })
+            })
+            .collect();
+        // the top level request fields bound by the path, e.g. `book` for `{book.name}`
+        let bound: BTreeSet<&str> = variables
+            .iter()
+            .map(|variable| variable.split('.').next().unwrap_or(variable))
+            .collect();
+        let unbound = || {
+            request
+                .into_iter()
+                .flat_map(|r| &r.fields)
+                .filter(|field| !bound.contains(field.name.as_str()))
+        };
+
+        match binding.body.as_deref() {
+            Some("*") => {
+                let schema = if bound.is_empty() {
+                    self.reference(&method.request_type, referenced)
+                } else {
+                    self.object_schema(unbound(), referenced)
+                };
+                operation.insert("requestBody".into(), request_body(schema));
+            }
+            Some(body) => {
+                let schema = request_field(body)
+                    .map_or(json!({}), |field| self.field_schema(field, referenced));
+                operation.insert("requestBody".into(), request_body(schema));
+                parameters.extend(
+                    unbound()
+                        .filter(|field| field.name != body)
+                        .filter_map(|field| self.query_parameter(field, referenced)),
+                );
+            }
+            None => {
+                parameters
+                    .extend(unbound().filter_map(|field| self.query_parameter(field, referenced)));
+            }
+        }
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), Value::Array(parameters));
+        }
+
+        let response = match binding.response_body.as_deref() {
+            Some(body) => self
+                .messages
+                .get(method.response_type.as_str())
+                .and_then(|r| r.fields.iter().find(|f| f.name == body))
+                .map_or(json!({}), |field| self.field_schema(field, referenced)),
+            None => self.reference(&method.response_type, referenced),
+        };
+        operation.insert(
+            "responses".into(),
+            json!({
+                "200": {
+                    "description": "A successful response.",
+                    "content": { "application/json": { "schema": response } }
+                }
+            }),
+        );
+
+        operation
+    }
+
+    /// The field at a dotted path from a message, e.g. `book.name`
+    fn nested_field(&self, message: &str, path: &str) -> Option<&'a Field> {
+        let (name, rest) = path
+            .split_once('.')
+            .map_or((path, None), |(n, r)| (n, Some(r)));
+        let field = self
+            .messages
+            .get(message)?
+            .fields
+            .iter()
+            .find(|f| f.name == name)?;
+        match rest {
+            Some(rest) => self.nested_field(field.type_name.as_deref()?, rest),
+            None => Some(field),
+        }
+    }
+
+    fn query_parameter(&self, field: &Field, referenced: &mut BTreeSet<String>) -> Option<Value> {
+        let type_name = field.type_name.as_deref()?;
+        if self.messages.contains_key(type_name) {
+            return None;
+        }
+        let mut parameter = json!({
+            "name": field.json_name,
+            "in": "query",
+            "schema": self.field_schema(field, referenced),
+        });
+        if let Some(description) = field.comments.leading.as_ref() {
+            parameter["description"] = json!(description.trim());
+        }
+        Some(parameter)
+    }
+
+    fn object_schema<'f>(
+        &self,
+        fields: impl Iterator<Item = &'f Field>,
+        referenced: &mut BTreeSet<String>,
+    ) -> Value {
+        let properties: Map<String, Value> = fields
+            .map(|field| {
+                let mut schema = self.field_schema(field, referenced);
+                if let Some(description) = field.comments.leading.as_ref() {
+                    // siblings of a `$ref` are ignored, so it is wrapped to keep the description
+                    if schema.get("$ref").is_some() {
+                        schema = json!({ "allOf": [schema] });
+                    }
+                    schema["description"] = json!(description.trim());
+                }
+                (field.json_name.clone(), schema)
+            })
+            .collect();
+        json!({ "type": "object", "properties": properties })
+    }
+
+    fn field_schema(&self, field: &Field, referenced: &mut BTreeSet<String>) -> Value {
+        let Some(type_name) = field.type_name.as_deref() else {
+            return json!({});
+        };
+
+        if let Some(entry) = self.map_entry(field) {
+            let value = entry.fields.iter().find(|f| f.number == 2);
+            return json!({
+                "type": "object",
+                "additionalProperties": value.map_or(json!({}), |v| self.field_schema(v, referenced)),
+            });
+        }
+
+        let schema = self.reference(type_name, referenced);
+        if field.repeated {
+            json!({ "type": "array", "items": schema })
+        } else {
+            schema
+        }
+    }
+
+    /// The synthesized `XEntry` message of a `map<K, V> x` field
+    fn map_entry(&self, field: &Field) -> Option<&'a Message> {
+        let entry = self.messages.get(field.type_name.as_deref()?)?;
+        (field.repeated && entry.map_entry).then_some(*entry)
+    }
+
+    /// The schema of a scalar, or a reference to the schema of a message or enum
+    fn reference(&self, type_name: &str, referenced: &mut BTreeSet<String>) -> Value {
+        if let Some(schema) = scalar_schema(type_name).or_else(|| well_known_schema(type_name)) {
+            return schema;
+        }
+        if !self.messages.contains_key(type_name) && !self.enums.contains_key(type_name) {
+            return json!({});
+        }
+        referenced.insert(type_name.to_string());
+        json!({ "$ref": format!("{}{}", self.reference_prefix, schema_name(type_name)) })
+    }
+
+    /// Schemas of the referenced messages and enums, and those they reference in turn, by
+    /// [schema_name]
+    pub(crate) fn components(&self, mut referenced: BTreeSet<String>) -> BTreeMap<String, Value> {
+        let mut components = BTreeMap::new();
+        let mut pending: Vec<String> = referenced.iter().cloned().collect();
+
+        while let Some(type_name) = pending.pop() {
+            let mut nested = BTreeSet::new();
+            let schema = if let Some(message) = self.messages.get(type_name.as_str()) {
+                let mut schema = self.object_schema(message.fields.iter(), &mut nested);
+                if let Some(description) = message.comments.leading.as_ref() {
+                    schema["description"] = json!(description.trim());
+                }
+                schema
+            } else if let Some(enumeration) = self.enums.get(type_name.as_str()) {
+                json!({
+                    "type": "string",
+                    "enum": enumeration.values.iter().map(|v| &v.name).collect::<Vec<_>>(),
+                })
+            } else {
+                continue;
+            };
+            components.insert(schema_name(&type_name).to_string(), schema);
+
+            for type_name in nested {
+                if referenced.insert(type_name.clone()) {
+                    pending.push(type_name);
+                }
+            }
+        }
+
+        components
+    }
+}
+
+fn request_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+/// The message or enum's full name, e.g. `helloworld.HelloRequest`
+pub(crate) fn schema_name(type_name: &str) -> &str {
+    type_name.trim_start_matches('.')
+}
+
+/// Scalars in the protobuf JSON mapping, where 64 bit integers are strings
+fn scalar_schema(type_name: &str) -> Option<Value> {
+    Some(match type_name {
+        "double" => json!({ "type": "number", "format": "double" }),
+        "float" => json!({ "type": "number", "format": "float" }),
+        "int32" | "sint32" | "sfixed32" => json!({ "type": "integer", "format": "int32" }),
+        "uint32" | "fixed32" => json!({ "type": "integer", "format": "int64" }),
+        "int64" | "sint64" | "sfixed64" => json!({ "type": "string", "format": "int64" }),
+        "uint64" | "fixed64" => json!({ "type": "string", "format": "uint64" }),
+        "bool" => json!({ "type": "boolean" }),
+        "string" => json!({ "type": "string" }),
+        "bytes" => json!({ "type": "string", "format": "byte" }),
+        _ => return None,
+    })
+}
+
+/// Well known types with a special JSON mapping
+fn well_known_schema(type_name: &str) -> Option<Value> {
+    Some(match type_name {
+        ".google.protobuf.Timestamp" => json!({ "type": "string", "format": "date-time" }),
+        ".google.protobuf.Duration" | ".google.protobuf.FieldMask" => json!({ "type": "string" }),
+        ".google.protobuf.Empty" | ".google.protobuf.Struct" => json!({ "type": "object" }),
+        ".google.protobuf.Value" => json!({}),
+        ".google.protobuf.ListValue" => json!({ "type": "array", "items": {} }),
+        ".google.protobuf.DoubleValue" => scalar_schema("double")?,
+        ".google.protobuf.FloatValue" => scalar_schema("float")?,
+        ".google.protobuf.Int64Value" => scalar_schema("int64")?,
+        ".google.protobuf.UInt64Value" => scalar_schema("uint64")?,
+        ".google.protobuf.Int32Value" => scalar_schema("int32")?,
+        ".google.protobuf.UInt32Value" => scalar_schema("uint32")?,
+        ".google.protobuf.BoolValue" => scalar_schema("bool")?,
+        ".google.protobuf.StringValue" => scalar_schema("string")?,
+        ".google.protobuf.BytesValue" => scalar_schema("bytes")?,
+        _ => return None,
+    })
+}
+
+/// The JSON name protoc derives from a field name, e.g. `page_size` is `pageSize`, for
+/// descriptors without the `json_name` protoc fills in
+pub(crate) fn json_name(name: &str) -> String {
+    let mut json_name = String::new();
+    let mut capitalize = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            json_name.extend(c.to_uppercase());
+            capitalize = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ProtobufPreprocessor;
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        MessageOptions, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    fn field(name: &str, number: i32, typ: Type) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            label: Some(Label::Optional.into()),
+            r#type: Some(typ.into()),
+            ..Default::default()
+        }
+    }
+
+    fn library_model() -> ProtoModel {
+        let message = |name: &str, field: Vec<FieldDescriptorProto>| DescriptorProto {
+            name: Some(name.into()),
+            field,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("library.proto".into()),
+            package: Some("library".into()),
+            message_type: vec![
+                message(
+                    "GetBookRequest",
+                    vec![
+                        field("name", 1, Type::String),
+                        field("read_mask_paths", 2, Type::String),
+                        FieldDescriptorProto {
+                            json_name: Some("view".into()),
+                            ..field("view_mode", 3, Type::String)
+                        },
+                    ],
+                ),
+                message(
+                    "Book",
+                    vec![
+                        field("name", 1, Type::String),
+                        field("page_count", 2, Type::Int64),
+                        FieldDescriptorProto {
+                            json_name: Some("isbn13".into()),
+                            ..field("isbn", 3, Type::String)
+                        },
+                    ],
+                ),
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Library".into()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("GetBook".into()),
+                    input_type: Some(".library.GetBookRequest".into()),
+                    output_type: Some(".library.Book".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".into()),
+            ..Default::default()
+        };
+
+        ProtobufPreprocessor::build_model(&FileDescriptorSet { file: vec![file] })
+    }
+
+    /// The encoding of the same service with its `google.api.http` option
+    fn library_bindings() -> Vec<u8> {
+        PartialFileDescriptorSet {
+            file: vec![PartialFile {
+                package: Some("library".into()),
+                service: vec![PartialService {
+                    name: Some("Library".into()),
+                    method: vec![PartialMethod {
+                        name: Some("GetBook".into()),
+                        options: Some(PartialMethodOptions {
+                            http: Some(HttpRule {
+                                pattern: Some(Pattern::Get("/v1/{name=shelves/*/books/*}".into())),
+                                additional_bindings: vec![HttpRule {
+                                    pattern: Some(Pattern::Get("/v1/books/{name}".into())),
+                                    ..Default::default()
+                                }],
+                                ..Default::default()
+                            }),
+                        }),
+                    }],
+                }],
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn should_map_a_get_binding_to_a_path_with_query_parameters() {
+        let bindings = http_bindings(&library_bindings()).unwrap();
+        let document = document(&library_model(), &bindings, "Library", "1.0.0");
+
+        assert_eq!(document["openapi"], json!("3.0.3"));
+        assert_eq!(
+            document["paths"]["/v1/{name}"]["get"],
+            json!({
+                "operationId": "Library_GetBook",
+                "tags": ["Library"],
+                "parameters": [
+                    { "name": "name", "in": "path", "required": true, "schema": { "type": "string" } },
+                    { "name": "readMaskPaths", "in": "query", "schema": { "type": "string" } },
+                    { "name": "view", "in": "query", "schema": { "type": "string" } },
+                ],
+                "responses": {
+                    "200": {
+                        "description": "A successful response.",
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/library.Book" } } }
+                    }
+                }
+            })
+        );
+        assert_eq!(
+            document["paths"]["/v1/books/{name}"]["get"]["operationId"],
+            json!("Library_GetBook2")
+        );
+        assert_eq!(
+            document["components"]["schemas"],
+            json!({
+                "library.Book": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "pageCount": { "type": "string", "format": "int64" },
+                        // as set by the `json_name` option
+                        "isbn13": { "type": "string" },
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn only_map_entry_messages_should_make_maps() {
+        let entry = |name: &str, map_entry: bool| DescriptorProto {
+            name: Some(name.into()),
+            field: vec![
+                field("key", 1, Type::String),
+                field("value", 2, Type::Int32),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(map_entry),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let repeated = |name: &str, number: i32, type_name: &str| FieldDescriptorProto {
+            label: Some(Label::Repeated.into()),
+            type_name: Some(type_name.into()),
+            ..field(name, number, Type::Message)
+        };
+        let file = FileDescriptorProto {
+            name: Some("library.proto".into()),
+            package: Some("library".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Shelf".into()),
+                field: vec![
+                    repeated("counts", 1, ".library.Shelf.CountsEntry"),
+                    repeated("index", 2, ".library.Shelf.IndexEntry"),
+                ],
+                // named like an entry, but a message of its own
+                nested_type: vec![entry("CountsEntry", true), entry("IndexEntry", false)],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".into()),
+            ..Default::default()
+        };
+        let model = ProtobufPreprocessor::build_model(&FileDescriptorSet { file: vec![file] });
+
+        let schemas = Schemas::new(&model, "#/components/schemas/");
+        let components = schemas.components(BTreeSet::from([".library.Shelf".to_string()]));
+        let properties = &components["library.Shelf"]["properties"];
+        assert_eq!(
+            properties["counts"],
+            json!({ "type": "object", "additionalProperties": { "type": "integer", "format": "int32" } })
+        );
+        assert_eq!(
+            properties["index"],
+            json!({ "type": "array", "items": { "$ref": "#/components/schemas/library.Shelf.IndexEntry" } })
+        );
+    }
+
+    #[test]
+    fn should_read_path_variables_and_bodies_from_the_rule() {
+        let binding = |template: &str| HttpBinding {
+            verb: "post".into(),
+            template: template.into(),
+            body: Some("*".into()),
+            response_body: None,
+        };
+
+        assert_eq!(
+            binding("/v1/{parent=shelves/*}/books:import").path(),
+            ("/v1/{parent}/books:import".into(), vec!["parent".into()])
+        );
+        assert_eq!(
+            binding("/v1/{book.name}/{id}").path(),
+            (
+                "/v1/{book.name}/{id}".into(),
+                vec!["book.name".into(), "id".into()]
+            )
+        );
+        assert_eq!(json_name("read_mask_paths"), "readMaskPaths");
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::anyhow;
+use std::cmp::Ordering;
+
+/// Order of the package chapters (and the child packages listed on nested package stubs)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PackageSort {
+    /// segment by segment, with runs of digits compared numerically, so `api.v2` comes before
+    /// `api.v10`
+    Natural,
+    /// plain string order, where `api.v10` comes before `api.v2`
+    Lexicographic,
+}
+
+impl TryFrom<&str> for PackageSort {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "natural" => Ok(Self::Natural),
+            "lexicographic" => Ok(Self::Lexicographic),
+            other => Err(anyhow!(
+                "`package_sort` should be one of \"natural\" or \"lexicographic\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+impl PackageSort {
+    pub(crate) fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::Lexicographic => a.cmp(b),
+            Self::Natural => a
+                .split('.')
+                .map(Chunks::new)
+                .cmp(b.split('.').map(Chunks::new))
+                // packages only equal naturally when they differ in leading zeros
+                .then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// The version a package ends in, by the `v1`, `v1beta1`, `v1p2alpha` convention of major
+/// version, optional point release and optional stability
+pub(crate) fn package_version(package: &str) -> Option<&str> {
+    let version = package.rsplit('.').next()?;
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let rest = version.strip_prefix('v')?;
+    let major = digits(rest);
+    if major == 0 {
+        return None;
+    }
+    let mut rest = &rest[major..];
+    if let Some(point) = rest.strip_prefix('p').filter(|point| digits(point) > 0) {
+        rest = &point[digits(point)..];
+    }
+    if let Some(stability) = rest
+        .strip_prefix("alpha")
+        .or_else(|| rest.strip_prefix("beta"))
+    {
+        rest = &stability[digits(stability)..];
+    }
+
+    rest.is_empty().then_some(version)
+}
+
+/// A package segment split into alternating runs of digits and other characters, e.g. `v1beta1`
+/// into `v`, `1`, `beta`, `1`. Compares run by run, numbers numerically, and a segment that is a
+/// prefix of another first (`v1` before `v1beta1`).
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Chunks<'a>(Vec<Chunk<'a>>);
+
+#[derive(PartialEq, Eq)]
+enum Chunk<'a> {
+    /// digits without leading zeros, so longer is larger
+    Number(&'a str),
+    Text(&'a str),
+}
+
+impl<'a> Chunks<'a> {
+    fn new(segment: &'a str) -> Self {
+        let mut chunks = Vec::new();
+        let mut rest = segment;
+
+        while let Some(first) = rest.chars().next() {
+            let is_digit = first.is_ascii_digit();
+            let end = rest
+                .find(|c: char| c.is_ascii_digit() != is_digit)
+                .unwrap_or(rest.len());
+            let (run, remaining) = rest.split_at(end);
+
+            chunks.push(if is_digit {
+                let trimmed = run.trim_start_matches('0');
+                Chunk::Number(if trimmed.is_empty() { "0" } else { trimmed })
+            } else {
+                Chunk::Text(run)
+            });
+            rest = remaining;
+        }
+
+        Self(chunks)
+    }
+}
+
+impl Ord for Chunk<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+            // numbers before words, as in `1` before `a`
+            (Self::Number(_), Self::Text(_)) => Ordering::Less,
+            (Self::Text(_), Self::Number(_)) => Ordering::Greater,
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Chunk<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted(sort: PackageSort, packages: &[&str]) -> Vec<String> {
+        let mut packages: Vec<String> = packages.iter().map(|p| p.to_string()).collect();
+        packages.sort_by(|a, b| sort.compare(a, b));
+        packages
+    }
+
+    #[test]
+    fn should_parse_the_trailing_version_of_a_package() {
+        for (package, version) in [
+            ("api.v1", Some("v1")),
+            ("company.orders.v2", Some("v2")),
+            ("v3", Some("v3")),
+            ("api.v1beta1", Some("v1beta1")),
+            ("api.v2alpha", Some("v2alpha")),
+            ("api.v1p2beta1", Some("v1p2beta1")),
+            ("api.v1.admin", None),
+            ("api.version", None),
+            ("api.v", None),
+            ("api.v1gamma", None),
+            ("", None),
+        ] {
+            assert_eq!(package_version(package), version, "{}", package);
+        }
+    }
+
+    #[test]
+    fn natural_sort_should_compare_versions_numerically() {
+        let packages = [
+            "api.v10",
+            "api.v2alpha",
+            "api.v1beta1",
+            "api.v2",
+            "api",
+            "api.v1",
+            "api.v1beta10",
+            "api.v1beta2",
+            "api.v01",
+            "api.v1.admin",
+            "apis",
+            "api.stable",
+            "api.2",
+        ];
+
+        assert_eq!(
+            sorted(PackageSort::Natural, &packages),
+            vec![
+                "api",
+                "api.2",
+                "api.stable",
+                "api.v01",
+                "api.v1",
+                "api.v1.admin",
+                "api.v1beta1",
+                "api.v1beta2",
+                "api.v1beta10",
+                "api.v2",
+                "api.v2alpha",
+                "api.v10",
+                "apis",
+            ]
+        );
+    }
+
+    #[test]
+    fn lexicographic_sort_should_keep_string_order() {
+        assert_eq!(
+            sorted(PackageSort::Lexicographic, &["api.v2", "api.v10", "api.v1"]),
+            vec!["api.v1", "api.v10", "api.v2"]
+        );
+    }
+}
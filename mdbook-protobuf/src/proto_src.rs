@@ -0,0 +1,283 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+/// `.proto` sources to build the descriptor set from on each run, from
+/// `[preprocessor.protobuf.proto_src]`, as an alternative to a prebuilt `proto_descriptor`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProtoSrc {
+    /// `--proto_path`s of protoc, relative to the book root
+    #[serde(default)]
+    include_paths: Vec<String>,
+    /// the files protoc compiles, relative to the book root
+    #[serde(default)]
+    files: Vec<String>,
+    /// a buf module (or workspace) directory to `buf build`, rather than invoking protoc
+    buf_module: Option<String>,
+    #[serde(default = "default_protoc")]
+    protoc: String,
+    #[serde(default = "default_buf")]
+    buf: String,
+}
+
+fn default_protoc() -> String {
+    "protoc".into()
+}
+
+fn default_buf() -> String {
+    "buf".into()
+}
+
+impl ProtoSrc {
+    /// Checks that either protoc `files` or a `buf_module` are set, without running either
+    pub(crate) fn check(&self) -> Result<()> {
+        match (&self.buf_module, self.files.is_empty()) {
+            (Some(_), false) => Err(anyhow!(
+                "`proto_src` should set either `files` (for protoc) or `buf_module`, not both"
+            )),
+            (None, true) => Err(anyhow!(
+                "`proto_src` should set `files` to compile with protoc, or a `buf_module`"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Builds the descriptor set into the temp directory, returning its path. The path is stable
+    /// for a book, so each run overwrites the last rather than leaving files behind.
+    pub(crate) fn build(&self, root: &Path) -> Result<PathBuf> {
+        self.check()?;
+
+        let digest = format!("{:x}", Sha256::digest(root.display().to_string()));
+        let descriptor_path =
+            std::env::temp_dir().join(format!("mdbook-protobuf-{}.pb", &digest[..16]));
+
+        match &self.buf_module {
+            Some(module) => {
+                let mut command = Command::new(&self.buf);
+                command.current_dir(root).args(["build", module, "-o", "-"]);
+                let output = run(&mut command, &self.buf, "buf")?;
+                fs::write(&descriptor_path, output.stdout)?;
+            }
+            None => {
+                let mut command = protoc_command(
+                    &self.protoc,
+                    &self.include_paths,
+                    &self.files,
+                    &descriptor_path,
+                );
+                command.current_dir(root);
+                run(&mut command, &self.protoc, "protoc")?;
+            }
+        }
+
+        Ok(descriptor_path)
+    }
+}
+
+/// Where the descriptor set is read from: a prebuilt `proto_descriptor`, or `proto_src`, which is
+/// only built the first time the descriptor set is needed rather than when the config is read
+pub(crate) enum DescriptorSource {
+    File(PathBuf),
+    Src {
+        proto_src: ProtoSrc,
+        root: PathBuf,
+        built: OnceLock<PathBuf>,
+    },
+}
+
+impl DescriptorSource {
+    pub(crate) fn src(proto_src: ProtoSrc, root: &Path) -> Result<Self> {
+        proto_src.check()?;
+
+        Ok(Self::Src {
+            proto_src,
+            root: root.to_path_buf(),
+            built: OnceLock::new(),
+        })
+    }
+
+    /// The path of the descriptor set, building it on the first call for `proto_src`
+    pub(crate) fn path(&self) -> Result<&Path> {
+        match self {
+            Self::File(path) => Ok(path),
+            Self::Src {
+                proto_src,
+                root,
+                built,
+            } => {
+                if let Some(path) = built.get() {
+                    return Ok(path);
+                }
+                let path = proto_src.build(root)?;
+                Ok(built.get_or_init(|| path))
+            }
+        }
+    }
+}
+
+/// Runs `program` (configured by `proto_src.<key>`), failing with its error output if it fails
+fn run(command: &mut Command, program: &str, key: &str) -> Result<Output> {
+    info!("Building the descriptor set with {:?}", command);
+
+    let output = command.output().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "`{}` was not found, is it installed and on the PATH? Set `proto_src.{}` to its path, or build the descriptor set yourself and set `proto_descriptor` instead of `proto_src`",
+            program,
+            key
+        ),
+        _ => anyhow!("failed to run `{}`: {}", program, e),
+    })?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{}` exited with {}:\n{}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output)
+}
+
+/// `protoc` writing a descriptor set with everything the reference uses: imported files (for
+/// links to them) and source info (for comments and `[src]` links)
+pub fn protoc_command<S: AsRef<OsStr>>(
+    protoc: &str,
+    proto_paths: &[S],
+    files: &[S],
+    descriptor_path: &Path,
+) -> Command {
+    let mut command = Command::new(protoc);
+    command
+        .arg("--include_imports")
+        .arg("--include_source_info")
+        .arg(format!(
+            "--descriptor_set_out={}",
+            descriptor_path.display()
+        ));
+
+    for proto_path in proto_paths {
+        let mut arg = OsString::from("--proto_path=");
+        arg.push(proto_path);
+        command.arg(arg);
+    }
+
+    command.args(files);
+    command
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn proto_src(config: serde_json::Value) -> ProtoSrc {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_build_the_descriptor_set_with_protoc_from_the_book_root() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        // records its arguments and working directory, then writes --descriptor_set_out
+        let protoc = root.path().join("fake-protoc");
+        fs::write(
+            &protoc,
+            r#"#!/bin/sh
+echo "$(pwd) $@" > "$(dirname "$0")/args"
+for arg in "$@"; do
+  case "$arg" in --descriptor_set_out=*) echo descriptor > "${arg#--descriptor_set_out=}";; esac
+done"#,
+        )
+        .unwrap();
+        fs::set_permissions(&protoc, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let descriptor_path = proto_src(serde_json::json!({
+            "include_paths": ["proto"],
+            "files": ["proto/hello.proto"],
+            "protoc": protoc,
+        }))
+        .build(root.path())
+        .expect("should succeed");
+
+        assert_eq!(
+            fs::read_to_string(&descriptor_path).unwrap(),
+            "descriptor\n"
+        );
+        let args = fs::read_to_string(root.path().join("args")).unwrap();
+        assert_eq!(
+            args.trim(),
+            format!(
+                "{} --include_imports --include_source_info --descriptor_set_out={} --proto_path=proto proto/hello.proto",
+                fs::canonicalize(root.path()).unwrap().display(),
+                descriptor_path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_binaries_should_explain_the_alternatives() {
+        let root = tempfile::tempdir().unwrap();
+
+        let error = proto_src(serde_json::json!({
+            "files": ["hello.proto"],
+            "protoc": "protoc-which-is-not-installed",
+        }))
+        .build(root.path())
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("`protoc-which-is-not-installed` was not found"));
+        assert!(error.contains("`proto_descriptor`"));
+
+        let error = proto_src(serde_json::json!({ "buf_module": "proto", "buf": "buf-missing" }))
+            .build(root.path())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Set `proto_src.buf`"), "{}", error);
+    }
+
+    #[test]
+    fn should_only_build_the_descriptor_set_when_first_needed() {
+        let root = tempfile::tempdir().unwrap();
+        let source = DescriptorSource::src(
+            proto_src(serde_json::json!({
+                "files": ["hello.proto"],
+                "protoc": "protoc-which-is-not-installed",
+            })),
+            root.path(),
+        )
+        .expect("should not run protoc");
+
+        let error = source.path().unwrap_err().to_string();
+        assert!(error.contains("`protoc-which-is-not-installed` was not found"));
+    }
+
+    #[test]
+    fn should_require_files_or_a_buf_module() {
+        let root = tempfile::tempdir().unwrap();
+
+        assert!(proto_src(serde_json::json!({ "include_paths": ["proto"] }))
+            .build(root.path())
+            .is_err());
+        assert!(DescriptorSource::src(
+            proto_src(serde_json::json!({ "include_paths": ["proto"] })),
+            root.path()
+        )
+        .is_err());
+        assert!(
+            proto_src(serde_json::json!({ "files": ["a.proto"], "buf_module": "." }))
+                .build(root.path())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use crate::reserved::Reserved;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::method_options::IdempotencyLevel;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileOptions,
+    MethodDescriptorProto,
+};
+use std::fmt::Write;
+
+pub(crate) const INDENT: &str = "  ";
+
+/// Reconstructs the `.proto` definition of a message (including its nested messages and enums)
+/// from the descriptor. Type references are written fully qualified so the snippet stands alone.
+///
+/// The output has no blank lines, as it is embedded in an html block of the generated markdown.
+pub(crate) fn message_definition(
+    file_descriptor: &FileDescriptorProto,
+    message_descriptor: &DescriptorProto,
+    fqsl: &str,
+) -> String {
+    let mut out = String::new();
+    write_message(&mut out, file_descriptor, message_descriptor, fqsl, 0);
+    out.trim_end().to_string()
+}
+
+fn write_message(
+    out: &mut String,
+    file_descriptor: &FileDescriptorProto,
+    message: &DescriptorProto,
+    fqsl: &str,
+    depth: usize,
+) {
+    let indent = INDENT.repeat(depth);
+    let inner = INDENT.repeat(depth + 1);
+    let _ = writeln!(out, "{}message {} {{", indent, message.name());
+
+    let mut written_oneofs = Vec::new();
+    for field in &message.field {
+        match field.oneof_index {
+            // proto3 `optional` is modelled as a synthetic single-field oneof; write it as a field
+            Some(oneof_index) if !field.proto3_optional() => {
+                if written_oneofs.contains(&oneof_index) {
+                    continue;
+                }
+                written_oneofs.push(oneof_index);
+
+                let oneof = &message.oneof_decl[oneof_index as usize];
+                let _ = writeln!(out, "{}oneof {} {{", inner, oneof.name());
+                for oneof_field in message
+                    .field
+                    .iter()
+                    .filter(|f| f.oneof_index == Some(oneof_index) && !f.proto3_optional())
+                {
+                    let _ = writeln!(
+                        out,
+                        "{}{}{}",
+                        inner,
+                        INDENT,
+                        field_definition(file_descriptor, message, fqsl, oneof_field)
+                    );
+                }
+                let _ = writeln!(out, "{}}}", inner);
+            }
+            _ => {
+                let _ = writeln!(
+                    out,
+                    "{}{}",
+                    inner,
+                    field_definition(file_descriptor, message, fqsl, field)
+                );
+            }
+        }
+    }
+
+    let reserved = Reserved::from_message(message);
+    for declaration in [reserved.numbers_declaration(), reserved.names_declaration()]
+        .into_iter()
+        .flatten()
+    {
+        let _ = writeln!(out, "{}{}", inner, declaration);
+    }
+
+    for nested in &message.nested_type {
+        if nested.options.as_ref().is_some_and(|o| o.map_entry()) {
+            continue;
+        }
+        let nested_fqsl = format!("{}.{}", fqsl, nested.name());
+        write_message(out, file_descriptor, nested, &nested_fqsl, depth + 1);
+    }
+
+    for nested in &message.enum_type {
+        write_enum(out, nested, depth + 1);
+    }
+
+    let _ = writeln!(out, "{}}}", indent);
+}
+
+fn write_enum(out: &mut String, enum_descriptor: &EnumDescriptorProto, depth: usize) {
+    let indent = INDENT.repeat(depth);
+    let _ = writeln!(out, "{}enum {} {{", indent, enum_descriptor.name());
+
+    if enum_descriptor
+        .options
+        .as_ref()
+        .is_some_and(|o| o.allow_alias())
+    {
+        let _ = writeln!(out, "{}{}option allow_alias = true;", indent, INDENT);
+    }
+
+    for value in &enum_descriptor.value {
+        let deprecated = value.options.as_ref().is_some_and(|o| o.deprecated());
+        let _ = writeln!(
+            out,
+            "{}{}{} = {}{};",
+            indent,
+            INDENT,
+            value.name(),
+            value.number(),
+            if deprecated {
+                " [deprecated = true]"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let reserved = Reserved::from_enum(enum_descriptor);
+    for declaration in [reserved.numbers_declaration(), reserved.names_declaration()]
+        .into_iter()
+        .flatten()
+    {
+        let _ = writeln!(out, "{}{}{}", indent, INDENT, declaration);
+    }
+
+    let _ = writeln!(out, "{}}}", indent);
+}
+
+pub(crate) fn field_definition(
+    file_descriptor: &FileDescriptorProto,
+    message: &DescriptorProto,
+    fqsl: &str,
+    field: &FieldDescriptorProto,
+) -> String {
+    let proto2 = matches!(file_descriptor.syntax(), "" | "proto2");
+
+    let (label, typ) = match map_entry(message, fqsl, field) {
+        Some(entry) => (
+            "",
+            format!(
+                "map<{}, {}>",
+                type_name(&entry.field[0]),
+                type_name(&entry.field[1])
+            ),
+        ),
+        None => {
+            let label = match field.label() {
+                Label::Repeated => "repeated ",
+                Label::Required => "required ",
+                Label::Optional if proto2 && field.oneof_index.is_none() => "optional ",
+                Label::Optional if field.proto3_optional() => "optional ",
+                Label::Optional => "",
+            };
+            (label, type_name(field))
+        }
+    };
+
+    let mut options = Vec::new();
+    if let Some(default_value) = &field.default_value {
+        options.push(match field.r#type() {
+            Type::String | Type::Bytes => format!("default = \"{}\"", default_value),
+            _ => format!("default = {}", default_value),
+        });
+    }
+    if field.options.as_ref().is_some_and(|o| o.deprecated()) {
+        options.push("deprecated = true".to_string());
+    }
+
+    let options = if options.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", options.join(", "))
+    };
+
+    format!(
+        "{}{} {} = {}{};",
+        label,
+        typ,
+        field.name(),
+        field.number(),
+        options
+    )
+}
+
+/// Reconstructs the `rpc` declaration of a method, with its options in a body when any are set
+/// (custom options are not in the descriptor as decoded, so are left out)
+pub(crate) fn method_definition(method: &MethodDescriptorProto) -> String {
+    let stream = |streaming: bool| if streaming { "stream " } else { "" };
+    let signature = format!(
+        "rpc {} ({}{}) returns ({}{})",
+        method.name(),
+        stream(method.client_streaming()),
+        method.input_type().trim_start_matches('.'),
+        stream(method.server_streaming()),
+        method.output_type().trim_start_matches('.'),
+    );
+
+    let mut options = Vec::new();
+    if let Some(method_options) = &method.options {
+        if method_options.deprecated() {
+            options.push("deprecated = true".to_string());
+        }
+        if method_options.idempotency_level() != IdempotencyLevel::IdempotencyUnknown {
+            options.push(format!(
+                "idempotency_level = {}",
+                method_options.idempotency_level().as_str_name()
+            ));
+        }
+    }
+
+    if options.is_empty() {
+        return format!("{};", signature);
+    }
+
+    let mut out = format!("{} {{\n", signature);
+    for option in options {
+        let _ = writeln!(out, "{}option {};", INDENT, option);
+    }
+    out.push('}');
+    out
+}
+
+/// The language specific package, namespace and prefix options that are set, with their field
+/// numbers, in `descriptor.proto` order
+pub(crate) fn language_options(options: &FileOptions) -> Vec<(i32, &'static str, String)> {
+    [
+        (1, "java_package", options.java_package.clone()),
+        (
+            8,
+            "java_outer_classname",
+            options.java_outer_classname.clone(),
+        ),
+        (
+            10,
+            "java_multiple_files",
+            options.java_multiple_files.map(|v| v.to_string()),
+        ),
+        (11, "go_package", options.go_package.clone()),
+        (36, "objc_class_prefix", options.objc_class_prefix.clone()),
+        (37, "csharp_namespace", options.csharp_namespace.clone()),
+        (39, "swift_prefix", options.swift_prefix.clone()),
+        (40, "php_class_prefix", options.php_class_prefix.clone()),
+        (41, "php_namespace", options.php_namespace.clone()),
+        (
+            44,
+            "php_metadata_namespace",
+            options.php_metadata_namespace.clone(),
+        ),
+        (45, "ruby_package", options.ruby_package.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(number, name, value)| value.map(|value| (number, name, value)))
+    .collect()
+}
+
+/// The synthetic `*Entry` message backing a `map<K, V>` field, if this field is one
+pub(crate) fn map_entry<'a>(
+    message: &'a DescriptorProto,
+    fqsl: &str,
+    field: &FieldDescriptorProto,
+) -> Option<&'a DescriptorProto> {
+    if field.label() != Label::Repeated || field.r#type() != Type::Message {
+        return None;
+    }
+
+    message.nested_type.iter().find(|nested| {
+        nested.options.as_ref().is_some_and(|o| o.map_entry())
+            && field.type_name() == format!("{}.{}", fqsl, nested.name())
+            && nested.field.len() == 2
+    })
+}
+
+fn type_name(field: &FieldDescriptorProto) -> String {
+    match field.r#type() {
+        Type::Message | Type::Enum | Type::Group => field.type_name().to_string(),
+        scalar => scalar
+            .as_str_name()
+            .trim_start_matches("TYPE_")
+            .to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_file;
+
+    #[test]
+    fn should_reconstruct_hello_request() {
+        let file = demo_file("helloworld.proto");
+        let message = file
+            .message_type
+            .iter()
+            .find(|m| m.name() == "HelloRequest")
+            .unwrap();
+
+        let definition = message_definition(&file, message, ".helloworld.HelloRequest");
+
+        assert!(definition.starts_with("message HelloRequest {\n"));
+        assert!(definition.contains("  string name = 1;"));
+        assert!(definition.ends_with('}'));
+        assert!(!definition.contains("\n\n"));
+    }
+
+    #[test]
+    fn should_reconstruct_methods_with_their_options() {
+        let file = demo_file("type_test.proto");
+        let method = |name: &str| {
+            file.service
+                .iter()
+                .flat_map(|service| &service.method)
+                .find(|m| m.name() == name)
+                .expect("method should be in the demo")
+        };
+
+        assert_eq!(
+            method_definition(method("GetMessage")),
+            "rpc GetMessage (testing.Message) returns (testing.OtherMessage) {\n  option idempotency_level = NO_SIDE_EFFECTS;\n}"
+        );
+        assert_eq!(
+            method_definition(&MethodDescriptorProto {
+                options: None,
+                ..method("PutMessage").clone()
+            }),
+            "rpc PutMessage (testing.Message) returns (testing.OtherMessage);"
+        );
+    }
+
+    #[test]
+    fn should_write_maps_oneofs_and_labels() {
+        let file = demo_file("type_test.proto");
+
+        let definitions: Vec<String> = file
+            .message_type
+            .iter()
+            .map(|m| message_definition(&file, m, &format!(".{}.{}", file.package(), m.name())))
+            .collect();
+        let all = definitions.join("\n");
+
+        assert!(all.contains("map<"), "{}", all);
+        assert!(all.contains("oneof "), "{}", all);
+        assert!(all.contains("repeated "), "{}", all);
+        assert!(
+            !all.contains("Entry {"),
+            "map entries should not be written: {}",
+            all
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use askama::Template;
+use prost_types::{DescriptorProto, EnumDescriptorProto};
+
+/// Highest field number, `max` in a message's reserved range
+const MAX_FIELD_NUMBER: i32 = 536_870_911;
+
+/// The reserved numbers and names of a message or enum
+#[derive(Template, Clone, Default, Debug)]
+#[template(path = "reserved.html")]
+pub(crate) struct Reserved {
+    /// end-inclusive ranges of reserved numbers
+    ranges: Vec<(i32, i32)>,
+    /// the highest number of the symbol, written `max` as a range end
+    max: i32,
+    names: Vec<String>,
+}
+
+impl Reserved {
+    pub(crate) fn from_message(message: &DescriptorProto) -> Self {
+        Self {
+            // message ranges are end-exclusive in the descriptor
+            ranges: message
+                .reserved_range
+                .iter()
+                .map(|range| (range.start(), range.end() - 1))
+                .collect(),
+            max: MAX_FIELD_NUMBER,
+            names: message.reserved_name.clone(),
+        }
+    }
+
+    pub(crate) fn from_enum(enumeration: &EnumDescriptorProto) -> Self {
+        Self {
+            // unlike message ranges, enum ranges are end-inclusive
+            ranges: enumeration
+                .reserved_range
+                .iter()
+                .map(|range| (range.start(), range.end()))
+                .collect(),
+            max: i32::MAX,
+            names: enumeration.reserved_name.clone(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.names.is_empty()
+    }
+
+    pub(crate) fn ranges(&self) -> &[(i32, i32)] {
+        &self.ranges
+    }
+
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The numbers as `.proto` source writes them (`2`, `9 to 11`, `100 to max`)
+    fn numbers(&self) -> Vec<String> {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| match (start, end) {
+                (start, end) if start == end => start.to_string(),
+                (start, end) if end == self.max => format!("{} to max", start),
+                (start, end) => format!("{} to {}", start, end),
+            })
+            .collect()
+    }
+
+    /// The numbers then the quoted names, as listed in the reference
+    fn entries(&self) -> Vec<String> {
+        self.numbers()
+            .into_iter()
+            .chain(self.quoted_names())
+            .collect()
+    }
+
+    fn quoted_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.names.iter().map(|name| format!("\"{}\"", name))
+    }
+
+    /// `reserved 2, 9 to 11;`, the statement declaring the numbers
+    pub(crate) fn numbers_declaration(&self) -> Option<String> {
+        (!self.ranges.is_empty()).then(|| format!("reserved {};", self.numbers().join(", ")))
+    }
+
+    /// `reserved "foo", "bar";`, the statement declaring the names
+    pub(crate) fn names_declaration(&self) -> Option<String> {
+        let names: Vec<String> = self.quoted_names().collect();
+        (!names.is_empty()).then(|| format!("reserved {};", names.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost_types::descriptor_proto::ReservedRange;
+    use prost_types::enum_descriptor_proto::EnumReservedRange;
+
+    #[test]
+    fn should_render_reserved_numbers_and_names_together() {
+        let message = DescriptorProto {
+            name: Some("Order".into()),
+            reserved_range: vec![
+                ReservedRange {
+                    start: Some(2),
+                    end: Some(3),
+                },
+                ReservedRange {
+                    start: Some(9),
+                    end: Some(12),
+                },
+                ReservedRange {
+                    start: Some(100),
+                    end: Some(MAX_FIELD_NUMBER + 1),
+                },
+            ],
+            reserved_name: vec!["legacy_id".into(), "notes".into()],
+            ..Default::default()
+        };
+
+        let reserved = Reserved::from_message(&message);
+        assert_eq!(
+            reserved.numbers_declaration().as_deref(),
+            Some("reserved 2, 9 to 11, 100 to max;")
+        );
+        assert_eq!(
+            reserved.names_declaration().as_deref(),
+            Some(r#"reserved "legacy_id", "notes";"#)
+        );
+
+        let rendered = reserved.render().expect("should render");
+        assert!(rendered.starts_with(r#"<div class="reserved">"#));
+        assert!(rendered.contains(
+            "<code>2</code>,&nbsp;<code>9 to 11</code>,&nbsp;<code>100 to max</code>,&nbsp;<code>&quot;legacy_id&quot;</code>,&nbsp;<code>&quot;notes&quot;</code>"
+        ));
+
+        let enumeration = EnumDescriptorProto {
+            name: Some("Status".into()),
+            reserved_range: vec![EnumReservedRange {
+                start: Some(5),
+                end: Some(5),
+            }],
+            ..Default::default()
+        };
+        let reserved = Reserved::from_enum(&enumeration);
+        assert_eq!(
+            reserved.numbers_declaration().as_deref(),
+            Some("reserved 5;")
+        );
+        assert_eq!(reserved.names_declaration(), None);
+        assert_eq!(reserved.ranges(), &[(5, 5)]);
+        assert!(Reserved::from_enum(&EnumDescriptorProto::default()).is_empty());
+    }
+}
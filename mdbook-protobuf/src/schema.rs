@@ -0,0 +1,474 @@
+use crate::diagram::DEFAULT_DIAGRAM_NODE_LIMIT;
+use crate::labels::Labels;
+use crate::links::{
+    DEFAULT_LINK_FUZZY_THRESHOLD, DEFAULT_MAX_CHAPTER_SIZE, DEFAULT_OUTPUT_PREFIX,
+    DEFAULT_PACKAGE_NAME, DEFAULT_SOURCE_LINK_LABEL,
+};
+use crate::view::DEFAULT_HEADING_BASE_LEVEL;
+use crate::PREPROCESSOR_NAME;
+use serde_json::{json, Value};
+
+/// JSON Schema for `book.toml`, describing every key of the `[preprocessor.protobuf]` table so
+/// that editors and tools such as `taplo` can complete and validate it. Other tables are left
+/// open.
+pub fn config_schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "mdbook book.toml with mdbook-protobuf",
+        "type": "object",
+        "properties": {
+            "preprocessor": {
+                "type": "object",
+                "properties": {
+                    PREPROCESSOR_NAME: preprocessor_schema()
+                }
+            }
+        }
+    })
+}
+
+fn preprocessor_schema() -> Value {
+    let mut properties = json!({
+        // accepted by mdbook for every preprocessor
+        "command": {
+            "description": "Command mdbook runs for the preprocessor",
+            "type": "string"
+        },
+        "renderers": {
+            "description": "Renderers the preprocessor runs for",
+            "type": "array",
+            "items": { "type": "string" }
+        },
+        "before": {
+            "description": "Preprocessors this one runs before",
+            "type": "array",
+            "items": { "type": "string" }
+        },
+        "after": {
+            "description": "Preprocessors this one runs after",
+            "type": "array",
+            "items": { "type": "string" }
+        }
+    });
+    for group in [
+        source_properties(),
+        layout_properties(),
+        content_properties(),
+        tooling_properties(),
+    ] {
+        if let (Value::Object(properties), Value::Object(group)) = (&mut properties, group) {
+            properties.extend(group);
+        }
+    }
+
+    json!({
+        "description": "Configuration of the mdbook-protobuf preprocessor",
+        "type": "object",
+        "oneOf": [{ "required": ["proto_descriptor"] }, { "required": ["proto_src"] }],
+        "additionalProperties": false,
+        "properties": properties
+    })
+}
+
+/// Where the descriptor set comes from and how it is checked
+fn source_properties() -> Value {
+    json!({
+        "proto_descriptor": {
+            "description": "Path to the file descriptor set, relative to the book root. binary or JSON encoded. A directory merges every `*.pb`, `*.binpb` and `*.json` file within it",
+            "type": "string"
+        },
+        "proto_src": proto_src_schema(),
+        "proto_src_dir": {
+            "description": "Directory of the `.proto` sources, relative to the book root, checked for files modified after the descriptor set was written",
+            "type": "string"
+        },
+        "stale_descriptor": {
+            "description": "Whether a descriptor set older than the sources in `proto_src_dir` fails the build or is warned about",
+            "enum": ["warn", "error"],
+            "default": "warn"
+        },
+        "strict": {
+            "description": "Fail the build, rather than warn, when types the descriptor set references are defined in files missing from it",
+            "type": "boolean",
+            "default": false
+        },
+        "schema_lints": {
+            "description": "Severity of the field number checks",
+            "enum": ["off", "warn", "error"],
+            "default": "warn"
+        },
+        "baseline_descriptor": {
+            "description": "Path to the descriptor set of a previous release, relative to the book root, to badge new and changed packages",
+            "type": "string"
+        },
+        "baseline_chapter_suffix": {
+            "description": "Suffix the chapter names of new and changed packages with `(new)` / `(updated)`",
+            "type": "boolean",
+            "default": false
+        }
+    })
+}
+
+/// Where the chapters go and how they are named and ordered
+fn layout_properties() -> Value {
+    json!({
+        "nest_under": {
+            "description": "Name of a top level chapter to place the generated reference under",
+            "type": "string"
+        },
+        "placement": {
+            "description": "Whether the reference goes before or after the book's top level chapters, when not nested under one",
+            "enum": ["start", "end"],
+            "default": "end"
+        },
+        "output_prefix": {
+            "description": "Directory within the book `src` the reference is generated in",
+            "type": "string",
+            "minLength": 1,
+            "default": DEFAULT_OUTPUT_PREFIX
+        },
+        "page_per": {
+            "description": "Render a chapter per package, or a chapter per proto file beneath each package",
+            "enum": ["package", "file"],
+            "default": "package"
+        },
+        "nested_namespaces": {
+            "description": "Nest package chapters to mirror the package hierarchy",
+            "type": "boolean",
+            "default": false
+        },
+        "generate_package_index_pages": {
+            "description": "Add a chapter, listing its sub packages, at the path of each parent package that declares no files",
+            "type": "boolean",
+            "default": false
+        },
+        "index_page": {
+            "description": "Add a landing chapter listing every package with its file and symbol counts, as the parent of the generated chapters",
+            "type": "boolean",
+            "default": false
+        },
+        "package_sort": {
+            "description": "Order of the package chapters: `natural` compares version segments numerically (`v2` before `v10`), `lexicographic` is plain string order",
+            "enum": ["natural", "lexicographic"],
+            "default": "natural"
+        },
+        "default_package_name": {
+            "description": "Chapter name of the symbols declared in files without a `package`",
+            "type": "string",
+            "minLength": 1,
+            "default": DEFAULT_PACKAGE_NAME
+        },
+        "strip_package_prefix": {
+            "description": "Package prefix (e.g. `com.mycorp.platform`) left out of chapter names. Paths and links stay fully qualified",
+            "type": "string"
+        },
+        "symbol_sort": {
+            "description": "Order of the services, messages and enums within each file",
+            "enum": ["source", "alpha"],
+            "default": "source"
+        },
+        "comment_format": {
+            "description": "How proto comments are rendered: as markdown with any raw html escaped, or as plain text",
+            "enum": ["markdown", "plaintext"],
+            "default": "markdown"
+        },
+        "heading_base_level": {
+            "description": "Markdown heading level of the top level symbols on each page",
+            "type": "integer",
+            "minimum": 1,
+            "maximum": 6,
+            "default": DEFAULT_HEADING_BASE_LEVEL
+        }
+    })
+}
+
+/// What each chapter renders
+fn content_properties() -> Value {
+    json!({
+        "proto_url_root": {
+            "description": "Url prefix for `[src]` links",
+            "type": "string"
+        },
+        "external_links_new_tab": {
+            "description": "Open `[src]` links and the protobuf.dev link of the `Any` note in a new tab",
+            "type": "boolean",
+            "default": true
+        },
+        "source_link_label": {
+            "description": "Text of the `[src]` links, or their title with `source_link_icon`",
+            "type": "string",
+            "minLength": 1,
+            "default": DEFAULT_SOURCE_LINK_LABEL
+        },
+        "source_link_icon": {
+            "description": "Render the `[src]` links as a code icon rather than text",
+            "type": "boolean",
+            "default": false
+        },
+        "related_methods": {
+            "description": "How each method's \"See also\" row is filled",
+            "enum": ["off", "manual", "heuristic"],
+            "default": "manual"
+        },
+        "exclude_deprecated": {
+            "description": "Leave deprecated services, methods, messages, enums and fields out of the reference",
+            "type": "boolean",
+            "default": false
+        },
+        "only_referenced": {
+            "description": "Render only the symbols linked to with `proto!()` in the book, and the types their fields, requests and responses refer to",
+            "type": "boolean",
+            "default": false
+        },
+        "emit_source_pages": {
+            "description": "Add a chapter with the `.proto` source of each file, reconstructed from the descriptor set, which `[src]` links point to when `proto_url_root` is not set",
+            "type": "boolean",
+            "default": false
+        },
+        "show_examples": {
+            "description": "Render a collapsible `grpcurl` example, with a sample of the request, on each method",
+            "type": "boolean",
+            "default": false
+        },
+        "grpcurl_examples": {
+            "description": "Render the `grpcurl` examples of `show_examples` (which this enables) calling this server",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "default_host": {
+                    "description": "`host:port` of the server the examples call",
+                    "type": "string",
+                    "default": "localhost:50051"
+                },
+                "plaintext": {
+                    "description": "Pass `-plaintext`, for servers without TLS",
+                    "type": "boolean",
+                    "default": true
+                },
+                "insecure": {
+                    "description": "Pass `-insecure`, skipping the verification of the server's certificate",
+                    "type": "boolean",
+                    "default": false
+                },
+                "cacert": { "description": "Pass `-cacert`, the certificate authority to verify the server with", "type": "string" },
+                "cert": { "description": "Pass `-cert`, the client certificate for mutual TLS", "type": "string" },
+                "key": { "description": "Pass `-key`, the client key for mutual TLS", "type": "string" }
+            }
+        },
+        "show_json_examples": {
+            "description": "Render a collapsible sample JSON payload of each message, in the protobuf JSON mapping",
+            "type": "boolean",
+            "default": false
+        },
+        "show_method_path": {
+            "description": "Render the gRPC path of each method, `/package.Service/Method`, above its `rpc` signature. Turn off for books documenting protobuf used outside of gRPC",
+            "type": "boolean",
+            "default": true
+        },
+        "hide_same_page_backlinks": {
+            "description": "Leave usages by symbols on the same chapter out of each symbol's backlinks, keeping those from other chapters and the book content",
+            "type": "boolean",
+            "default": false
+        },
+        "show_package_version": {
+            "description": "Render the version a package ends in (`v2` of `api.v2`, `v1beta1` of `api.v1beta1`) as a badge at the top of its page",
+            "type": "boolean",
+            "default": false
+        },
+        "diagrams": {
+            "description": "Draw a diagram of the references between the services, messages and enums at the top of each package page; `mermaid` needs the `mdbook-mermaid` preprocessor",
+            "enum": ["off", "mermaid"],
+            "default": "off"
+        },
+        "diagram_node_limit": {
+            "description": "Packages whose diagram would have more nodes than this are left without one, with a warning",
+            "type": "integer",
+            "minimum": 0,
+            "default": DEFAULT_DIAGRAM_NODE_LIMIT
+        },
+        "option_attributes": {
+            "description": "Custom options (extensions of the message, field and method options) to render as a table of attributes on the symbols they are set on, as display names keyed by extension number, e.g. `{ 50001 = \"owner\" }`",
+            "type": "object",
+            "propertyNames": { "pattern": "^[1-9][0-9]*$" },
+            "additionalProperties": { "type": "string" }
+        },
+        "labels": labels_schema()
+    })
+}
+
+/// Linking, generated files and caching
+fn tooling_properties() -> Value {
+    json!({
+        "link_fuzzy_threshold": {
+            "description": "Minimum fuzzy match score for a symbol to be suggested when a `proto!()` link does not resolve",
+            "type": "integer",
+            "minimum": 0,
+            "default": DEFAULT_LINK_FUZZY_THRESHOLD
+        },
+        "max_chapter_size": {
+            "description": "Chapters larger than this many bytes are skipped, with a warning, unless they contain a `proto!(` link",
+            "type": "integer",
+            "minimum": 0,
+            "default": DEFAULT_MAX_CHAPTER_SIZE
+        },
+        "emit_symbol_manifest": {
+            "description": "Write `symbols.json`, listing every symbol, into the generated directory",
+            "type": "boolean",
+            "default": false
+        },
+        "emit_graph": {
+            "description": "Write the type dependency graph of every service, message and enum, as Graphviz DOT, to this path relative to the book root",
+            "type": "string"
+        },
+        "generate_dot": {
+            "description": "Write the graph of which messages refer to which through their fields to `<output_prefix>/dependencies.dot`, with a chapter drawing it in mermaid",
+            "type": "boolean",
+            "default": false
+        },
+        "symbol_index": {
+            "description": "Add an A-Z chapter of every service, method, message and enum, grouped by first letter",
+            "type": "boolean",
+            "default": false
+        },
+        "emit_search_index": {
+            "description": "Write `proto-search.json`, a search index of every symbol in mdbook's format, into the generated directory",
+            "type": "boolean",
+            "default": false
+        },
+        "incremental": {
+            "description": "Cache the rendered reference beside the build directory and reuse it while its inputs are unchanged",
+            "type": "boolean",
+            "default": false
+        },
+        "cache_dir": {
+            "description": "Directory, relative to the book root, to keep the render cache in instead of `.mdbook-protobuf-cache` beside the build directory. Turns on `incremental`",
+            "type": "string"
+        }
+    })
+}
+
+fn proto_src_schema() -> Value {
+    json!({
+        "description": "`.proto` sources to build the descriptor set from with protoc (`files`) or buf (`buf_module`) on each run, instead of `proto_descriptor`",
+        "type": "object",
+        "additionalProperties": false,
+        "oneOf": [{ "required": ["files"] }, { "required": ["buf_module"] }],
+        "properties": {
+            "include_paths": {
+                "description": "Directories protoc searches for imports, relative to the book root",
+                "type": "array",
+                "items": { "type": "string" }
+            },
+            "files": {
+                "description": "The `.proto` files protoc compiles, relative to the book root",
+                "type": "array",
+                "items": { "type": "string" },
+                "minItems": 1
+            },
+            "buf_module": {
+                "description": "Directory of a buf module or workspace to `buf build`, relative to the book root",
+                "type": "string"
+            },
+            "protoc": {
+                "description": "The protoc binary to invoke",
+                "type": "string",
+                "default": "protoc"
+            },
+            "buf": {
+                "description": "The buf binary to invoke",
+                "type": "string",
+                "default": "buf"
+            }
+        }
+    })
+}
+
+fn labels_schema() -> Value {
+    json!({
+        "description": "Text of the headings and captions of the generated reference, to localize it",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": Labels::KEYS
+            .iter()
+            .map(|key| (key.to_string(), json!({ "type": "string" })))
+            .collect::<serde_json::Map<_, _>>()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostics::StaleDescriptor;
+    use crate::diagram::Diagrams;
+    use crate::lints::SchemaLints;
+    use crate::package_sort::PackageSort;
+    use crate::view::{CommentFormat, PagePer, RelatedMethods, SymbolSort};
+    use crate::Placement;
+
+    fn property(key: &str) -> Value {
+        config_schema()["properties"]["preprocessor"]["properties"][PREPROCESSOR_NAME]["properties"]
+            [key]
+            .clone()
+    }
+
+    fn enum_values(key: &str) -> Vec<String> {
+        property(key)["enum"]
+            .as_array()
+            .unwrap_or_else(|| panic!("`{}` should have enum values", key))
+            .iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn enum_options_should_list_exactly_the_accepted_values() {
+        for value in enum_values("related_methods") {
+            assert!(
+                RelatedMethods::try_from(value.as_str()).is_ok(),
+                "{}",
+                value
+            );
+        }
+        for value in enum_values("symbol_sort") {
+            assert!(SymbolSort::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("comment_format") {
+            assert!(CommentFormat::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("schema_lints") {
+            assert!(SchemaLints::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("page_per") {
+            assert!(PagePer::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("placement") {
+            assert!(Placement::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("stale_descriptor") {
+            assert!(
+                StaleDescriptor::try_from(value.as_str()).is_ok(),
+                "{}",
+                value
+            );
+        }
+        for value in enum_values("package_sort") {
+            assert!(PackageSort::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+        for value in enum_values("diagrams") {
+            assert!(Diagrams::try_from(value.as_str()).is_ok(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn should_require_the_descriptor_or_its_sources_and_reject_unknown_keys() {
+        let table = &config_schema()["properties"]["preprocessor"]["properties"][PREPROCESSOR_NAME];
+
+        assert_eq!(
+            table["oneOf"],
+            json!([{ "required": ["proto_descriptor"] }, { "required": ["proto_src"] }])
+        );
+        assert_eq!(table["additionalProperties"], json!(false));
+        assert_eq!(property("heading_base_level")["default"], json!(2));
+        assert_eq!(property("output_prefix")["default"], json!("proto"));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::view::ProtoNamespaceTemplate;
+use anyhow::{Context, Result};
+use elasticlunr::config::{SearchBool, SearchOptions, SearchOptionsField};
+use elasticlunr::{Index, IndexBuilder};
+use mdbook::config::Search;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const SEARCH_INDEX_FILE: &str = "proto-search.json";
+
+/// mdbook drops tokens longer than this from its index
+const MAX_WORD_LENGTH_TO_INDEX: usize = 80;
+
+/// Tokenizes the same way as mdbook's own search index
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| s.len() <= MAX_WORD_LENGTH_TO_INDEX)
+        .collect()
+}
+
+/// Reduces rendered html (such as comments with resolved links) to its text
+fn strip_html(html: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").expect("should be valid regex"));
+
+    tag.replace_all(html, " ")
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Serialize)]
+struct ResultsOptions {
+    limit_results: u32,
+    teaser_word_count: u32,
+}
+
+/// Search index of every proto symbol in the shape of mdbook's `searchindex.json`, so that it can
+/// be loaded by mdbook's searcher in place of (or merged into) the generated index. Written to
+/// `<book src>/proto/proto-search.json` when `emit_search_index = true`.
+#[derive(Serialize)]
+pub(crate) struct ProtoSearchIndex {
+    results_options: ResultsOptions,
+    search_options: SearchOptions,
+    /// url of each document, indexed by its document ref
+    doc_urls: Vec<String>,
+    index: Index,
+}
+
+impl ProtoSearchIndex {
+    pub(crate) fn from_namespaces(
+        namespaces: &BTreeMap<String, ProtoNamespaceTemplate>,
+        search_config: &Search,
+    ) -> Self {
+        let mut index = IndexBuilder::new()
+            .add_field_with_tokenizer("title", Box::new(tokenize))
+            .add_field_with_tokenizer("body", Box::new(tokenize))
+            .add_field_with_tokenizer("breadcrumbs", Box::new(tokenize))
+            .build();
+        let mut doc_urls = Vec::new();
+
+        for (package, namespace) in namespaces {
+            namespace.visit_symbols(|symbol| {
+                let link = symbol.symbol_link();
+                let title = link.id();
+                let body = match symbol.summary() {
+                    Some(summary) => format!("{} {}", symbol.fqsl(), strip_html(&summary)),
+                    None => symbol.fqsl(),
+                };
+                let breadcrumbs = format!("{} » {}", package, title);
+
+                index.add_doc(&doc_urls.len().to_string(), [&title, &body, &breadcrumbs]);
+                doc_urls.push(
+                    link.href()
+                        .trim_start_matches('/')
+                        .replacen(".md#", ".html#", 1),
+                );
+            });
+        }
+
+        let mut fields = BTreeMap::new();
+        for (field, boost) in [
+            ("title", search_config.boost_title),
+            ("body", search_config.boost_paragraph),
+            ("breadcrumbs", search_config.boost_hierarchy),
+        ] {
+            fields.insert(
+                field.to_string(),
+                SearchOptionsField {
+                    boost: Some(boost),
+                    ..Default::default()
+                },
+            );
+        }
+
+        Self {
+            results_options: ResultsOptions {
+                limit_results: search_config.limit_results,
+                teaser_word_count: search_config.teaser_word_count,
+            },
+            search_options: SearchOptions {
+                bool: if search_config.use_boolean_and {
+                    SearchBool::And
+                } else {
+                    SearchBool::Or
+                },
+                expand: search_config.expand,
+                fields,
+            },
+            doc_urls,
+            index,
+        }
+    }
+
+    /// Writes the index, leaving the file untouched when unchanged so `mdbook serve` does not
+    /// rebuild in a loop
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        // converting through `Value` sorts the map keys, keeping the output stable
+        let json = serde_json::to_string(&serde_json::to_value(self)?)?;
+
+        if fs::read_to_string(path).is_ok_and(|existing| existing == json) {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+
+        fs::write(path, json)
+            .with_context(|| format!("Failed to write search index to {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_strip_tags_and_decode_entities() {
+        assert_eq!(
+            strip_html(r#"Sends a <a href="/proto/a.md#B">greeting</a>&nbsp;to &lt;name&gt;"#),
+            "Sends a greeting to <name>"
+        );
+    }
+}
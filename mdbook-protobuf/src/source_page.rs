@@ -0,0 +1,524 @@
+use crate::proto_text::{field_definition, language_options, map_entry, INDENT};
+use crate::reserved::Reserved;
+use prost_types::source_code_info::Location;
+use prost_types::{DescriptorProto, EnumDescriptorProto, FileDescriptorProto};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+/// Directory (within the output prefix) of the source pages, prefixed with `_` as package names
+/// cannot be, so it never clashes with a package chapter
+pub(crate) const SOURCE_PAGES_DIR: &str = "_source";
+
+// field numbers of `FileDescriptorProto`, `DescriptorProto`, `EnumDescriptorProto` and
+// `ServiceDescriptorProto`, the path elements of `source_code_info` locations
+const FILE_PACKAGE: i32 = 2;
+const FILE_DEPENDENCY: i32 = 3;
+const FILE_MESSAGE: i32 = 4;
+const FILE_ENUM: i32 = 5;
+const FILE_SERVICE: i32 = 6;
+const FILE_OPTIONS: i32 = 8;
+const FILE_SYNTAX: i32 = 12;
+const MESSAGE_FIELD: i32 = 2;
+const MESSAGE_NESTED: i32 = 3;
+const MESSAGE_ENUM: i32 = 4;
+const MESSAGE_ONEOF: i32 = 8;
+const MESSAGE_RESERVED_RANGE: i32 = 9;
+const MESSAGE_RESERVED_NAME: i32 = 10;
+const ENUM_VALUE: i32 = 2;
+const ENUM_RESERVED_RANGE: i32 = 4;
+const ENUM_RESERVED_NAME: i32 = 5;
+const SERVICE_METHOD: i32 = 2;
+
+/// Page of a file's source within [SOURCE_PAGES_DIR], e.g. `google/protobuf/empty` for
+/// `google/protobuf/empty.proto`
+pub(crate) fn page(file_name: &str) -> &str {
+    file_name.strip_suffix(".proto").unwrap_or(file_name)
+}
+
+/// Reconstructs the `.proto` source of a file as a highlighted html block, with the comments
+/// from its `source_code_info`. Declarations are in source order (descriptor order without
+/// source info) and each symbol is wrapped in an element with the id its reference heading has,
+/// so `[src]` links can land on it. Type references link to the reference, by `hrefs` (fqsl to
+/// href), when rendered.
+///
+/// The layout is not the original's: type references are written fully qualified, and options
+/// other than those the reference shows are left out.
+pub(crate) fn source_page(file: &FileDescriptorProto, hrefs: &HashMap<String, String>) -> String {
+    let mut writer = SourceWriter {
+        locations: file
+            .source_code_info
+            .iter()
+            .flat_map(|info| &info.location)
+            .map(|location| (location.path.clone(), location))
+            .collect(),
+        hrefs,
+        out: String::new(),
+    };
+    writer.write_file(file);
+
+    format!(
+        "<pre class=\"proto-source\"><code class=\"language-protobuf\">{}</code></pre>\n",
+        writer.out.trim_end()
+    )
+}
+
+/// A declaration within a scope, to be written in source order
+enum Declaration<'a> {
+    Line(String),
+    Message(&'a DescriptorProto, String),
+    Enum(&'a EnumDescriptorProto, String),
+    /// the ids of the symbols declared (none for a oneof, and a map field's entry message along
+    /// with the field), its first line(s) and, when they open a block (e.g. a service), its members
+    Block(Vec<String>, String, Vec<(Vec<i32>, Declaration<'a>)>),
+}
+
+struct SourceWriter<'a> {
+    locations: HashMap<Vec<i32>, &'a Location>,
+    hrefs: &'a HashMap<String, String>,
+    out: String,
+}
+
+impl<'a> SourceWriter<'a> {
+    fn write_file(&mut self, file: &'a FileDescriptorProto) {
+        let mut declarations = Vec::new();
+
+        if matches!(file.syntax(), "proto2" | "proto3") {
+            declarations.push((
+                vec![FILE_SYNTAX],
+                Declaration::Line(format!("syntax = \"{}\";", file.syntax())),
+            ));
+        }
+        if !file.package().is_empty() {
+            declarations.push((
+                vec![FILE_PACKAGE],
+                Declaration::Line(format!("package {};", file.package())),
+            ));
+        }
+        for (idx, dependency) in file.dependency.iter().enumerate() {
+            let public = if file.public_dependency.contains(&(idx as i32)) {
+                "public "
+            } else {
+                ""
+            };
+            declarations.push((
+                vec![FILE_DEPENDENCY, idx as i32],
+                Declaration::Line(format!("import {}\"{}\";", public, dependency)),
+            ));
+        }
+        if let Some(options) = &file.options {
+            for (number, name, value) in language_options(options) {
+                let value = match name {
+                    "java_multiple_files" => value,
+                    _ => format!("\"{}\"", value),
+                };
+                declarations.push((
+                    vec![FILE_OPTIONS, number],
+                    Declaration::Line(format!("option {} = {};", name, value)),
+                ));
+            }
+        }
+
+        let scope = if file.package().is_empty() {
+            String::new()
+        } else {
+            format!(".{}", file.package())
+        };
+        for (idx, message) in file.message_type.iter().enumerate() {
+            declarations.push((
+                vec![FILE_MESSAGE, idx as i32],
+                Declaration::Message(message, format!("{}.{}", scope, message.name())),
+            ));
+        }
+        for (idx, enumeration) in file.enum_type.iter().enumerate() {
+            declarations.push((
+                vec![FILE_ENUM, idx as i32],
+                Declaration::Enum(enumeration, enumeration.name().to_string()),
+            ));
+        }
+        for (idx, service) in file.service.iter().enumerate() {
+            let path = vec![FILE_SERVICE, idx as i32];
+            let methods = service
+                .method
+                .iter()
+                .enumerate()
+                .map(|(method_idx, method)| {
+                    let stream = |streaming: bool| if streaming { "stream " } else { "" };
+                    let mut line = format!(
+                        "rpc {} ({}{}) returns ({}{})",
+                        method.name(),
+                        stream(method.client_streaming()),
+                        method.input_type(),
+                        stream(method.server_streaming()),
+                        method.output_type()
+                    );
+                    line.push_str(if method.options.as_ref().is_some_and(|o| o.deprecated()) {
+                        " {\n  option deprecated = true;\n}"
+                    } else {
+                        ";"
+                    });
+                    (
+                        [path.as_slice(), &[SERVICE_METHOD, method_idx as i32]].concat(),
+                        Declaration::Block(
+                            vec![format!("{}::{}", service.name(), method.name())],
+                            line,
+                            Vec::new(),
+                        ),
+                    )
+                })
+                .collect();
+            declarations.push((
+                path,
+                Declaration::Block(
+                    vec![service.name().to_string()],
+                    format!("service {} {{", service.name()),
+                    methods,
+                ),
+            ));
+        }
+
+        self.write_declarations(file, declarations, 0);
+    }
+
+    /// Writes the declarations in source order, those at the top level separated by blank lines
+    /// (other than consecutive ones of the same kind, such as imports)
+    fn write_declarations(
+        &mut self,
+        file: &'a FileDescriptorProto,
+        mut declarations: Vec<(Vec<i32>, Declaration<'a>)>,
+        depth: usize,
+    ) {
+        // stable, so declarations without a location keep their descriptor order
+        declarations.sort_by_key(|(path, _)| self.start(path));
+
+        let mut previous_kind = None;
+        for (path, declaration) in declarations {
+            if depth == 0 && previous_kind.is_some() {
+                let line = matches!(declaration, Declaration::Line(_));
+                if !line || previous_kind != Some(path[0]) {
+                    self.out.push('\n');
+                }
+            }
+            previous_kind = Some(path[0]);
+
+            self.write_leading_comments(&path, depth);
+            let indent = INDENT.repeat(depth);
+
+            match declaration {
+                Declaration::Line(line) => {
+                    let _ = write!(self.out, "{}{}", indent, self.linked(&line));
+                    self.write_trailing_comment(&path, depth);
+                }
+                Declaration::Message(message, fqsl) => {
+                    self.write_message(file, message, &path, &fqsl, depth)
+                }
+                Declaration::Enum(enumeration, id) => {
+                    self.write_enum(enumeration, &path, &id, depth)
+                }
+                Declaration::Block(ids, header, members) => {
+                    let opens = header.ends_with('{');
+                    let header = header.replace('\n', &format!("\n{}", indent));
+                    let header = anchored(&ids, &self.linked(&header));
+                    let _ = write!(self.out, "{}{}", indent, header);
+                    self.write_trailing_comment(&path, depth);
+                    if opens {
+                        self.write_declarations(file, members, depth + 1);
+                        let _ = writeln!(self.out, "{}}}", indent);
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_message(
+        &mut self,
+        file: &'a FileDescriptorProto,
+        message: &'a DescriptorProto,
+        path: &[i32],
+        fqsl: &str,
+        depth: usize,
+    ) {
+        let id = fqsl
+            .strip_prefix(&format!(".{}.", file.package()))
+            .unwrap_or(fqsl.trim_start_matches('.'))
+            .to_string();
+        let member_path = |kind: i32, idx: usize| [path, &[kind, idx as i32]].concat();
+
+        let mut members = Vec::new();
+        let mut oneofs: Vec<Vec<_>> = message.oneof_decl.iter().map(|_| Vec::new()).collect();
+        for (idx, field) in message.field.iter().enumerate() {
+            let mut ids = vec![format!("{}::{}", id, field.name())];
+            if let Some(entry) = map_entry(message, fqsl, field) {
+                ids.push(format!("{}.{}", id, entry.name()));
+            }
+            let declaration = (
+                member_path(MESSAGE_FIELD, idx),
+                Declaration::Block(
+                    ids,
+                    field_definition(file, message, fqsl, field),
+                    Vec::new(),
+                ),
+            );
+            match field.oneof_index {
+                // proto3 `optional` is modelled as a synthetic single-field oneof
+                Some(oneof_index) if !field.proto3_optional() => {
+                    oneofs[oneof_index as usize].push(declaration)
+                }
+                _ => members.push(declaration),
+            }
+        }
+        for (idx, fields) in oneofs.into_iter().enumerate() {
+            if !fields.is_empty() {
+                members.push((
+                    member_path(MESSAGE_ONEOF, idx),
+                    Declaration::Block(
+                        Vec::new(),
+                        format!("oneof {} {{", message.oneof_decl[idx].name()),
+                        fields,
+                    ),
+                ));
+            }
+        }
+        for (idx, nested) in message.nested_type.iter().enumerate() {
+            if nested.options.as_ref().is_some_and(|o| o.map_entry()) {
+                continue;
+            }
+            members.push((
+                member_path(MESSAGE_NESTED, idx),
+                Declaration::Message(nested, format!("{}.{}", fqsl, nested.name())),
+            ));
+        }
+        for (idx, nested) in message.enum_type.iter().enumerate() {
+            members.push((
+                member_path(MESSAGE_ENUM, idx),
+                Declaration::Enum(nested, format!("{}.{}", id, nested.name())),
+            ));
+        }
+        let reserved = Reserved::from_message(message);
+        if let Some(numbers) = reserved.numbers_declaration() {
+            members.push((
+                [path, &[MESSAGE_RESERVED_RANGE]].concat(),
+                Declaration::Line(numbers),
+            ));
+        }
+        if let Some(names) = reserved.names_declaration() {
+            members.push((
+                [path, &[MESSAGE_RESERVED_NAME]].concat(),
+                Declaration::Line(names),
+            ));
+        }
+
+        let indent = INDENT.repeat(depth);
+        let header = anchored(
+            std::slice::from_ref(&id),
+            &format!("message {} {{", message.name()),
+        );
+        let _ = write!(self.out, "{}{}", indent, header);
+        self.write_trailing_comment(path, depth);
+        self.write_declarations(file, members, depth + 1);
+        let _ = writeln!(self.out, "{}}}", indent);
+    }
+
+    fn write_enum(
+        &mut self,
+        enumeration: &EnumDescriptorProto,
+        path: &[i32],
+        id: &str,
+        depth: usize,
+    ) {
+        let indent = INDENT.repeat(depth);
+        let inner = INDENT.repeat(depth + 1);
+        let header = anchored(
+            &[id.to_string()],
+            &format!("enum {} {{", enumeration.name()),
+        );
+        let _ = write!(self.out, "{}{}", indent, header);
+        self.write_trailing_comment(path, depth);
+
+        if enumeration
+            .options
+            .as_ref()
+            .is_some_and(|o| o.allow_alias())
+        {
+            let _ = writeln!(self.out, "{}option allow_alias = true;", inner);
+        }
+
+        let mut values: Vec<(Vec<i32>, String)> = enumeration
+            .value
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                let deprecated = value.options.as_ref().is_some_and(|o| o.deprecated());
+                (
+                    [path, &[ENUM_VALUE, idx as i32]].concat(),
+                    format!(
+                        "{} = {}{};",
+                        value.name(),
+                        value.number(),
+                        if deprecated {
+                            " [deprecated = true]"
+                        } else {
+                            ""
+                        }
+                    ),
+                )
+            })
+            .collect();
+        let reserved = Reserved::from_enum(enumeration);
+        if let Some(numbers) = reserved.numbers_declaration() {
+            values.push(([path, &[ENUM_RESERVED_RANGE]].concat(), numbers));
+        }
+        if let Some(names) = reserved.names_declaration() {
+            values.push(([path, &[ENUM_RESERVED_NAME]].concat(), names));
+        }
+        values.sort_by_key(|(path, _)| self.start(path));
+
+        for (path, value) in values {
+            self.write_leading_comments(&path, depth + 1);
+            let _ = write!(self.out, "{}{}", inner, value);
+            self.write_trailing_comment(&path, depth + 1);
+        }
+
+        let _ = writeln!(self.out, "{}}}", indent);
+    }
+
+    /// Start of the declaration's span, or after everything with one when it has no location
+    fn start(&self, path: &[i32]) -> (i32, i32) {
+        match self.locations.get(path).map(|location| &location.span[..]) {
+            Some([line, column, ..]) => (*line, *column),
+            _ => (i32::MAX, i32::MAX),
+        }
+    }
+
+    fn write_leading_comments(&mut self, path: &[i32], depth: usize) {
+        let Some(location) = self.locations.get(path).copied() else {
+            return;
+        };
+
+        for detached in &location.leading_detached_comments {
+            self.write_comment(detached, depth);
+            self.out.push('\n');
+        }
+        if let Some(leading) = &location.leading_comments {
+            self.write_comment(leading, depth);
+        }
+    }
+
+    /// Single line trailing comments stay on the declaration's line, longer ones follow it. Ends
+    /// the declaration's line either way.
+    fn write_trailing_comment(&mut self, path: &[i32], depth: usize) {
+        let trailing = self
+            .locations
+            .get(path)
+            .and_then(|location| location.trailing_comments.as_deref());
+
+        match trailing.map(|comment| comment.trim_end_matches('\n')) {
+            Some(comment) if !comment.contains('\n') => {
+                let _ = writeln!(self.out, " //{}", escape(comment));
+            }
+            Some(comment) => {
+                self.out.push('\n');
+                self.write_comment(comment, depth + 1);
+            }
+            None => self.out.push('\n'),
+        }
+    }
+
+    fn write_comment(&mut self, comment: &str, depth: usize) {
+        let indent = INDENT.repeat(depth);
+        for line in comment.trim_end_matches('\n').split('\n') {
+            let _ = writeln!(self.out, "{}//{}", indent, escape(line));
+        }
+    }
+
+    /// The line escaped, with each fully qualified type reference linked to the reference
+    fn linked(&self, line: &str) -> String {
+        static TYPE_REFERENCE: OnceLock<Regex> = OnceLock::new();
+        let type_reference =
+            TYPE_REFERENCE.get_or_init(|| Regex::new(r"\.[A-Za-z_][A-Za-z0-9_.]*").unwrap());
+
+        type_reference
+            .replace_all(&escape(line), |captures: &regex::Captures| {
+                let fqsl = &captures[0];
+                match self.hrefs.get(fqsl) {
+                    Some(href) => format!("<a href=\"{}\">{}</a>", href, fqsl),
+                    None => fqsl.to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// The html wrapped in an element with the id of the symbol it declares, so `[src]` links scroll
+/// to it. Any further ids are given empty elements before it.
+fn anchored(ids: &[String], html: &str) -> String {
+    match ids {
+        [] => html.to_string(),
+        [id, others @ ..] => {
+            let others: String = others
+                .iter()
+                .map(|other| format!("<span id=\"{}\"></span>", escape(other)))
+                .collect();
+            format!("{}<span id=\"{}\">{}</span>", others, escape(id), html)
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::demo_file;
+
+    #[test]
+    fn should_reconstruct_the_file_in_source_order_with_comments() {
+        let hrefs = HashMap::from([(
+            ".helloworld.HelloRequest".to_string(),
+            "/proto/helloworld.md#HelloRequest".to_string(),
+        )]);
+
+        let page = source_page(&demo_file("helloworld.proto"), &hrefs);
+
+        assert!(page.starts_with(
+            "<pre class=\"proto-source\"><code class=\"language-protobuf\">syntax = &quot;proto3&quot;;\n\npackage helloworld;\n\n"
+        ));
+        // the service is declared before the messages
+        assert!(page.find("service Greeter").unwrap() < page.find("message HelloRequest").unwrap());
+        assert!(page.contains(
+            "// The request message containing the user's name.\n<span id=\"HelloRequest\">message HelloRequest {</span>\n  // *who* is making the request\n  <span id=\"HelloRequest::name\">string name = 1;</span>\n}"
+        ));
+        assert!(page.contains(
+            "<span id=\"Greeter::SayHello\">rpc SayHello (<a href=\"/proto/helloworld.md#HelloRequest\">.helloworld.HelloRequest</a>) returns (.helloworld.HelloReply);</span>"
+        ));
+        assert!(page.ends_with("}</code></pre>\n"));
+    }
+
+    #[test]
+    fn should_keep_trailing_comments_and_nested_declarations_in_place() {
+        let page = source_page(&demo_file("type_test.proto"), &HashMap::new());
+
+        assert!(page.contains("  VALUE_3 = 3; // comment on VALUE_3\n"));
+        assert!(page.contains(
+            "<span id=\"Comments\">message Comments {</span> // inline with [Comments] message\n"
+        ));
+        assert!(page.contains(
+            "  <span id=\"TypeTest.DeprecatedMessage\">message DeprecatedMessage {</span>\n    <span id=\"TypeTest.DeprecatedMessage::field_name\">bool field_name = 1;</span>\n  }\n}"
+        ));
+        assert!(page.contains("oneof_double_type = 1;</span> // comment on oneof_double_type\n"));
+        assert!(
+            !page.contains("Entry {"),
+            "map entries should not be written"
+        );
+        // but `[src]` links of map entries land on their field
+        assert!(page.contains(
+            "<span id=\"RepeatedTypes.Fixed32MapEntry\"></span><span id=\"RepeatedTypes::fixed32_map\">"
+        ));
+    }
+}
@@ -1,12 +1,29 @@
-use crate::links::{Backlink, Backlinks, ProtoSymbol, SymbolLink};
+use crate::attributes::{Attributes, OptionAttributes};
+use crate::baseline::PackageChange;
+use crate::editions::{EditionsFeatures, FieldPresence};
+use crate::labels::Labels;
+use crate::links::{
+    Backlink, Backlinks, ProtoSymbol, SymbolKind, SymbolLink, DEFAULT_SOURCE_LINK_LABEL,
+};
+use crate::model;
+use crate::openapi;
+use crate::proto_text;
+use crate::reserved::Reserved;
+use crate::source_page;
+use anyhow::anyhow;
 use askama::Template;
-use prost_types::field_descriptor_proto::Type;
+use log::warn;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::method_options::IdempotencyLevel;
 use prost_types::source_code_info::Location;
 use prost_types::{
     DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
     OneofDescriptorProto,
 };
-use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
 pub(crate) enum FieldType {
     Symbol(SymbolLink),
@@ -14,6 +31,110 @@ pub(crate) enum FieldType {
     Unimplemented,
 }
 
+/// Every package, and the kind of every message and enum by fully qualified name (e.g.
+/// `.pkg.Outer.Inner`), for fields whose descriptor gives a `type_name` but no `type`. Also
+/// holds what is needed to work out the effective default of each field.
+pub(crate) struct TypeIndex {
+    pub(crate) packages: HashSet<String>,
+    kinds: HashMap<String, Type>,
+    /// name of the first value of each enum, the default of fields without a `[default = ...]`
+    enum_defaults: HashMap<String, String>,
+    editions: EditionsFeatures,
+    attributes: OptionAttributes,
+}
+
+impl TypeIndex {
+    pub(crate) fn new(files: &[FileDescriptorProto]) -> Self {
+        let mut index = Self {
+            packages: files.iter().map(|f| f.package().to_string()).collect(),
+            kinds: HashMap::new(),
+            enum_defaults: HashMap::new(),
+            editions: EditionsFeatures::default(),
+            attributes: OptionAttributes::default(),
+        };
+
+        for file in files {
+            let scope = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            for message in &file.message_type {
+                index.insert_message(&scope, message);
+            }
+            for enum_type in &file.enum_type {
+                index.insert_enum(&scope, enum_type);
+            }
+        }
+
+        index
+    }
+
+    fn insert_message(&mut self, scope: &str, message: &DescriptorProto) {
+        let name = format!("{}.{}", scope, message.name());
+        for nested in &message.nested_type {
+            self.insert_message(&name, nested);
+        }
+        for enum_type in &message.enum_type {
+            self.insert_enum(&name, enum_type);
+        }
+        self.kinds.insert(name, Type::Message);
+    }
+
+    fn insert_enum(&mut self, scope: &str, enum_type: &EnumDescriptorProto) {
+        let name = format!("{}.{}", scope, enum_type.name());
+        if let Some(first) = enum_type.value.first() {
+            self.enum_defaults
+                .insert(name.clone(), first.name().to_string());
+        }
+        self.kinds.insert(name, Type::Enum);
+    }
+
+    /// Adds the features of the editions files, which decide the presence and default of their
+    /// fields
+    pub(crate) fn with_editions(mut self, editions: EditionsFeatures) -> Self {
+        self.editions = editions;
+        self
+    }
+
+    /// Adds the configured custom options of each symbol, rendered as its attributes
+    pub(crate) fn with_attributes(mut self, attributes: OptionAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Value a field of `kind` reads as when unset and without a `[default = ...]`
+    fn zero_value(&self, kind: Type, type_name: &str) -> Option<String> {
+        match kind {
+            Type::Message | Type::Group => None,
+            Type::Enum => self.enum_defaults.get(type_name).cloned(),
+            Type::Bool => Some("false".into()),
+            Type::String | Type::Bytes => Some(String::new()),
+            _ => Some("0".into()),
+        }
+    }
+
+    /// Resolves a type name as protoc does: fully qualified names (with a leading `.`) as is,
+    /// relative ones in `scope` and then each enclosing scope in turn
+    pub(crate) fn resolve(&self, type_name: &str, scope: &str) -> Option<(String, Type)> {
+        if type_name.starts_with('.') {
+            return self
+                .kinds
+                .get(type_name)
+                .map(|kind| (type_name.to_string(), *kind));
+        }
+
+        let mut scope = scope;
+        loop {
+            let candidate = format!("{}.{}", scope, type_name);
+            if let Some(kind) = self.kinds.get(&candidate) {
+                return Some((candidate, *kind));
+            }
+            scope = &scope[..scope.rfind('.')?];
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "source.html")]
 struct Source {
@@ -23,14 +144,41 @@ struct Source {
     end_column: i32,
     file_path: String,
     url: Option<String>,
+    /// open the link in a new tab, with `external_links_new_tab`
+    new_tab: bool,
+    /// the symbol on its file's source page, with `emit_source_pages`
+    page: Option<String>,
+    /// text of the link, from `source_link_label`
+    label: String,
+    /// render an icon in place of the text (which remains its title), with `source_link_icon`
+    icon: bool,
 }
 
 impl Source {
-    fn set_source_url(&mut self, url: String) {
-        self.url = Some(url)
+    fn set_source_url(&mut self, url: String, new_tab: bool) {
+        self.url = Some(url);
+        self.new_tab = new_tab;
+    }
+
+    fn set_link_label(&mut self, label: &str, icon: bool) {
+        self.label = label.to_string();
+        self.icon = icon;
+    }
+
+    /// Points the link at the symbol with `id` on the source page of the file, the page being
+    /// below `pages_root`
+    fn set_source_page(&mut self, pages_root: &str, id: &str) {
+        self.page = Some(format!(
+            "{}/{}.md#{}",
+            pages_root,
+            source_page::page(&self.file_path),
+            id
+        ));
     }
 
-    fn from_location(location: &Location, file_path: &str) -> Self {
+    /// `None` (with a warning) when the span is not the 3 or 4 elements protoc writes, so a
+    /// malformed descriptor only loses the `[src]` link
+    fn from_location(location: &Location, file_path: &str) -> Option<Self> {
         let mut src = match location.span.as_slice().to_owned()[..] {
             [start_line, start_column, end_line, end_column] => Self {
                 start_line,
@@ -39,6 +187,10 @@ impl Source {
                 end_line,
                 file_path: file_path.to_string(),
                 url: None,
+                new_tab: false,
+                page: None,
+                label: DEFAULT_SOURCE_LINK_LABEL.into(),
+                icon: false,
             },
             [start_line, start_column, end_column] => Self {
                 start_line: start_line.clone(),
@@ -47,17 +199,31 @@ impl Source {
                 end_line: start_line,
                 file_path: file_path.to_string(),
                 url: None,
+                new_tab: false,
+                page: None,
+                label: DEFAULT_SOURCE_LINK_LABEL.into(),
+                icon: false,
             },
-            _ => panic!("unexpected Location::span format"),
+            ref span => {
+                warn!(
+                    "ignoring source location in {} with unexpected span {:?}",
+                    file_path, span
+                );
+                return None;
+            }
         };
 
         src.start_line += 1;
         src.end_line += 1;
 
-        src
+        Some(src)
     }
 
     fn href(&self) -> String {
+        if let Some(page) = &self.page {
+            return page.clone();
+        }
+
         let line = if self.end_line == self.start_line {
             format!("L{}", self.start_line)
         } else {
@@ -73,13 +239,60 @@ impl Source {
     }
 }
 
+/// Heading level of services, and top level messages & enums within a namespace chapter. Methods
+/// and nested symbols are one level deeper per level of nesting.
+/// Level of the top level symbol headings unless `heading_base_level` is configured
+pub(crate) const DEFAULT_HEADING_BASE_LEVEL: usize = 2;
+
+/// Symbols are emitted as markdown headings (surrounded by blank lines to break out of the html
+/// block) so that mdbook assigns them anchors and indexes them as search results.
+fn symbol_heading(level: usize, name: &str, id: &str, deprecated: bool) -> String {
+    let deprecated_class = if deprecated { " .deprecated" } else { "" };
+    format!(
+        "\n\n{} {} {{#{} .symbol{}}}\n\n",
+        "#".repeat(level.min(6)),
+        name,
+        id,
+        deprecated_class
+    )
+}
+
 // Any filter defined in the module `filters` is accessible in your template.
 mod filters {
-    // This filter does not have extra arguments
+    use pulldown_cmark::{Event, Tag};
+
+    /// Renders a comment as markdown. Raw html in the comment is escaped rather than passed
+    /// through, as comments are not trusted to be valid (or safe) html, and links to scripts are
+    /// left without a destination.
     pub fn md<T: std::fmt::Display>(markdown_input: T) -> ::askama::Result<String> {
         let markdown = markdown_input.to_string();
 
-        let parser = pulldown_cmark::Parser::new(markdown.as_str());
+        let parser = pulldown_cmark::Parser::new(markdown.as_str()).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if is_script_url(&dest_url) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: "".into(),
+                title,
+                id,
+            }),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if is_script_url(&dest_url) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: "".into(),
+                title,
+                id,
+            }),
+            event => event,
+        });
 
         // Write to a new String buffer.
         let mut html_output = String::new();
@@ -87,6 +300,74 @@ mod filters {
 
         Ok(html_output)
     }
+
+    /// browsers ignore case and any whitespace or control characters in the scheme
+    fn is_script_url(url: &str) -> bool {
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        ["javascript:", "vbscript:", "data:"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+    }
+
+    /// Opens the absolute (`http` and `https`) links of rendered html in a new tab when `new_tab`
+    /// is set, as the `[src]` links do, leaving relative links within the book alone
+    pub fn new_tab<T: std::fmt::Display>(html: T, new_tab: &bool) -> ::askama::Result<String> {
+        let mut html = html.to_string();
+        if *new_tab {
+            for scheme in ["http://", "https://"] {
+                html = html.replace(
+                    &format!("<a href=\"{}", scheme),
+                    &format!("<a target=\"_blank\" rel=\"noopener\" href=\"{}", scheme),
+                );
+            }
+        }
+        Ok(html)
+    }
+
+    /// Renders a comment as text: html escaped, with a paragraph per blank line separated block
+    /// and the line breaks within each kept
+    pub fn plaintext<T: std::fmt::Display>(text: T) -> ::askama::Result<String> {
+        let text = text
+            .to_string()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;");
+
+        Ok(text
+            .split("\n\n")
+            .map(|paragraph| paragraph.trim_matches('\n'))
+            .filter(|paragraph| !paragraph.trim().is_empty())
+            .map(|paragraph| format!("<p>{}</p>\n", paragraph.replace('\n', "<br>\n")))
+            .collect())
+    }
+}
+
+/// How comments are rendered: as markdown (with raw html escaped), or as plain text
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub(crate) enum CommentFormat {
+    #[default]
+    Markdown,
+    Plaintext,
+}
+
+impl TryFrom<&str> for CommentFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "markdown" => Ok(Self::Markdown),
+            "plaintext" => Ok(Self::Plaintext),
+            other => Err(anyhow!(
+                "`comment_format` should be one of \"markdown\" or \"plaintext\", got \"{}\"",
+                other
+            )),
+        }
+    }
 }
 
 #[derive(Template, Default)]
@@ -95,75 +376,608 @@ struct Comments {
     leading: Option<String>,
     trailing: Option<String>,
     leading_detached: Vec<String>,
+    deprecation: Option<DeprecationNotice>,
+    directives: Vec<Directive>,
+    format: CommentFormat,
 }
 
 impl Comments {
     fn from_location(location: &Option<Location>) -> Self {
+        Self::read(location, &[])
+    }
+
+    /// Like `from_location`, also reading the method directives (`@see`, `@timeout`,
+    /// `@retries`) out of the leading comment
+    fn from_method_location(location: &Option<Location>) -> Self {
+        Self::read(location, &Directive::METHOD)
+    }
+
+    fn read(location: &Option<Location>, directives: &[&str]) -> Self {
         if let Some(location) = location {
+            let (leading, directives) = match &location.leading_comments {
+                Some(leading) => Directive::extract(&Self::sanitise(leading), directives),
+                None => (None, Vec::new()),
+            };
+
+            let (leading, deprecation) = match &leading {
+                Some(leading) => DeprecationNotice::extract(leading),
+                None => (None, None),
+            };
+
             Self {
-                leading: location.leading_comments.clone(),
-                trailing: location.trailing_comments.clone(),
-                leading_detached: location.leading_detached_comments.clone(),
+                leading,
+                trailing: location.trailing_comments.as_deref().map(Self::sanitise),
+                leading_detached: location
+                    .leading_detached_comments
+                    .iter()
+                    .map(|comment| Self::sanitise(comment))
+                    .collect(),
+                deprecation,
+                directives,
+                format: CommentFormat::Markdown,
             }
         } else {
             Default::default()
         }
     }
+
+    fn html(&self, comment: &str) -> ::askama::Result<String> {
+        match self.format {
+            CommentFormat::Markdown => filters::md(comment),
+            CommentFormat::Plaintext => filters::plaintext(comment),
+        }
+    }
+
+    /// The markdown of a comment as written: without the `//` prefixes of line comments, or the
+    /// `/*`, `*/` delimiters and ` * ` prefixes of block comments (which protoc strips, but other
+    /// descriptor writers may not), and with the indentation common to its lines removed so that
+    /// indented markdown (code blocks, nested lists) keeps its meaning
+    fn sanitise(raw: &str) -> String {
+        let trimmed = raw.trim();
+        let block = trimmed.starts_with("/*") && trimmed.ends_with("*/");
+        let line_comment = raw
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .all(|line| line.trim_start().starts_with("//"));
+
+        let lines: Vec<&str> = if block {
+            let inner = trimmed
+                .trim_start_matches("/**")
+                .trim_start_matches("/*")
+                .trim_end_matches("*/");
+            inner
+                .lines()
+                .map(|line| {
+                    let line = line.trim_start();
+                    match line.strip_prefix('*') {
+                        Some(rest) => rest.strip_prefix(' ').unwrap_or(rest),
+                        None => line,
+                    }
+                })
+                .collect()
+        } else if line_comment {
+            raw.lines()
+                .map(|line| {
+                    let line = line.trim_start();
+                    line.strip_prefix("///")
+                        .or_else(|| line.strip_prefix("//"))
+                        .unwrap_or(line)
+                })
+                .collect()
+        } else {
+            raw.lines().collect()
+        };
+
+        let indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let mut sanitised = lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // block comments end on the line of the delimiter, and have no trailing newline to keep
+        if block {
+            sanitised = sanitised.trim_matches('\n').to_string();
+        } else if raw.ends_with('\n') {
+            sanitised.push('\n');
+        }
+        sanitised
+    }
+
+    /// Nothing to render: no deprecation notice, and no comment with any text
+    fn is_empty(&self) -> bool {
+        let has_text = |comment: &String| !comment.trim().is_empty();
+
+        self.deprecation.is_none()
+            && !self.leading.as_ref().is_some_and(has_text)
+            && !self.trailing.as_ref().is_some_and(has_text)
+            && !self.leading_detached.iter().any(has_text)
+    }
+
+    fn directive_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.directives
+            .iter()
+            .filter(move |directive| directive.name == name)
+            .map(|directive| directive.value.as_str())
+    }
+
+    fn resolve_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        if let Some(deprecation) = self.deprecation.as_mut() {
+            deprecation.replacement_link = deprecation.replacement.as_deref().and_then(resolve);
+        }
+
+        for comment in self
+            .leading
+            .iter_mut()
+            .chain(self.trailing.iter_mut())
+            .chain(self.leading_detached.iter_mut())
+        {
+            *comment = link_javadoc_references(comment, resolve);
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        if let Some(deprecation) = self.deprecation.as_mut() {
+            deprecation.labels = labels.clone();
+        }
+    }
+
+    fn set_format(&mut self, format: CommentFormat) {
+        self.format = format;
+    }
+
+    fn to_model(&self) -> model::Comments {
+        model::Comments {
+            leading: self.leading.clone(),
+            trailing: self.trailing.clone(),
+            leading_detached: self.leading_detached.clone(),
+            deprecation: self.deprecation.as_ref().map(|d| d.note.clone()),
+        }
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.leading
+            .as_ref()
+            .and_then(|comment| comment.trim().split("\n\n").next())
+            .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|summary| !summary.is_empty())
+    }
+}
+
+/// Replaces Javadoc style `{@link Symbol}` and `{@link Symbol label}` references with markdown
+/// links. `pkg.Foo#bar` is accepted for `pkg.Foo::bar`; references that do not resolve to exactly
+/// one symbol are left as code.
+fn link_javadoc_references(comment: &str, resolve: &dyn Fn(&str) -> Option<SymbolLink>) -> String {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+
+    let link = LINK.get_or_init(|| {
+        Regex::new(r"\{@link(?:plain)?\s+([\w.#:]+)(?:\s+([^}]*?))?\s*\}")
+            .expect("should be valid regex")
+    });
+
+    link.replace_all(comment, |caps: &regex::Captures| {
+        let reference = &caps[1];
+        let label = match caps.get(2) {
+            Some(label) if !label.as_str().is_empty() => label.as_str().to_string(),
+            _ => format!("`{}`", reference),
+        };
+
+        match resolve(&reference.trim_start_matches('.').replace('#', "::")) {
+            Some(symbol_link) => format!("[{}]({})", label, symbol_link.href()),
+            None => {
+                warn!(
+                    "`{{@link {}}}` does not resolve to a single symbol",
+                    reference
+                );
+                label
+            }
+        }
+    })
+    .into_owned()
+}
+
+/// A `@name value` line in a leading comment, used to attach structured metadata to symbols
+struct Directive {
+    name: String,
+    value: String,
+}
+
+impl Directive {
+    /// The directives read from method comments. Other `@word` lines (e.g. `@param`) are docs
+    const METHOD: [&'static str; 3] = ["see", "timeout", "retries"];
+
+    /// Splits the lines of the named directives out of a leading comment, returning the
+    /// remaining comment. Lines in fenced or indented code blocks are left as they are.
+    fn extract(comment: &str, names: &[&str]) -> (Option<String>, Vec<Self>) {
+        static DIRECTIVE: OnceLock<Regex> = OnceLock::new();
+
+        // more indented than this is an indented code block
+        let directive = DIRECTIVE.get_or_init(|| {
+            Regex::new(r"^ {0,3}@(\w+)(?:\s+(.*?))?\s*$").expect("should be valid regex")
+        });
+
+        let mut directives = Vec::new();
+        let mut remaining = Vec::new();
+        let mut fence: Option<&str> = None;
+
+        for line in comment.lines() {
+            let trimmed = line.trim_start();
+            match fence {
+                Some(marker) => {
+                    if trimmed.starts_with(marker) {
+                        fence = None;
+                    }
+                    remaining.push(line);
+                    continue;
+                }
+                None => {
+                    fence = ["```", "~~~"]
+                        .into_iter()
+                        .find(|marker| trimmed.starts_with(marker));
+                }
+            }
+
+            match directive
+                .captures(line)
+                .filter(|caps| names.contains(&&caps[1]))
+            {
+                Some(caps) => directives.push(Self {
+                    name: caps[1].to_string(),
+                    value: caps.get(2).map_or("", |m| m.as_str()).to_string(),
+                }),
+                None => remaining.push(line),
+            }
+        }
+
+        let remaining = remaining.join("\n");
+        let remaining = if remaining.trim().is_empty() {
+            None
+        } else {
+            Some(remaining)
+        };
+
+        (remaining, directives)
+    }
+}
+
+/// Callout for the conventional `// Deprecated: use Foo instead.` comment paragraph
+#[derive(Template)]
+#[template(path = "deprecation_notice.html")]
+struct DeprecationNotice {
+    note: String,
+    replacement: Option<String>,
+    replacement_link: Option<SymbolLink>,
+    labels: Arc<Labels>,
+}
+
+impl DeprecationNotice {
+    /// Splits the deprecation paragraph out of a leading comment, returning the remaining comment
+    fn extract(comment: &str) -> (Option<String>, Option<Self>) {
+        static DEPRECATED: OnceLock<Regex> = OnceLock::new();
+        static REPLACEMENT: OnceLock<Regex> = OnceLock::new();
+
+        let deprecated = DEPRECATED.get_or_init(|| {
+            Regex::new(r"(?is)^\s*deprecated:\s*(.*)$").expect("should be valid regex")
+        });
+        let replacement = REPLACEMENT.get_or_init(|| {
+            Regex::new(r"(?i)\b(?:use|replaced by|see)\s+`?([A-Za-z_][\w.]*(?:::\w+)?)`?")
+                .expect("should be valid regex")
+        });
+
+        let mut notice = None;
+        let mut remaining = Vec::new();
+
+        for paragraph in comment.split("\n\n") {
+            match deprecated.captures(paragraph) {
+                Some(caps) if notice.is_none() => {
+                    let note = caps[1].split_whitespace().collect::<Vec<_>>().join(" ");
+                    notice = Some(Self {
+                        replacement: replacement
+                            .captures(&note)
+                            .map(|caps| caps[1].trim_end_matches('.').to_string()),
+                        note,
+                        replacement_link: None,
+                        labels: Default::default(),
+                    });
+                }
+                _ => remaining.push(paragraph),
+            }
+        }
+
+        let remaining = remaining.join("\n\n");
+        let remaining = if remaining.trim().is_empty() {
+            None
+        } else {
+            Some(remaining)
+        };
+
+        (remaining, notice)
+    }
 }
 
 #[derive(Template)]
 #[template(path = "field.html")]
 struct SimpleField {
     name: String,
+    /// name in the protobuf JSON mapping, from the `json_name` option or derived by protoc
+    json_name: String,
     comments: Comments,
     source: Option<Source>,
     typ: FieldType,
     optional: bool,
+    repeated: bool,
+    number: i32,
     oneof_index: Option<i32>,
+    /// name of the oneof the field is a member of, other than the synthetic oneof of a proto3
+    /// `optional` field
+    oneof: Option<String>,
     deprecated: bool,
+    /// proto2 `[default = ...]` (or the effective default of an editions field), as the
+    /// descriptor's text representation
+    default_value: Option<String>,
+    /// whether the field tracks being set apart from holding its default (it has a hazzer), or
+    /// `None` for repeated and map fields, which have no presence
+    has_presence: Option<bool>,
+    /// the `debug_redact` option, marking the field sensitive
+    redacted: bool,
+    /// a repeated scalar field encoded as one length delimited record rather than a record per
+    /// element
+    packed: bool,
+    /// the configured custom options set on the field, with `option_attributes`
+    attributes: Option<Attributes>,
+    /// open the protobuf.dev link of the `Any` note in a new tab, with `external_links_new_tab`
+    new_tab: bool,
     self_link: SymbolLink,
+    labels: Arc<Labels>,
 }
 
 impl SimpleField {
     fn from_descriptor(
-        file_descriptor: &FileDescriptorProto,
+        source_file: &SourceFile,
         field_descriptor: &FieldDescriptorProto,
         path: &[i32],
-        packages: &HashSet<String>,
+        types: &TypeIndex,
         parent_symbol: &SymbolLink,
         symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
     ) -> Self {
+        let file_descriptor = source_file.descriptor;
         let name: String = field_descriptor.name().into();
         let mut self_link = parent_symbol.clone();
         self_link.set_property(name.clone());
         symbol_usages.entry(self_link.clone()).or_default();
-        let location = read_source_code_info(file_descriptor, path);
+        let location = source_file.location(path);
+
+        let resolved = match field_descriptor.r#type {
+            // descriptors built without resolving types only name them. A fully qualified name
+            // that isn't in the set (e.g. of a group, or from a missing import) is taken to be a
+            // message
+            None => types
+                .resolve(field_descriptor.type_name(), &parent_symbol.fqsl())
+                .or_else(|| {
+                    let type_name = field_descriptor.type_name();
+                    type_name
+                        .starts_with('.')
+                        .then(|| (type_name.to_string(), Type::Message))
+                }),
+            // a type number newer than `Type` knows of is left unresolved, rather than panicking
+            Some(label) => Type::try_from(label)
+                .ok()
+                .map(|typ| (field_descriptor.type_name().to_string(), typ)),
+        };
+
+        // in editions files, presence and defaults follow the features rather than the syntax.
+        // Repeated, message and oneof fields are the same in every syntax.
+        let editions_presence = types
+            .editions
+            .field_presence(&parent_symbol.fqsl(), field_descriptor.name())
+            .filter(|_| {
+                field_descriptor.label() != Label::Repeated
+                    && field_descriptor.oneof_index.is_none()
+                    && !matches!(resolved, Some((_, Type::Message | Type::Group)))
+            });
+
+        let default_value = field_descriptor.default_value.clone().or_else(|| {
+            editions_presence?;
+            let (type_name, kind) = resolved.as_ref()?;
+            types.zero_value(*kind, type_name)
+        });
+
+        // scalars (enums included) are packed when the option says so, and otherwise by default
+        // from proto3 on
+        let packed = field_descriptor.label() == Label::Repeated
+            && resolved.as_ref().is_some_and(|(_, kind)| {
+                !matches!(
+                    kind,
+                    Type::String | Type::Bytes | Type::Message | Type::Group
+                )
+            })
+            && field_descriptor
+                .options
+                .as_ref()
+                .and_then(|options| options.packed)
+                .unwrap_or(matches!(file_descriptor.syntax(), "proto3" | "editions"));
+
+        // message and oneof fields always have presence, proto3 scalars only when `optional`
+        let has_presence = (field_descriptor.label() != Label::Repeated).then(|| {
+            field_descriptor.oneof_index.is_some()
+                || matches!(resolved, Some((_, Type::Message | Type::Group)))
+                || match file_descriptor.syntax() {
+                    "proto3" => false,
+                    "editions" => editions_presence != Some(FieldPresence::Implicit),
+                    _ => true,
+                }
+        });
+
         Self {
+            json_name: field_descriptor
+                .json_name
+                .clone()
+                .unwrap_or_else(|| openapi::json_name(field_descriptor.name())),
             name,
             comments: Comments::from_location(&location),
             source: location
-                .map(|location| Source::from_location(&location, file_descriptor.name())),
-            typ: match field_descriptor.r#type {
-                None => {
-                    FieldType::Unimplemented // todo look up fully qualified from index.
+                .and_then(|location| Source::from_location(&location, file_descriptor.name())),
+            typ: match resolved {
+                // proto2 groups are messages declared in place of the field
+                Some((fqsl, Type::Enum | Type::Message | Type::Group)) if fqsl.starts_with('.') => {
+                    FieldType::Symbol(SymbolLink::from_fqsl(fqsl, &types.packages))
+                }
+                Some((_, t)) if !matches!(t, Type::Enum | Type::Message | Type::Group) => {
+                    FieldType::Primitive(t)
+                }
+                _ if field_descriptor.type_name().is_empty() => {
+                    warn!(
+                        "field `{}` in {} has neither a type nor a type name, so its type is not rendered",
+                        field_descriptor.name(),
+                        parent_symbol.fqsl()
+                    );
+                    FieldType::Unimplemented
+                }
+                _ => {
+                    warn!(
+                        "could not resolve type `{}` of field `{}` in {}",
+                        field_descriptor.type_name(),
+                        field_descriptor.name(),
+                        parent_symbol.fqsl()
+                    );
+                    FieldType::Unimplemented
                 }
-                Some(label) => match Type::try_from(label).expect("should be of type") {
-                    Type::Enum | Type::Message => FieldType::Symbol(SymbolLink::from_fqsl(
-                        field_descriptor.type_name().to_string(),
-                        packages,
-                    )),
-                    t => FieldType::Primitive(t),
-                },
             },
-            optional: field_descriptor.proto3_optional.unwrap_or(false),
+            optional: field_descriptor.proto3_optional.unwrap_or(false)
+                || editions_presence == Some(FieldPresence::Explicit),
+            repeated: field_descriptor.label() == Label::Repeated,
+            number: field_descriptor.number(),
             oneof_index: field_descriptor.oneof_index,
+            oneof: None,
             deprecated: field_descriptor
                 .clone()
                 .options
                 .map_or(false, |o| o.deprecated()),
+            default_value,
+            has_presence,
+            packed,
+            redacted: types
+                .editions
+                .is_redacted(&parent_symbol.fqsl(), field_descriptor.name()),
+            attributes: types.attributes.get(&format!(
+                "{}.{}",
+                parent_symbol.fqsl(),
+                field_descriptor.name()
+            )),
+            new_tab: false,
             self_link,
+            labels: Default::default(),
+        }
+    }
+
+    fn to_model(&self, oneof: Option<&str>) -> model::Field {
+        model::Field {
+            name: self.name.clone(),
+            json_name: self.json_name.clone(),
+            fqsl: self.self_link.fqsl(),
+            number: self.number,
+            type_name: match &self.typ {
+                FieldType::Symbol(link) => Some(link.fqsl()),
+                // `TYPE_STRING` as written in a proto file
+                FieldType::Primitive(t) => {
+                    Some(t.as_str_name().trim_start_matches("TYPE_").to_lowercase())
+                }
+                FieldType::Unimplemented => None,
+            },
+            repeated: self.repeated,
+            optional: self.optional,
+            deprecated: self.deprecated,
+            default_value: self.default_value.clone(),
+            oneof: oneof.map(String::from),
+            comments: self.comments.to_model(),
+        }
+    }
+
+    /// `google.protobuf.Any` fields link to `Any`, which says nothing of the messages they hold, so
+    /// are rendered with a note on how the type is resolved
+    fn is_any(&self) -> bool {
+        matches!(&self.typ, FieldType::Symbol(link) if link.fqsl() == ".google.protobuf.Any")
+    }
+
+    fn presence_label(&self) -> Option<&str> {
+        self.has_presence.map(|has_presence| {
+            if has_presence {
+                self.labels.has_presence.as_str()
+            } else {
+                self.labels.no_presence.as_str()
+            }
+        })
+    }
+
+    /// The default as it would be written in the proto file, with string values quoted
+    fn default_display(&self) -> Option<String> {
+        let value = self.default_value.as_ref()?;
+        Some(match self.typ {
+            FieldType::Primitive(Type::String | Type::Bytes) => format!("\"{}\"", value),
+            _ => value.clone(),
+        })
+    }
+}
+
+impl ProtoSymbol for SimpleField {
+    fn symbol_link(&self) -> &SymbolLink {
+        &self.self_link
+    }
+
+    fn kind(&self) -> SymbolKind {
+        SymbolKind::Field
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.comments.summary()
+    }
+
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        self.comments.resolve_links(resolve)
+    }
+
+    // fields do not render their usages
+    fn set_backlinks(&mut self, _backlinks: Backlinks) {}
+
+    fn set_source_url(&mut self, source_url: String, new_tab: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_url(source_url, new_tab)
+        }
+    }
+
+    fn set_source_page(&mut self, pages_root: &str) {
+        let id = self.symbol_link().id();
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_page(pages_root, &id)
+        }
+    }
+
+    fn set_source_link_label(&mut self, label: &str, icon: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_link_label(label, icon)
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
+        self.comments.set_labels(labels);
+        if let Some(attributes) = self.attributes.as_mut() {
+            attributes.labels = labels.clone();
         }
     }
+
+    fn set_comment_format(&mut self, format: CommentFormat) {
+        self.comments.set_format(format);
+    }
 }
 
 #[derive(Template)]
@@ -176,11 +990,11 @@ struct OneOfField {
 
 impl OneOfField {
     fn from_descriptor(
-        file_descriptor: &FileDescriptorProto,
+        source_file: &SourceFile,
         oneof_descriptor: &OneofDescriptorProto,
         path: &[i32],
     ) -> Self {
-        let location = read_source_code_info(file_descriptor, path);
+        let location = source_file.location(path);
         Self {
             name: oneof_descriptor.name().into(),
             comments: Comments::from_location(&location),
@@ -207,25 +1021,39 @@ pub(crate) struct ProtoMessage {
     deprecated: bool,
     self_link: SymbolLink,
     backlinks: Backlinks,
+    heading_base_level: usize,
+    /// the message reconstructed as `.proto` source, for copying
+    definition: String,
+    /// sample JSON payload of the message, when `show_json_examples` is set
+    json_example: Option<String>,
+    reserved: Reserved,
+    /// the configured custom options set on the message, with `option_attributes`
+    attributes: Option<Attributes>,
+    /// the synthetic entry message of a `map<K, V>` field
+    map_entry: bool,
+    labels: Arc<Labels>,
 }
 
 impl ProtoMessage {
     fn from_descriptor(
-        file_descriptor: &FileDescriptorProto,
+        source_file: &SourceFile,
         message_descriptor: &DescriptorProto,
         source_path: &[i32],
         parent_messages: Vec<String>,
-        packages: &HashSet<String>,
+        types: &TypeIndex,
         package: String,
         symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
     ) -> Self {
+        let file_descriptor = source_file.descriptor;
         let name: String = message_descriptor.name().into();
         let mut message_path = parent_messages.clone();
         message_path.push(message_descriptor.name().into());
 
         let fqsl = format!(".{}.{}", package, message_path.join("."));
-        let self_link = SymbolLink::from_fqsl(fqsl, packages);
+        let definition = proto_text::message_definition(file_descriptor, message_descriptor, &fqsl);
+        let self_link = SymbolLink::from_fqsl(fqsl, &types.packages);
         symbol_usages.entry(self_link.clone()).or_default();
+        let attributes = types.attributes.get(&self_link.fqsl());
 
         let all_fields: Vec<SimpleField> = message_descriptor
             .field
@@ -236,10 +1064,10 @@ impl ProtoMessage {
                 nested_path.extend(&[MESSAGE_FIELD_TAG, idx as i32]);
 
                 SimpleField::from_descriptor(
-                    file_descriptor,
+                    source_file,
                     f,
                     nested_path.as_ref(),
-                    packages,
+                    types,
                     &self_link,
                     symbol_usages,
                 )
@@ -255,14 +1083,14 @@ impl ProtoMessage {
                 nested_path.extend(&[MESSAGE_ONEOF_TAG, idx as i32]);
                 (
                     idx as i32,
-                    OneOfField::from_descriptor(file_descriptor, o, nested_path.as_ref()),
+                    OneOfField::from_descriptor(source_file, o, nested_path.as_ref()),
                 )
             })
             .collect();
 
         let mut fields = Vec::new();
 
-        for field in all_fields {
+        for mut field in all_fields {
             if let FieldType::Symbol(symbol_link) = &field.typ {
                 let mut field_ref = self_link.clone();
                 field_ref.set_property(field.name.clone());
@@ -270,15 +1098,18 @@ impl ProtoMessage {
                 symbol_usages
                     .entry(symbol_link.clone())
                     .or_default()
-                    .push(Backlink::Symbol(field_ref.clone()));
+                    .push(Backlink::Symbol {
+                        link: field_ref.clone(),
+                        context: None,
+                    });
             }
 
             if let Some(oneof_index) = field.oneof_index {
-                oneofs
-                    .get_mut(&oneof_index)
-                    .expect("field should exist")
-                    .fields
-                    .push(field)
+                let oneof = oneofs.get_mut(&oneof_index).expect("field should exist");
+                if !field.optional {
+                    field.oneof = Some(oneof.name.clone());
+                }
+                oneof.fields.push(field)
             } else {
                 fields.push(Field::Simple(field));
             }
@@ -286,14 +1117,14 @@ impl ProtoMessage {
 
         fields.extend(oneofs.into_values().into_iter().map(Field::OneOf));
 
-        let location = read_source_code_info(file_descriptor, source_path);
+        let location = source_file.location(source_path);
         Self {
             name,
             self_link,
             namespace: parent_messages,
             comments: Comments::from_location(&location),
             source: location
-                .map(|location| Source::from_location(&location, file_descriptor.name())),
+                .and_then(|location| Source::from_location(&location, file_descriptor.name())),
             nested_message: message_descriptor
                 .nested_type
                 .iter()
@@ -302,11 +1133,11 @@ impl ProtoMessage {
                     let mut nested_path = source_path.to_vec();
                     nested_path.extend(&[idx as i32]);
                     ProtoMessage::from_descriptor(
-                        file_descriptor,
+                        source_file,
                         m,
                         nested_path.as_ref(),
                         message_path.clone(),
-                        packages,
+                        types,
                         package.clone(),
                         symbol_usages,
                     )
@@ -320,10 +1151,10 @@ impl ProtoMessage {
                     let mut nested_path = source_path.to_vec();
                     nested_path.extend(&[idx as i32]);
                     Enum::from_descriptor(
-                        file_descriptor,
+                        source_file,
                         m,
                         nested_path.as_ref(),
-                        packages,
+                        &types.packages,
                         package.clone(),
                         message_path.clone(),
                         symbol_usages,
@@ -336,8 +1167,69 @@ impl ProtoMessage {
                 .clone()
                 .map_or(false, |o| o.deprecated()),
             backlinks: Default::default(),
+            heading_base_level: DEFAULT_HEADING_BASE_LEVEL,
+            definition,
+            json_example: None,
+            reserved: Reserved::from_message(message_descriptor),
+            attributes,
+            map_entry: message_descriptor
+                .options
+                .as_ref()
+                .is_some_and(|o| o.map_entry()),
+            labels: Default::default(),
+        }
+    }
+}
+
+impl ProtoMessage {
+    fn to_model(&self) -> model::Message {
+        let mut fields = Vec::new();
+        let mut oneofs = Vec::new();
+
+        for field in &self.fields {
+            match field {
+                Field::Simple(field) => fields.push(field.to_model(None)),
+                Field::OneOf(oneof) => {
+                    fields.extend(oneof.fields.iter().map(|f| f.to_model(Some(&oneof.name))));
+                    let first_number = oneof.fields.iter().map(|f| f.number).min();
+                    oneofs.push((
+                        first_number,
+                        model::Oneof {
+                            name: oneof.name.clone(),
+                            comments: oneof.comments.to_model(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        // oneofs are collected after the other fields, in no particular order
+        fields.sort_by_key(|field| field.number);
+        oneofs.sort_by_key(|(first_number, _)| *first_number);
+
+        model::Message {
+            name: self.name.clone(),
+            fqsl: self.self_link.fqsl(),
+            comments: self.comments.to_model(),
+            deprecated: self.deprecated,
+            map_entry: self.map_entry,
+            fields,
+            oneofs: oneofs.into_iter().map(|(_, oneof)| oneof).collect(),
+            messages: self.nested_message.iter().map(Self::to_model).collect(),
+            enums: self.nested_enum.iter().map(Enum::to_model).collect(),
+            reserved_ranges: self.reserved.ranges().to_vec(),
+            reserved_names: self.reserved.names().to_vec(),
         }
     }
+
+    fn heading(&self) -> String {
+        symbol_heading(
+            self.heading_base_level + self.namespace.len(),
+            &self.name,
+            &self.self_link.id(),
+            self.deprecated,
+        )
+    }
 }
 
 impl ProtoSymbol for ProtoMessage {
@@ -345,14 +1237,66 @@ impl ProtoSymbol for ProtoMessage {
         &self.self_link
     }
 
+    fn kind(&self) -> SymbolKind {
+        SymbolKind::Message
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.comments.summary()
+    }
+
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        self.comments.resolve_links(resolve)
+    }
+
     fn set_backlinks(&mut self, backlinks: Backlinks) {
         self.backlinks = backlinks
     }
 
-    fn set_source_url(&mut self, source_url: String) {
+    fn set_source_url(&mut self, source_url: String, new_tab: bool) {
         self.source
             .as_mut()
-            .map(|src| src.set_source_url(source_url));
+            .map(|src| src.set_source_url(source_url, new_tab));
+    }
+
+    fn set_source_page(&mut self, pages_root: &str) {
+        let id = self.symbol_link().id();
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_page(pages_root, &id)
+        }
+    }
+
+    fn set_source_link_label(&mut self, label: &str, icon: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_link_label(label, icon)
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
+        self.comments.set_labels(labels);
+        self.backlinks.set_labels(labels);
+        if let Some(attributes) = self.attributes.as_mut() {
+            attributes.labels = labels.clone();
+        }
+        for field in &mut self.fields {
+            if let Field::OneOf(oneof) = field {
+                oneof.comments.set_labels(labels);
+            }
+        }
+    }
+
+    fn set_comment_format(&mut self, format: CommentFormat) {
+        self.comments.set_format(format);
+        for field in &mut self.fields {
+            if let Field::OneOf(oneof) = field {
+                oneof.comments.set_format(format);
+            }
+        }
     }
 }
 
@@ -371,13 +1315,18 @@ pub(crate) struct Enum {
     source: Option<Source>,
     values: Vec<EnumValue>,
     namespace: Vec<String>,
+    allow_alias: bool,
+    deprecated: bool,
+    reserved: Reserved,
     backlinks: Backlinks,
     self_link: SymbolLink,
+    heading_base_level: usize,
+    labels: Arc<Labels>,
 }
 
 impl Enum {
     fn from_descriptor(
-        file_descriptor: &FileDescriptorProto,
+        source_file: &SourceFile,
         enum_descriptor: &EnumDescriptorProto,
         path: &[i32],
         packages: &HashSet<String>,
@@ -385,6 +1334,7 @@ impl Enum {
         namespace: Vec<String>,
         symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
     ) -> Self {
+        let file_descriptor = source_file.descriptor;
         let name: String = enum_descriptor.name().into();
 
         let mut fq = namespace.clone();
@@ -393,7 +1343,7 @@ impl Enum {
         let self_link = SymbolLink::from_fqsl(fqsl, packages);
         symbol_usages.entry(self_link.clone()).or_default();
 
-        let location = read_source_code_info(file_descriptor, path);
+        let location = source_file.location(path);
 
         Self {
             name,
@@ -404,7 +1354,7 @@ impl Enum {
                 .map(|(idx, v)| {
                     let mut nested_path = path.to_vec();
                     nested_path.extend(&[ENUM_FIELD_TAG, idx as i32]);
-                    let location = read_source_code_info(file_descriptor, &nested_path);
+                    let location = source_file.location(&nested_path);
                     EnumValue {
                         name: v.name().to_string(),
                         tag: v.number(),
@@ -414,12 +1364,76 @@ impl Enum {
                 })
                 .collect(),
             namespace,
+            allow_alias: enum_descriptor
+                .options
+                .as_ref()
+                .is_some_and(|o| o.allow_alias()),
+            deprecated: enum_descriptor
+                .options
+                .as_ref()
+                .is_some_and(|o| o.deprecated()),
+            reserved: Reserved::from_enum(enum_descriptor),
             backlinks: Default::default(),
             self_link,
+            heading_base_level: DEFAULT_HEADING_BASE_LEVEL,
+            labels: Default::default(),
             comments: Comments::from_location(&location),
             source: location
-                .map(|location| Source::from_location(&location, file_descriptor.name())),
+                .and_then(|location| Source::from_location(&location, file_descriptor.name())),
+        }
+    }
+}
+
+impl Enum {
+    fn to_model(&self) -> model::Enum {
+        model::Enum {
+            name: self.name.clone(),
+            fqsl: self.self_link.fqsl(),
+            comments: self.comments.to_model(),
+            deprecated: self.deprecated,
+            allow_alias: self.allow_alias,
+            values: self
+                .values
+                .iter()
+                .map(|value| model::EnumValue {
+                    name: value.name.clone(),
+                    number: value.tag,
+                    deprecated: value.deprecated,
+                    comments: value.comments.to_model(),
+                })
+                .collect(),
+            reserved_ranges: self.reserved.ranges().to_vec(),
+            reserved_names: self.reserved.names().to_vec(),
+        }
+    }
+
+    fn heading(&self) -> String {
+        symbol_heading(
+            self.heading_base_level + self.namespace.len(),
+            &self.name,
+            &self.self_link.id(),
+            self.deprecated,
+        )
+    }
+
+    /// Groups of value names that share the same tag, only populated when `allow_alias` is set
+    fn aliased_values(&self) -> Vec<(i32, Vec<String>)> {
+        if !self.allow_alias {
+            return Vec::new();
         }
+
+        let mut by_tag: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+        for value in &self.values {
+            by_tag
+                .entry(value.tag)
+                .or_default()
+                .push(value.name.clone());
+        }
+
+        by_tag
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .collect()
     }
 }
 
@@ -428,14 +1442,59 @@ impl ProtoSymbol for Enum {
         &self.self_link
     }
 
+    fn kind(&self) -> SymbolKind {
+        SymbolKind::Enum
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.comments.summary()
+    }
+
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        self.comments.resolve_links(resolve)
+    }
+
     fn set_backlinks(&mut self, backlinks: Backlinks) {
         self.backlinks = backlinks
     }
 
-    fn set_source_url(&mut self, source_url: String) {
+    fn set_source_url(&mut self, source_url: String, new_tab: bool) {
         self.source
             .as_mut()
-            .map(|src| src.set_source_url(source_url));
+            .map(|src| src.set_source_url(source_url, new_tab));
+    }
+
+    fn set_source_page(&mut self, pages_root: &str) {
+        let id = self.symbol_link().id();
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_page(pages_root, &id)
+        }
+    }
+
+    fn set_source_link_label(&mut self, label: &str, icon: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_link_label(label, icon)
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
+        self.comments.set_labels(labels);
+        self.backlinks.set_labels(labels);
+        for value in &mut self.values {
+            value.comments.set_labels(labels);
+        }
+    }
+
+    fn set_comment_format(&mut self, format: CommentFormat) {
+        self.comments.set_format(format);
+        for value in &mut self.values {
+            value.comments.set_format(format);
+        }
     }
 }
 
@@ -447,11 +1506,46 @@ struct Method {
     response_message: SymbolLink,
     request_stream: bool,
     response_stream: bool,
+    /// gRPC path the method is called on, `/package.Service/Method`, unless `show_method_path`
+    /// is off
+    path: Option<String>,
+    /// the method as declared, `rpc Method (Request) returns (stream Response);`, with a body of
+    /// its options when it has any
+    signature: String,
+    /// `idempotency_level` of the method options, `None` when left at `IDEMPOTENCY_UNKNOWN`
+    idempotency_level: Option<IdempotencyLevel>,
+    /// the configured custom options set on the method, with `option_attributes`
+    attributes: Option<Attributes>,
     comments: Comments,
     source: Option<Source>,
     deprecated: bool,
     self_link: SymbolLink,
     backlinks: Backlinks,
+    related: Vec<SymbolLink>,
+    operational_notes: Option<OperationalNotes>,
+    /// `grpcurl` command calling the method, when `grpcurl_examples` (or `show_examples`) is set
+    example: Option<String>,
+    heading_base_level: usize,
+    labels: Arc<Labels>,
+}
+
+impl Method {
+    fn heading(&self) -> String {
+        symbol_heading(
+            self.heading_base_level + 1,
+            &self.name,
+            &self.self_link.id(),
+            self.deprecated,
+        )
+    }
+
+    fn idempotency_label(&self) -> Option<&str> {
+        match self.idempotency_level? {
+            IdempotencyLevel::IdempotencyUnknown => None,
+            IdempotencyLevel::NoSideEffects => Some(self.labels.no_side_effects.as_str()),
+            IdempotencyLevel::Idempotent => Some(self.labels.idempotent.as_str()),
+        }
+    }
 }
 
 impl ProtoSymbol for Method {
@@ -459,14 +1553,150 @@ impl ProtoSymbol for Method {
         &self.self_link
     }
 
+    fn kind(&self) -> SymbolKind {
+        SymbolKind::Method
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.comments.summary()
+    }
+
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        self.comments.resolve_links(resolve)
+    }
+
     fn set_backlinks(&mut self, backlinks: Backlinks) {
         self.backlinks = backlinks
     }
 
-    fn set_source_url(&mut self, source_url: String) {
+    fn set_source_url(&mut self, source_url: String, new_tab: bool) {
         self.source
             .as_mut()
-            .map(|src| src.set_source_url(source_url));
+            .map(|src| src.set_source_url(source_url, new_tab));
+    }
+
+    fn set_source_page(&mut self, pages_root: &str) {
+        let id = self.symbol_link().id();
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_page(pages_root, &id)
+        }
+    }
+
+    fn set_source_link_label(&mut self, label: &str, icon: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_link_label(label, icon)
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
+        self.comments.set_labels(labels);
+        self.backlinks.set_labels(labels);
+        if let Some(notes) = self.operational_notes.as_mut() {
+            notes.labels = labels.clone();
+        }
+        if let Some(attributes) = self.attributes.as_mut() {
+            attributes.labels = labels.clone();
+        }
+    }
+
+    fn set_comment_format(&mut self, format: CommentFormat) {
+        self.comments.set_format(format);
+    }
+}
+
+/// Retry guidance from a method's `@retries` directive
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RetryHint {
+    Never,
+    /// only when the request was not processed, e.g. `UNAVAILABLE`
+    Unavailable,
+    Idempotent,
+}
+
+impl RetryHint {
+    fn description<'a>(&self, labels: &'a Labels) -> &'a str {
+        match self {
+            Self::Never => &labels.retry_never,
+            Self::Unavailable => &labels.retry_unavailable,
+            Self::Idempotent => &labels.retry_idempotent,
+        }
+    }
+}
+
+impl TryFrom<&str> for RetryHint {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "never" => Ok(Self::Never),
+            "unavailable" => Ok(Self::Unavailable),
+            "idempotent" => Ok(Self::Idempotent),
+            other => Err(anyhow!(
+                "should be one of \"never\", \"unavailable\" or \"idempotent\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+/// Per-method guidance from the `@timeout 5s` and `@retries idempotent` directives
+#[derive(Template, PartialEq, Debug)]
+#[template(path = "operational_notes.html")]
+struct OperationalNotes {
+    timeout: Option<String>,
+    retries: Option<RetryHint>,
+    labels: Arc<Labels>,
+}
+
+impl OperationalNotes {
+    /// Reads the directives of the method, warning about (and dropping) invalid values
+    fn from_comments(comments: &Comments, method_id: &str) -> Option<Self> {
+        static DURATION: OnceLock<Regex> = OnceLock::new();
+
+        let duration = DURATION.get_or_init(|| {
+            Regex::new(r"^(?:\d+(?:\.\d+)?(?:ns|us|µs|ms|s|m|h))+$").expect("should be valid regex")
+        });
+
+        let timeout = comments
+            .directive_values("timeout")
+            .next()
+            .and_then(|value| {
+                if duration.is_match(value) {
+                    Some(value.to_string())
+                } else {
+                    warn!(
+                        "`@timeout {}` on {} is not a duration such as `5s`, `500ms` or `1m30s`",
+                        value, method_id
+                    );
+                    None
+                }
+            });
+
+        let retries = comments
+            .directive_values("retries")
+            .next()
+            .and_then(|value| match RetryHint::try_from(value) {
+                Ok(hint) => Some(hint),
+                Err(e) => {
+                    warn!("`@retries` on {} {}", method_id, e);
+                    None
+                }
+            });
+
+        if timeout.is_none() && retries.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            timeout,
+            retries,
+            labels: Default::default(),
+        })
     }
 }
 
@@ -477,8 +1707,202 @@ struct Service {
     methods: Vec<Method>,
     comments: Comments,
     source: Option<Source>,
+    deprecated: bool,
     self_link: SymbolLink,
     backlinks: Backlinks,
+    heading_base_level: usize,
+}
+
+impl Service {
+    fn to_model(&self) -> model::Service {
+        model::Service {
+            name: self.name.clone(),
+            fqsl: self.self_link.fqsl(),
+            comments: self.comments.to_model(),
+            deprecated: self.deprecated,
+            methods: self
+                .methods
+                .iter()
+                .map(|method| model::Method {
+                    name: method.name.clone(),
+                    fqsl: method.self_link.fqsl(),
+                    comments: method.comments.to_model(),
+                    deprecated: method.deprecated,
+                    request_type: method.request_message.fqsl(),
+                    response_type: method.response_message.fqsl(),
+                    client_streaming: method.request_stream,
+                    server_streaming: method.response_stream,
+                })
+                .collect(),
+        }
+    }
+
+    fn heading(&self) -> String {
+        symbol_heading(
+            self.heading_base_level,
+            &self.name,
+            &self.self_link.id(),
+            self.deprecated,
+        )
+    }
+}
+
+/// Whether each package renders as a single chapter, or as a chapter per file beneath it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PagePer {
+    Package,
+    File,
+}
+
+impl TryFrom<&str> for PagePer {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "package" => Ok(Self::Package),
+            "file" => Ok(Self::File),
+            other => Err(anyhow!(
+                "`page_per` should be one of \"package\" or \"file\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+/// Order of the services, messages and enums within each file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum SymbolSort {
+    /// as declared in the proto file
+    Source,
+    Alpha,
+}
+
+impl TryFrom<&str> for SymbolSort {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "source" => Ok(Self::Source),
+            "alpha" => Ok(Self::Alpha),
+            other => Err(anyhow!(
+                "`symbol_sort` should be one of \"source\" or \"alpha\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum RelatedMethods {
+    Off,
+    /// only methods listed with `@see` directives
+    Manual,
+    /// `@see` directives, falling back to methods of the same service that act on the same
+    /// resource
+    Heuristic,
+}
+
+impl TryFrom<&str> for RelatedMethods {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "off" => Ok(Self::Off),
+            "manual" => Ok(Self::Manual),
+            "heuristic" => Ok(Self::Heuristic),
+            other => Err(anyhow!(
+                "`related_methods` should be one of \"off\", \"heuristic\" or \"manual\", got \"{}\"",
+                other
+            )),
+        }
+    }
+}
+
+/// The resource a method may act on, taken as the name less its leading verb with any plural
+/// stripped, so `CreateInvoice` and `ListInvoices` both give `Invoice`. As `-es` plurals can't
+/// be told apart from `-e` words by spelling, `ListAddresses` gives both `Address` and
+/// `Addresse`, and `ListInvoices` both `Invoice` and `Invoic`.
+fn method_resources(method_name: &str) -> Vec<String> {
+    let Some(verb_end) = method_name
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| c.is_uppercase())
+        .map(|(idx, _)| idx)
+    else {
+        return Vec::new();
+    };
+
+    let resource = &method_name[verb_end..];
+
+    if let Some(stem) = resource.strip_suffix("ies") {
+        vec![format!("{}y", stem)]
+    } else if resource.ends_with('s') && !resource.ends_with("ss") {
+        let mut singulars = vec![resource[..resource.len() - 1].to_string()];
+        if let Some(stem) = resource.strip_suffix("es") {
+            singulars.push(stem.to_string());
+        }
+        singulars
+    } else {
+        vec![resource.to_string()]
+    }
+}
+
+impl Service {
+    fn assign_related_methods(&mut self, mode: RelatedMethods) {
+        if mode == RelatedMethods::Off {
+            return;
+        }
+
+        let related: Vec<Vec<SymbolLink>> = self
+            .methods
+            .iter()
+            .map(|method| {
+                let see: Vec<&str> = method.comments.directive_values("see").collect();
+
+                if !see.is_empty() {
+                    return see
+                        .into_iter()
+                        .filter_map(|name| {
+                            let target = self.methods.iter().find(|m| m.name == name);
+                            if target.is_none() {
+                                warn!(
+                                    "`@see {}` on {} does not name a method of the same service",
+                                    name,
+                                    method.self_link.id()
+                                );
+                            }
+                            target.map(|m| m.self_link.clone())
+                        })
+                        .collect();
+                }
+
+                if mode == RelatedMethods::Manual {
+                    return Vec::new();
+                }
+
+                let resources = method_resources(&method.name);
+
+                self.methods
+                    .iter()
+                    .filter(|other| other.name != method.name)
+                    .filter(|other| {
+                        let same_resource = method_resources(&other.name)
+                            .iter()
+                            .any(|resource| resources.contains(resource));
+                        let same_response = other.response_message == method.response_message
+                            && !other.response_message.is_well_known_type();
+
+                        same_resource || same_response
+                    })
+                    .map(|other| other.self_link.clone())
+                    .collect()
+            })
+            .collect();
+
+        for (method, related) in self.methods.iter_mut().zip(related) {
+            method.related = related;
+        }
+    }
 }
 
 impl ProtoSymbol for Service {
@@ -486,14 +1910,52 @@ impl ProtoSymbol for Service {
         &self.self_link
     }
 
+    fn kind(&self) -> SymbolKind {
+        SymbolKind::Service
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.comments.summary()
+    }
+
+    fn resolve_comment_links(&mut self, resolve: &dyn Fn(&str) -> Option<SymbolLink>) {
+        self.comments.resolve_links(resolve)
+    }
+
     fn set_backlinks(&mut self, backlinks: Backlinks) {
         self.backlinks = backlinks
     }
 
-    fn set_source_url(&mut self, source_url: String) {
+    fn set_source_url(&mut self, source_url: String, new_tab: bool) {
         self.source
             .as_mut()
-            .map(|src| src.set_source_url(source_url));
+            .map(|src| src.set_source_url(source_url, new_tab));
+    }
+
+    fn set_source_page(&mut self, pages_root: &str) {
+        let id = self.symbol_link().id();
+        if let Some(src) = self.source.as_mut() {
+            src.set_source_page(pages_root, &id)
+        }
+    }
+
+    fn set_source_link_label(&mut self, label: &str, icon: bool) {
+        if let Some(src) = self.source.as_mut() {
+            src.set_link_label(label, icon)
+        }
+    }
+
+    fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.comments.set_labels(labels);
+        self.backlinks.set_labels(labels);
+    }
+
+    fn set_comment_format(&mut self, format: CommentFormat) {
+        self.comments.set_format(format);
     }
 }
 
@@ -504,17 +1966,142 @@ pub struct ProtoFileDescriptorTemplate {
     messages: Vec<ProtoMessage>,
     enums: Vec<Enum>,
     filename: String,
+    /// language specific package, namespace and prefix options, by option name
+    options: Vec<(&'static str, String)>,
+    /// `option deprecated = true;`, deprecating everything the file declares
+    deprecated: bool,
+    /// `proto2`, `proto3` or the edition, e.g. `edition 2023`
+    syntax: String,
+    imports: Vec<Import>,
+    /// chapter path (under `proto/`) when rendered with a page per file
+    page: String,
+    labels: Arc<Labels>,
+}
+
+/// A file the file imports, linked when it is rendered too
+pub(crate) struct Import {
+    filename: String,
+    public: bool,
+    weak: bool,
+    href: Option<String>,
 }
 
 impl ProtoFileDescriptorTemplate {
+    /// The package path followed by the file stem, e.g. `company/api/v1/orders`
+    fn page(descriptor: &FileDescriptorProto) -> String {
+        let stem = Path::new(descriptor.name())
+            .file_stem()
+            .map_or(descriptor.name().into(), |stem| stem.to_string_lossy());
+
+        if descriptor.package().is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}/{}", descriptor.package().replace('.', "/"), stem)
+        }
+    }
+
+    /// Id of the file's section, for linking to it from the files importing it
+    fn anchor(&self) -> String {
+        format!("file-{}", self.filename)
+    }
+
+    fn syntax(descriptor: &FileDescriptorProto, types: &TypeIndex) -> String {
+        match descriptor.syntax() {
+            "proto3" => "proto3".into(),
+            "editions" => types
+                .editions
+                .edition(descriptor.name())
+                .map_or("editions".into(), |edition| format!("edition {}", edition)),
+            // protoc leaves `syntax` unset in proto2 files
+            _ => "proto2".into(),
+        }
+    }
+
+    fn imports(descriptor: &FileDescriptorProto) -> Vec<Import> {
+        descriptor
+            .dependency
+            .iter()
+            .enumerate()
+            .map(|(idx, filename)| Import {
+                filename: filename.clone(),
+                public: descriptor.public_dependency.contains(&(idx as i32)),
+                weak: descriptor.weak_dependency.contains(&(idx as i32)),
+                href: None,
+            })
+            .collect()
+    }
+
+    /// The language specific options set on the file, in `descriptor.proto` order
+    fn options(descriptor: &FileDescriptorProto) -> Vec<(&'static str, String)> {
+        descriptor
+            .options
+            .as_ref()
+            .map(proto_text::language_options)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(_, name, value)| (name, value))
+            .collect()
+    }
+
+    /// Visits the links created with the file's symbols: their own links and the types they refer
+    /// to. Links assigned later (backlinks, comment and related method links) are not visited.
+    fn mutate_links(&mut self, mutator: &mut dyn FnMut(&mut SymbolLink)) {
+        fn mutate_message_links(
+            message: &mut ProtoMessage,
+            mutator: &mut dyn FnMut(&mut SymbolLink),
+        ) {
+            mutator(&mut message.self_link);
+
+            for field in &mut message.fields {
+                let fields = match field {
+                    Field::Simple(simple_field) => std::slice::from_mut(simple_field),
+                    Field::OneOf(oneof) => oneof.fields.as_mut_slice(),
+                };
+                for field in fields {
+                    mutator(&mut field.self_link);
+                    if let FieldType::Symbol(link) = &mut field.typ {
+                        mutator(link);
+                    }
+                }
+            }
+
+            for enum_type in &mut message.nested_enum {
+                mutator(&mut enum_type.self_link);
+            }
+
+            for nested in &mut message.nested_message {
+                mutate_message_links(nested, mutator);
+            }
+        }
+
+        for service in &mut self.services {
+            mutator(&mut service.self_link);
+            for method in &mut service.methods {
+                mutator(&mut method.self_link);
+                mutator(&mut method.request_message);
+                mutator(&mut method.response_message);
+            }
+        }
+
+        for message in &mut self.messages {
+            mutate_message_links(message, mutator);
+        }
+
+        for enum_type in &mut self.enums {
+            mutator(&mut enum_type.self_link);
+        }
+    }
+
     pub(crate) fn from_descriptor(
         descriptor: FileDescriptorProto,
-        packages: &HashSet<String>,
+        types: &TypeIndex,
+        symbol_sort: SymbolSort,
         symbol_usages: &mut HashMap<SymbolLink, Vec<Backlink>>,
     ) -> Self {
         let parent_messages = vec![];
+        let source_file = SourceFile::new(&descriptor);
 
-        let services = descriptor
+        let mut services: Vec<Service> = descriptor
             .service
             .iter()
             .enumerate()
@@ -523,12 +2110,11 @@ impl ProtoFileDescriptorTemplate {
 
                 let service_link = SymbolLink::from_fqsl(
                     format!(".{}.{}", descriptor.package(), &service_name,),
-                    packages,
+                    &types.packages,
                 );
 
                 symbol_usages.entry(service_link.clone()).or_default();
-                let location =
-                    read_source_code_info(&descriptor, &[SERVICE_TAG, service_idx as i32]);
+                let location = source_file.location(&[SERVICE_TAG, service_idx as i32]);
                 Service {
                     name: service_name.clone(),
                     methods: s
@@ -542,96 +2128,171 @@ impl ProtoFileDescriptorTemplate {
                             method_link.set_property(method_name.clone());
                             symbol_usages.entry(method_link.clone()).or_default();
 
-                            let request_message =
-                                SymbolLink::from_fqsl(m.input_type.clone().unwrap(), packages);
+                            let request_message = SymbolLink::from_fqsl(
+                                m.input_type.clone().unwrap(),
+                                &types.packages,
+                            );
+
+                            // the backlinks say which side of the method the message is on
+                            let usage = |side: &str, streaming: bool| Backlink::Symbol {
+                                link: method_link.clone(),
+                                context: Some(if streaming {
+                                    format!("stream {}", side)
+                                } else {
+                                    side.to_string()
+                                }),
+                            };
 
                             symbol_usages
                                 .entry(request_message.clone())
                                 .or_default()
-                                .push(Backlink::Symbol(method_link.clone()));
+                                .push(usage("request", m.client_streaming()));
 
-                            let response_message =
-                                SymbolLink::from_fqsl(m.output_type.clone().unwrap(), packages);
+                            let response_message = SymbolLink::from_fqsl(
+                                m.output_type.clone().unwrap(),
+                                &types.packages,
+                            );
 
                             symbol_usages
                                 .entry(response_message.clone())
                                 .or_default()
-                                .push(Backlink::Symbol(method_link.clone()));
-
-                            let location = read_source_code_info(
-                                &descriptor,
-                                &[
-                                    SERVICE_TAG,
-                                    service_idx as i32,
-                                    SERVICE_METHOD_TAG,
-                                    method_idx as i32,
-                                ],
-                            );
+                                .push(usage("response", m.server_streaming()));
+
+                            let location = source_file.location(&[
+                                SERVICE_TAG,
+                                service_idx as i32,
+                                SERVICE_METHOD_TAG,
+                                method_idx as i32,
+                            ]);
+
+                            let comments = Comments::from_method_location(&location);
+
+                            let signature = proto_text::method_definition(m);
+                            let path = match descriptor.package() {
+                                "" => format!("/{}/{}", service_name, method_name),
+                                package => format!("/{}.{}/{}", package, service_name, method_name),
+                            };
 
                             Method {
+                                operational_notes: OperationalNotes::from_comments(
+                                    &comments,
+                                    &method_link.id(),
+                                ),
                                 name: method_name,
                                 request_message,
                                 response_message,
                                 request_stream: m.client_streaming(),
                                 response_stream: m.server_streaming(),
+                                path: Some(path),
+                                signature,
+                                idempotency_level: m
+                                    .options
+                                    .as_ref()
+                                    .map(|o| o.idempotency_level())
+                                    .filter(|level| *level != IdempotencyLevel::IdempotencyUnknown),
+                                attributes: types.attributes.get(&format!(
+                                    "{}.{}",
+                                    service_link.fqsl(),
+                                    m.name()
+                                )),
                                 self_link: method_link,
                                 deprecated: m.options.clone().map_or(false, |o| o.deprecated()),
                                 backlinks: Default::default(),
-                                comments: Comments::from_location(&location),
-                                source: location.map(|location| {
+                                related: Vec::new(),
+                                example: None,
+                                heading_base_level: DEFAULT_HEADING_BASE_LEVEL,
+                                labels: Default::default(),
+                                comments,
+                                source: location.and_then(|location| {
                                     Source::from_location(&location, descriptor.name())
                                 }),
                             }
                         })
                         .collect(),
+                    deprecated: s.options.as_ref().is_some_and(|o| o.deprecated()),
                     self_link: service_link,
                     backlinks: Default::default(),
+                    heading_base_level: DEFAULT_HEADING_BASE_LEVEL,
                     comments: Comments::from_location(&location),
                     source: location
-                        .map(|location| Source::from_location(&location, descriptor.name())),
+                        .and_then(|location| Source::from_location(&location, descriptor.name())),
                 }
             })
             .collect();
 
-        let messages = descriptor
+        let mut messages: Vec<ProtoMessage> = descriptor
             .message_type
             .iter()
             .enumerate()
             .map(|(message_idx, m)| {
                 ProtoMessage::from_descriptor(
-                    &descriptor,
+                    &source_file,
                     m,
                     &[MESSAGE_TYPE_TAG, message_idx as i32],
                     parent_messages.clone(),
-                    packages,
+                    types,
                     descriptor.package().to_string(),
                     symbol_usages,
                 )
             })
             .collect();
 
-        let enums = descriptor
+        let mut enums: Vec<Enum> = descriptor
             .enum_type
             .iter()
             .enumerate()
             .map(|(enum_idx, e)| {
                 Enum::from_descriptor(
-                    &descriptor,
+                    &source_file,
                     e,
                     &[ENUM_TYPE_TAG, enum_idx as i32],
-                    packages,
+                    &types.packages,
                     descriptor.package().to_string(),
                     parent_messages.clone(),
-                    symbol_usages
+                    symbol_usages,
                 )
             })
             .collect();
 
+        if symbol_sort == SymbolSort::Alpha {
+            services.sort_by(|a, b| a.name.cmp(&b.name));
+            messages.sort_by(|a, b| a.name.cmp(&b.name));
+            enums.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
         Self {
             services,
             messages,
             enums,
             filename: descriptor.name().into(),
+            options: Self::options(&descriptor),
+            deprecated: descriptor
+                .options
+                .as_ref()
+                .is_some_and(|options| options.deprecated()),
+            syntax: Self::syntax(&descriptor, types),
+            imports: Self::imports(&descriptor),
+            page: Self::page(&descriptor),
+            labels: Default::default(),
+        }
+    }
+}
+
+/// In-page link to a symbol heading, keyed by the same id as `proto!()` links use
+#[derive(Template)]
+#[template(path = "toc_entry.html")]
+struct TocEntry {
+    name: String,
+    id: String,
+    children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    fn from_enum(enum_type: &Enum) -> Self {
+        Self {
+            name: enum_type.name.clone(),
+            id: enum_type.self_link.id(),
+            children: Vec::new(),
         }
     }
 }
@@ -640,42 +2301,463 @@ impl ProtoFileDescriptorTemplate {
 #[template(path = "namespace.html")]
 pub struct ProtoNamespaceTemplate {
     files: Vec<ProtoFileDescriptorTemplate>,
+    /// difference from the baseline descriptor, if one is configured
+    change: Option<PackageChange>,
+    /// trailing version segment of the package, e.g. `v2`, with `show_package_version`
+    version: Option<String>,
+    labels: Arc<Labels>,
 }
 
 impl ProtoNamespaceTemplate {
+    pub(crate) fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
     pub(crate) fn add_file(&mut self, file: ProtoFileDescriptorTemplate) {
         self.files.push(file);
     }
 
-    pub(crate) fn mutate_messages<F>(messages: &mut Vec<ProtoMessage>, mut mutator: F)
-    where
-        F: Fn(&mut dyn ProtoSymbol) + Clone,
-    {
-        for message in messages {
-            mutator(message);
-            Self::mutate_messages(&mut message.nested_message, mutator.clone());
-        }
+    pub(crate) fn set_change(&mut self, change: PackageChange) {
+        self.change = Some(change);
     }
 
-    pub(crate) fn mutate_symbols<F>(&mut self, mut mutator: F)
-    where
-        F: Fn(&mut dyn ProtoSymbol) + Clone,
-    {
-        for mut file in &mut self.files {
-            Self::mutate_messages(&mut file.messages, mutator.clone());
+    pub(crate) fn change(&self) -> Option<PackageChange> {
+        self.change
+    }
 
-            for enum_type in &mut file.enums {
-                mutator(enum_type)
-            }
+    pub(crate) fn set_version(&mut self, version: &str) {
+        self.version = Some(version.to_string());
+    }
 
-            for service in &mut file.services {
-                mutator(service);
+    /// The version and baseline change badges shown at the top of the package page, if any
+    pub(crate) fn badges(&self) -> Option<String> {
+        let version = self
+            .version
+            .as_ref()
+            .map(|version| format!(r#"<span class="badge version">{}</span>"#, version));
+        let badges: Vec<String> = version
+            .into_iter()
+            .chain(self.change.map(|change| change.badge()))
+            .collect();
 
-                for method in &mut service.methods {
-                    mutator(method);
-                }
-            }
-        }
+        (!badges.is_empty()).then(|| format!(r#"<div class="badges">{}</div>"#, badges.join(" ")))
+    }
+
+    pub(crate) fn mutate_links(&mut self, mutator: &mut dyn FnMut(&mut SymbolLink)) {
+        for file in &mut self.files {
+            file.mutate_links(mutator);
+        }
+    }
+
+    /// Drops the deprecated services, methods, messages, enums and fields, returning the links of
+    /// those dropped (not including the symbols within them)
+    pub(crate) fn remove_deprecated(&mut self) -> Vec<SymbolLink> {
+        fn retain_current<T: ProtoSymbol>(symbols: &mut Vec<T>, removed: &mut Vec<SymbolLink>) {
+            symbols.retain(|symbol| {
+                if symbol.deprecated() {
+                    removed.push(symbol.symbol_link().clone());
+                }
+                !symbol.deprecated()
+            });
+        }
+
+        fn remove_from_messages(messages: &mut Vec<ProtoMessage>, removed: &mut Vec<SymbolLink>) {
+            retain_current(messages, removed);
+
+            for message in messages {
+                for field in &mut message.fields {
+                    match field {
+                        Field::Simple(simple_field) if simple_field.deprecated => {
+                            removed.push(simple_field.self_link.clone())
+                        }
+                        Field::OneOf(oneof) => retain_current(&mut oneof.fields, removed),
+                        _ => {}
+                    }
+                }
+                message.fields.retain(|field| match field {
+                    Field::Simple(simple_field) => !simple_field.deprecated,
+                    Field::OneOf(oneof) => !oneof.fields.is_empty(),
+                });
+
+                retain_current(&mut message.nested_enum, removed);
+                remove_from_messages(&mut message.nested_message, removed);
+            }
+        }
+
+        let mut removed = Vec::new();
+
+        for file in &mut self.files {
+            retain_current(&mut file.services, &mut removed);
+            for service in &mut file.services {
+                retain_current(&mut service.methods, &mut removed);
+            }
+            remove_from_messages(&mut file.messages, &mut removed);
+            retain_current(&mut file.enums, &mut removed);
+        }
+
+        removed
+    }
+
+    /// Drops the top level services, messages and enums not kept (so everything within them),
+    /// then the files left without any, returning the links of the dropped symbols
+    pub(crate) fn retain_symbols(&mut self, keep: &HashSet<String>) -> Vec<SymbolLink> {
+        fn retain_kept<T: ProtoSymbol>(
+            symbols: &mut Vec<T>,
+            keep: &HashSet<String>,
+            removed: &mut Vec<SymbolLink>,
+        ) {
+            symbols.retain(|symbol| {
+                let kept = keep.contains(&symbol.fqsl());
+                if !kept {
+                    removed.push(symbol.symbol_link().clone());
+                }
+                kept
+            });
+        }
+
+        let mut removed = Vec::new();
+
+        for file in &mut self.files {
+            retain_kept(&mut file.services, keep, &mut removed);
+            retain_kept(&mut file.messages, keep, &mut removed);
+            retain_kept(&mut file.enums, keep, &mut removed);
+        }
+        self.files.retain(|file| {
+            !(file.services.is_empty() && file.messages.is_empty() && file.enums.is_empty())
+        });
+
+        removed
+    }
+
+    /// Whether the package has no files left to render
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The files of the package as plain data, see [model::ProtoModel]
+    pub(crate) fn to_model(&self) -> Vec<model::File> {
+        self.files
+            .iter()
+            .map(|file| model::File {
+                name: file.filename.clone(),
+                services: file.services.iter().map(Service::to_model).collect(),
+                messages: file.messages.iter().map(ProtoMessage::to_model).collect(),
+                enums: file.enums.iter().map(Enum::to_model).collect(),
+            })
+            .collect()
+    }
+
+    /// The page of each top level symbol, keyed by package path and symbol name
+    pub(crate) fn file_pages(&self) -> Vec<((String, String), String)> {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                let links = file
+                    .services
+                    .iter()
+                    .map(|s| &s.self_link)
+                    .chain(file.messages.iter().map(|m| &m.self_link))
+                    .chain(file.enums.iter().map(|e| &e.self_link));
+
+                links.map(|link| (link.page_key(), file.page.clone()))
+            })
+            .collect()
+    }
+
+    /// The href of each file's section, keyed by filename, `page` being the chapter path of the
+    /// namespace (when not rendered with a page per file)
+    pub(crate) fn file_hrefs(
+        &self,
+        page: &str,
+        page_per: PagePer,
+        output_prefix: &str,
+    ) -> Vec<(String, String)> {
+        self.files
+            .iter()
+            .map(|file| {
+                let page = match page_per {
+                    PagePer::Package => page,
+                    PagePer::File => &file.page,
+                };
+                let href = format!("/{}/{}.md#{}", output_prefix, page, file.anchor());
+                (file.filename.clone(), href)
+            })
+            .collect()
+    }
+
+    /// Links the imports of each file to the section of the imported file, keyed by filename
+    pub(crate) fn set_import_hrefs(&mut self, hrefs: &HashMap<String, String>) {
+        for import in self.files.iter_mut().flat_map(|file| &mut file.imports) {
+            import.href = hrefs.get(&import.filename).cloned();
+        }
+    }
+
+    /// Splits into one single-file namespace per file, with the page and filename of each
+    pub(crate) fn into_files(self) -> Vec<(String, String, ProtoNamespaceTemplate)> {
+        self.files
+            .into_iter()
+            .map(|file| {
+                (
+                    file.page.clone(),
+                    file.filename.clone(),
+                    ProtoNamespaceTemplate {
+                        labels: file.labels.clone(),
+                        files: vec![file],
+                        change: None,
+                        version: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_heading_base_level(&mut self, level: usize) {
+        fn set_message_level(message: &mut ProtoMessage, level: usize) {
+            message.heading_base_level = level;
+            message
+                .nested_enum
+                .iter_mut()
+                .for_each(|e| e.heading_base_level = level);
+            message
+                .nested_message
+                .iter_mut()
+                .for_each(|m| set_message_level(m, level));
+        }
+
+        for file in &mut self.files {
+            for service in &mut file.services {
+                service.heading_base_level = level;
+                service
+                    .methods
+                    .iter_mut()
+                    .for_each(|m| m.heading_base_level = level);
+            }
+            file.messages
+                .iter_mut()
+                .for_each(|m| set_message_level(m, level));
+            file.enums
+                .iter_mut()
+                .for_each(|e| e.heading_base_level = level);
+        }
+    }
+
+    /// Groups of in-page links to the top level symbols of every file, omitting empty groups
+    fn toc(&self) -> Vec<(&str, Vec<TocEntry>)> {
+        fn message_entry(message: &ProtoMessage) -> TocEntry {
+            TocEntry {
+                name: message.name.clone(),
+                id: message.self_link.id(),
+                children: message
+                    .nested_message
+                    .iter()
+                    .map(message_entry)
+                    .chain(message.nested_enum.iter().map(TocEntry::from_enum))
+                    .collect(),
+            }
+        }
+
+        let services: Vec<TocEntry> = self
+            .files
+            .iter()
+            .flat_map(|f| &f.services)
+            .map(|service| TocEntry {
+                name: service.name.clone(),
+                id: service.self_link.id(),
+                children: Vec::new(),
+            })
+            .collect();
+
+        let messages: Vec<TocEntry> = self
+            .files
+            .iter()
+            .flat_map(|f| &f.messages)
+            .map(message_entry)
+            .collect();
+
+        let enums: Vec<TocEntry> = self
+            .files
+            .iter()
+            .flat_map(|f| &f.enums)
+            .map(TocEntry::from_enum)
+            .collect();
+
+        [
+            (self.labels.services.as_str(), services),
+            (self.labels.messages.as_str(), messages),
+            (self.labels.enums.as_str(), enums),
+        ]
+        .into_iter()
+        .filter(|(_, entries)| !entries.is_empty())
+        .collect()
+    }
+
+    /// Localizes the headings and captions, once the backlinks are assigned
+    pub(crate) fn set_labels(&mut self, labels: &Arc<Labels>) {
+        self.labels = labels.clone();
+        for file in &mut self.files {
+            file.labels = labels.clone();
+        }
+        self.mutate_symbols(|symbol| symbol.set_labels(labels));
+    }
+
+    /// Renders every comment as `format`
+    pub(crate) fn set_comment_format(&mut self, format: CommentFormat) {
+        self.mutate_symbols(|symbol| symbol.set_comment_format(format));
+    }
+
+    /// Sets the example of each method from `examples`, keyed by fqsl
+    pub(crate) fn set_examples(&mut self, examples: &HashMap<String, String>) {
+        for file in &mut self.files {
+            for method in file.services.iter_mut().flat_map(|s| &mut s.methods) {
+                method.example = examples.get(&method.fqsl()).cloned();
+            }
+        }
+    }
+
+    /// Drops the gRPC path of each method, for `show_method_path = false`
+    pub(crate) fn hide_method_paths(&mut self) {
+        for file in &mut self.files {
+            for method in file.services.iter_mut().flat_map(|s| &mut s.methods) {
+                method.path = None;
+            }
+        }
+    }
+
+    /// Sets the JSON example of each message (nested ones included) from `examples`, keyed by fqsl
+    pub(crate) fn set_json_examples(&mut self, examples: &HashMap<String, String>) {
+        fn set_message_example(message: &mut ProtoMessage, examples: &HashMap<String, String>) {
+            message.json_example = examples.get(&message.self_link.fqsl()).cloned();
+            message
+                .nested_message
+                .iter_mut()
+                .for_each(|m| set_message_example(m, examples));
+        }
+
+        for file in &mut self.files {
+            for message in &mut file.messages {
+                set_message_example(message, examples);
+            }
+        }
+    }
+
+    /// Opens the external links of field notes (the protobuf.dev link of the `Any` note) in a
+    /// new tab, with `external_links_new_tab`
+    pub(crate) fn set_external_links_new_tab(&mut self, new_tab: bool) {
+        fn set_message_new_tab(message: &mut ProtoMessage, new_tab: bool) {
+            for field in &mut message.fields {
+                match field {
+                    Field::Simple(simple_field) => simple_field.new_tab = new_tab,
+                    Field::OneOf(oneof) => {
+                        oneof.fields.iter_mut().for_each(|f| f.new_tab = new_tab)
+                    }
+                }
+            }
+            message
+                .nested_message
+                .iter_mut()
+                .for_each(|m| set_message_new_tab(m, new_tab));
+        }
+
+        for file in &mut self.files {
+            for message in &mut file.messages {
+                set_message_new_tab(message, new_tab);
+            }
+        }
+    }
+
+    pub(crate) fn assign_related_methods(&mut self, mode: RelatedMethods) {
+        for file in &mut self.files {
+            for service in &mut file.services {
+                service.assign_related_methods(mode);
+            }
+        }
+    }
+
+    pub(crate) fn mutate_messages<F>(messages: &mut Vec<ProtoMessage>, mut mutator: F)
+    where
+        F: Fn(&mut dyn ProtoSymbol) + Clone,
+    {
+        for message in messages {
+            mutator(message);
+
+            for field in &mut message.fields {
+                match field {
+                    Field::Simple(simple_field) => mutator(simple_field),
+                    Field::OneOf(oneof) => oneof.fields.iter_mut().for_each(|f| mutator(f)),
+                }
+            }
+
+            for enum_type in &mut message.nested_enum {
+                mutator(enum_type);
+            }
+
+            Self::mutate_messages(&mut message.nested_message, mutator.clone());
+        }
+    }
+
+    pub(crate) fn mutate_symbols<F>(&mut self, mut mutator: F)
+    where
+        F: Fn(&mut dyn ProtoSymbol) + Clone,
+    {
+        for mut file in &mut self.files {
+            Self::mutate_messages(&mut file.messages, mutator.clone());
+
+            for enum_type in &mut file.enums {
+                mutator(enum_type)
+            }
+
+            for service in &mut file.services {
+                mutator(service);
+
+                for method in &mut service.methods {
+                    mutator(method);
+                }
+            }
+        }
+    }
+
+    fn visit_messages<F>(messages: &[ProtoMessage], visitor: &mut F)
+    where
+        F: FnMut(&dyn ProtoSymbol),
+    {
+        for message in messages {
+            visitor(message);
+
+            for field in &message.fields {
+                match field {
+                    Field::Simple(simple_field) => visitor(simple_field),
+                    Field::OneOf(oneof) => oneof.fields.iter().for_each(|f| visitor(f)),
+                }
+            }
+
+            for enum_type in &message.nested_enum {
+                visitor(enum_type);
+            }
+
+            Self::visit_messages(&message.nested_message, visitor);
+        }
+    }
+
+    /// read-only walk over every linkable symbol, including fields and nested enums
+    pub(crate) fn visit_symbols<F>(&self, mut visitor: F)
+    where
+        F: FnMut(&dyn ProtoSymbol),
+    {
+        for file in &self.files {
+            for service in &file.services {
+                visitor(service);
+
+                for method in &service.methods {
+                    visitor(method);
+                }
+            }
+
+            Self::visit_messages(&file.messages, &mut visitor);
+
+            for enum_type in &file.enums {
+                visitor(enum_type);
+            }
+        }
     }
 }
 
@@ -689,13 +2771,1755 @@ const ENUM_TYPE_TAG: i32 = 5;
 const SERVICE_TAG: i32 = 6;
 const ENUM_FIELD_TAG: i32 = 2;
 
-fn read_source_code_info(descriptor: &FileDescriptorProto, path: &[i32]) -> Option<Location> {
-    if let Some(info) = &descriptor.source_code_info {
-        info.location
+/// A file descriptor with its `source_code_info` locations indexed by path, once per file rather
+/// than scanned for each symbol. The first location of a path wins, should a descriptor writer
+/// repeat one.
+struct SourceFile<'a> {
+    descriptor: &'a FileDescriptorProto,
+    locations: HashMap<&'a [i32], &'a Location>,
+}
+
+impl<'a> SourceFile<'a> {
+    fn new(descriptor: &'a FileDescriptorProto) -> Self {
+        let mut locations = HashMap::new();
+        for location in descriptor
+            .source_code_info
             .iter()
-            .find(|location| location.path == path)
-            .cloned()
-    } else {
-        None
+            .flat_map(|info| &info.location)
+        {
+            locations
+                .entry(location.path.as_slice())
+                .or_insert(location);
+        }
+        Self {
+            descriptor,
+            locations,
+        }
+    }
+
+    fn location(&self, path: &[i32]) -> Option<Location> {
+        self.locations.get(path).map(|location| (*location).clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+    use prost_types::{
+        EnumOptions, EnumValueDescriptorProto, FieldOptions, FileDescriptorSet, FileOptions,
+        MethodDescriptorProto, ServiceDescriptorProto, ServiceOptions,
+    };
+
+    fn render_file(descriptor: FileDescriptorProto) -> ProtoFileDescriptorTemplate {
+        let types = TypeIndex::new(std::slice::from_ref(&descriptor));
+        ProtoFileDescriptorTemplate::from_descriptor(
+            descriptor,
+            &types,
+            SymbolSort::Source,
+            &mut HashMap::new(),
+        )
+    }
+
+    fn enum_value(name: &str, number: i32) -> EnumValueDescriptorProto {
+        EnumValueDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            options: None,
+        }
+    }
+
+    #[test]
+    fn should_render_allow_alias_badge_and_group_aliased_values() {
+        let enum_descriptor = EnumDescriptorProto {
+            name: Some("Status".into()),
+            value: vec![
+                enum_value("UNKNOWN", 0),
+                enum_value("STARTED", 1),
+                enum_value("RUNNING", 1),
+                enum_value("STOPPED", 2),
+            ],
+            options: Some(EnumOptions {
+                allow_alias: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let rendered = Enum::from_descriptor(
+            &SourceFile::new(&FileDescriptorProto::default()),
+            &enum_descriptor,
+            &[ENUM_TYPE_TAG, 0],
+            &HashSet::from(["pkg".into()]),
+            "pkg".into(),
+            vec![],
+            &mut HashMap::new(),
+        )
+        .render()
+        .expect("should render");
+
+        assert!(rendered.contains(r#"<span class="badge allow-alias">allow_alias</span>"#));
+        assert!(rendered.contains(r#"<li data-tag="1"><code>1</code>:&nbsp;STARTED, RUNNING</li>"#));
+        assert!(!rendered.contains(r#"<li data-tag="0">"#));
+    }
+
+    #[test]
+    fn should_not_render_allow_alias_badge_by_default() {
+        let enum_descriptor = EnumDescriptorProto {
+            name: Some("Status".into()),
+            value: vec![enum_value("UNKNOWN", 0), enum_value("UNSET", 0)],
+            ..Default::default()
+        };
+
+        let enum_template = Enum::from_descriptor(
+            &SourceFile::new(&FileDescriptorProto::default()),
+            &enum_descriptor,
+            &[ENUM_TYPE_TAG, 0],
+            &HashSet::from(["pkg".into()]),
+            "pkg".into(),
+            vec![],
+            &mut HashMap::new(),
+        );
+        assert!(enum_template.aliased_values().is_empty());
+
+        let rendered = enum_template.render().expect("should render");
+        assert!(!rendered.contains("allow-alias"));
+    }
+
+    fn leading_comment(comment: &str) -> Option<Location> {
+        Some(Location {
+            leading_comments: Some(comment.into()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn should_sanitise_raw_line_and_block_comments() {
+        // as protoc writes them
+        assert_eq!(
+            Comments::sanitise(" Charges the card.\n\n Example:\n\n     charge(card)\n"),
+            "Charges the card.\n\nExample:\n\n    charge(card)\n"
+        );
+        assert_eq!(
+            Comments::sanitise("// Charges the card.\n//\n// * once\n// * per order\n"),
+            "Charges the card.\n\n* once\n* per order\n"
+        );
+        assert_eq!(
+            Comments::sanitise("/**\n * Charges the **card**.\n *\n *   - once\n */"),
+            "Charges the **card**.\n\n  - once"
+        );
+        assert_eq!(
+            Comments::sanitise("/* Charges the card. */"),
+            "Charges the card."
+        );
+
+        let comments = Comments::from_method_location(&leading_comment(
+            "/**\n * Charges the **card**.\n * @timeout 5s\n */",
+        ));
+        assert_eq!(comments.leading.as_deref(), Some("Charges the **card**."));
+        assert_eq!(
+            comments.directive_values("timeout").collect::<Vec<_>>(),
+            ["5s"]
+        );
+        assert!(comments
+            .render()
+            .unwrap()
+            .contains("<p>Charges the <strong>card</strong>.</p>"));
+    }
+
+    #[test]
+    fn should_extract_deprecation_notice_from_leading_comment() {
+        let comments = Comments::from_location(&leading_comment(
+            " Old style greeting.\n\n Deprecated: use `HelloRequest` instead.\n",
+        ));
+
+        assert_eq!(comments.leading.as_deref(), Some("Old style greeting."));
+
+        let notice = comments
+            .deprecation
+            .expect("should have deprecation notice");
+        assert_eq!(notice.note, "use `HelloRequest` instead.");
+        assert_eq!(notice.replacement.as_deref(), Some("HelloRequest"));
+    }
+
+    #[test]
+    fn should_not_extract_deprecation_notice_from_unrelated_comment() {
+        let comments =
+            Comments::from_location(&leading_comment(" This is not deprecated: honestly.\n"));
+
+        assert!(comments.deprecation.is_none());
+        assert!(comments.leading.is_some());
+    }
+
+    #[test]
+    fn should_render_resolved_replacement_link_in_deprecation_notice() {
+        let packages = HashSet::from(["helloworld".into()]);
+        let links = [SymbolLink::from_fqsl(
+            ".helloworld.HelloRequest".into(),
+            &packages,
+        )];
+
+        let mut comments =
+            Comments::from_location(&leading_comment(" Deprecated: use HelloRequest instead."));
+        comments.resolve_links(&|query| crate::links::resolve_unique(&links, query).cloned());
+
+        let rendered = comments.render().expect("should render");
+
+        assert!(rendered.contains(r#"<div class="deprecation-notice">"#));
+        assert!(rendered.contains(
+            r#"Replacement:&nbsp;<a href="/proto/helloworld.md#HelloRequest">HelloRequest</a>"#
+        ));
+        assert!(!rendered.contains(r#"<div class="leading comment">"#));
+    }
+
+    #[test]
+    fn should_link_javadoc_references_in_comments() {
+        let packages = HashSet::from(["helloworld".into()]);
+        let links = [
+            SymbolLink::from_fqsl(".helloworld.HelloRequest".into(), &packages),
+            SymbolLink::from_fqsl(".helloworld.Greeter::SayHello".into(), &packages),
+        ];
+
+        let mut comments = Comments::from_location(&leading_comment(
+            " Built from a {@link HelloRequest}, see {@link helloworld.Greeter#SayHello the rpc}.\n Not {@link Missing}.",
+        ));
+        comments.resolve_links(&|query| crate::links::resolve_unique(&links, query).cloned());
+
+        let mut say_hello = links[1].clone();
+        say_hello.set_label("the rpc".into());
+        assert!(comments
+            .leading
+            .as_ref()
+            .unwrap()
+            .contains(&say_hello.to_markdown_link()));
+
+        let rendered = comments.render().expect("should render");
+
+        assert!(
+            rendered.contains(
+                r#"<a href="/proto/helloworld.md#HelloRequest"><code>HelloRequest</code></a>"#
+            ),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains(r#"<a href="/proto/helloworld.md#Greeter::SayHello">the rpc</a>"#),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains("Not <code>Missing</code>."),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn should_read_operational_notes_from_directives() {
+        let comments = Comments::from_method_location(&leading_comment(
+            " Charges the card.\n @timeout 1m30s\n @retries idempotent\n",
+        ));
+
+        let notes = OperationalNotes::from_comments(&comments, "Billing::Charge")
+            .expect("should have notes");
+        assert_eq!(
+            notes,
+            OperationalNotes {
+                timeout: Some("1m30s".into()),
+                retries: Some(RetryHint::Idempotent),
+                labels: Default::default(),
+            }
+        );
+
+        let rendered = notes.render().expect("should render");
+        assert!(rendered.contains("<tr><td>Timeout</td><td><code>1m30s</code></td></tr>"));
+        assert!(rendered.contains("<td>Safe to retry, the method is idempotent</td>"));
+
+        let labels = Labels::with_overrides(
+            [("retry_idempotent".to_string(), "Wiederholbar".to_string())].into(),
+        )
+        .unwrap();
+        let rendered = OperationalNotes {
+            labels: Arc::new(labels),
+            ..notes
+        }
+        .render()
+        .expect("should render");
+        assert!(rendered.contains("<td>Wiederholbar</td>"), "{}", rendered);
+    }
+
+    #[test]
+    fn should_only_read_method_directives_outside_code_blocks() {
+        let comment = " The customer.\n @param id of the customer\n\n ```java\n @Override\n @see Other\n ```\n";
+
+        let field = Comments::from_location(&leading_comment(comment));
+        assert!(field.directives.is_empty());
+        assert_eq!(
+            field.leading.as_deref(),
+            Some("The customer.\n@param id of the customer\n\n```java\n@Override\n@see Other\n```")
+        );
+
+        let rendered = field.render().expect("should render");
+        assert!(
+            rendered.contains("@param id of the customer"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("@Override\n@see Other"), "{}", rendered);
+
+        let method = Comments::from_method_location(&leading_comment(
+            " Charges the card.\n @param card to charge\n @see Refund\n\n ```\n @timeout 5s\n ```\n",
+        ));
+        assert_eq!(
+            method.directive_values("see").collect::<Vec<_>>(),
+            ["Refund"]
+        );
+        assert_eq!(method.directive_values("timeout").count(), 0);
+        let leading = method.leading.unwrap();
+        assert!(leading.contains("@param card to charge"), "{}", leading);
+        assert!(leading.contains("```\n@timeout 5s\n```"), "{}", leading);
+    }
+
+    #[test]
+    fn should_drop_invalid_timeout() {
+        let comments = Comments::from_method_location(&leading_comment(
+            " @timeout five seconds\n @retries never\n",
+        ));
+
+        let notes = OperationalNotes::from_comments(&comments, "Billing::Charge")
+            .expect("should keep the valid retries hint");
+        assert_eq!(notes.timeout, None);
+        assert_eq!(notes.retries, Some(RetryHint::Never));
+
+        let comments = Comments::from_method_location(&leading_comment(" @timeout 5 seconds\n"));
+        assert_eq!(
+            OperationalNotes::from_comments(&comments, "Billing::Charge"),
+            None
+        );
+    }
+
+    #[test]
+    fn should_not_render_operational_notes_without_directives() {
+        let service = billing_service(vec![method(
+            "GetInvoice",
+            ".billing.GetInvoiceRequest",
+            ".billing.Invoice",
+        )]);
+
+        let rendered = service.methods[0].render().expect("should render");
+
+        assert!(service.methods[0].operational_notes.is_none());
+        assert!(!rendered.contains("operational-notes"));
+    }
+
+    #[test]
+    fn should_skip_source_links_with_unexpected_span() {
+        let location = |span: Vec<i32>| Location {
+            path: vec![MESSAGE_TYPE_TAG, 0],
+            span,
+            ..Default::default()
+        };
+
+        assert!(Source::from_location(&location(vec![1, 2]), "a.proto").is_none());
+        assert!(Source::from_location(&location(vec![1, 2, 3, 4, 5]), "a.proto").is_none());
+        assert!(Source::from_location(&location(vec![1, 2, 3]), "a.proto").is_some());
+
+        let descriptor = FileDescriptorProto {
+            name: Some("a.proto".into()),
+            package: Some("pkg".into()),
+            message_type: vec![prost_types::DescriptorProto {
+                name: Some("Message".into()),
+                ..Default::default()
+            }],
+            source_code_info: Some(prost_types::SourceCodeInfo {
+                location: vec![Location {
+                    leading_comments: Some(" Still documented.\n".into()),
+                    ..location(vec![7])
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let rendered = file.render().expect("should render");
+        assert!(rendered.contains("Still documented."));
+        assert!(!rendered.contains(r#"class="source""#));
+    }
+
+    fn method(name: &str, input: &str, output: &str) -> MethodDescriptorProto {
+        MethodDescriptorProto {
+            name: Some(name.into()),
+            input_type: Some(input.into()),
+            output_type: Some(output.into()),
+            ..Default::default()
+        }
+    }
+
+    fn billing_service(methods: Vec<MethodDescriptorProto>) -> Service {
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            service: vec![ServiceDescriptorProto {
+                name: Some("Billing".into()),
+                method: methods,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut file = render_file(descriptor);
+
+        file.services.remove(0)
+    }
+
+    #[test]
+    fn should_escape_html_in_comments_and_defaults() {
+        let comments = Comments {
+            leading: Some(" Never <script>alert(1)</script> & <b>bold</b>\n".into()),
+            trailing: Some("<div>\nblock\n</div>\n".into()),
+            ..Default::default()
+        };
+        let rendered = comments.render().expect("should render");
+
+        assert!(!rendered.contains("<script>"), "{}", rendered);
+        assert!(!rendered.contains("<div>\n"), "{}", rendered);
+        assert!(rendered.contains("&lt;script&gt;alert(1)&lt;/script&gt; &amp; &lt;b&gt;bold"));
+
+        let descriptor = FileDescriptorProto {
+            name: Some("greeting.proto".into()),
+            package: Some("greeting".into()),
+            syntax: Some("proto2".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Greeting".into()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("text".into()),
+                    number: Some(1),
+                    label: Some(Label::Optional.into()),
+                    r#type: Some(Type::String.into()),
+                    default_value: Some("<script>alert(1)</script>".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let file = render_file(descriptor);
+        let rendered = file.messages[0].render().expect("should render");
+
+        assert!(!rendered.contains("<script>"), "{}", rendered);
+        assert!(rendered.contains("&lt;script&gt;"), "{}", rendered);
+    }
+
+    #[test]
+    fn should_neutralise_scripts_in_comments_in_either_format() {
+        let mut comments = Comments {
+            leading: Some(
+                "Never <script>alert(1)</script>, [click](javascript:alert(1)).\nThen `run`\n\nDone\n"
+                    .into(),
+            ),
+            ..Default::default()
+        };
+
+        let markdown = comments.render().expect("should render");
+        assert!(!markdown.contains("<script>"), "{}", markdown);
+        assert!(!markdown.contains("javascript:"), "{}", markdown);
+        assert!(markdown.contains("&lt;script&gt;"), "{}", markdown);
+        assert!(markdown.contains("<code>run</code>"), "{}", markdown);
+
+        comments.set_format(CommentFormat::Plaintext);
+        let plaintext = comments.render().expect("should render");
+        assert!(!plaintext.contains("<script>"), "{}", plaintext);
+        assert!(plaintext.contains(
+            "<p>Never &lt;script&gt;alert(1)&lt;/script&gt;, [click](javascript:alert(1)).<br>\nThen `run`</p>\n<p>Done</p>"
+        ), "{}", plaintext);
+    }
+
+    #[test]
+    fn should_not_render_comments_for_undocumented_symbols() {
+        let blank = |path: Vec<i32>| Location {
+            path,
+            span: vec![0, 0, 1],
+            leading_comments: Some(" \n".into()),
+            leading_detached_comments: vec!["\n".into()],
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            service: vec![ServiceDescriptorProto {
+                name: Some("Billing".into()),
+                method: vec![method("Charge", ".billing.Invoice", ".billing.Invoice")],
+                ..Default::default()
+            }],
+            message_type: vec![DescriptorProto {
+                name: Some("Invoice".into()),
+                ..Default::default()
+            }],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Currency".into()),
+                ..Default::default()
+            }],
+            // the message has whitespace only comments, the others none at all
+            source_code_info: Some(prost_types::SourceCodeInfo {
+                location: vec![blank(vec![MESSAGE_TYPE_TAG, 0])],
+            }),
+            ..Default::default()
+        };
+        let file = render_file(descriptor);
+
+        assert!(file.messages[0].comments.is_empty());
+        for rendered in [
+            file.services[0].render(),
+            file.services[0].methods[0].render(),
+            file.messages[0].render(),
+            file.enums[0].render(),
+        ] {
+            let rendered = rendered.expect("should render");
+            assert!(!rendered.contains("comment"), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn should_mark_deprecated_services() {
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            service: vec![ServiceDescriptorProto {
+                name: Some("LegacyBilling".into()),
+                options: Some(ServiceOptions {
+                    deprecated: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let file = render_file(descriptor);
+
+        let service = &file.services[0];
+        assert!(service.deprecated());
+        assert!(service
+            .render()
+            .expect("should render")
+            .contains("{#LegacyBilling .symbol .deprecated}"));
+
+        let service = billing_service(vec![]);
+        assert!(!service.deprecated());
+        assert!(service
+            .render()
+            .expect("should render")
+            .contains("{#Billing .symbol}"));
+    }
+
+    fn service_with_related_methods(
+        methods: Vec<MethodDescriptorProto>,
+        mode: RelatedMethods,
+    ) -> Service {
+        let mut service = billing_service(methods);
+        service.assign_related_methods(mode);
+        service
+    }
+
+    fn related_ids(service: &Service, method_name: &str) -> Vec<String> {
+        service
+            .methods
+            .iter()
+            .find(|m| m.name == method_name)
+            .expect("method should exist")
+            .related
+            .iter()
+            .map(|link| link.id())
+            .collect()
+    }
+
+    #[test]
+    fn should_relate_crud_methods_acting_on_the_same_resource() {
+        let service = service_with_related_methods(
+            vec![
+                method(
+                    "CreateInvoice",
+                    ".billing.CreateInvoiceRequest",
+                    ".billing.Invoice",
+                ),
+                method(
+                    "GetInvoice",
+                    ".billing.GetInvoiceRequest",
+                    ".billing.Invoice",
+                ),
+                method(
+                    "ListInvoices",
+                    ".billing.ListInvoicesRequest",
+                    ".billing.ListInvoicesResponse",
+                ),
+                method("Ping", ".google.protobuf.Empty", ".google.protobuf.Empty"),
+            ],
+            RelatedMethods::Heuristic,
+        );
+
+        assert_eq!(
+            related_ids(&service, "CreateInvoice"),
+            vec!["Billing::GetInvoice", "Billing::ListInvoices"]
+        );
+        assert_eq!(
+            related_ids(&service, "ListInvoices"),
+            vec!["Billing::CreateInvoice", "Billing::GetInvoice"]
+        );
+        assert!(related_ids(&service, "Ping").is_empty());
+
+        let rendered = service.methods[0].render().expect("should render");
+        assert!(rendered.contains(r#"<div class="see-also">"#));
+    }
+
+    #[test]
+    fn should_relate_methods_on_resources_with_es_plurals() {
+        let service = service_with_related_methods(
+            vec![
+                method(
+                    "GetAddress",
+                    ".billing.GetAddressRequest",
+                    ".billing.Address",
+                ),
+                method(
+                    "ListAddresses",
+                    ".billing.ListAddressesRequest",
+                    ".billing.ListAddressesResponse",
+                ),
+                method("GetBox", ".billing.GetBoxRequest", ".billing.Box"),
+                method(
+                    "ListBoxes",
+                    ".billing.ListBoxesRequest",
+                    ".billing.ListBoxesResponse",
+                ),
+                method("GetCache", ".billing.GetCacheRequest", ".billing.Cache"),
+                method(
+                    "ListCaches",
+                    ".billing.ListCachesRequest",
+                    ".billing.ListCachesResponse",
+                ),
+            ],
+            RelatedMethods::Heuristic,
+        );
+
+        assert_eq!(
+            related_ids(&service, "ListAddresses"),
+            vec!["Billing::GetAddress"]
+        );
+        assert_eq!(related_ids(&service, "ListBoxes"), vec!["Billing::GetBox"]);
+        assert_eq!(
+            related_ids(&service, "ListCaches"),
+            vec!["Billing::GetCache"]
+        );
+    }
+
+    #[test]
+    fn should_not_relate_methods_acting_on_different_resources() {
+        let service = service_with_related_methods(
+            vec![
+                method(
+                    "SayHello",
+                    ".billing.HelloRequest",
+                    ".google.protobuf.Empty",
+                ),
+                method(
+                    "GetWeather",
+                    ".billing.WeatherRequest",
+                    ".google.protobuf.Empty",
+                ),
+                method("Ping", ".google.protobuf.Empty", ".google.protobuf.Empty"),
+            ],
+            RelatedMethods::Heuristic,
+        );
+
+        for method in &service.methods {
+            assert!(method.related.is_empty());
+            assert!(!method.render().expect("should render").contains("see-also"));
+        }
+    }
+
+    #[test]
+    fn should_prefer_see_directive_and_respect_mode() {
+        let methods = || {
+            vec![
+                method(
+                    "CreateInvoice",
+                    ".billing.CreateInvoiceRequest",
+                    ".billing.Invoice",
+                ),
+                method(
+                    "GetInvoice",
+                    ".billing.GetInvoiceRequest",
+                    ".billing.Invoice",
+                ),
+                method(
+                    "VoidPayment",
+                    ".billing.VoidPaymentRequest",
+                    ".billing.Payment",
+                ),
+            ]
+        };
+
+        let mut service = service_with_related_methods(methods(), RelatedMethods::Off);
+        assert!(related_ids(&service, "CreateInvoice").is_empty());
+
+        service.methods[0].comments = Comments::from_method_location(&leading_comment(
+            " Creates an invoice.\n @see VoidPayment\n",
+        ));
+        assert_eq!(
+            service.methods[0].comments.leading.as_deref(),
+            Some("Creates an invoice.")
+        );
+
+        service.assign_related_methods(RelatedMethods::Manual);
+        assert_eq!(
+            related_ids(&service, "CreateInvoice"),
+            vec!["Billing::VoidPayment"]
+        );
+        assert!(related_ids(&service, "GetInvoice").is_empty());
+
+        service.assign_related_methods(RelatedMethods::Heuristic);
+        assert_eq!(
+            related_ids(&service, "CreateInvoice"),
+            vec!["Billing::VoidPayment"]
+        );
+        assert_eq!(
+            related_ids(&service, "GetInvoice"),
+            vec!["Billing::CreateInvoice"]
+        );
+    }
+
+    #[test]
+    fn should_render_bidirectional_streaming_badge() {
+        let service = billing_service(vec![
+            MethodDescriptorProto {
+                client_streaming: Some(true),
+                server_streaming: Some(true),
+                ..method("SyncLedger", ".billing.LedgerEntry", ".billing.LedgerEntry")
+            },
+            MethodDescriptorProto {
+                server_streaming: Some(true),
+                ..method("WatchInvoice", ".billing.Invoice", ".billing.Invoice")
+            },
+        ]);
+
+        let bidi = service.methods[0].render().expect("should render");
+        assert!(
+            bidi.contains(r#"<span class="badge bidi-streaming">bidirectional streaming</span>"#)
+        );
+        assert_eq!(bidi.matches("stream&nbsp;").count(), 2);
+
+        let server_only = service.methods[1].render().expect("should render");
+        assert!(!server_only.contains("bidi-streaming"));
+        assert_eq!(server_only.matches("stream&nbsp;").count(), 1);
+    }
+
+    #[test]
+    fn should_render_the_grpcurl_example_with_a_note_on_streams() {
+        let mut service = billing_service(vec![
+            method(
+                "GetInvoice",
+                ".billing.GetInvoiceRequest",
+                ".billing.Invoice",
+            ),
+            MethodDescriptorProto {
+                client_streaming: Some(true),
+                ..method(
+                    "ImportInvoices",
+                    ".billing.Invoice",
+                    ".billing.ImportSummary",
+                )
+            },
+            MethodDescriptorProto {
+                server_streaming: Some(true),
+                ..method("WatchInvoice", ".billing.Invoice", ".billing.Invoice")
+            },
+        ]);
+        for method in &mut service.methods {
+            method.example = Some(format!("grpcurl -d '{{}}' localhost:50051 {}", method.name));
+        }
+
+        let note = r#"<div class="note">grpcurl reads a request stream"#;
+        let unary = service.methods[0].render().expect("should render");
+        assert!(unary.contains(
+            r#"<pre><code class="language-bash">grpcurl -d &#x27;{}&#x27; localhost:50051 GetInvoice</code></pre>"#
+        ), "{}", unary);
+        assert!(!unary.contains(note));
+
+        for streaming in &service.methods[1..] {
+            let rendered = streaming.render().expect("should render");
+            assert!(rendered.contains(r#"<code class="language-bash">"#));
+            assert!(rendered.contains(note), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn should_render_the_path_and_signature_of_each_method() {
+        let service = billing_service(vec![
+            method(
+                "GetInvoice",
+                ".billing.GetInvoiceRequest",
+                ".billing.Invoice",
+            ),
+            MethodDescriptorProto {
+                client_streaming: Some(true),
+                server_streaming: Some(true),
+                ..method("SyncLedger", ".billing.LedgerEntry", ".billing.LedgerEntry")
+            },
+        ]);
+
+        let unary = service.methods[0].render().expect("should render");
+        assert!(unary.contains(r#"<code class="method-path">/billing.Billing/GetInvoice</code>"#));
+        assert!(unary.contains(
+            r#"<pre><code class="language-protobuf">rpc GetInvoice (billing.GetInvoiceRequest) returns (billing.Invoice);</code></pre>"#
+        ));
+
+        let bidi = service.methods[1].render().expect("should render");
+        assert!(bidi.contains(
+            r#"rpc SyncLedger (stream billing.LedgerEntry) returns (stream billing.LedgerEntry);"#
+        ));
+    }
+
+    #[test]
+    fn method_backlinks_should_say_whether_the_message_is_the_request_or_response() {
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            service: vec![ServiceDescriptorProto {
+                name: Some("Billing".into()),
+                method: vec![
+                    method("GetInvoice", ".billing.Invoice", ".billing.Invoice"),
+                    MethodDescriptorProto {
+                        server_streaming: Some(true),
+                        ..method("WatchInvoice", ".billing.Invoice", ".billing.Invoice")
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let types = TypeIndex::new(std::slice::from_ref(&descriptor));
+        let mut symbol_usages = HashMap::new();
+        ProtoFileDescriptorTemplate::from_descriptor(
+            descriptor,
+            &types,
+            SymbolSort::Source,
+            &mut symbol_usages,
+        );
+
+        let invoice = SymbolLink::from_fqsl(".billing.Invoice".into(), &types.packages);
+        let rendered = Backlinks::new(symbol_usages[&invoice].clone())
+            .render()
+            .expect("should render");
+        let context = |method: &str, context: &str| {
+            format!(
+                r#"<a href="/proto/billing.md#Billing::{}">Billing::{}</a>&nbsp;<span class="backlink-context">{}</span>"#,
+                method, method, context
+            )
+        };
+        assert!(rendered.contains(&context("GetInvoice", "request")));
+        assert!(rendered.contains(&context("GetInvoice", "response")));
+        assert!(rendered.contains(&context("WatchInvoice", "request")));
+        assert!(rendered.contains(&context("WatchInvoice", "stream response")));
+    }
+
+    #[test]
+    fn alpha_symbol_sort_should_reorder_messages() {
+        let message = |name: &str| DescriptorProto {
+            name: Some(name.into()),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("zoo.proto".into()),
+            package: Some("zoo".into()),
+            message_type: vec![message("Zebra"), message("Aardvark"), message("Moose")],
+            ..Default::default()
+        };
+
+        let names = |symbol_sort| {
+            ProtoFileDescriptorTemplate::from_descriptor(
+                descriptor.clone(),
+                &TypeIndex::new(std::slice::from_ref(&descriptor)),
+                symbol_sort,
+                &mut HashMap::new(),
+            )
+            .messages
+            .into_iter()
+            .map(|m| m.name)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(SymbolSort::Source),
+            vec!["Zebra", "Aardvark", "Moose"]
+        );
+        assert_eq!(names(SymbolSort::Alpha), vec!["Aardvark", "Moose", "Zebra"]);
+    }
+
+    #[test]
+    fn toc_should_omit_empty_groups_and_nest_messages() {
+        let descriptor = FileDescriptorProto {
+            name: Some("zoo.proto".into()),
+            package: Some("zoo".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Enclosure".into()),
+                nested_type: vec![DescriptorProto {
+                    name: Some("Gate".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut namespace = ProtoNamespaceTemplate::default();
+        namespace.add_file(render_file(descriptor));
+
+        let toc = namespace.toc();
+        assert_eq!(toc.len(), 1);
+
+        let (label, entries) = &toc[0];
+        assert_eq!(*label, "Messages");
+        assert_eq!(entries[0].id, "Enclosure");
+        assert_eq!(entries[0].children[0].id, "Enclosure.Gate");
+
+        let rendered = namespace.render().expect("should render");
+        assert!(rendered.contains(r##"<a href="#Enclosure.Gate">Gate</a>"##));
+        assert!(!rendered.contains("<label>Services</label>"));
+        assert!(!rendered.contains("<label>Enums</label>"));
+    }
+
+    #[test]
+    fn nested_messages_should_render_breadcrumbs_to_their_enclosing_messages() {
+        let descriptor = FileDescriptorProto {
+            name: Some("zoo.proto".into()),
+            package: Some("zoo".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Enclosure".into()),
+                nested_type: vec![DescriptorProto {
+                    name: Some("Gate".into()),
+                    nested_type: vec![DescriptorProto {
+                        name: Some("Latch".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let breadcrumbs = |message: &ProtoMessage| -> Vec<String> {
+            message
+                .self_link
+                .ancestors()
+                .iter()
+                .map(SymbolLink::to_markdown_link)
+                .collect()
+        };
+        let enclosure = &file.messages[0];
+        let gate = &enclosure.nested_message[0];
+        assert_eq!(
+            breadcrumbs(gate),
+            vec![
+                "[Enclosure](/proto/zoo.md#Enclosure)",
+                "[Gate](/proto/zoo.md#Enclosure.Gate)",
+            ]
+        );
+        assert_eq!(breadcrumbs(&gate.nested_message[0]).len(), 3);
+
+        let rendered = enclosure.render().expect("should render");
+        assert_eq!(rendered.matches(r#"<nav class="breadcrumbs">"#).count(), 2);
+        assert!(rendered.contains(
+            r##"<nav class="breadcrumbs"><a href="/proto/zoo.md#Enclosure">Enclosure</a><span class="breadcrumb-separator">›</span><a href="/proto/zoo.md#Enclosure.Gate">Gate</a></nav>"##
+        ));
+    }
+
+    #[test]
+    fn should_render_a_badge_on_packed_fields() {
+        let field = |name: &str, typ: Type, packed: Option<bool>| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            r#type: Some(typ.into()),
+            label: Some(Label::Repeated.into()),
+            options: packed.map(|packed| FieldOptions {
+                packed: Some(packed),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let packed = |syntax: &str, fields: Vec<FieldDescriptorProto>| {
+            let descriptor = FileDescriptorProto {
+                name: Some("scores.proto".into()),
+                package: Some("scores".into()),
+                syntax: Some(syntax.into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Scores".into()),
+                    field: fields,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            let file = render_file(descriptor);
+            file.messages[0]
+                .fields
+                .iter()
+                .map(|field| match field {
+                    Field::Simple(simple_field) => simple_field
+                        .render()
+                        .expect("should render")
+                        .contains(r#"<span class="packed">packed</span>"#),
+                    Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+                })
+                .collect::<Vec<bool>>()
+        };
+
+        assert_eq!(
+            packed(
+                "proto3",
+                vec![
+                    field("points", Type::Int32, None),
+                    field("weights", Type::Double, Some(false)),
+                    field("names", Type::String, None),
+                ]
+            ),
+            [true, false, false]
+        );
+        assert_eq!(
+            packed(
+                "proto2",
+                vec![
+                    field("points", Type::Int32, None),
+                    field("weights", Type::Double, Some(true)),
+                ]
+            ),
+            [false, true]
+        );
+    }
+
+    #[test]
+    fn should_render_whether_proto3_fields_have_presence() {
+        let field = |name: &str, number: i32, typ: Type| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            r#type: Some(typ.into()),
+            label: Some(Label::Optional.into()),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("quota.proto".into()),
+            package: Some("quota".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Quota".into()),
+                field: vec![
+                    field("used", 1, Type::Int64),
+                    // `optional int64 limit = 2;`, in a synthetic oneof
+                    FieldDescriptorProto {
+                        oneof_index: Some(0),
+                        proto3_optional: Some(true),
+                        ..field("limit", 2, Type::Int64)
+                    },
+                    FieldDescriptorProto {
+                        label: Some(Label::Repeated.into()),
+                        ..field("history", 3, Type::Int64)
+                    },
+                ],
+                oneof_decl: vec![OneofDescriptorProto {
+                    name: Some("_limit".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let rendered = file.messages[0].render().expect("should render");
+        let field = |name: &str| {
+            rendered
+                .split(r#"<fieldset class="field""#)
+                .find(|field| field.starts_with(&format!(r#" id="Quota::{}""#, name)))
+                .unwrap_or_else(|| panic!("{} should be rendered", name))
+                .to_string()
+        };
+
+        assert!(field("used").contains(r#"<span class="presence">no presence</span>"#));
+        assert!(field("limit").contains(r#"<span class="presence">has presence</span>"#));
+        assert!(!field("history").contains(r#"class="presence""#));
+    }
+
+    #[test]
+    fn should_render_the_oneof_of_each_member() {
+        let field = |name: &str, number: i32, oneof_index: i32| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            r#type: Some(Type::String.into()),
+            label: Some(Label::Optional.into()),
+            oneof_index: Some(oneof_index),
+            ..Default::default()
+        };
+        let oneof = |name: &str| OneofDescriptorProto {
+            name: Some(name.into()),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("contact.proto".into()),
+            package: Some("contact".into()),
+            syntax: Some("proto3".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Contact".into()),
+                field: vec![
+                    field("email", 1, 0),
+                    field("phone", 2, 0),
+                    FieldDescriptorProto {
+                        proto3_optional: Some(true),
+                        ..field("nickname", 3, 1)
+                    },
+                ],
+                oneof_decl: vec![oneof("method"), oneof("_nickname")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let rendered = file.messages[0].render().expect("should render");
+        for name in ["email", "phone"] {
+            let field = rendered
+                .split(r#"<fieldset class="field""#)
+                .find(|field| field.starts_with(&format!(r#" id="Contact::{}""#, name)))
+                .unwrap_or_else(|| panic!("{} should be rendered", name));
+            assert!(field.starts_with(&format!(r#" id="Contact::{}" data-oneof="method">"#, name)));
+            assert!(
+                field.contains(r#"<span class="keyword">oneof</span>&nbsp;<code>method</code>"#)
+            );
+        }
+        // the synthetic oneof of a proto3 `optional` field is not shown
+        assert!(!rendered.contains("_nickname</code>"));
+        assert!(!rendered.contains(r#"data-oneof="_nickname""#));
+    }
+
+    #[test]
+    fn should_render_a_badge_on_redacted_fields() {
+        use prost::encoding;
+
+        // `FieldOptions.debug_redact` is newer than prost-types, so the descriptor set is encoded
+        // by hand: `message Credentials { string user = 1; string password = 2 [debug_redact = true]; }`
+        let message = |tag: u32, fields: Vec<Vec<u8>>| {
+            let mut buf = Vec::new();
+            encoding::bytes::encode(tag, &fields.concat(), &mut buf);
+            buf
+        };
+        let text = |tag: u32, value: &str| {
+            let mut buf = Vec::new();
+            encoding::string::encode(tag, &value.to_string(), &mut buf);
+            buf
+        };
+        let mut debug_redact = Vec::new();
+        encoding::bool::encode(16, &true, &mut debug_redact);
+        let field = |name: &str, number: u32, options: Vec<Vec<u8>>| {
+            let mut field = vec![text(1, name)];
+            let mut number_and_type = Vec::new();
+            encoding::int32::encode(3, &(number as i32), &mut number_and_type);
+            encoding::int32::encode(5, &(Type::String as i32), &mut number_and_type);
+            field.push(number_and_type);
+            if !options.is_empty() {
+                field.push(message(8, options));
+            }
+            message(2, field)
+        };
+
+        let encoded = message(
+            1,
+            vec![
+                text(1, "auth.proto"),
+                text(2, "auth"),
+                message(
+                    4,
+                    vec![
+                        text(1, "Credentials"),
+                        field("user", 1, vec![]),
+                        field("password", 2, vec![debug_redact]),
+                    ],
+                ),
+                text(12, "proto3"),
+            ],
+        );
+
+        let descriptor = FileDescriptorSet::decode(encoded.as_slice())
+            .unwrap()
+            .file
+            .remove(0);
+        let types = TypeIndex::new(std::slice::from_ref(&descriptor))
+            .with_editions(EditionsFeatures::decode(&encoded).unwrap());
+        let file = ProtoFileDescriptorTemplate::from_descriptor(
+            descriptor,
+            &types,
+            SymbolSort::Source,
+            &mut HashMap::new(),
+        );
+
+        let rendered: Vec<String> = file.messages[0]
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Simple(simple_field) => simple_field.render().expect("should render"),
+                Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+            })
+            .collect();
+
+        assert!(!rendered[0].contains(r#"class="redacted""#));
+        assert!(rendered[1].contains(r#"<span class="redacted">redacted</span>"#));
+    }
+
+    #[test]
+    fn should_render_configured_custom_options_as_attributes() {
+        use prost::encoding;
+
+        // custom options are extensions, which prost-types can't encode, so the descriptor set is
+        // encoded by hand: `message Invoice { option (owner) = "billing"; option (tier) = 2; }`
+        // with `string total = 1 [(owner) = "ledger"];`
+        let message = |tag: u32, fields: Vec<Vec<u8>>| {
+            let mut buf = Vec::new();
+            encoding::bytes::encode(tag, &fields.concat(), &mut buf);
+            buf
+        };
+        let text = |tag: u32, value: &str| {
+            let mut buf = Vec::new();
+            encoding::string::encode(tag, &value.to_string(), &mut buf);
+            buf
+        };
+        let mut tier = Vec::new();
+        encoding::int32::encode(50002, &2, &mut tier);
+        let mut number_and_type = Vec::new();
+        encoding::int32::encode(3, &1, &mut number_and_type);
+        encoding::int32::encode(5, &(Type::String as i32), &mut number_and_type);
+
+        let encoded = message(
+            1,
+            vec![
+                text(1, "billing.proto"),
+                text(2, "billing"),
+                message(
+                    4,
+                    vec![
+                        text(1, "Invoice"),
+                        message(
+                            2,
+                            vec![
+                                text(1, "total"),
+                                number_and_type,
+                                message(8, vec![text(50001, "ledger")]),
+                            ],
+                        ),
+                        message(7, vec![text(50001, "billing"), tier]),
+                    ],
+                ),
+                text(12, "proto3"),
+            ],
+        );
+
+        let names = BTreeMap::from([(50001, "owner".into()), (50002, "tier".into())]);
+        let descriptor = FileDescriptorSet::decode(encoded.as_slice())
+            .unwrap()
+            .file
+            .remove(0);
+        let types = TypeIndex::new(std::slice::from_ref(&descriptor))
+            .with_attributes(OptionAttributes::decode(&encoded, &names).unwrap());
+        let mut file = ProtoFileDescriptorTemplate::from_descriptor(
+            descriptor,
+            &types,
+            SymbolSort::Source,
+            &mut HashMap::new(),
+        );
+        file.messages[0].set_labels(&Arc::new(Labels::default()));
+
+        let rendered = file.messages[0].render().expect("should render");
+        assert!(rendered.contains(r#"<th colspan="2">Attributes</th>"#));
+        assert!(rendered.contains(concat!(
+            r#"<tbody><tr><td><code>owner</code></td><td><code>billing</code></td></tr>"#,
+            r#"<tr><td><code>tier</code></td><td><code>2</code></td></tr></tbody>"#
+        )));
+        assert!(rendered
+            .contains(r#"<tr><td><code>owner</code></td><td><code>ledger</code></td></tr>"#));
+    }
+
+    #[test]
+    fn should_render_proto2_default_values() {
+        let field = |name: &str, typ: Type, default_value: &str| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            r#type: Some(typ.into()),
+            default_value: Some(default_value.into()),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("legacy.proto".into()),
+            package: Some("legacy".into()),
+            syntax: Some("proto2".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Settings".into()),
+                field: vec![
+                    field("retries", Type::Int32, "3"),
+                    field("ratio", Type::Double, "0.5"),
+                    field("enabled", Type::Bool, "true"),
+                    field("label", Type::String, "none"),
+                    FieldDescriptorProto {
+                        name: Some("plain".into()),
+                        number: Some(5),
+                        r#type: Some(Type::Int64.into()),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let rendered: Vec<String> = file.messages[0]
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Simple(simple_field) => simple_field.render().expect("should render"),
+                Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+            })
+            .collect();
+
+        for (rendered, expected) in rendered
+            .iter()
+            .zip(["3", "0.5", "true", "&quot;none&quot;"])
+        {
+            assert!(
+                rendered.contains(&format!("Default:&nbsp;<code>{}</code>", expected)),
+                "{} missing default {}",
+                rendered,
+                expected
+            );
+        }
+        assert!(!rendered[4].contains("Default:"));
+    }
+
+    #[test]
+    fn should_resolve_fields_without_a_type_from_the_type_name() {
+        let field = |name: &str, type_name: &str| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            type_name: Some(type_name.into()),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Plan".into()),
+                    enum_type: vec![EnumDescriptorProto {
+                        name: Some("Tier".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Invoice".into()),
+                    field: vec![
+                        field("plan", ".billing.Plan"),
+                        field("status", "Status"),
+                        field("tier", "Plan.Tier"),
+                        field("missing", "Missing"),
+                        // a group, or a type from a file missing from the set
+                        field("discount", ".promotions.Discount"),
+                        FieldDescriptorProto {
+                            r#type: Some(Type::Group.into()),
+                            ..field("adjustment", ".billing.Invoice.Adjustment")
+                        },
+                        FieldDescriptorProto {
+                            type_name: None,
+                            ..field("untyped", "")
+                        },
+                    ],
+                    nested_type: vec![DescriptorProto {
+                        name: Some("Adjustment".into()),
+                        ..Default::default()
+                    }],
+                    enum_type: vec![EnumDescriptorProto {
+                        name: Some("Status".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        let field_types: Vec<Option<String>> = file.messages[1]
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Simple(simple_field) => match &simple_field.typ {
+                    FieldType::Symbol(link) => Some(link.fqsl()),
+                    _ => None,
+                },
+                Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+            })
+            .collect();
+
+        assert_eq!(
+            field_types,
+            vec![
+                Some(".billing.Plan".to_string()),
+                Some(".billing.Invoice.Status".to_string()),
+                Some(".billing.Plan.Tier".to_string()),
+                None,
+                Some(".promotions.Discount".to_string()),
+                Some(".billing.Invoice.Adjustment".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn only_scalar_fields_should_render_as_primitives() {
+        // every `Type`, and a type number newer than it knows of
+        let fields = (1..=19)
+            .map(|number| FieldDescriptorProto {
+                name: Some(format!("field_{}", number)),
+                number: Some(number),
+                r#type: Some(number),
+                type_name: Type::try_from(number)
+                    .is_ok_and(|typ| matches!(typ, Type::Enum | Type::Message | Type::Group))
+                    .then(|| ".billing.Invoice".into()),
+                ..Default::default()
+            })
+            .collect();
+
+        let descriptor = FileDescriptorProto {
+            name: Some("billing.proto".into()),
+            package: Some("billing".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Invoice".into()),
+                field: fields,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+
+        for (field, number) in file.messages[0].fields.iter().zip(1..) {
+            let Field::Simple(field) = field else {
+                unreachable!("no oneofs in fixture")
+            };
+            match (Type::try_from(number), &field.typ) {
+                (Ok(Type::Enum | Type::Message | Type::Group), FieldType::Symbol(_)) => {}
+                (Ok(typ), FieldType::Primitive(primitive)) => assert_eq!(*primitive, typ),
+                (Err(_), FieldType::Unimplemented) => {}
+                _ => panic!("field type {} is not rendered as expected", number),
+            }
+        }
+        assert!(file.render().is_ok());
+    }
+
+    #[test]
+    fn any_fields_should_note_how_the_type_is_resolved() {
+        let field = |name: &str, type_name: &str| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            r#type: Some(Type::Message.into()),
+            type_name: Some(type_name.into()),
+            ..Default::default()
+        };
+        let descriptor = FileDescriptorProto {
+            name: Some("events.proto".into()),
+            package: Some("events".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Event".into()),
+                field: vec![
+                    field("payload", ".google.protobuf.Any"),
+                    field("parent", ".events.Event"),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+        let rendered: Vec<String> = file.messages[0]
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Simple(simple_field) => simple_field.render().expect("should render"),
+                Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+            })
+            .collect();
+
+        assert!(rendered[0].contains(
+            "<p>Can hold any protobuf message. The actual type is identified by the <code>type_url</code> field"
+        ));
+        assert!(rendered[0].contains(
+            r#"<a href="https://protobuf.dev/reference/protobuf/google.protobuf/#any">"#
+        ));
+        assert!(!rendered[1].contains("type_url"));
+    }
+
+    #[test]
+    fn should_render_a_banner_on_deprecated_files() {
+        let descriptor = |deprecated: bool| FileDescriptorProto {
+            name: Some("legacy.proto".into()),
+            package: Some("legacy".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Order".into()),
+                ..Default::default()
+            }],
+            options: Some(FileOptions {
+                deprecated: Some(deprecated),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let render = |descriptor: FileDescriptorProto| {
+            render_file(descriptor).render().expect("should render")
+        };
+
+        let rendered = render(descriptor(true));
+        assert!(rendered.starts_with(r#"<fieldset class="mdbook-proto deprecated-file" id="#));
+        assert!(rendered.contains(
+            r#"<div class="note">Everything declared in this file is deprecated.</div>"#
+        ));
+
+        let rendered = render(descriptor(false));
+        assert!(rendered.starts_with(r#"<fieldset class="mdbook-proto" id="#));
+        assert!(!rendered.contains("file-deprecation"));
+    }
+
+    #[test]
+    fn should_render_the_language_specific_file_options() {
+        let descriptor = FileDescriptorProto {
+            name: Some("events.proto".into()),
+            package: Some("events".into()),
+            options: Some(FileOptions {
+                go_package: Some("example.com/events;events".into()),
+                swift_prefix: Some("EVT".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let file = render_file(descriptor);
+        assert_eq!(
+            file.options,
+            vec![
+                ("go_package", "example.com/events;events".to_string()),
+                ("swift_prefix", "EVT".to_string()),
+            ]
+        );
+
+        let rendered = file.render().expect("should render");
+        assert!(rendered.contains("<summary>Language options</summary>"));
+        assert!(rendered
+            .contains("<tr><td><code>swift_prefix</code></td><td><code>EVT</code></td></tr>"));
+    }
+
+    #[test]
+    fn should_link_the_imports_of_each_file() {
+        let file = |name: &str, dependency: &[&str]| FileDescriptorProto {
+            name: Some(name.into()),
+            package: Some("shop".into()),
+            dependency: dependency.iter().map(|d| d.to_string()).collect(),
+            message_type: vec![DescriptorProto {
+                name: Some(format!("{}Message", dependency.len())),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let orders = FileDescriptorProto {
+            public_dependency: vec![0],
+            weak_dependency: vec![2],
+            ..file(
+                "shop/orders.proto",
+                &[
+                    "shop/money.proto",
+                    "google/api/annotations.proto",
+                    "shop/legacy.proto",
+                ],
+            )
+        };
+        let money = file("shop/money.proto", &[]);
+
+        let types = TypeIndex::new(&[orders.clone(), money.clone()]);
+        let template = |descriptor| {
+            ProtoFileDescriptorTemplate::from_descriptor(
+                descriptor,
+                &types,
+                SymbolSort::Source,
+                &mut HashMap::new(),
+            )
+        };
+        let mut document = BTreeMap::from([(
+            "shop".to_string(),
+            ProtoNamespaceTemplate {
+                files: vec![template(orders), template(money)],
+                labels: Default::default(),
+                change: None,
+                version: None,
+            },
+        )]);
+
+        for (page_per, href) in [
+            (PagePer::Package, "/proto/shop.md#file-shop/money.proto"),
+            (PagePer::File, "/proto/shop/money.md#file-shop/money.proto"),
+        ] {
+            crate::links::assign_import_links(&mut document, page_per, "proto");
+            let rendered = document["shop"].render().expect("should render");
+
+            assert!(rendered.contains(r#"id="file-shop/money.proto""#));
+            assert!(rendered.contains(&format!(
+                r#"<a href="{}"><code>shop/money.proto</code></a>"#,
+                href
+            )));
+            // imports outside of the descriptor set are listed without a link
+            assert!(rendered.contains("<code>google/api/annotations.proto</code>"));
+            assert!(!rendered.contains("annotations.proto</code></a>"));
+            assert_eq!(
+                rendered
+                    .matches(r#"<span class="import-kind">public</span>"#)
+                    .count(),
+                1
+            );
+            assert_eq!(
+                rendered
+                    .matches(r#"<span class="import-kind">weak</span>"#)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn should_render_the_effective_default_of_editions_fields() {
+        let field = |name: &str, typ: Type| FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(1),
+            r#type: Some(typ.into()),
+            ..Default::default()
+        };
+        let enum_value = |name: &str, number: i32| EnumValueDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            ..Default::default()
+        };
+
+        let descriptor = FileDescriptorProto {
+            name: Some("settings.proto".into()),
+            package: Some("settings".into()),
+            syntax: Some("editions".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Limits".into()),
+                field: vec![
+                    field("retries", Type::Int32),
+                    FieldDescriptorProto {
+                        type_name: Some(".settings.Level".into()),
+                        ..field("level", Type::Enum)
+                    },
+                    FieldDescriptorProto {
+                        label: Some(Label::Repeated.into()),
+                        ..field("tags", Type::String)
+                    },
+                ],
+                ..Default::default()
+            }],
+            // a closed enum needn't start at zero, unlike proto3's
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Level".into()),
+                value: vec![enum_value("LEVEL_HIGH", 2), enum_value("LEVEL_LOW", 1)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let encoded = FileDescriptorSet {
+            file: vec![descriptor.clone()],
+        }
+        .encode_to_vec();
+        let types = TypeIndex::new(std::slice::from_ref(&descriptor))
+            .with_editions(EditionsFeatures::decode(&encoded).unwrap());
+        let file = ProtoFileDescriptorTemplate::from_descriptor(
+            descriptor,
+            &types,
+            SymbolSort::Source,
+            &mut HashMap::new(),
+        );
+
+        let rendered: Vec<String> = file.messages[0]
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Simple(simple_field) => simple_field.render().expect("should render"),
+                Field::OneOf(_) => unreachable!("no oneofs in fixture"),
+            })
+            .collect();
+
+        // editions fields have explicit presence by default, and proto3 would read both as 0
+        assert!(rendered[0].contains(r#"<span class="optional keyword">optional</span>"#));
+        assert!(rendered[0].contains("Default:&nbsp;<code>0</code>"));
+        assert!(rendered[1].contains("Default:&nbsp;<code>LEVEL_HIGH</code>"));
+        assert!(!rendered[2].contains("Default:"));
+        assert!(!rendered[2].contains("optional"));
+    }
+
+    #[test]
+    fn should_render_the_syntax_of_each_file() {
+        let render = |syntax: Option<&str>, edition: Option<i32>| {
+            let descriptor = FileDescriptorProto {
+                name: Some("limits.proto".into()),
+                package: Some("limits".into()),
+                syntax: syntax.map(String::from),
+                ..Default::default()
+            };
+            // `prost_types` predates the `edition` field, so it is appended to the encoding
+            let mut file = descriptor.encode_to_vec();
+            if let Some(edition) = edition {
+                prost::encoding::int32::encode(14, &edition, &mut file);
+            }
+            let mut encoded = Vec::new();
+            prost::encoding::bytes::encode(1, &file, &mut encoded);
+
+            let types = TypeIndex::new(std::slice::from_ref(&descriptor))
+                .with_editions(EditionsFeatures::decode(&encoded).unwrap());
+            ProtoFileDescriptorTemplate::from_descriptor(
+                descriptor,
+                &types,
+                SymbolSort::Source,
+                &mut HashMap::new(),
+            )
+            .render()
+            .expect("should render")
+        };
+        let badge = |syntax: &str| {
+            format!(
+                r#"<label>limits.proto <span class="badge syntax">{}</span></label>"#,
+                syntax
+            )
+        };
+
+        // protoc leaves `syntax` unset for proto2
+        assert!(render(None, None).contains(&badge("proto2")));
+        assert!(render(Some("proto2"), None).contains(&badge("proto2")));
+        assert!(render(Some("proto3"), None).contains(&badge("proto3")));
+        assert!(render(Some("editions"), Some(1000)).contains(&badge("edition 2023")));
+        assert!(render(Some("editions"), Some(99999)).contains(&badge("editions")));
     }
 }